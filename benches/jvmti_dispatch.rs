@@ -0,0 +1,61 @@
+//! Measures the wrapper overhead of `Jvmti` calls against a stub function table.
+//!
+//! No JVM is involved: each stub returns immediately, so the numbers are the
+//! cost of dispatch, error mapping, and string conversion alone.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use jvmti_bindings::env::Jvmti;
+use jvmti_bindings::sys::{jni, jvmti};
+use std::os::raw::{c_char, c_uchar};
+
+static NAME: &[u8] = b"run\0";
+static SIGNATURE: &[u8] = b"()V\0";
+
+unsafe extern "system" fn get_tag(_env: *mut jvmti::jvmtiEnv, _object: jni::jobject, tag_ptr: *mut jni::jlong) -> jvmti::jvmtiError {
+    *tag_ptr = 42;
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn get_method_name(
+    _env: *mut jvmti::jvmtiEnv,
+    _method: jni::jmethodID,
+    name_ptr: *mut *mut c_char,
+    signature_ptr: *mut *mut c_char,
+    generic_ptr: *mut *mut c_char,
+) -> jvmti::jvmtiError {
+    *name_ptr = NAME.as_ptr() as *mut c_char;
+    *signature_ptr = SIGNATURE.as_ptr() as *mut c_char;
+    *generic_ptr = std::ptr::null_mut();
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn deallocate(_env: *mut jvmti::jvmtiEnv, _mem: *mut c_uchar) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::NONE
+}
+
+fn stub_env() -> *mut jvmti::jvmtiEnv {
+    let stub = Box::leak(Box::new(common::stub_jvmti(|functions| {
+        functions.GetTag = Some(get_tag);
+        functions.GetMethodName = Some(get_method_name);
+        functions.Deallocate = Some(deallocate);
+    })));
+    stub.raw()
+}
+
+fn bench_dispatch(c: &mut Criterion) {
+    let jvmti = unsafe { Jvmti::from_raw(stub_env()) };
+
+    c.bench_function("jvmti_get_tag", |b| {
+        b.iter(|| jvmti.get_tag(black_box(std::ptr::null_mut())))
+    });
+
+    c.bench_function("jvmti_get_method_name", |b| {
+        b.iter(|| jvmti.get_method_name(black_box(std::ptr::null_mut())))
+    });
+}
+
+criterion_group!(benches, bench_dispatch);
+criterion_main!(benches);
@@ -91,14 +91,7 @@ impl Agent for ClassLogger {
         self.classes_loaded.fetch_add(1, Ordering::Relaxed);
 
         // Get class name (may be null for some system classes)
-        let class_name = if name.is_null() {
-            "<unknown>".to_string()
-        } else {
-            unsafe { std::ffi::CStr::from_ptr(name) }
-                .to_str()
-                .unwrap_or("<invalid>")
-                .to_string()
-        };
+        let class_name = unsafe { class_name(name) }.unwrap_or("<unknown>");
 
         // Log the class load
         println!(
@@ -29,7 +29,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         .unwrap();
 
     let key = env.new_string_utf("java.version").unwrap();
-    let value = env.call_static_object_method(system, get_prop, &[jni::jvalue { l: key }]);
+    let value = env.call_static_object_method(system, get_prop, &jvalues![key]);
 
     if env.exception_check() {
         env.exception_describe();
@@ -3,15 +3,812 @@
 //! This module provides simple helpers for tagging objects and extracting
 //! reference edges using JVMTI heap callbacks. It is intentionally conservative
 //! and designed for tooling, not production hot paths.
+//!
+//! [`HeapGraph::build`] captures the whole object graph with sizes and class
+//! tags, from which [`HeapGraph::dominators`] derives the dominator tree and
+//! retained sizes, as in heap-dump analyzers, and [`HeapGraph::paths_to_roots`]
+//! explains why an object is still alive. [`HeapGraph::write_hprof`] exports
+//! the graph for Eclipse MAT or VisualVM.
 
-use crate::env::Jvmti;
+use crate::env::{HeapClosures, IterationControl, JniEnv, Jvmti, VisitControl};
 use crate::sys::{jni, jvmti};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::{self, Write};
 use std::os::raw::c_void;
 use std::ptr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone)]
+/// Object reference graph keyed by object tag.
+///
+/// [`build_heap_graph`] fills only `edges`; [`HeapGraph::build`] fills every field.
+#[derive(Debug, Clone, Default)]
 pub struct HeapGraph {
+    /// `(referrer_tag, referenced_tag)` pairs.
     pub edges: Vec<(jni::jlong, jni::jlong)>,
+    /// Objects by tag.
+    pub nodes: HashMap<jni::jlong, HeapNode>,
+    /// Tags of objects referenced directly from a GC root.
+    pub roots: Vec<jni::jlong>,
+    /// Class signatures by class tag.
+    pub class_names: HashMap<jni::jlong, String>,
+    /// Kind and index of the reference behind each edge, keyed by
+    /// `(referrer_tag, referenced_tag)`; root references use referrer `0`.
+    /// Only the first reference between a pair is kept.
+    pub reference_info: HashMap<(jni::jlong, jni::jlong), ReferenceInfo>,
+    /// Field names by class tag, in JVMTI field-index order. Filled by
+    /// [`resolve_field_names`](Self::resolve_field_names).
+    pub field_names: HashMap<jni::jlong, Vec<String>>,
+}
+
+/// The `JVMTI_HEAP_REFERENCE_*` kind and index of one reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReferenceInfo {
+    pub kind: jvmti::jvmtiHeapReferenceKind,
+    /// Field, array, or constant-pool index where the kind has one.
+    pub index: Option<jni::jint>,
+}
+
+/// One step of a path returned by [`HeapGraph::paths_to_roots`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceEdge {
+    /// Tag of the referrer, or `None` for the GC root reference.
+    pub referrer: Option<jni::jlong>,
+    pub referenced: jni::jlong,
+    /// `JVMTI_HEAP_REFERENCE_*` kind, e.g. `FIELD`, `ARRAY_ELEMENT`, `STATIC_FIELD`, `STACK_LOCAL`.
+    pub kind: jvmti::jvmtiHeapReferenceKind,
+    pub index: Option<jni::jint>,
+    /// Signature of the referrer's class; for `STATIC_FIELD`, the class owning the field.
+    pub referrer_class: Option<String>,
+    /// Field name for `FIELD` and `STATIC_FIELD` references, once resolved.
+    pub field_name: Option<String>,
+}
+
+/// Longest reference chain [`HeapGraph::paths_to_roots`] will report.
+pub const MAX_PATH_LENGTH: usize = 1024;
+
+/// One object in a [`HeapGraph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapNode {
+    /// Tag of the object's class; a key into [`HeapGraph::class_names`].
+    pub class_tag: jni::jlong,
+    /// Shallow size in bytes.
+    pub size: u64,
+    /// Array length for arrays, `-1` otherwise.
+    pub length: jni::jint,
+}
+
+const UNDEFINED: usize = usize::MAX;
+
+/// Dominator tree and retained sizes computed by [`HeapGraph::dominators`].
+///
+/// Objects unreachable from the roots are not part of the tree.
+#[derive(Debug, Clone)]
+pub struct Dominators {
+    // Index 0 is a virtual root above all GC roots.
+    tags: Vec<jni::jlong>,
+    index: HashMap<jni::jlong, usize>,
+    idom: Vec<usize>,
+    retained: Vec<u64>,
+    // Reachable nodes in DFS postorder; dominators come after what they dominate.
+    postorder: Vec<usize>,
+}
+
+impl Dominators {
+    /// Tag of the object's immediate dominator.
+    ///
+    /// `None` if the object is dominated only by the GC roots as a whole, or is
+    /// not in the tree.
+    pub fn immediate_dominator(&self, tag: jni::jlong) -> Option<jni::jlong> {
+        let &node = self.index.get(&tag)?;
+        match self.idom[node] {
+            UNDEFINED | 0 => None,
+            dom => Some(self.tags[dom]),
+        }
+    }
+
+    /// Bytes that would be freed if the object were collected: its own size
+    /// plus the size of everything it dominates.
+    pub fn retained_size(&self, tag: jni::jlong) -> Option<u64> {
+        let &node = self.index.get(&tag)?;
+        (self.idom[node] != UNDEFINED).then_some(self.retained[node])
+    }
+
+    /// Total size of all objects reachable from the roots.
+    pub fn total_size(&self) -> u64 {
+        self.retained[0]
+    }
+
+    fn children(&self) -> Vec<Vec<usize>> {
+        let mut children = vec![Vec::new(); self.tags.len()];
+        for &node in &self.postorder {
+            if node != 0 {
+                children[self.idom[node]].push(node);
+            }
+        }
+        children
+    }
+}
+
+impl HeapGraph {
+    /// Captures the full object graph of the heap.
+    ///
+    /// Every loaded class is tagged `1..=n` and every reachable object gets a
+    /// fresh tag above that, which becomes its node id. The graph takes over the
+    /// environment's tags: existing tags are cleared first, and the new ones are
+    /// left in place so node ids can be mapped back to objects with
+    /// `GetObjectsWithTags`. Requires `can_tag_objects`.
+    pub fn build(jvmti_env: &Jvmti) -> Result<HeapGraph, jvmti::jvmtiError> {
+        jvmti_env.iterate_heap_with(0, ptr::null_mut(), |mut object| {
+            if object.tag() != 0 {
+                object.set_tag(0);
+            }
+            IterationControl::Continue
+        })?;
+
+        let classes = jvmti_env.get_loaded_classes()?;
+        let mut class_names = HashMap::with_capacity(classes.len());
+        for (index, &klass) in classes.iter().enumerate() {
+            let tag = index as jni::jlong + 1;
+            jvmti_env.set_tag(klass, tag)?;
+            let name = jvmti_env
+                .get_class_signature(klass)
+                .map(|(sig, _)| sig)
+                .unwrap_or_else(|_| "<unknown>".to_string());
+            class_names.insert(tag, name);
+        }
+
+        let mut next_tag = classes.len() as jni::jlong + 1;
+        let mut nodes = HashMap::new();
+        let mut edges = Vec::new();
+        let mut roots = Vec::new();
+        let mut reference_info = HashMap::new();
+        jvmti_env.follow_references_with(
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            HeapClosures::new().on_reference(|mut reference| {
+                if reference.tag() == 0 {
+                    reference.set_tag(next_tag);
+                    next_tag += 1;
+                }
+                let tag = reference.tag();
+                nodes.entry(tag).or_insert(HeapNode {
+                    class_tag: reference.class_tag(),
+                    size: reference.size().max(0) as u64,
+                    length: reference.length(),
+                });
+                let referrer = match reference.referrer_tag() {
+                    None => {
+                        roots.push(tag);
+                        0
+                    }
+                    Some(0) => return VisitControl::Follow,
+                    Some(referrer) => {
+                        edges.push((referrer, tag));
+                        referrer
+                    }
+                };
+                reference_info
+                    .entry((referrer, tag))
+                    .or_insert(ReferenceInfo { kind: reference.kind(), index: reference.index() });
+                VisitControl::Follow
+            }),
+        )?;
+        roots.sort_unstable();
+        roots.dedup();
+
+        Ok(HeapGraph { edges, nodes, roots, class_names, reference_info, field_names: HashMap::new() })
+    }
+
+    /// Fills [`field_names`](Self::field_names) for every tagged class so that
+    /// [`paths_to_roots`](Self::paths_to_roots) can name fields.
+    ///
+    /// JVMTI numbers fields across the class hierarchy: fields of all
+    /// implemented interfaces first, then those of each superclass from
+    /// `java.lang.Object` down, then the class's own. The relative order of
+    /// interface constants is not specified, so their names are best-effort;
+    /// class fields are exact. Classes that fail to resolve are skipped.
+    pub fn resolve_field_names(&mut self, jvmti_env: &Jvmti, jni_env: &JniEnv) -> Result<(), jvmti::jvmtiError> {
+        let class_tags: Vec<jni::jlong> = self.class_names.keys().copied().collect();
+        if class_tags.is_empty() {
+            return Ok(());
+        }
+        let (classes, tags) = jvmti_env.get_objects_with_tags(&class_tags)?;
+        for (klass, tag) in classes.into_iter().zip(tags) {
+            let names = jni_env.with_local_frame(64, |jni_env| field_index_names(jvmti_env, jni_env, klass));
+            if let Ok(Ok(names)) = names {
+                self.field_names.insert(tag, names);
+            }
+            jni_env.delete_local_ref(klass);
+        }
+        Ok(())
+    }
+
+    /// Finds reference chains from GC roots to `target_tag`.
+    ///
+    /// Searches breadth-first backwards from the target, so each chain is a
+    /// shortest path from its root; at most one chain is returned per root
+    /// object and at most `max_paths` in total, nearest root first. Each chain
+    /// starts with the root reference and ends at the target. Cycles are
+    /// visited once and chains longer than [`MAX_PATH_LENGTH`] are dropped.
+    pub fn paths_to_roots(&self, target_tag: jni::jlong, max_paths: usize) -> Vec<Vec<ReferenceEdge>> {
+        let mut paths = Vec::new();
+        if max_paths == 0 {
+            return paths;
+        }
+        let roots: HashSet<jni::jlong> = self.roots.iter().copied().collect();
+        let mut referrers: HashMap<jni::jlong, Vec<jni::jlong>> = HashMap::new();
+        for &(referrer, referenced) in &self.edges {
+            referrers.entry(referenced).or_default().push(referrer);
+        }
+
+        // Maps each visited object to the next object towards the target.
+        let mut next_hop: HashMap<jni::jlong, jni::jlong> = HashMap::new();
+        let mut queue = VecDeque::from([(target_tag, 0usize)]);
+        next_hop.insert(target_tag, target_tag);
+        while let Some((tag, depth)) = queue.pop_front() {
+            if roots.contains(&tag) {
+                paths.push(self.path_from(tag, target_tag, &next_hop));
+                if paths.len() == max_paths {
+                    break;
+                }
+                // A longer chain through this root adds nothing.
+                continue;
+            }
+            if depth + 1 >= MAX_PATH_LENGTH {
+                continue;
+            }
+            for &referrer in referrers.get(&tag).into_iter().flatten() {
+                if let Entry::Vacant(entry) = next_hop.entry(referrer) {
+                    entry.insert(tag);
+                    queue.push_back((referrer, depth + 1));
+                }
+            }
+        }
+        paths
+    }
+
+    fn path_from(&self, root: jni::jlong, target: jni::jlong, next_hop: &HashMap<jni::jlong, jni::jlong>) -> Vec<ReferenceEdge> {
+        let mut path = vec![self.reference_edge(None, root)];
+        let mut tag = root;
+        while tag != target {
+            let next = next_hop[&tag];
+            path.push(self.reference_edge(Some(tag), next));
+            tag = next;
+        }
+        path
+    }
+
+    fn reference_edge(&self, referrer: Option<jni::jlong>, referenced: jni::jlong) -> ReferenceEdge {
+        let info = self
+            .reference_info
+            .get(&(referrer.unwrap_or(0), referenced))
+            .copied()
+            .unwrap_or(ReferenceInfo { kind: jvmti::JVMTI_HEAP_REFERENCE_OTHER, index: None });
+        // Static fields are reported on the class object, whose tag is its class tag.
+        let class_tag = match (referrer, info.kind) {
+            (Some(referrer), jvmti::JVMTI_HEAP_REFERENCE_STATIC_FIELD) => Some(referrer),
+            (Some(referrer), _) => self.nodes.get(&referrer).map(|node| node.class_tag),
+            (None, _) => None,
+        };
+        let field_name = match (class_tag, info.kind, info.index) {
+            (Some(class_tag), jvmti::JVMTI_HEAP_REFERENCE_FIELD | jvmti::JVMTI_HEAP_REFERENCE_STATIC_FIELD, Some(index)) => self
+                .field_names
+                .get(&class_tag)
+                .and_then(|names| names.get(usize::try_from(index).ok()?))
+                .cloned(),
+            _ => None,
+        };
+        ReferenceEdge {
+            referrer,
+            referenced,
+            kind: info.kind,
+            index: info.index,
+            referrer_class: class_tag.and_then(|tag| self.class_names.get(&tag).cloned()),
+            field_name,
+        }
+    }
+
+    /// Computes the dominator tree over `nodes`, `edges`, and `roots`.
+    ///
+    /// Uses the iterative algorithm of Cooper, Harvey, and Kennedy from a
+    /// virtual root that references every GC root. Tags that appear only in
+    /// `edges` or `roots` are treated as zero-sized objects.
+    pub fn dominators(&self) -> Dominators {
+        let mut tags = vec![0];
+        let mut index = HashMap::with_capacity(self.nodes.len() + 1);
+        let mut node_of = |tag: jni::jlong, tags: &mut Vec<jni::jlong>| {
+            *index.entry(tag).or_insert_with(|| {
+                tags.push(tag);
+                tags.len() - 1
+            })
+        };
+        for &tag in self.nodes.keys() {
+            node_of(tag, &mut tags);
+        }
+        let root_nodes: Vec<usize> = self.roots.iter().map(|&root| node_of(root, &mut tags)).collect();
+        let edge_nodes: Vec<(usize, usize)> = self
+            .edges
+            .iter()
+            .map(|&(referrer, referenced)| (node_of(referrer, &mut tags), node_of(referenced, &mut tags)))
+            .collect();
+        let count = tags.len();
+        let mut successors = vec![Vec::new(); count];
+        successors[0] = root_nodes;
+        for (from, to) in edge_nodes {
+            successors[from].push(to);
+        }
+
+        let mut postorder = Vec::with_capacity(count);
+        let mut visited = vec![false; count];
+        let mut stack = vec![(0usize, 0usize)];
+        visited[0] = true;
+        while let Some((node, next)) = stack.last_mut() {
+            if let Some(&successor) = successors[*node].get(*next) {
+                *next += 1;
+                if !visited[successor] {
+                    visited[successor] = true;
+                    stack.push((successor, 0));
+                }
+            } else {
+                postorder.push(*node);
+                stack.pop();
+            }
+        }
+
+        let mut post_number = vec![UNDEFINED; count];
+        for (number, &node) in postorder.iter().enumerate() {
+            post_number[node] = number;
+        }
+        let mut predecessors = vec![Vec::new(); count];
+        for &node in &postorder {
+            for &successor in &successors[node] {
+                predecessors[successor].push(node);
+            }
+        }
+
+        let mut idom = vec![UNDEFINED; count];
+        idom[0] = 0;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            // Reverse postorder, skipping the virtual root.
+            for &node in postorder.iter().rev().skip(1) {
+                let mut new_idom = UNDEFINED;
+                for &pred in &predecessors[node] {
+                    if idom[pred] == UNDEFINED {
+                        continue;
+                    }
+                    new_idom = if new_idom == UNDEFINED {
+                        pred
+                    } else {
+                        intersect(&idom, &post_number, pred, new_idom)
+                    };
+                }
+                if idom[node] != new_idom {
+                    idom[node] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut retained: Vec<u64> = tags
+            .iter()
+            .enumerate()
+            .map(|(node, tag)| if node == 0 { 0 } else { self.nodes.get(tag).map_or(0, |n| n.size) })
+            .collect();
+        for &node in &postorder {
+            if node != 0 {
+                retained[idom[node]] += retained[node];
+            }
+        }
+
+        Dominators { tags, index, idom, retained, postorder }
+    }
+
+    /// Retained size per class, largest first.
+    ///
+    /// A class's retained size is what collecting all of its instances together
+    /// would free, so instances dominated by another instance of the same class
+    /// are not counted twice. Classes without a signature in `class_names` are
+    /// reported as `<unknown>`.
+    pub fn retained_size_by_class(&self) -> Vec<(String, u64)> {
+        let dominators = self.dominators();
+        let children = dominators.children();
+        let mut totals: HashMap<jni::jlong, u64> = HashMap::new();
+        let mut active: HashMap<jni::jlong, usize> = HashMap::new();
+        // (node, exiting): instances count only when no ancestor shares their class.
+        let mut stack: Vec<(usize, bool)> = children[0].iter().map(|&child| (child, false)).collect();
+        while let Some((node, exiting)) = stack.pop() {
+            let class_tag = self.nodes.get(&dominators.tags[node]).map_or(0, |n| n.class_tag);
+            let depth = active.entry(class_tag).or_insert(0);
+            if exiting {
+                *depth -= 1;
+                continue;
+            }
+            if *depth == 0 {
+                *totals.entry(class_tag).or_insert(0) += dominators.retained[node];
+            }
+            *depth += 1;
+            stack.push((node, true));
+            stack.extend(children[node].iter().map(|&child| (child, false)));
+        }
+
+        let mut by_class: Vec<(String, u64)> = totals
+            .into_iter()
+            .map(|(class_tag, size)| {
+                let name = self.class_names.get(&class_tag).cloned().unwrap_or_else(|| "<unknown>".to_string());
+                (name, size)
+            })
+            .collect();
+        by_class.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        by_class
+    }
+
+    /// Writes the graph as an HPROF 1.0.2 heap dump for Eclipse MAT or VisualVM.
+    ///
+    /// Object ids are the graph's tags. The dump carries references only: each
+    /// class declares one object field per field index seen on its instances
+    /// (named from `field_names` when resolved), primitive fields are omitted,
+    /// primitive arrays are zero-filled, and classes have no superclass. Tools
+    /// can therefore show the reference graph, GC roots, and retained sizes,
+    /// but not field values or the class hierarchy. Objects whose class is not
+    /// in `class_names` are left out.
+    ///
+    /// Fails with `InvalidData` if a count does not fit its HPROF field, such
+    /// as a class with more than 65535 static or instance reference fields.
+    pub fn write_hprof<W: Write>(&self, w: W) -> io::Result<()> {
+        let mut out = io::BufWriter::new(w);
+        out.write_all(b"JAVA PROFILE 1.0.2\0")?;
+        out.write_all(&(HPROF_ID_SIZE as u32).to_be_bytes())?;
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64);
+        out.write_all(&millis.to_be_bytes())?;
+
+        let mut outgoing: HashMap<jni::jlong, Vec<(ReferenceInfo, jni::jlong)>> = HashMap::new();
+        for (&(referrer, referenced), &info) in &self.reference_info {
+            if referrer != 0 {
+                outgoing.entry(referrer).or_default().push((info, referenced));
+            }
+        }
+        for references in outgoing.values_mut() {
+            references.sort_by_key(|(info, referenced)| (info.kind, info.index, *referenced));
+        }
+        let references_of = |tag: jni::jlong, kind: jvmti::jvmtiHeapReferenceKind| {
+            outgoing
+                .get(&tag)
+                .into_iter()
+                .flatten()
+                .filter(move |(info, _)| info.kind == kind)
+                .map(|(info, referenced)| (info.index.unwrap_or(0), *referenced))
+        };
+
+        let mut instance_fields: HashMap<jni::jlong, BTreeMap<jni::jint, usize>> = HashMap::new();
+        let mut nodes: Vec<(&jni::jlong, &HeapNode)> = self.nodes.iter().collect();
+        nodes.sort_unstable_by_key(|(tag, _)| **tag);
+        for &(&tag, node) in &nodes {
+            if !self.class_names.contains_key(&tag) {
+                let fields = instance_fields.entry(node.class_tag).or_default();
+                for (index, _) in references_of(tag, jvmti::JVMTI_HEAP_REFERENCE_FIELD) {
+                    fields.insert(index, 0);
+                }
+            }
+        }
+        for fields in instance_fields.values_mut() {
+            for (slot, position) in fields.values_mut().enumerate() {
+                *position = slot;
+            }
+        }
+
+        let mut class_tags: Vec<jni::jlong> = self.class_names.keys().copied().collect();
+        class_tags.sort_unstable();
+        let mut strings = HprofStrings::default();
+        let field_name = |class_tag: jni::jlong, index: jni::jint| {
+            self.field_names
+                .get(&class_tag)
+                .and_then(|names| names.get(usize::try_from(index).ok()?))
+                .cloned()
+                .unwrap_or_else(|| format!("field{index}"))
+        };
+        let mut class_dumps = Vec::with_capacity(class_tags.len());
+        for (serial, &class_tag) in class_tags.iter().enumerate() {
+            let name_id = strings.intern(&mut out, hprof_class_name(&self.class_names[&class_tag]))?;
+            let mut body = Vec::with_capacity(4 + 2 * HPROF_ID_SIZE + 4);
+            body.extend_from_slice(&(serial as u32 + 1).to_be_bytes());
+            body.extend_from_slice(&(class_tag as u64).to_be_bytes());
+            body.extend_from_slice(&HPROF_STACK_TRACE_SERIAL.to_be_bytes());
+            body.extend_from_slice(&name_id.to_be_bytes());
+            write_hprof_record(&mut out, HPROF_LOAD_CLASS, &body)?;
+
+            let mut statics = Vec::new();
+            for (index, referenced) in references_of(class_tag, jvmti::JVMTI_HEAP_REFERENCE_STATIC_FIELD) {
+                statics.push((strings.intern(&mut out, &field_name(class_tag, index))?, referenced));
+            }
+            let mut fields = Vec::new();
+            for &index in instance_fields.get(&class_tag).into_iter().flat_map(|fields| fields.keys()) {
+                fields.push(strings.intern(&mut out, &field_name(class_tag, index))?);
+            }
+            let loader = references_of(class_tag, jvmti::JVMTI_HEAP_REFERENCE_CLASS_LOADER).next().map_or(0, |(_, loader)| loader);
+            class_dumps.push((class_tag, loader, statics, fields));
+        }
+
+        let mut body = Vec::with_capacity(12);
+        body.extend_from_slice(&HPROF_STACK_TRACE_SERIAL.to_be_bytes());
+        body.extend_from_slice(&0u32.to_be_bytes());
+        body.extend_from_slice(&0u32.to_be_bytes());
+        write_hprof_record(&mut out, HPROF_STACK_TRACE, &body)?;
+
+        let mut segment = HprofSegment::default();
+        for &root in &self.roots {
+            let kind = self.reference_info.get(&(0, root)).map(|info| info.kind);
+            let tag = match kind {
+                Some(jvmti::JVMTI_HEAP_REFERENCE_SYSTEM_CLASS) => HPROF_GC_ROOT_STICKY_CLASS,
+                Some(jvmti::JVMTI_HEAP_REFERENCE_JNI_GLOBAL) => HPROF_GC_ROOT_JNI_GLOBAL,
+                Some(jvmti::JVMTI_HEAP_REFERENCE_MONITOR) => HPROF_GC_ROOT_MONITOR_USED,
+                _ => HPROF_GC_ROOT_UNKNOWN,
+            };
+            segment.u1(tag);
+            segment.id(root);
+            if tag == HPROF_GC_ROOT_JNI_GLOBAL {
+                segment.id(0);
+            }
+            segment.flush_if_full(&mut out)?;
+        }
+
+        for (class_tag, loader, statics, fields) in &class_dumps {
+            segment.u1(HPROF_GC_CLASS_DUMP);
+            segment.id(*class_tag);
+            segment.u4(HPROF_STACK_TRACE_SERIAL);
+            segment.id(0); // superclass
+            segment.id(*loader);
+            segment.id(0); // signers
+            segment.id(0); // protection domain
+            segment.id(0); // reserved
+            segment.id(0); // reserved
+            segment.u4(hprof_count(fields.len() * HPROF_ID_SIZE, "instance field bytes")?);
+            segment.u2(0); // constant pool
+            segment.u2(hprof_count(statics.len(), "static fields")?);
+            for &(name_id, referenced) in statics {
+                segment.id(name_id as jni::jlong);
+                segment.u1(HPROF_OBJECT);
+                segment.id(referenced);
+            }
+            segment.u2(hprof_count(fields.len(), "instance fields")?);
+            for &name_id in fields {
+                segment.id(name_id as jni::jlong);
+                segment.u1(HPROF_OBJECT);
+            }
+            segment.flush_if_full(&mut out)?;
+        }
+
+        for &(&tag, node) in &nodes {
+            if self.class_names.contains_key(&tag) {
+                continue;
+            }
+            let Some(class_name) = self.class_names.get(&node.class_tag) else {
+                continue;
+            };
+            let length = node.length.max(0) as usize;
+            match class_name.as_bytes() {
+                [b'[', b'L' | b'[', ..] => {
+                    let mut elements = vec![0; length];
+                    for (index, referenced) in references_of(tag, jvmti::JVMTI_HEAP_REFERENCE_ARRAY_ELEMENT) {
+                        if let Some(element) = usize::try_from(index).ok().and_then(|index| elements.get_mut(index)) {
+                            *element = referenced;
+                        }
+                    }
+                    segment.u1(HPROF_GC_OBJ_ARRAY_DUMP);
+                    segment.id(tag);
+                    segment.u4(HPROF_STACK_TRACE_SERIAL);
+                    segment.u4(hprof_count(length, "array elements")?);
+                    segment.id(node.class_tag);
+                    for element in elements {
+                        segment.id(element);
+                    }
+                }
+                [b'[', element_type] => {
+                    let Some((type_code, element_size)) = hprof_primitive(*element_type) else {
+                        continue;
+                    };
+                    segment.u1(HPROF_GC_PRIM_ARRAY_DUMP);
+                    segment.id(tag);
+                    segment.u4(HPROF_STACK_TRACE_SERIAL);
+                    segment.u4(hprof_count(length, "array elements")?);
+                    segment.u1(type_code);
+                    segment.bytes.resize(segment.bytes.len() + length * element_size, 0);
+                }
+                _ => {
+                    let slots = instance_fields.get(&node.class_tag);
+                    let mut values = vec![0; slots.map_or(0, |slots| slots.len())];
+                    for (index, referenced) in references_of(tag, jvmti::JVMTI_HEAP_REFERENCE_FIELD) {
+                        if let Some(&slot) = slots.and_then(|slots| slots.get(&index)) {
+                            values[slot] = referenced;
+                        }
+                    }
+                    segment.u1(HPROF_GC_INSTANCE_DUMP);
+                    segment.id(tag);
+                    segment.u4(HPROF_STACK_TRACE_SERIAL);
+                    segment.id(node.class_tag);
+                    segment.u4(hprof_count(values.len() * HPROF_ID_SIZE, "instance field bytes")?);
+                    for value in values {
+                        segment.id(value);
+                    }
+                }
+            }
+            segment.flush_if_full(&mut out)?;
+        }
+        segment.flush(&mut out)?;
+        write_hprof_record(&mut out, HPROF_HEAP_DUMP_END, &[])?;
+        out.flush()
+    }
+}
+
+fn field_index_names(jvmti_env: &Jvmti, jni_env: &JniEnv, klass: jni::jclass) -> Result<Vec<String>, jvmti::jvmtiError> {
+    let mut chain = vec![klass];
+    while let Some(superclass) = jni_env.get_superclass(*chain.last().unwrap()) {
+        chain.push(superclass);
+    }
+    chain.reverse();
+
+    let mut seen = HashSet::new();
+    let mut names = Vec::new();
+    for &class in &chain {
+        collect_interface_fields(jvmti_env, class, &mut seen, &mut names)?;
+    }
+    for &class in &chain {
+        for field in jvmti_env.get_class_fields(class)? {
+            names.push(jvmti_env.get_field_name(class, field)?.0);
+        }
+    }
+    Ok(names)
+}
+
+fn collect_interface_fields(
+    jvmti_env: &Jvmti,
+    klass: jni::jclass,
+    seen: &mut HashSet<String>,
+    names: &mut Vec<String>,
+) -> Result<(), jvmti::jvmtiError> {
+    for interface in jvmti_env.get_implemented_interfaces(klass)? {
+        let (signature, _) = jvmti_env.get_class_signature(interface)?;
+        if !seen.insert(signature) {
+            continue;
+        }
+        collect_interface_fields(jvmti_env, interface, seen, names)?;
+        for field in jvmti_env.get_class_fields(interface)? {
+            names.push(jvmti_env.get_field_name(interface, field)?.0);
+        }
+    }
+    Ok(())
+}
+
+const HPROF_ID_SIZE: usize = 8;
+const HPROF_STACK_TRACE_SERIAL: u32 = 1;
+// Segments are flushed once they pass this size, well below the u4 record limit.
+const HPROF_SEGMENT_LIMIT: usize = 1 << 24;
+
+const HPROF_UTF8: u8 = 0x01;
+const HPROF_LOAD_CLASS: u8 = 0x02;
+const HPROF_STACK_TRACE: u8 = 0x05;
+const HPROF_HEAP_DUMP_SEGMENT: u8 = 0x1C;
+const HPROF_HEAP_DUMP_END: u8 = 0x2C;
+
+const HPROF_GC_ROOT_UNKNOWN: u8 = 0xFF;
+const HPROF_GC_ROOT_JNI_GLOBAL: u8 = 0x01;
+const HPROF_GC_ROOT_STICKY_CLASS: u8 = 0x05;
+const HPROF_GC_ROOT_MONITOR_USED: u8 = 0x07;
+const HPROF_GC_CLASS_DUMP: u8 = 0x20;
+const HPROF_GC_INSTANCE_DUMP: u8 = 0x21;
+const HPROF_GC_OBJ_ARRAY_DUMP: u8 = 0x22;
+const HPROF_GC_PRIM_ARRAY_DUMP: u8 = 0x23;
+
+const HPROF_OBJECT: u8 = 2;
+
+fn write_hprof_record<W: Write>(out: &mut W, tag: u8, body: &[u8]) -> io::Result<()> {
+    let length = u32::try_from(body.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "HPROF record too large"))?;
+    out.write_all(&[tag])?;
+    out.write_all(&0u32.to_be_bytes())?;
+    out.write_all(&length.to_be_bytes())?;
+    out.write_all(body)
+}
+
+/// Narrows a count to the width of its HPROF field, refusing to write a
+/// corrupt dump when it does not fit.
+fn hprof_count<T: TryFrom<usize>>(count: usize, what: &str) -> io::Result<T> {
+    T::try_from(count).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("too many {what} for HPROF: {count}")))
+}
+
+/// HPROF names classes by internal name: `java/lang/String`, but `[I` for arrays.
+fn hprof_class_name(signature: &str) -> &str {
+    signature
+        .strip_prefix('L')
+        .and_then(|name| name.strip_suffix(';'))
+        .unwrap_or(signature)
+}
+
+/// HPROF basic type and element size for a primitive descriptor character.
+fn hprof_primitive(descriptor: u8) -> Option<(u8, usize)> {
+    Some(match descriptor {
+        b'Z' => (4, 1),
+        b'C' => (5, 2),
+        b'F' => (6, 4),
+        b'D' => (7, 8),
+        b'B' => (8, 1),
+        b'S' => (9, 2),
+        b'I' => (10, 4),
+        b'J' => (11, 8),
+        _ => return None,
+    })
+}
+
+#[derive(Default)]
+struct HprofStrings {
+    ids: HashMap<String, u64>,
+}
+
+impl HprofStrings {
+    fn intern<W: Write>(&mut self, out: &mut W, value: &str) -> io::Result<u64> {
+        if let Some(&id) = self.ids.get(value) {
+            return Ok(id);
+        }
+        let id = self.ids.len() as u64 + 1;
+        let mut body = Vec::with_capacity(HPROF_ID_SIZE + value.len());
+        body.extend_from_slice(&id.to_be_bytes());
+        body.extend_from_slice(value.as_bytes());
+        write_hprof_record(out, HPROF_UTF8, &body)?;
+        self.ids.insert(value.to_string(), id);
+        Ok(id)
+    }
+}
+
+#[derive(Default)]
+struct HprofSegment {
+    bytes: Vec<u8>,
+}
+
+impl HprofSegment {
+    fn u1(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u2(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u4(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn id(&mut self, tag: jni::jlong) {
+        self.bytes.extend_from_slice(&(tag as u64).to_be_bytes());
+    }
+
+    fn flush_if_full<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if self.bytes.len() >= HPROF_SEGMENT_LIMIT {
+            self.flush(out)?;
+        }
+        Ok(())
+    }
+
+    fn flush<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if !self.bytes.is_empty() {
+            write_hprof_record(out, HPROF_HEAP_DUMP_SEGMENT, &self.bytes)?;
+            self.bytes.clear();
+        }
+        Ok(())
+    }
+}
+
+fn intersect(idom: &[usize], post_number: &[usize], mut a: usize, mut b: usize) -> usize {
+    while a != b {
+        while post_number[a] < post_number[b] {
+            a = idom[a];
+        }
+        while post_number[b] < post_number[a] {
+            b = idom[b];
+        }
+    }
+    a
 }
 
 #[derive(Debug, Clone)]
@@ -62,24 +859,29 @@ struct EdgeCollector {
     edges: Vec<(jni::jlong, jni::jlong)>,
 }
 
+#[allow(clippy::too_many_arguments)]
 unsafe extern "system" fn edge_collector_cb(
-    _reference_kind: jni::jint,
-    _reference_info: jvmti::jvmtiObjectReferenceInfo,
+    _reference_kind: jvmti::jvmtiHeapReferenceKind,
+    _reference_info: *const jvmti::jvmtiHeapReferenceInfo,
     _class_tag: jni::jlong,
-    referrer_tag: jni::jlong,
-    target_tag: jni::jlong,
-    _reference_index: jni::jint,
+    _referrer_class_tag: jni::jlong,
+    _size: jni::jlong,
+    tag_ptr: *mut jni::jlong,
+    referrer_tag_ptr: *mut jni::jlong,
+    _length: jni::jint,
     user_data: *mut c_void,
-    _index_ptr: *mut jni::jint,
 ) -> jni::jint {
     if user_data.is_null() {
-        return jvmti::JVMTI_ITERATION_CONTINUE;
+        return jvmti::JVMTI_VISIT_OBJECTS;
     }
+    // `referrer_tag_ptr` is null for heap roots.
+    let referrer_tag = if referrer_tag_ptr.is_null() { 0 } else { unsafe { *referrer_tag_ptr } };
+    let target_tag = if tag_ptr.is_null() { 0 } else { unsafe { *tag_ptr } };
     if referrer_tag != 0 && target_tag != 0 {
         let collector = &mut *(user_data as *mut EdgeCollector);
         collector.edges.push((referrer_tag, target_tag));
     }
-    jvmti::JVMTI_ITERATION_CONTINUE
+    jvmti::JVMTI_VISIT_OBJECTS
 }
 
 /// Builds a heap reference edge list using `FollowReferences`.
@@ -92,12 +894,7 @@ pub fn build_heap_graph(
     initial_object: jni::jobject,
 ) -> Result<HeapGraph, jvmti::jvmtiError> {
     let mut collector = EdgeCollector { edges: Vec::new() };
-    let callbacks = jvmti::jvmtiHeapCallbacks {
-        heap_root_callback: None,
-        stack_reference_callback: None,
-        object_reference_callback: Some(edge_collector_cb),
-        object_callback: None,
-    };
+    let callbacks = jvmti::jvmtiHeapCallbacks::default().heap_reference(edge_collector_cb);
 
     jvmti_env.follow_references(
         heap_filter,
@@ -107,5 +904,5 @@ pub fn build_heap_graph(
         &mut collector as *mut EdgeCollector as *const c_void,
     )?;
 
-    Ok(HeapGraph { edges: collector.edges })
+    Ok(HeapGraph { edges: collector.edges, ..HeapGraph::default() })
 }
@@ -0,0 +1,121 @@
+//! Memoized `FindClass` lookups.
+//!
+//! `FindClass` returns a local reference that dies when the current callback
+//! returns, so agents that keep calling into the same few classes either look
+//! them up every time or juggle global references by hand. [`ClassCache`]
+//! resolves each class once, keeps a global reference, and hands it out by
+//! name from any thread.
+//!
+//! ```rust,ignore
+//! use jvmti_bindings::env::ClassCache;
+//! use std::sync::OnceLock;
+//!
+//! static CLASSES: OnceLock<ClassCache> = OnceLock::new();
+//!
+//! impl Agent for Tracer {
+//!     fn method_entry_with_env(&self, _jvmti: &Jvmti, jni: &JniEnv, _thread: jni::jthread, _method: jni::jmethodID) {
+//!         if let Some(system) = CLASSES.get_or_init(ClassCache::new).get(jni, "java.lang.System") {
+//!             // call static methods on `system`
+//!         }
+//!     }
+//!
+//!     fn shutdown(&self, jni: &JniEnv) {
+//!         if let Some(classes) = CLASSES.get() {
+//!             classes.clear(jni);
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::jni_wrapper::JniEnv;
+use crate::sys::jni;
+
+/// Class global references memoized by internal name (`java/lang/System`).
+///
+/// Lookups go through the class loader of the calling code, as with
+/// `FindClass`; use one cache per loader if that matters. The global
+/// references are released by [`clear`](Self::clear), which should be called
+/// from [`Agent::shutdown`](crate::Agent::shutdown); dropping the cache without clearing it leaks them.
+pub struct ClassCache {
+    classes: Mutex<HashMap<String, jni::jclass>>,
+}
+
+// SAFETY: the map only holds JNI global references, which are valid on every
+// thread; all access to the map goes through the mutex.
+unsafe impl Send for ClassCache {}
+unsafe impl Sync for ClassCache {}
+
+impl ClassCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        ClassCache { classes: Mutex::new(HashMap::new()) }
+    }
+
+    fn classes(&self) -> MutexGuard<'_, HashMap<String, jni::jclass>> {
+        self.classes.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns the class named `name`, resolving it on first use.
+    ///
+    /// `name` may use dots or slashes (`java.lang.System` or
+    /// `java/lang/System`). The result is a global reference owned by the
+    /// cache: do not delete it. Returns `None` if the class cannot be found,
+    /// leaving the `NoClassDefFoundError` pending; failed lookups are retried
+    /// on the next call.
+    pub fn get(&self, jni: &JniEnv, name: &str) -> Option<jni::jclass> {
+        let name = name.replace('.', "/");
+        if let Some(&class) = self.classes().get(&name) {
+            return Some(class);
+        }
+        // Resolve without holding the lock: loading the class can run Java
+        // code and callbacks that use this cache on the same thread.
+        let local = jni.find_class(&name)?;
+        let global = jni.new_global_ref(local);
+        jni.delete_local_ref(local);
+        if global.is_null() {
+            return None;
+        }
+        let mut classes = self.classes();
+        if let Some(&existing) = classes.get(&name) {
+            // Another thread resolved it first.
+            jni.delete_global_ref(global);
+            return Some(existing);
+        }
+        classes.insert(name, global);
+        Some(global)
+    }
+
+    /// Returns the cached class without resolving it.
+    pub fn get_cached(&self, name: &str) -> Option<jni::jclass> {
+        self.classes().get(&name.replace('.', "/")).copied()
+    }
+
+    /// Deletes every cached global reference and empties the cache.
+    ///
+    /// Call this from [`Agent::shutdown`](crate::Agent::shutdown); classes
+    /// returned earlier must not be used afterwards.
+    pub fn clear(&self, jni: &JniEnv) {
+        for (_, class) in self.classes().drain() {
+            jni.delete_global_ref(class);
+        }
+    }
+
+    /// Number of cached classes.
+    pub fn len(&self) -> usize {
+        self.classes().len()
+    }
+
+    /// Returns true if no class is cached.
+    pub fn is_empty(&self) -> bool {
+        self.classes().is_empty()
+    }
+}
+
+impl Default for ClassCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,177 @@
+//! `ClassFileLoadHook` dispatch by class name.
+//!
+//! A transformer usually only cares about a handful of classes, yet the hook
+//! fires for every class the VM loads. [`ClassTransformerRegistry`] pairs
+//! class name patterns with transformers; once installed, the crate's
+//! `ClassFileLoadHook` trampoline runs only the transformers whose pattern
+//! matches, feeding each one the previous one's output:
+//!
+//! ```rust,ignore
+//! use jvmti_bindings::env::ClassTransformerRegistry;
+//!
+//! let registry = ClassTransformerRegistry::new()
+//!     .register("com/example/service/*Handler", |_name, bytes| add_timing(bytes))
+//!     .register("com.example.db.**", |_name, bytes| add_tracing(bytes));
+//! jvmti.install_transformers(registry)?;
+//! ```
+//!
+//! Patterns are matched against the internal class name (`java/lang/String`)
+//! and may be written with dots or slashes:
+//!
+//! - `*` matches any run of characters within one package segment;
+//! - `**` matches anything, including `/`, so `com/example/**` is a prefix match;
+//! - `?` matches one character other than `/`;
+//! - anything else matches itself.
+//!
+//! Nothing matches implicitly, so JDK classes are only transformed by a
+//! pattern that names them.
+//!
+//! Classes retransformed through
+//! [`Jvmti::retransform_with`](crate::env::Jvmti::retransform_with) go through
+//! the registry as well, after that call's closure and on its output.
+
+use crate::sys::jni;
+
+type Transformer = Box<dyn Fn(&str, &[u8]) -> Option<Vec<u8>> + Send + Sync>;
+
+/// Class name patterns and the transformers to run for matching classes.
+///
+/// Transformers see the internal class name and the current class file bytes
+/// and return replacement bytes, or `None` to pass the bytes on unchanged.
+/// They run in registration order and may be called from several threads at
+/// once, as classes load concurrently.
+#[derive(Default)]
+pub struct ClassTransformerRegistry {
+    transformers: Vec<(String, Transformer)>,
+}
+
+impl ClassTransformerRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `transformer` for classes matching `pattern`.
+    pub fn register<F>(mut self, pattern: &str, transformer: F) -> Self
+    where
+        F: Fn(&str, &[u8]) -> Option<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.transformers.push((pattern.replace('.', "/"), Box::new(transformer)));
+        self
+    }
+
+    /// Returns true if any pattern matches the internal class name `name`.
+    pub fn matches(&self, name: &str) -> bool {
+        self.transformers.iter().any(|(pattern, _)| pattern_matches(pattern.as_bytes(), name.as_bytes()))
+    }
+
+    /// Runs the transformers matching `name` in order, each on the previous
+    /// one's output. Returns the final bytes, or `None` if no transformer
+    /// replaced them.
+    ///
+    /// Panics from a transformer are handled like panics from [`Agent`](crate::Agent)
+    /// callbacks, per the [`PanicPolicy`](crate::PanicPolicy); a transformer
+    /// that panics under the swallow policy leaves the bytes unchanged.
+    pub fn transform(&self, name: &str, class_data: &[u8]) -> Option<Vec<u8>> {
+        let mut current: Option<Vec<u8>> = None;
+        for (pattern, transformer) in &self.transformers {
+            if !pattern_matches(pattern.as_bytes(), name.as_bytes()) {
+                continue;
+            }
+            let input = current.as_deref().unwrap_or(class_data);
+            if let Some(bytes) = crate::guard_agent_call("ClassFileLoadHook", None, || transformer(name, input)) {
+                current = Some(bytes);
+            }
+        }
+        current
+    }
+
+    /// Number of registered transformers.
+    pub fn len(&self) -> usize {
+        self.transformers.len()
+    }
+
+    /// Returns true if no transformer is registered.
+    pub fn is_empty(&self) -> bool {
+        self.transformers.is_empty()
+    }
+}
+
+/// Glob matching with backtracking, linear for typical patterns: on a
+/// mismatch the last `*` takes one more character, and once it would have to
+/// take a `/`, the last `**` does instead.
+fn pattern_matches(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Resume points as (pattern index after the star, name index it extends to).
+    let mut star: Option<(usize, usize)> = None;
+    let mut double_star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') if pattern.get(p + 1) == Some(&b'*') => {
+                p += 2;
+                double_star = Some((p, n));
+                star = None;
+                continue;
+            }
+            Some(b'*') => {
+                p += 1;
+                star = Some((p, n));
+                continue;
+            }
+            Some(b'?') if name[n] != b'/' => {
+                p += 1;
+                n += 1;
+                continue;
+            }
+            Some(&expected) if expected != b'?' && expected == name[n] => {
+                p += 1;
+                n += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match (star, double_star) {
+            (Some((star_p, star_n)), _) if name[star_n] != b'/' => {
+                star = Some((star_p, star_n + 1));
+                (p, n) = (star_p, star_n + 1);
+            }
+            (_, Some((double_p, double_n))) => {
+                star = None;
+                double_star = Some((double_p, double_n + 1));
+                (p, n) = (double_p, double_n + 1);
+            }
+            _ => return false,
+        }
+    }
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
+/// Replaces the class bytes in a `ClassFileLoadHook` with the registry's output.
+///
+/// Starts from bytes an earlier handler already installed, if any, and
+/// releases them when a transformer replaces them in turn.
+pub(crate) unsafe fn dispatch(
+    registry: &ClassTransformerRegistry, jvmti_env: &crate::env::Jvmti, name: *const std::os::raw::c_char,
+    class_data_len: jni::jint, class_data: *const std::os::raw::c_uchar,
+    new_class_data_len: *mut jni::jint, new_class_data: *mut *mut std::os::raw::c_uchar,
+) {
+    if new_class_data.is_null() || new_class_data_len.is_null() || class_data.is_null() || class_data_len < 0 {
+        return;
+    }
+    let Some(name) = crate::class_name(name) else { return };
+    if !registry.matches(name) {
+        return;
+    }
+    let previous = *new_class_data;
+    let input = if previous.is_null() {
+        crate::class_data_slice(class_data, class_data_len)
+    } else {
+        crate::class_data_slice(previous, *new_class_data_len)
+    };
+    let Some(bytes) = registry.transform(name, input) else { return };
+    if crate::set_new_class_data(jvmti_env, "class transformers", &bytes, new_class_data_len, new_class_data)
+        && !previous.is_null()
+    {
+        let _ = jvmti_env.deallocate(previous);
+    }
+}
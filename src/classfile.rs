@@ -3,8 +3,21 @@
 //! This module provides a zero-dependency parser for `.class` files,
 //! including all standard attributes defined from Java 8 through Java 27.
 
+use std::collections::HashMap;
 use std::fmt;
 
+use crate::mutf8;
+
+mod access_flags;
+mod annotations;
+pub mod bytecode;
+pub mod descriptor;
+pub mod instrument;
+mod maxes;
+
+pub use access_flags::{AccessFlags, FlagTarget};
+pub use annotations::{AnnotationInfo, AnnotationValue};
+
 #[derive(Debug, Clone)]
 pub struct ClassFile {
     pub minor_version: u16,
@@ -41,6 +54,16 @@ pub struct ConstantPool {
 }
 
 impl ConstantPool {
+    /// Parses a bare `constant_pool` table: the entries without the leading
+    /// `constant_pool_count`, which is passed as `count` instead.
+    ///
+    /// This is the layout `GetConstantPool` returns (see
+    /// [`Jvmti::get_constant_pool_parsed`](crate::env::Jvmti::get_constant_pool_parsed)).
+    /// Bytes after the last entry are ignored.
+    pub fn parse_entries(count: u16, bytes: &[u8]) -> Result<Self, ClassFileError> {
+        parse_constant_pool_entries(&mut Reader::new(bytes), count)
+    }
+
     pub fn get(&self, index: u16) -> Result<&CpInfo, ClassFileError> {
         if index == 0 {
             return Err(ClassFileError::InvalidConstantPoolIndex(index));
@@ -57,9 +80,170 @@ impl ConstantPool {
             _ => Err(ClassFileError::InvalidConstantPoolIndex(index)),
         }
     }
+
+    /// The `constant_pool_count` value: one more than the highest valid index.
+    ///
+    /// Index 0 and the slot following each `Long`/`Double` are unusable, so
+    /// [`ConstantPool::get`] rejects them.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.len() <= 1
+    }
+
+    /// Iterates over `(index, entry)` pairs, skipping unusable slots.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &CpInfo)> + '_ {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| entry.as_ref().map(|entry| (index as u16, entry)))
+    }
+
+    /// Resolves a `CONSTANT_Class` entry to its internal name, e.g. `java/lang/String`.
+    pub fn get_class_name(&self, index: u16) -> Result<&str, ClassFileError> {
+        match self.get(index)? {
+            CpInfo::Class { name_index } => self.get_utf8(*name_index),
+            _ => Err(ClassFileError::InvalidConstantPoolIndex(index)),
+        }
+    }
+
+    /// Resolves a `CONSTANT_NameAndType` entry to `(name, descriptor)`.
+    pub fn get_name_and_type(&self, index: u16) -> Result<(&str, &str), ClassFileError> {
+        match self.get(index)? {
+            CpInfo::NameAndType { name_index, descriptor_index } => {
+                Ok((self.get_utf8(*name_index)?, self.get_utf8(*descriptor_index)?))
+            }
+            _ => Err(ClassFileError::InvalidConstantPoolIndex(index)),
+        }
+    }
+
+    /// Resolves a `Fieldref`, `Methodref`, or `InterfaceMethodref` entry to
+    /// `(class_name, name, descriptor)`.
+    pub fn get_member_ref(&self, index: u16) -> Result<(&str, &str, &str), ClassFileError> {
+        match self.get(index)? {
+            CpInfo::Fieldref { class_index, name_and_type_index }
+            | CpInfo::Methodref { class_index, name_and_type_index }
+            | CpInfo::InterfaceMethodref { class_index, name_and_type_index } => {
+                let class_name = self.get_class_name(*class_index)?;
+                let (name, descriptor) = self.get_name_and_type(*name_and_type_index)?;
+                Ok((class_name, name, descriptor))
+            }
+            _ => Err(ClassFileError::InvalidConstantPoolIndex(index)),
+        }
+    }
+
+    /// Resolves a `CONSTANT_String` entry to its value.
+    pub fn get_string(&self, index: u16) -> Result<&str, ClassFileError> {
+        match self.get(index)? {
+            CpInfo::String { string_index } => self.get_utf8(*string_index),
+            _ => Err(ClassFileError::InvalidConstantPoolIndex(index)),
+        }
+    }
+
+    /// Adds `entry` and returns its index, reusing an identical existing entry.
+    ///
+    /// `Long` and `Double` take two slots. Returns `Unencodable` if the pool
+    /// would outgrow the 65535 slots a class file can address.
+    pub fn add(&mut self, entry: CpInfo) -> Result<u16, ClassFileError> {
+        if let Some((index, _)) = self.iter().find(|(_, existing)| **existing == entry) {
+            return Ok(index);
+        }
+        let wide = matches!(entry, CpInfo::Long(_) | CpInfo::Double(_));
+        let index = self.entries.len();
+        if index + usize::from(wide) > u16::MAX as usize - 1 {
+            return Err(ClassFileError::Unencodable("constant pool is full".to_string()));
+        }
+        self.entries.push(Some(entry));
+        if wide {
+            self.entries.push(None);
+        }
+        Ok(index as u16)
+    }
+
+    /// Adds (or finds) a `CONSTANT_Utf8` entry.
+    pub fn add_utf8(&mut self, value: &str) -> Result<u16, ClassFileError> {
+        self.add(CpInfo::Utf8(value.to_string()))
+    }
+
+    /// Adds (or finds) a `CONSTANT_Class` entry for an internal name such as `java/lang/String`.
+    pub fn add_class(&mut self, name: &str) -> Result<u16, ClassFileError> {
+        let name_index = self.add_utf8(name)?;
+        self.add(CpInfo::Class { name_index })
+    }
+
+    /// Adds (or finds) a `CONSTANT_String` entry.
+    pub fn add_string(&mut self, value: &str) -> Result<u16, ClassFileError> {
+        let string_index = self.add_utf8(value)?;
+        self.add(CpInfo::String { string_index })
+    }
+
+    /// Adds (or finds) a `CONSTANT_NameAndType` entry.
+    pub fn add_name_and_type(&mut self, name: &str, descriptor: &str) -> Result<u16, ClassFileError> {
+        let name_index = self.add_utf8(name)?;
+        let descriptor_index = self.add_utf8(descriptor)?;
+        self.add(CpInfo::NameAndType { name_index, descriptor_index })
+    }
+
+    /// Adds (or finds) a `CONSTANT_Methodref` entry for a method of class `owner`.
+    pub fn add_methodref(&mut self, owner: &str, name: &str, descriptor: &str) -> Result<u16, ClassFileError> {
+        let class_index = self.add_class(owner)?;
+        let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+        self.add(CpInfo::Methodref { class_index, name_and_type_index })
+    }
 }
 
-#[derive(Debug, Clone)]
+impl FieldInfo {
+    pub fn flags(&self) -> AccessFlags {
+        AccessFlags::field(self.access_flags)
+    }
+
+    pub fn name<'a>(&self, cp: &'a ConstantPool) -> Result<&'a str, ClassFileError> {
+        cp.get_utf8(self.name_index)
+    }
+
+    pub fn descriptor<'a>(&self, cp: &'a ConstantPool) -> Result<&'a str, ClassFileError> {
+        cp.get_utf8(self.descriptor_index)
+    }
+}
+
+impl MethodInfo {
+    pub fn flags(&self) -> AccessFlags {
+        AccessFlags::method(self.access_flags)
+    }
+
+    pub fn name<'a>(&self, cp: &'a ConstantPool) -> Result<&'a str, ClassFileError> {
+        cp.get_utf8(self.name_index)
+    }
+
+    pub fn descriptor<'a>(&self, cp: &'a ConstantPool) -> Result<&'a str, ClassFileError> {
+        cp.get_utf8(self.descriptor_index)
+    }
+
+    /// The method's `Code` attribute, or `None` for abstract and native methods.
+    ///
+    /// The attribute is parsed eagerly by [`ClassFile::parse`], including its
+    /// exception table and nested attributes (`LineNumberTable`,
+    /// `LocalVariableTable`, `StackMapTable`, ...).
+    pub fn code(&self) -> Option<&CodeAttribute> {
+        self.attributes.iter().find_map(|attr| match attr {
+            AttributeInfo::Code(code) => Some(code),
+            _ => None,
+        })
+    }
+
+    /// Mutable access to the `Code` attribute, for in-place instrumentation
+    /// before [`ClassFile::to_bytes`].
+    pub fn code_mut(&mut self) -> Option<&mut CodeAttribute> {
+        self.attributes.iter_mut().find_map(|attr| match attr {
+            AttributeInfo::Code(code) => Some(code),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum CpInfo {
     Utf8(String),
     Integer(i32),
@@ -127,6 +311,90 @@ pub struct CodeAttribute {
     pub attributes: Vec<AttributeInfo>,
 }
 
+impl CodeAttribute {
+    /// The `start_pc` to line mappings from every `LineNumberTable`
+    /// attribute, sorted by `start_pc`.
+    ///
+    /// A compiler may split the table over several attributes; this is the
+    /// class-file counterpart of `Jvmti::get_line_number_table`.
+    pub fn line_numbers(&self) -> Vec<LineNumberEntry> {
+        let mut lines: Vec<LineNumberEntry> = self
+            .attributes
+            .iter()
+            .filter_map(|attr| match attr {
+                AttributeInfo::LineNumberTable { entries } => Some(entries.iter().cloned()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        lines.sort_by_key(|entry| entry.start_pc);
+        lines
+    }
+
+    /// The source line of the instruction at `pc`: the entry with the
+    /// greatest `start_pc` not after `pc`.
+    pub fn line_number_at(&self, pc: u16) -> Option<u16> {
+        self.line_numbers()
+            .iter()
+            .rev()
+            .find(|entry| entry.start_pc <= pc)
+            .map(|entry| entry.line_number)
+    }
+
+    /// The `LocalVariableTable` entries with names and descriptors resolved,
+    /// plus the generic signature from `LocalVariableTypeTable` where present.
+    ///
+    /// Sorted by slot, then `start_pc`. Mirrors `Jvmti::get_local_variable_table`.
+    pub fn local_variables(&self, cp: &ConstantPool) -> Result<Vec<LocalVariable>, ClassFileError> {
+        let mut signatures = HashMap::new();
+        for attr in &self.attributes {
+            if let AttributeInfo::LocalVariableTypeTable { entries } = attr {
+                for entry in entries {
+                    signatures.insert((entry.index, entry.start_pc), cp.get_utf8(entry.signature_index)?);
+                }
+            }
+        }
+
+        let mut locals = Vec::new();
+        for attr in &self.attributes {
+            if let AttributeInfo::LocalVariableTable { entries } = attr {
+                for entry in entries {
+                    locals.push(LocalVariable {
+                        start_pc: entry.start_pc,
+                        length: entry.length,
+                        name: cp.get_utf8(entry.name_index)?.to_string(),
+                        descriptor: cp.get_utf8(entry.descriptor_index)?.to_string(),
+                        signature: signatures.get(&(entry.index, entry.start_pc)).map(|sig| sig.to_string()),
+                        index: entry.index,
+                    });
+                }
+            }
+        }
+        locals.sort_by_key(|local| (local.index, local.start_pc));
+        Ok(locals)
+    }
+}
+
+/// A `LocalVariableTable` entry with its constant pool references resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalVariable {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name: String,
+    pub descriptor: String,
+    /// Generic signature from `LocalVariableTypeTable`, e.g. `Ljava/util/List<Ljava/lang/String;>;`.
+    pub signature: Option<String>,
+    /// Local variable slot.
+    pub index: u16,
+}
+
+impl LocalVariable {
+    /// Returns true if the variable is in scope at `pc`.
+    pub fn is_live_at(&self, pc: u16) -> bool {
+        pc >= self.start_pc && (pc as u32) < self.start_pc as u32 + self.length as u32
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ExceptionTableEntry {
     pub start_pc: u16,
@@ -325,6 +593,9 @@ pub enum ClassFileError {
     InvalidConstantPoolTag(u8),
     InvalidUtf8,
     InvalidAttribute(String),
+    Unencodable(String),
+    InvalidOpcode(u8),
+    InvalidDescriptor(String),
 }
 
 impl fmt::Display for ClassFileError {
@@ -336,6 +607,9 @@ impl fmt::Display for ClassFileError {
             ClassFileError::InvalidConstantPoolTag(t) => write!(f, "invalid constant pool tag: {t}"),
             ClassFileError::InvalidUtf8 => write!(f, "invalid UTF-8"),
             ClassFileError::InvalidAttribute(name) => write!(f, "invalid attribute: {name}"),
+            ClassFileError::Unencodable(what) => write!(f, "cannot encode class file: {what}"),
+            ClassFileError::InvalidOpcode(op) => write!(f, "invalid opcode: {op:#04x}"),
+            ClassFileError::InvalidDescriptor(d) => write!(f, "invalid descriptor: {d}"),
         }
     }
 }
@@ -448,10 +722,77 @@ impl ClassFile {
             attributes,
         })
     }
+
+    pub fn flags(&self) -> AccessFlags {
+        AccessFlags::class(self.access_flags)
+    }
+
+    /// Internal name of this class, e.g. `com/example/Foo`.
+    pub fn this_class_name(&self) -> Result<&str, ClassFileError> {
+        self.constant_pool.get_class_name(self.this_class)
+    }
+
+    /// Internal name of the superclass, or `None` for `java/lang/Object`
+    /// (and `module-info`), whose `super_class` is 0.
+    pub fn super_class_name(&self) -> Result<Option<&str>, ClassFileError> {
+        if self.super_class == 0 {
+            return Ok(None);
+        }
+        self.constant_pool.get_class_name(self.super_class).map(Some)
+    }
+
+    /// Internal names of the directly implemented interfaces.
+    pub fn interface_names(&self) -> Result<Vec<&str>, ClassFileError> {
+        self.interfaces.iter().map(|&index| self.constant_pool.get_class_name(index)).collect()
+    }
+
+    /// Serializes the class back into `.class` file bytes.
+    ///
+    /// Unmodified output of [`ClassFile::parse`] is reproduced byte for byte
+    /// (assuming the constant pool has no duplicate attribute-name entries).
+    /// Attribute names are resolved through the constant pool, so any
+    /// attribute added to the tree must have its name present as a
+    /// `CONSTANT_Utf8` entry; otherwise [`ClassFileError::Unencodable`] is
+    /// returned, as it is for tables that exceed the classfile size limits.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ClassFileError> {
+        let mut w = Writer::new(&self.constant_pool);
+        w.write_u4(0xCAFEBABE);
+        w.write_u2(self.minor_version);
+        w.write_u2(self.major_version);
+        write_constant_pool(&mut w, &self.constant_pool)?;
+        w.write_u2(self.access_flags);
+        w.write_u2(self.this_class);
+        w.write_u2(self.super_class);
+        w.write_count(self.interfaces.len(), "interfaces")?;
+        for &interface in &self.interfaces {
+            w.write_u2(interface);
+        }
+        w.write_count(self.fields.len(), "fields")?;
+        for field in &self.fields {
+            w.write_u2(field.access_flags);
+            w.write_u2(field.name_index);
+            w.write_u2(field.descriptor_index);
+            write_attributes(&mut w, &field.attributes)?;
+        }
+        w.write_count(self.methods.len(), "methods")?;
+        for method in &self.methods {
+            w.write_u2(method.access_flags);
+            w.write_u2(method.name_index);
+            w.write_u2(method.descriptor_index);
+            write_attributes(&mut w, &method.attributes)?;
+        }
+        write_attributes(&mut w, &self.attributes)?;
+        Ok(w.out)
+    }
 }
 
 fn parse_constant_pool(r: &mut Reader) -> Result<ConstantPool, ClassFileError> {
-    let count = r.read_u2()? as usize;
+    let count = r.read_u2()?;
+    parse_constant_pool_entries(r, count)
+}
+
+fn parse_constant_pool_entries(r: &mut Reader, count: u16) -> Result<ConstantPool, ClassFileError> {
+    let count = count as usize;
     let mut entries: Vec<Option<CpInfo>> = Vec::with_capacity(count);
     entries.push(None); // index 0 is unused
 
@@ -462,8 +803,7 @@ fn parse_constant_pool(r: &mut Reader) -> Result<ConstantPool, ClassFileError> {
             1 => {
                 let len = r.read_u2()? as usize;
                 let bytes = r.read_bytes(len)?;
-                let s = String::from_utf8_lossy(bytes).to_string();
-                CpInfo::Utf8(s)
+                CpInfo::Utf8(mutf8::decode(bytes))
             }
             3 => CpInfo::Integer(r.read_u4()? as i32),
             4 => {
@@ -1015,3 +1355,523 @@ fn parse_module_attribute(r: &mut Reader) -> Result<ModuleAttribute, ClassFileEr
         provides,
     })
 }
+
+struct Writer<'a> {
+    out: Vec<u8>,
+    utf8_indices: HashMap<&'a str, u16>,
+}
+
+impl<'a> Writer<'a> {
+    fn new(cp: &'a ConstantPool) -> Self {
+        let mut utf8_indices = HashMap::new();
+        for (index, entry) in cp.entries.iter().enumerate() {
+            if let Some(CpInfo::Utf8(s)) = entry {
+                utf8_indices.entry(s.as_str()).or_insert(index as u16);
+            }
+        }
+        Self { out: Vec::new(), utf8_indices }
+    }
+
+    fn write_u1(&mut self, v: u8) {
+        self.out.push(v);
+    }
+
+    fn write_u2(&mut self, v: u16) {
+        self.out.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn write_u4(&mut self, v: u32) {
+        self.out.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.out.extend_from_slice(bytes);
+    }
+
+    fn write_count(&mut self, len: usize, what: &str) -> Result<(), ClassFileError> {
+        let len = u16::try_from(len).map_err(|_| ClassFileError::Unencodable(format!("too many {what}")))?;
+        self.write_u2(len);
+        Ok(())
+    }
+
+    fn write_count_u1(&mut self, len: usize, what: &str) -> Result<(), ClassFileError> {
+        let len = u8::try_from(len).map_err(|_| ClassFileError::Unencodable(format!("too many {what}")))?;
+        self.write_u1(len);
+        Ok(())
+    }
+
+    fn utf8_index(&self, name: &str) -> Result<u16, ClassFileError> {
+        self.utf8_indices
+            .get(name)
+            .copied()
+            .ok_or_else(|| ClassFileError::Unencodable(format!("attribute name `{name}` is not in the constant pool")))
+    }
+}
+
+fn write_constant_pool(w: &mut Writer, cp: &ConstantPool) -> Result<(), ClassFileError> {
+    w.write_count(cp.entries.len(), "constant pool entries")?;
+    for entry in cp.entries.iter().flatten() {
+        match entry {
+            CpInfo::Utf8(s) => {
+                let bytes = mutf8::encode(s);
+                w.write_u1(1);
+                w.write_count(bytes.len(), "bytes in a CONSTANT_Utf8 entry")?;
+                w.write_bytes(&bytes);
+            }
+            CpInfo::Integer(v) => {
+                w.write_u1(3);
+                w.write_u4(*v as u32);
+            }
+            CpInfo::Float(v) => {
+                w.write_u1(4);
+                w.write_u4(v.to_bits());
+            }
+            CpInfo::Long(v) => {
+                w.write_u1(5);
+                w.write_bytes(&v.to_be_bytes());
+            }
+            CpInfo::Double(v) => {
+                w.write_u1(6);
+                w.write_bytes(&v.to_bits().to_be_bytes());
+            }
+            CpInfo::Class { name_index } => {
+                w.write_u1(7);
+                w.write_u2(*name_index);
+            }
+            CpInfo::String { string_index } => {
+                w.write_u1(8);
+                w.write_u2(*string_index);
+            }
+            CpInfo::Fieldref { class_index, name_and_type_index } => {
+                w.write_u1(9);
+                w.write_u2(*class_index);
+                w.write_u2(*name_and_type_index);
+            }
+            CpInfo::Methodref { class_index, name_and_type_index } => {
+                w.write_u1(10);
+                w.write_u2(*class_index);
+                w.write_u2(*name_and_type_index);
+            }
+            CpInfo::InterfaceMethodref { class_index, name_and_type_index } => {
+                w.write_u1(11);
+                w.write_u2(*class_index);
+                w.write_u2(*name_and_type_index);
+            }
+            CpInfo::NameAndType { name_index, descriptor_index } => {
+                w.write_u1(12);
+                w.write_u2(*name_index);
+                w.write_u2(*descriptor_index);
+            }
+            CpInfo::MethodHandle { reference_kind, reference_index } => {
+                w.write_u1(15);
+                w.write_u1(*reference_kind);
+                w.write_u2(*reference_index);
+            }
+            CpInfo::MethodType { descriptor_index } => {
+                w.write_u1(16);
+                w.write_u2(*descriptor_index);
+            }
+            CpInfo::Dynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                w.write_u1(17);
+                w.write_u2(*bootstrap_method_attr_index);
+                w.write_u2(*name_and_type_index);
+            }
+            CpInfo::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                w.write_u1(18);
+                w.write_u2(*bootstrap_method_attr_index);
+                w.write_u2(*name_and_type_index);
+            }
+            CpInfo::Module { name_index } => {
+                w.write_u1(19);
+                w.write_u2(*name_index);
+            }
+            CpInfo::Package { name_index } => {
+                w.write_u1(20);
+                w.write_u2(*name_index);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn attribute_name(attr: &AttributeInfo) -> &str {
+    match attr {
+        AttributeInfo::ConstantValue { .. } => "ConstantValue",
+        AttributeInfo::Code(_) => "Code",
+        AttributeInfo::StackMapTable(_) => "StackMapTable",
+        AttributeInfo::Exceptions { .. } => "Exceptions",
+        AttributeInfo::InnerClasses { .. } => "InnerClasses",
+        AttributeInfo::EnclosingMethod { .. } => "EnclosingMethod",
+        AttributeInfo::Synthetic => "Synthetic",
+        AttributeInfo::Signature { .. } => "Signature",
+        AttributeInfo::SourceFile { .. } => "SourceFile",
+        AttributeInfo::SourceDebugExtension { .. } => "SourceDebugExtension",
+        AttributeInfo::LineNumberTable { .. } => "LineNumberTable",
+        AttributeInfo::LocalVariableTable { .. } => "LocalVariableTable",
+        AttributeInfo::LocalVariableTypeTable { .. } => "LocalVariableTypeTable",
+        AttributeInfo::Deprecated => "Deprecated",
+        AttributeInfo::RuntimeVisibleAnnotations { .. } => "RuntimeVisibleAnnotations",
+        AttributeInfo::RuntimeInvisibleAnnotations { .. } => "RuntimeInvisibleAnnotations",
+        AttributeInfo::RuntimeVisibleParameterAnnotations { .. } => "RuntimeVisibleParameterAnnotations",
+        AttributeInfo::RuntimeInvisibleParameterAnnotations { .. } => "RuntimeInvisibleParameterAnnotations",
+        AttributeInfo::RuntimeVisibleTypeAnnotations { .. } => "RuntimeVisibleTypeAnnotations",
+        AttributeInfo::RuntimeInvisibleTypeAnnotations { .. } => "RuntimeInvisibleTypeAnnotations",
+        AttributeInfo::AnnotationDefault { .. } => "AnnotationDefault",
+        AttributeInfo::BootstrapMethods { .. } => "BootstrapMethods",
+        AttributeInfo::MethodParameters { .. } => "MethodParameters",
+        AttributeInfo::Module(_) => "Module",
+        AttributeInfo::ModulePackages { .. } => "ModulePackages",
+        AttributeInfo::ModuleMainClass { .. } => "ModuleMainClass",
+        AttributeInfo::ModuleHashes { .. } => "ModuleHashes",
+        AttributeInfo::ModuleTarget { .. } => "ModuleTarget",
+        AttributeInfo::ModuleResolution { .. } => "ModuleResolution",
+        AttributeInfo::NestHost { .. } => "NestHost",
+        AttributeInfo::NestMembers { .. } => "NestMembers",
+        AttributeInfo::Record { .. } => "Record",
+        AttributeInfo::PermittedSubclasses { .. } => "PermittedSubclasses",
+        AttributeInfo::Unknown { name, .. } => name,
+    }
+}
+
+fn write_attributes(w: &mut Writer, attrs: &[AttributeInfo]) -> Result<(), ClassFileError> {
+    w.write_count(attrs.len(), "attributes")?;
+    for attr in attrs {
+        let name_index = w.utf8_index(attribute_name(attr))?;
+        w.write_u2(name_index);
+        let length_pos = w.out.len();
+        w.write_u4(0);
+        write_attribute_body(w, attr)?;
+        let length = u32::try_from(w.out.len() - length_pos - 4)
+            .map_err(|_| ClassFileError::Unencodable(format!("attribute `{}` is too large", attribute_name(attr))))?;
+        w.out[length_pos..length_pos + 4].copy_from_slice(&length.to_be_bytes());
+    }
+    Ok(())
+}
+
+fn write_u2_list(w: &mut Writer, values: &[u16], what: &str) -> Result<(), ClassFileError> {
+    w.write_count(values.len(), what)?;
+    for &v in values {
+        w.write_u2(v);
+    }
+    Ok(())
+}
+
+fn write_attribute_body(w: &mut Writer, attr: &AttributeInfo) -> Result<(), ClassFileError> {
+    match attr {
+        AttributeInfo::ConstantValue { constantvalue_index } => w.write_u2(*constantvalue_index),
+        AttributeInfo::Code(code) => write_code_attribute(w, code)?,
+        AttributeInfo::StackMapTable(table) => write_stack_map_table(w, table)?,
+        AttributeInfo::Exceptions { exception_index_table } => {
+            write_u2_list(w, exception_index_table, "exceptions")?;
+        }
+        AttributeInfo::InnerClasses { classes } => {
+            w.write_count(classes.len(), "inner classes")?;
+            for class in classes {
+                w.write_u2(class.inner_class_info_index);
+                w.write_u2(class.outer_class_info_index);
+                w.write_u2(class.inner_name_index);
+                w.write_u2(class.inner_class_access_flags);
+            }
+        }
+        AttributeInfo::EnclosingMethod { class_index, method_index } => {
+            w.write_u2(*class_index);
+            w.write_u2(*method_index);
+        }
+        AttributeInfo::Synthetic | AttributeInfo::Deprecated => {}
+        AttributeInfo::Signature { signature_index } => w.write_u2(*signature_index),
+        AttributeInfo::SourceFile { sourcefile_index } => w.write_u2(*sourcefile_index),
+        AttributeInfo::SourceDebugExtension { debug_extension } => w.write_bytes(debug_extension),
+        AttributeInfo::LineNumberTable { entries } => {
+            w.write_count(entries.len(), "line number entries")?;
+            for entry in entries {
+                w.write_u2(entry.start_pc);
+                w.write_u2(entry.line_number);
+            }
+        }
+        AttributeInfo::LocalVariableTable { entries } => {
+            w.write_count(entries.len(), "local variable entries")?;
+            for entry in entries {
+                w.write_u2(entry.start_pc);
+                w.write_u2(entry.length);
+                w.write_u2(entry.name_index);
+                w.write_u2(entry.descriptor_index);
+                w.write_u2(entry.index);
+            }
+        }
+        AttributeInfo::LocalVariableTypeTable { entries } => {
+            w.write_count(entries.len(), "local variable type entries")?;
+            for entry in entries {
+                w.write_u2(entry.start_pc);
+                w.write_u2(entry.length);
+                w.write_u2(entry.name_index);
+                w.write_u2(entry.signature_index);
+                w.write_u2(entry.index);
+            }
+        }
+        AttributeInfo::RuntimeVisibleAnnotations { annotations }
+        | AttributeInfo::RuntimeInvisibleAnnotations { annotations } => write_annotations(w, annotations)?,
+        AttributeInfo::RuntimeVisibleParameterAnnotations { parameter_annotations }
+        | AttributeInfo::RuntimeInvisibleParameterAnnotations { parameter_annotations } => {
+            w.write_count_u1(parameter_annotations.len(), "annotated parameters")?;
+            for annotations in parameter_annotations {
+                write_annotations(w, annotations)?;
+            }
+        }
+        AttributeInfo::RuntimeVisibleTypeAnnotations { annotations }
+        | AttributeInfo::RuntimeInvisibleTypeAnnotations { annotations } => write_type_annotations(w, annotations)?,
+        AttributeInfo::AnnotationDefault { default_value } => write_element_value(w, default_value)?,
+        AttributeInfo::BootstrapMethods { methods } => {
+            w.write_count(methods.len(), "bootstrap methods")?;
+            for method in methods {
+                w.write_u2(method.bootstrap_method_ref);
+                write_u2_list(w, &method.bootstrap_arguments, "bootstrap arguments")?;
+            }
+        }
+        AttributeInfo::MethodParameters { parameters } => {
+            w.write_count_u1(parameters.len(), "method parameters")?;
+            for parameter in parameters {
+                w.write_u2(parameter.name_index);
+                w.write_u2(parameter.access_flags);
+            }
+        }
+        AttributeInfo::Module(module) => write_module_attribute(w, module)?,
+        AttributeInfo::ModulePackages { packages } => write_u2_list(w, packages, "module packages")?,
+        AttributeInfo::ModuleMainClass { main_class_index } => w.write_u2(*main_class_index),
+        AttributeInfo::ModuleHashes { algorithm_index, modules } => {
+            w.write_u2(*algorithm_index);
+            w.write_count(modules.len(), "module hashes")?;
+            for module in modules {
+                w.write_u2(module.module_name_index);
+                w.write_count(module.hash.len(), "hash bytes")?;
+                w.write_bytes(&module.hash);
+            }
+        }
+        AttributeInfo::ModuleTarget { target_platform_index } => w.write_u2(*target_platform_index),
+        AttributeInfo::ModuleResolution { resolution_flags } => w.write_u2(*resolution_flags),
+        AttributeInfo::NestHost { host_class_index } => w.write_u2(*host_class_index),
+        AttributeInfo::NestMembers { classes } => write_u2_list(w, classes, "nest members")?,
+        AttributeInfo::Record { components } => {
+            w.write_count(components.len(), "record components")?;
+            for component in components {
+                w.write_u2(component.name_index);
+                w.write_u2(component.descriptor_index);
+                write_attributes(w, &component.attributes)?;
+            }
+        }
+        AttributeInfo::PermittedSubclasses { classes } => write_u2_list(w, classes, "permitted subclasses")?,
+        AttributeInfo::Unknown { info, .. } => w.write_bytes(info),
+    }
+    Ok(())
+}
+
+fn write_code_attribute(w: &mut Writer, code: &CodeAttribute) -> Result<(), ClassFileError> {
+    w.write_u2(code.max_stack);
+    w.write_u2(code.max_locals);
+    let code_length = u32::try_from(code.code.len())
+        .map_err(|_| ClassFileError::Unencodable("method code is too large".to_string()))?;
+    w.write_u4(code_length);
+    w.write_bytes(&code.code);
+    w.write_count(code.exception_table.len(), "exception table entries")?;
+    for entry in &code.exception_table {
+        w.write_u2(entry.start_pc);
+        w.write_u2(entry.end_pc);
+        w.write_u2(entry.handler_pc);
+        w.write_u2(entry.catch_type);
+    }
+    write_attributes(w, &code.attributes)
+}
+
+fn write_stack_map_table(w: &mut Writer, table: &StackMapTableAttribute) -> Result<(), ClassFileError> {
+    w.write_count(table.entries.len(), "stack map frames")?;
+    for frame in &table.entries {
+        match frame {
+            StackMapFrame::Same { offset_delta } if *offset_delta <= 63 => w.write_u1(*offset_delta as u8),
+            StackMapFrame::SameLocals1StackItem { offset_delta, stack } if *offset_delta <= 63 => {
+                w.write_u1(64 + *offset_delta as u8);
+                write_verification_type_info(w, stack);
+            }
+            StackMapFrame::Same { offset_delta } | StackMapFrame::SameExtended { offset_delta } => {
+                w.write_u1(251);
+                w.write_u2(*offset_delta);
+            }
+            StackMapFrame::SameLocals1StackItem { offset_delta, stack }
+            | StackMapFrame::SameLocals1StackItemExtended { offset_delta, stack } => {
+                w.write_u1(247);
+                w.write_u2(*offset_delta);
+                write_verification_type_info(w, stack);
+            }
+            StackMapFrame::Chop { offset_delta, k } => {
+                if !(1..=3).contains(k) {
+                    return Err(ClassFileError::Unencodable(format!("chop frame with k = {k}")));
+                }
+                w.write_u1(251 - *k);
+                w.write_u2(*offset_delta);
+            }
+            StackMapFrame::Append { offset_delta, locals } => {
+                if !(1..=3).contains(&locals.len()) {
+                    return Err(ClassFileError::Unencodable(format!("append frame with {} locals", locals.len())));
+                }
+                w.write_u1(251 + locals.len() as u8);
+                w.write_u2(*offset_delta);
+                for local in locals {
+                    write_verification_type_info(w, local);
+                }
+            }
+            StackMapFrame::Full { offset_delta, locals, stack } => {
+                w.write_u1(255);
+                w.write_u2(*offset_delta);
+                w.write_count(locals.len(), "stack map locals")?;
+                for local in locals {
+                    write_verification_type_info(w, local);
+                }
+                w.write_count(stack.len(), "stack map stack items")?;
+                for item in stack {
+                    write_verification_type_info(w, item);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_verification_type_info(w: &mut Writer, info: &VerificationTypeInfo) {
+    match info {
+        VerificationTypeInfo::Top => w.write_u1(0),
+        VerificationTypeInfo::Integer => w.write_u1(1),
+        VerificationTypeInfo::Float => w.write_u1(2),
+        VerificationTypeInfo::Double => w.write_u1(3),
+        VerificationTypeInfo::Long => w.write_u1(4),
+        VerificationTypeInfo::Null => w.write_u1(5),
+        VerificationTypeInfo::UninitializedThis => w.write_u1(6),
+        VerificationTypeInfo::Object(index) => {
+            w.write_u1(7);
+            w.write_u2(*index);
+        }
+        VerificationTypeInfo::Uninitialized(offset) => {
+            w.write_u1(8);
+            w.write_u2(*offset);
+        }
+    }
+}
+
+fn write_annotations(w: &mut Writer, annotations: &[Annotation]) -> Result<(), ClassFileError> {
+    w.write_count(annotations.len(), "annotations")?;
+    for annotation in annotations {
+        write_annotation(w, annotation)?;
+    }
+    Ok(())
+}
+
+fn write_annotation(w: &mut Writer, annotation: &Annotation) -> Result<(), ClassFileError> {
+    w.write_u2(annotation.type_index);
+    write_element_value_pairs(w, &annotation.element_value_pairs)
+}
+
+fn write_element_value_pairs(w: &mut Writer, pairs: &[ElementValuePair]) -> Result<(), ClassFileError> {
+    w.write_count(pairs.len(), "annotation element values")?;
+    for pair in pairs {
+        w.write_u2(pair.element_name_index);
+        write_element_value(w, &pair.value)?;
+    }
+    Ok(())
+}
+
+fn write_element_value(w: &mut Writer, value: &ElementValue) -> Result<(), ClassFileError> {
+    match value {
+        ElementValue::Const { tag, const_value_index } => {
+            w.write_u1(*tag);
+            w.write_u2(*const_value_index);
+        }
+        ElementValue::EnumConst { type_name_index, const_name_index } => {
+            w.write_u1(b'e');
+            w.write_u2(*type_name_index);
+            w.write_u2(*const_name_index);
+        }
+        ElementValue::ClassInfo { class_info_index } => {
+            w.write_u1(b'c');
+            w.write_u2(*class_info_index);
+        }
+        ElementValue::AnnotationValue(annotation) => {
+            w.write_u1(b'@');
+            write_annotation(w, annotation)?;
+        }
+        ElementValue::ArrayValue(values) => {
+            w.write_u1(b'[');
+            w.write_count(values.len(), "array element values")?;
+            for value in values {
+                write_element_value(w, value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_type_annotations(w: &mut Writer, annotations: &[TypeAnnotation]) -> Result<(), ClassFileError> {
+    w.write_count(annotations.len(), "type annotations")?;
+    for annotation in annotations {
+        w.write_u1(annotation.target_type);
+        match &annotation.target_info {
+            TargetInfo::TypeParameter { index } | TargetInfo::FormalParameter { index } => w.write_u1(*index),
+            TargetInfo::Supertype { index } | TargetInfo::Throws { index } => w.write_u2(*index),
+            TargetInfo::TypeParameterBound { type_parameter_index, bound_index } => {
+                w.write_u1(*type_parameter_index);
+                w.write_u1(*bound_index);
+            }
+            TargetInfo::Empty => {}
+            TargetInfo::Localvar { table } => {
+                w.write_count(table.len(), "localvar targets")?;
+                for entry in table {
+                    w.write_u2(entry.start_pc);
+                    w.write_u2(entry.length);
+                    w.write_u2(entry.index);
+                }
+            }
+            TargetInfo::Catch { exception_table_index } => w.write_u2(*exception_table_index),
+            TargetInfo::Offset { offset } => w.write_u2(*offset),
+            TargetInfo::TypeArgument { offset, type_argument_index } => {
+                w.write_u2(*offset);
+                w.write_u1(*type_argument_index);
+            }
+        }
+        w.write_count_u1(annotation.target_path.len(), "type path entries")?;
+        for entry in &annotation.target_path {
+            w.write_u1(entry.type_path_kind);
+            w.write_u1(entry.type_argument_index);
+        }
+        w.write_u2(annotation.type_index);
+        write_element_value_pairs(w, &annotation.element_value_pairs)?;
+    }
+    Ok(())
+}
+
+fn write_module_attribute(w: &mut Writer, module: &ModuleAttribute) -> Result<(), ClassFileError> {
+    w.write_u2(module.module_name_index);
+    w.write_u2(module.module_flags);
+    w.write_u2(module.module_version_index);
+    w.write_count(module.requires.len(), "module requires")?;
+    for requires in &module.requires {
+        w.write_u2(requires.requires_index);
+        w.write_u2(requires.requires_flags);
+        w.write_u2(requires.requires_version_index);
+    }
+    w.write_count(module.exports.len(), "module exports")?;
+    for exports in &module.exports {
+        w.write_u2(exports.exports_index);
+        w.write_u2(exports.exports_flags);
+        write_u2_list(w, &exports.exports_to, "module exports targets")?;
+    }
+    w.write_count(module.opens.len(), "module opens")?;
+    for opens in &module.opens {
+        w.write_u2(opens.opens_index);
+        w.write_u2(opens.opens_flags);
+        write_u2_list(w, &opens.opens_to, "module opens targets")?;
+    }
+    write_u2_list(w, &module.uses, "module uses")?;
+    w.write_count(module.provides.len(), "module provides")?;
+    for provides in &module.provides {
+        w.write_u2(provides.provides_index);
+        write_u2_list(w, &provides.provides_with, "module provides implementations")?;
+    }
+    Ok(())
+}
+
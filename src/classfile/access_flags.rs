@@ -0,0 +1,209 @@
+//! Access and property flags shared by class files and JVMTI modifiers.
+
+use std::fmt;
+
+/// What an [`AccessFlags`] value describes.
+///
+/// Several bits mean different things depending on the target
+/// (`0x0020` is `ACC_SUPER` on classes but `ACC_SYNCHRONIZED` on methods), so
+/// `Display` uses the target to choose keywords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FlagTarget {
+    Class,
+    Field,
+    Method,
+}
+
+/// Typed view over `access_flags` / JVMTI modifier bitmasks.
+///
+/// The predicates test raw bits; the ones that share a bit (`is_super` and
+/// `is_synchronized`, `is_volatile` and `is_bridge`, `is_transient` and
+/// `is_varargs`) are only meaningful for the matching [`FlagTarget`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AccessFlags {
+    bits: u16,
+    target: FlagTarget,
+}
+
+impl AccessFlags {
+    pub const PUBLIC: u16 = 0x0001;
+    pub const PRIVATE: u16 = 0x0002;
+    pub const PROTECTED: u16 = 0x0004;
+    pub const STATIC: u16 = 0x0008;
+    pub const FINAL: u16 = 0x0010;
+    pub const SUPER: u16 = 0x0020;
+    pub const SYNCHRONIZED: u16 = 0x0020;
+    pub const VOLATILE: u16 = 0x0040;
+    pub const BRIDGE: u16 = 0x0040;
+    pub const TRANSIENT: u16 = 0x0080;
+    pub const VARARGS: u16 = 0x0080;
+    pub const NATIVE: u16 = 0x0100;
+    pub const INTERFACE: u16 = 0x0200;
+    pub const ABSTRACT: u16 = 0x0400;
+    pub const STRICT: u16 = 0x0800;
+    pub const SYNTHETIC: u16 = 0x1000;
+    pub const ANNOTATION: u16 = 0x2000;
+    pub const ENUM: u16 = 0x4000;
+    pub const MODULE: u16 = 0x8000;
+    pub const MANDATED: u16 = 0x8000;
+
+    pub const fn new(bits: u16, target: FlagTarget) -> Self {
+        Self { bits, target }
+    }
+
+    pub const fn class(bits: u16) -> Self {
+        Self::new(bits, FlagTarget::Class)
+    }
+
+    pub const fn field(bits: u16) -> Self {
+        Self::new(bits, FlagTarget::Field)
+    }
+
+    pub const fn method(bits: u16) -> Self {
+        Self::new(bits, FlagTarget::Method)
+    }
+
+    /// The raw flag bits.
+    pub const fn bits(self) -> u16 {
+        self.bits
+    }
+
+    pub const fn target(self) -> FlagTarget {
+        self.target
+    }
+
+    /// Returns `true` if every bit in `flag` is set.
+    pub const fn contains(self, flag: u16) -> bool {
+        self.bits & flag == flag
+    }
+
+    pub const fn is_public(self) -> bool {
+        self.contains(Self::PUBLIC)
+    }
+
+    pub const fn is_private(self) -> bool {
+        self.contains(Self::PRIVATE)
+    }
+
+    pub const fn is_protected(self) -> bool {
+        self.contains(Self::PROTECTED)
+    }
+
+    pub const fn is_static(self) -> bool {
+        self.contains(Self::STATIC)
+    }
+
+    pub const fn is_final(self) -> bool {
+        self.contains(Self::FINAL)
+    }
+
+    pub const fn is_super(self) -> bool {
+        self.contains(Self::SUPER)
+    }
+
+    pub const fn is_synchronized(self) -> bool {
+        self.contains(Self::SYNCHRONIZED)
+    }
+
+    pub const fn is_volatile(self) -> bool {
+        self.contains(Self::VOLATILE)
+    }
+
+    pub const fn is_bridge(self) -> bool {
+        self.contains(Self::BRIDGE)
+    }
+
+    pub const fn is_transient(self) -> bool {
+        self.contains(Self::TRANSIENT)
+    }
+
+    pub const fn is_varargs(self) -> bool {
+        self.contains(Self::VARARGS)
+    }
+
+    pub const fn is_native(self) -> bool {
+        self.contains(Self::NATIVE)
+    }
+
+    pub const fn is_interface(self) -> bool {
+        self.contains(Self::INTERFACE)
+    }
+
+    pub const fn is_abstract(self) -> bool {
+        self.contains(Self::ABSTRACT)
+    }
+
+    pub const fn is_strict(self) -> bool {
+        self.contains(Self::STRICT)
+    }
+
+    pub const fn is_synthetic(self) -> bool {
+        self.contains(Self::SYNTHETIC)
+    }
+
+    pub const fn is_annotation(self) -> bool {
+        self.contains(Self::ANNOTATION)
+    }
+
+    pub const fn is_enum(self) -> bool {
+        self.contains(Self::ENUM)
+    }
+
+    pub const fn is_module(self) -> bool {
+        self.contains(Self::MODULE)
+    }
+
+    pub const fn is_mandated(self) -> bool {
+        self.contains(Self::MANDATED)
+    }
+}
+
+impl fmt::Display for AccessFlags {
+    /// Prints the Java source modifiers in canonical order, e.g. `public static final`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keywords: &[(u16, &str)] = match self.target {
+            FlagTarget::Class => &[
+                (Self::PUBLIC, "public"),
+                (Self::PROTECTED, "protected"),
+                (Self::PRIVATE, "private"),
+                (Self::ABSTRACT, "abstract"),
+                (Self::STATIC, "static"),
+                (Self::FINAL, "final"),
+                (Self::STRICT, "strictfp"),
+            ],
+            FlagTarget::Field => &[
+                (Self::PUBLIC, "public"),
+                (Self::PROTECTED, "protected"),
+                (Self::PRIVATE, "private"),
+                (Self::STATIC, "static"),
+                (Self::FINAL, "final"),
+                (Self::TRANSIENT, "transient"),
+                (Self::VOLATILE, "volatile"),
+            ],
+            FlagTarget::Method => &[
+                (Self::PUBLIC, "public"),
+                (Self::PROTECTED, "protected"),
+                (Self::PRIVATE, "private"),
+                (Self::ABSTRACT, "abstract"),
+                (Self::STATIC, "static"),
+                (Self::FINAL, "final"),
+                (Self::SYNCHRONIZED, "synchronized"),
+                (Self::NATIVE, "native"),
+                (Self::STRICT, "strictfp"),
+            ],
+        };
+        // Interfaces are implicitly abstract; javap omits the keyword.
+        let skip = if self.target == FlagTarget::Class && self.is_interface() { Self::ABSTRACT } else { 0 };
+        let mut first = true;
+        for &(flag, keyword) in keywords {
+            if flag != skip && self.contains(flag) {
+                if !first {
+                    f.write_str(" ")?;
+                }
+                f.write_str(keyword)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
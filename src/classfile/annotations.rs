@@ -0,0 +1,180 @@
+//! Annotations with their constant pool references resolved.
+//!
+//! The parser keeps `RuntimeVisibleAnnotations` as [`Annotation`]s holding
+//! constant pool indices. [`AnnotationInfo`] resolves them to type
+//! descriptors, element names, and typed values, which is what selecting
+//! methods by annotation needs:
+//!
+//! ```rust,ignore
+//! let class = ClassFile::parse(bytes)?;
+//! for method in &class.methods {
+//!     if method.has_annotation(&class.constant_pool, "Lcom/example/Timed;")? {
+//!         // instrument it
+//!     }
+//! }
+//! ```
+
+use super::descriptor::signature_to_java_name;
+use super::{Annotation, AttributeInfo, ClassFile, ClassFileError, ConstantPool, CpInfo, ElementValue, FieldInfo, MethodInfo};
+
+/// A resolved annotation, e.g. `@Timed(value = "db", percentiles = {0.5, 0.99})`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationInfo {
+    /// Field descriptor of the annotation interface, e.g. `Lcom/example/Timed;`.
+    pub type_descriptor: String,
+    /// Explicitly given elements in class file order; defaults are not included.
+    pub elements: Vec<(String, AnnotationValue)>,
+}
+
+/// A resolved annotation element value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnnotationValue {
+    Byte(i8),
+    Char(u16),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Boolean(bool),
+    String(String),
+    /// An enum constant, by the enum's field descriptor and the constant name.
+    Enum { type_descriptor: String, const_name: String },
+    /// A class literal, by return descriptor (`Ljava/lang/String;`, `I`, `V`).
+    Class(String),
+    Annotation(AnnotationInfo),
+    Array(Vec<AnnotationValue>),
+}
+
+impl AnnotationInfo {
+    /// The annotation type's Java name, e.g. `com.example.Timed`.
+    pub fn type_name(&self) -> String {
+        signature_to_java_name(&self.type_descriptor)
+    }
+
+    /// The value of element `name`, if it was given explicitly.
+    pub fn get(&self, name: &str) -> Option<&AnnotationValue> {
+        self.elements.iter().find(|(element, _)| element == name).map(|(_, value)| value)
+    }
+}
+
+impl Annotation {
+    /// Resolves the type and element values through `cp`.
+    pub fn resolve(&self, cp: &ConstantPool) -> Result<AnnotationInfo, ClassFileError> {
+        let mut elements = Vec::with_capacity(self.element_value_pairs.len());
+        for pair in &self.element_value_pairs {
+            elements.push((cp.get_utf8(pair.element_name_index)?.to_string(), pair.value.resolve(cp)?));
+        }
+        Ok(AnnotationInfo { type_descriptor: cp.get_utf8(self.type_index)?.to_string(), elements })
+    }
+}
+
+impl ElementValue {
+    /// Resolves a value through `cp`, checking the constant type against the tag.
+    pub fn resolve(&self, cp: &ConstantPool) -> Result<AnnotationValue, ClassFileError> {
+        Ok(match self {
+            ElementValue::Const { tag, const_value_index } => {
+                let index = *const_value_index;
+                match (tag, cp.get(index)?) {
+                    (b'B', CpInfo::Integer(v)) => AnnotationValue::Byte(*v as i8),
+                    (b'C', CpInfo::Integer(v)) => AnnotationValue::Char(*v as u16),
+                    (b'S', CpInfo::Integer(v)) => AnnotationValue::Short(*v as i16),
+                    (b'I', CpInfo::Integer(v)) => AnnotationValue::Int(*v),
+                    (b'Z', CpInfo::Integer(v)) => AnnotationValue::Boolean(*v != 0),
+                    (b'J', CpInfo::Long(v)) => AnnotationValue::Long(*v),
+                    (b'F', CpInfo::Float(v)) => AnnotationValue::Float(*v),
+                    (b'D', CpInfo::Double(v)) => AnnotationValue::Double(*v),
+                    (b's', CpInfo::Utf8(v)) => AnnotationValue::String(v.clone()),
+                    _ => return Err(ClassFileError::InvalidConstantPoolIndex(index)),
+                }
+            }
+            ElementValue::EnumConst { type_name_index, const_name_index } => AnnotationValue::Enum {
+                type_descriptor: cp.get_utf8(*type_name_index)?.to_string(),
+                const_name: cp.get_utf8(*const_name_index)?.to_string(),
+            },
+            ElementValue::ClassInfo { class_info_index } => AnnotationValue::Class(cp.get_utf8(*class_info_index)?.to_string()),
+            ElementValue::AnnotationValue(annotation) => AnnotationValue::Annotation(annotation.resolve(cp)?),
+            ElementValue::ArrayValue(values) => {
+                AnnotationValue::Array(values.iter().map(|value| value.resolve(cp)).collect::<Result<_, _>>()?)
+            }
+        })
+    }
+}
+
+fn visible_annotations(attributes: &[AttributeInfo], cp: &ConstantPool) -> Result<Vec<AnnotationInfo>, ClassFileError> {
+    let mut out = Vec::new();
+    for attr in attributes {
+        if let AttributeInfo::RuntimeVisibleAnnotations { annotations } = attr {
+            for annotation in annotations {
+                out.push(annotation.resolve(cp)?);
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn has_visible_annotation(attributes: &[AttributeInfo], cp: &ConstantPool, type_descriptor: &str) -> Result<bool, ClassFileError> {
+    for attr in attributes {
+        if let AttributeInfo::RuntimeVisibleAnnotations { annotations } = attr {
+            for annotation in annotations {
+                if cp.get_utf8(annotation.type_index)? == type_descriptor {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
+impl ClassFile {
+    /// The class's `RuntimeVisibleAnnotations`, resolved.
+    pub fn annotations(&self) -> Result<Vec<AnnotationInfo>, ClassFileError> {
+        visible_annotations(&self.attributes, &self.constant_pool)
+    }
+
+    /// Returns true if the class carries a runtime-visible annotation of
+    /// `type_descriptor`, e.g. `Lcom/example/Component;`.
+    pub fn has_annotation(&self, type_descriptor: &str) -> Result<bool, ClassFileError> {
+        has_visible_annotation(&self.attributes, &self.constant_pool, type_descriptor)
+    }
+}
+
+impl FieldInfo {
+    /// The field's `RuntimeVisibleAnnotations`, resolved.
+    pub fn annotations(&self, cp: &ConstantPool) -> Result<Vec<AnnotationInfo>, ClassFileError> {
+        visible_annotations(&self.attributes, cp)
+    }
+
+    /// Returns true if the field carries a runtime-visible annotation of `type_descriptor`.
+    pub fn has_annotation(&self, cp: &ConstantPool, type_descriptor: &str) -> Result<bool, ClassFileError> {
+        has_visible_annotation(&self.attributes, cp, type_descriptor)
+    }
+}
+
+impl MethodInfo {
+    /// The method's `RuntimeVisibleAnnotations`, resolved.
+    pub fn annotations(&self, cp: &ConstantPool) -> Result<Vec<AnnotationInfo>, ClassFileError> {
+        visible_annotations(&self.attributes, cp)
+    }
+
+    /// Returns true if the method carries a runtime-visible annotation of `type_descriptor`.
+    pub fn has_annotation(&self, cp: &ConstantPool, type_descriptor: &str) -> Result<bool, ClassFileError> {
+        has_visible_annotation(&self.attributes, cp, type_descriptor)
+    }
+
+    /// The `RuntimeVisibleParameterAnnotations`, one list per parameter.
+    ///
+    /// Empty if the attribute is absent. javac may omit synthetic and implicit
+    /// parameters, so the list can be shorter than the descriptor's parameters.
+    pub fn parameter_annotations(&self, cp: &ConstantPool) -> Result<Vec<Vec<AnnotationInfo>>, ClassFileError> {
+        for attr in &self.attributes {
+            if let AttributeInfo::RuntimeVisibleParameterAnnotations { parameter_annotations } = attr {
+                return parameter_annotations
+                    .iter()
+                    .map(|annotations| annotations.iter().map(|annotation| annotation.resolve(cp)).collect())
+                    .collect();
+            }
+        }
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,494 @@
+//! JVM bytecode decoding.
+//!
+//! [`InstructionIterator`] walks the `code` array of a [`CodeAttribute`],
+//! decoding each instruction's operands, including `tableswitch`/
+//! `lookupswitch` padding and `wide`-prefixed forms.
+
+use std::fmt;
+
+use super::{ClassFileError, CodeAttribute};
+
+macro_rules! opcodes {
+    ($($name:ident = $value:literal => $mnemonic:literal,)*) => {
+        /// Every opcode defined by the JVM specification, including the
+        /// reserved `breakpoint`, `impdep1`, and `impdep2`.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        #[repr(u8)]
+        pub enum Opcode {
+            $($name = $value,)*
+        }
+
+        impl Opcode {
+            /// Decodes an opcode byte, returning `None` for unassigned values.
+            pub const fn from_u8(byte: u8) -> Option<Self> {
+                match byte {
+                    $($value => Some(Opcode::$name),)*
+                    _ => None,
+                }
+            }
+
+            /// The lower-case mnemonic used by `javap`, e.g. `invokevirtual`.
+            pub const fn mnemonic(self) -> &'static str {
+                match self {
+                    $(Opcode::$name => $mnemonic,)*
+                }
+            }
+        }
+    };
+}
+
+opcodes! {
+    Nop = 0x00 => "nop",
+    AconstNull = 0x01 => "aconst_null",
+    IconstM1 = 0x02 => "iconst_m1",
+    Iconst0 = 0x03 => "iconst_0",
+    Iconst1 = 0x04 => "iconst_1",
+    Iconst2 = 0x05 => "iconst_2",
+    Iconst3 = 0x06 => "iconst_3",
+    Iconst4 = 0x07 => "iconst_4",
+    Iconst5 = 0x08 => "iconst_5",
+    Lconst0 = 0x09 => "lconst_0",
+    Lconst1 = 0x0A => "lconst_1",
+    Fconst0 = 0x0B => "fconst_0",
+    Fconst1 = 0x0C => "fconst_1",
+    Fconst2 = 0x0D => "fconst_2",
+    Dconst0 = 0x0E => "dconst_0",
+    Dconst1 = 0x0F => "dconst_1",
+    Bipush = 0x10 => "bipush",
+    Sipush = 0x11 => "sipush",
+    Ldc = 0x12 => "ldc",
+    LdcW = 0x13 => "ldc_w",
+    Ldc2W = 0x14 => "ldc2_w",
+    Iload = 0x15 => "iload",
+    Lload = 0x16 => "lload",
+    Fload = 0x17 => "fload",
+    Dload = 0x18 => "dload",
+    Aload = 0x19 => "aload",
+    Iload0 = 0x1A => "iload_0",
+    Iload1 = 0x1B => "iload_1",
+    Iload2 = 0x1C => "iload_2",
+    Iload3 = 0x1D => "iload_3",
+    Lload0 = 0x1E => "lload_0",
+    Lload1 = 0x1F => "lload_1",
+    Lload2 = 0x20 => "lload_2",
+    Lload3 = 0x21 => "lload_3",
+    Fload0 = 0x22 => "fload_0",
+    Fload1 = 0x23 => "fload_1",
+    Fload2 = 0x24 => "fload_2",
+    Fload3 = 0x25 => "fload_3",
+    Dload0 = 0x26 => "dload_0",
+    Dload1 = 0x27 => "dload_1",
+    Dload2 = 0x28 => "dload_2",
+    Dload3 = 0x29 => "dload_3",
+    Aload0 = 0x2A => "aload_0",
+    Aload1 = 0x2B => "aload_1",
+    Aload2 = 0x2C => "aload_2",
+    Aload3 = 0x2D => "aload_3",
+    Iaload = 0x2E => "iaload",
+    Laload = 0x2F => "laload",
+    Faload = 0x30 => "faload",
+    Daload = 0x31 => "daload",
+    Aaload = 0x32 => "aaload",
+    Baload = 0x33 => "baload",
+    Caload = 0x34 => "caload",
+    Saload = 0x35 => "saload",
+    Istore = 0x36 => "istore",
+    Lstore = 0x37 => "lstore",
+    Fstore = 0x38 => "fstore",
+    Dstore = 0x39 => "dstore",
+    Astore = 0x3A => "astore",
+    Istore0 = 0x3B => "istore_0",
+    Istore1 = 0x3C => "istore_1",
+    Istore2 = 0x3D => "istore_2",
+    Istore3 = 0x3E => "istore_3",
+    Lstore0 = 0x3F => "lstore_0",
+    Lstore1 = 0x40 => "lstore_1",
+    Lstore2 = 0x41 => "lstore_2",
+    Lstore3 = 0x42 => "lstore_3",
+    Fstore0 = 0x43 => "fstore_0",
+    Fstore1 = 0x44 => "fstore_1",
+    Fstore2 = 0x45 => "fstore_2",
+    Fstore3 = 0x46 => "fstore_3",
+    Dstore0 = 0x47 => "dstore_0",
+    Dstore1 = 0x48 => "dstore_1",
+    Dstore2 = 0x49 => "dstore_2",
+    Dstore3 = 0x4A => "dstore_3",
+    Astore0 = 0x4B => "astore_0",
+    Astore1 = 0x4C => "astore_1",
+    Astore2 = 0x4D => "astore_2",
+    Astore3 = 0x4E => "astore_3",
+    Iastore = 0x4F => "iastore",
+    Lastore = 0x50 => "lastore",
+    Fastore = 0x51 => "fastore",
+    Dastore = 0x52 => "dastore",
+    Aastore = 0x53 => "aastore",
+    Bastore = 0x54 => "bastore",
+    Castore = 0x55 => "castore",
+    Sastore = 0x56 => "sastore",
+    Pop = 0x57 => "pop",
+    Pop2 = 0x58 => "pop2",
+    Dup = 0x59 => "dup",
+    DupX1 = 0x5A => "dup_x1",
+    DupX2 = 0x5B => "dup_x2",
+    Dup2 = 0x5C => "dup2",
+    Dup2X1 = 0x5D => "dup2_x1",
+    Dup2X2 = 0x5E => "dup2_x2",
+    Swap = 0x5F => "swap",
+    Iadd = 0x60 => "iadd",
+    Ladd = 0x61 => "ladd",
+    Fadd = 0x62 => "fadd",
+    Dadd = 0x63 => "dadd",
+    Isub = 0x64 => "isub",
+    Lsub = 0x65 => "lsub",
+    Fsub = 0x66 => "fsub",
+    Dsub = 0x67 => "dsub",
+    Imul = 0x68 => "imul",
+    Lmul = 0x69 => "lmul",
+    Fmul = 0x6A => "fmul",
+    Dmul = 0x6B => "dmul",
+    Idiv = 0x6C => "idiv",
+    Ldiv = 0x6D => "ldiv",
+    Fdiv = 0x6E => "fdiv",
+    Ddiv = 0x6F => "ddiv",
+    Irem = 0x70 => "irem",
+    Lrem = 0x71 => "lrem",
+    Frem = 0x72 => "frem",
+    Drem = 0x73 => "drem",
+    Ineg = 0x74 => "ineg",
+    Lneg = 0x75 => "lneg",
+    Fneg = 0x76 => "fneg",
+    Dneg = 0x77 => "dneg",
+    Ishl = 0x78 => "ishl",
+    Lshl = 0x79 => "lshl",
+    Ishr = 0x7A => "ishr",
+    Lshr = 0x7B => "lshr",
+    Iushr = 0x7C => "iushr",
+    Lushr = 0x7D => "lushr",
+    Iand = 0x7E => "iand",
+    Land = 0x7F => "land",
+    Ior = 0x80 => "ior",
+    Lor = 0x81 => "lor",
+    Ixor = 0x82 => "ixor",
+    Lxor = 0x83 => "lxor",
+    Iinc = 0x84 => "iinc",
+    I2l = 0x85 => "i2l",
+    I2f = 0x86 => "i2f",
+    I2d = 0x87 => "i2d",
+    L2i = 0x88 => "l2i",
+    L2f = 0x89 => "l2f",
+    L2d = 0x8A => "l2d",
+    F2i = 0x8B => "f2i",
+    F2l = 0x8C => "f2l",
+    F2d = 0x8D => "f2d",
+    D2i = 0x8E => "d2i",
+    D2l = 0x8F => "d2l",
+    D2f = 0x90 => "d2f",
+    I2b = 0x91 => "i2b",
+    I2c = 0x92 => "i2c",
+    I2s = 0x93 => "i2s",
+    Lcmp = 0x94 => "lcmp",
+    Fcmpl = 0x95 => "fcmpl",
+    Fcmpg = 0x96 => "fcmpg",
+    Dcmpl = 0x97 => "dcmpl",
+    Dcmpg = 0x98 => "dcmpg",
+    Ifeq = 0x99 => "ifeq",
+    Ifne = 0x9A => "ifne",
+    Iflt = 0x9B => "iflt",
+    Ifge = 0x9C => "ifge",
+    Ifgt = 0x9D => "ifgt",
+    Ifle = 0x9E => "ifle",
+    IfIcmpeq = 0x9F => "if_icmpeq",
+    IfIcmpne = 0xA0 => "if_icmpne",
+    IfIcmplt = 0xA1 => "if_icmplt",
+    IfIcmpge = 0xA2 => "if_icmpge",
+    IfIcmpgt = 0xA3 => "if_icmpgt",
+    IfIcmple = 0xA4 => "if_icmple",
+    IfAcmpeq = 0xA5 => "if_acmpeq",
+    IfAcmpne = 0xA6 => "if_acmpne",
+    Goto = 0xA7 => "goto",
+    Jsr = 0xA8 => "jsr",
+    Ret = 0xA9 => "ret",
+    Tableswitch = 0xAA => "tableswitch",
+    Lookupswitch = 0xAB => "lookupswitch",
+    Ireturn = 0xAC => "ireturn",
+    Lreturn = 0xAD => "lreturn",
+    Freturn = 0xAE => "freturn",
+    Dreturn = 0xAF => "dreturn",
+    Areturn = 0xB0 => "areturn",
+    Return = 0xB1 => "return",
+    Getstatic = 0xB2 => "getstatic",
+    Putstatic = 0xB3 => "putstatic",
+    Getfield = 0xB4 => "getfield",
+    Putfield = 0xB5 => "putfield",
+    Invokevirtual = 0xB6 => "invokevirtual",
+    Invokespecial = 0xB7 => "invokespecial",
+    Invokestatic = 0xB8 => "invokestatic",
+    Invokeinterface = 0xB9 => "invokeinterface",
+    Invokedynamic = 0xBA => "invokedynamic",
+    New = 0xBB => "new",
+    Newarray = 0xBC => "newarray",
+    Anewarray = 0xBD => "anewarray",
+    Arraylength = 0xBE => "arraylength",
+    Athrow = 0xBF => "athrow",
+    Checkcast = 0xC0 => "checkcast",
+    Instanceof = 0xC1 => "instanceof",
+    Monitorenter = 0xC2 => "monitorenter",
+    Monitorexit = 0xC3 => "monitorexit",
+    Wide = 0xC4 => "wide",
+    Multianewarray = 0xC5 => "multianewarray",
+    Ifnull = 0xC6 => "ifnull",
+    Ifnonnull = 0xC7 => "ifnonnull",
+    GotoW = 0xC8 => "goto_w",
+    JsrW = 0xC9 => "jsr_w",
+    Breakpoint = 0xCA => "breakpoint",
+    Impdep1 = 0xFE => "impdep1",
+    Impdep2 = 0xFF => "impdep2",
+}
+
+impl Opcode {
+    /// The opcode byte.
+    pub const fn as_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+/// Decoded operands of an [`Instruction`].
+///
+/// Branch offsets are relative to the instruction's own offset, as in the
+/// class file; use [`Instruction::branch_targets`] for absolute targets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operands {
+    None,
+    /// `bipush`.
+    Byte(i8),
+    /// `sipush`.
+    Short(i16),
+    /// Local variable slot of loads, stores, and `ret` (widened by `wide`).
+    Local(u16),
+    /// Constant pool index (`ldc`, field and method access, `new`, `checkcast`, ...).
+    ConstantPool(u16),
+    Iinc { index: u16, delta: i16 },
+    /// Relative branch offset of `if*`, `goto`, `jsr`, and their `_w` forms.
+    Branch(i32),
+    /// `newarray` element type (`T_BOOLEAN` = 4 through `T_LONG` = 11).
+    NewArray(u8),
+    InvokeInterface { index: u16, count: u8 },
+    InvokeDynamic { index: u16 },
+    MultiANewArray { index: u16, dimensions: u8 },
+    TableSwitch { default: i32, low: i32, high: i32, offsets: Vec<i32> },
+    LookupSwitch { default: i32, pairs: Vec<(i32, i32)> },
+}
+
+/// A single decoded instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// Offset of the opcode (or of the `wide` prefix) within the code array.
+    pub offset: u32,
+    pub opcode: Opcode,
+    /// `true` if the instruction was prefixed by `wide`.
+    pub wide: bool,
+    pub operands: Operands,
+    /// Encoded length in bytes, including any `wide` prefix and switch padding.
+    pub length: u32,
+}
+
+impl Instruction {
+    /// Offset of the instruction that follows this one.
+    pub fn next_offset(&self) -> u32 {
+        self.offset + self.length
+    }
+
+    /// Absolute branch targets (including switch defaults), empty for
+    /// non-branching instructions.
+    pub fn branch_targets(&self) -> Vec<i64> {
+        let base = self.offset as i64;
+        match &self.operands {
+            Operands::Branch(delta) => vec![base + *delta as i64],
+            Operands::TableSwitch { default, offsets, .. } => std::iter::once(default)
+                .chain(offsets)
+                .map(|delta| base + *delta as i64)
+                .collect(),
+            Operands::LookupSwitch { default, pairs } => std::iter::once(default)
+                .chain(pairs.iter().map(|(_, delta)| delta))
+                .map(|delta| base + *delta as i64)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>5}: ", self.offset)?;
+        if self.wide {
+            f.write_str("wide ")?;
+        }
+        write!(f, "{}", self.opcode)?;
+        let base = self.offset as i64;
+        match &self.operands {
+            Operands::None => Ok(()),
+            Operands::Byte(v) => write!(f, " {v}"),
+            Operands::Short(v) => write!(f, " {v}"),
+            Operands::Local(index) => write!(f, " {index}"),
+            Operands::ConstantPool(index) | Operands::InvokeDynamic { index } => write!(f, " #{index}"),
+            Operands::Iinc { index, delta } => write!(f, " {index}, {delta}"),
+            Operands::Branch(delta) => write!(f, " {}", base + *delta as i64),
+            Operands::NewArray(atype) => write!(f, " {atype}"),
+            Operands::InvokeInterface { index, count } => write!(f, " #{index}, {count}"),
+            Operands::MultiANewArray { index, dimensions } => write!(f, " #{index}, {dimensions}"),
+            Operands::TableSwitch { default, low, offsets, .. } => {
+                f.write_str(" {")?;
+                for (i, delta) in offsets.iter().enumerate() {
+                    write!(f, " {}: {};", *low as i64 + i as i64, base + *delta as i64)?;
+                }
+                write!(f, " default: {} }}", base + *default as i64)
+            }
+            Operands::LookupSwitch { default, pairs } => {
+                f.write_str(" {")?;
+                for (key, delta) in pairs {
+                    write!(f, " {key}: {};", base + *delta as i64)?;
+                }
+                write!(f, " default: {} }}", base + *default as i64)
+            }
+        }
+    }
+}
+
+/// Iterator over the instructions of a method's code array.
+///
+/// Yields an error (and then stops) on a truncated instruction or an
+/// unassigned opcode byte.
+#[derive(Debug, Clone)]
+pub struct InstructionIterator<'a> {
+    code: &'a [u8],
+    pos: usize,
+    failed: bool,
+}
+
+impl<'a> InstructionIterator<'a> {
+    pub fn new(code: &'a [u8]) -> Self {
+        Self { code, pos: 0, failed: false }
+    }
+
+    fn u1(&mut self) -> Result<u8, ClassFileError> {
+        let v = *self.code.get(self.pos).ok_or(ClassFileError::UnexpectedEof)?;
+        self.pos += 1;
+        Ok(v)
+    }
+
+    fn u2(&mut self) -> Result<u16, ClassFileError> {
+        Ok(u16::from_be_bytes([self.u1()?, self.u1()?]))
+    }
+
+    fn i4(&mut self) -> Result<i32, ClassFileError> {
+        Ok(i32::from_be_bytes([self.u1()?, self.u1()?, self.u1()?, self.u1()?]))
+    }
+
+    fn decode(&mut self) -> Result<Instruction, ClassFileError> {
+        let start = self.pos;
+        let mut byte = self.u1()?;
+        let wide = byte == Opcode::Wide as u8;
+        if wide {
+            byte = self.u1()?;
+        }
+        let opcode = Opcode::from_u8(byte).ok_or(ClassFileError::InvalidOpcode(byte))?;
+
+        use Opcode::*;
+        let operands = match opcode {
+            Iload | Lload | Fload | Dload | Aload | Istore | Lstore | Fstore | Dstore | Astore | Ret => {
+                Operands::Local(if wide { self.u2()? } else { self.u1()? as u16 })
+            }
+            Iinc if wide => Operands::Iinc { index: self.u2()?, delta: self.u2()? as i16 },
+            Iinc => Operands::Iinc { index: self.u1()? as u16, delta: self.u1()? as i8 as i16 },
+            _ if wide => return Err(ClassFileError::InvalidOpcode(byte)),
+            Bipush => Operands::Byte(self.u1()? as i8),
+            Sipush => Operands::Short(self.u2()? as i16),
+            Ldc => Operands::ConstantPool(self.u1()? as u16),
+            LdcW | Ldc2W | Getstatic | Putstatic | Getfield | Putfield | Invokevirtual | Invokespecial
+            | Invokestatic | New | Anewarray | Checkcast | Instanceof => Operands::ConstantPool(self.u2()?),
+            Ifeq | Ifne | Iflt | Ifge | Ifgt | Ifle | IfIcmpeq | IfIcmpne | IfIcmplt | IfIcmpge | IfIcmpgt
+            | IfIcmple | IfAcmpeq | IfAcmpne | Goto | Jsr | Ifnull | Ifnonnull => {
+                Operands::Branch(self.u2()? as i16 as i32)
+            }
+            GotoW | JsrW => Operands::Branch(self.i4()?),
+            Newarray => Operands::NewArray(self.u1()?),
+            Invokeinterface => {
+                let index = self.u2()?;
+                let count = self.u1()?;
+                self.u1()?;
+                Operands::InvokeInterface { index, count }
+            }
+            Invokedynamic => {
+                let index = self.u2()?;
+                self.u2()?;
+                Operands::InvokeDynamic { index }
+            }
+            Multianewarray => Operands::MultiANewArray { index: self.u2()?, dimensions: self.u1()? },
+            Tableswitch | Lookupswitch => {
+                // Operands start at the next multiple of four from the start of the code array.
+                while self.pos % 4 != 0 {
+                    self.u1()?;
+                }
+                let default = self.i4()?;
+                if opcode == Tableswitch {
+                    let low = self.i4()?;
+                    let high = self.i4()?;
+                    let count = (high as i64 - low as i64 + 1).max(0) as usize;
+                    if count > self.code.len() {
+                        return Err(ClassFileError::UnexpectedEof);
+                    }
+                    let mut offsets = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        offsets.push(self.i4()?);
+                    }
+                    Operands::TableSwitch { default, low, high, offsets }
+                } else {
+                    let npairs = self.i4()?.max(0) as usize;
+                    if npairs > self.code.len() {
+                        return Err(ClassFileError::UnexpectedEof);
+                    }
+                    let mut pairs = Vec::with_capacity(npairs);
+                    for _ in 0..npairs {
+                        pairs.push((self.i4()?, self.i4()?));
+                    }
+                    Operands::LookupSwitch { default, pairs }
+                }
+            }
+            _ => Operands::None,
+        };
+
+        Ok(Instruction {
+            offset: start as u32,
+            opcode,
+            wide,
+            operands,
+            length: (self.pos - start) as u32,
+        })
+    }
+}
+
+impl Iterator for InstructionIterator<'_> {
+    type Item = Result<Instruction, ClassFileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.pos >= self.code.len() {
+            return None;
+        }
+        let result = self.decode();
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+impl CodeAttribute {
+    /// Iterates over the decoded instructions of this method body.
+    pub fn instructions(&self) -> InstructionIterator<'_> {
+        InstructionIterator::new(&self.code)
+    }
+}
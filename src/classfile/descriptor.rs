@@ -0,0 +1,199 @@
+//! JVM field and method descriptor parsing (JVMS §4.3).
+//!
+//! Descriptors such as `(Ljava/lang/String;[I)V` are what class files and
+//! JVMTI's `GetMethodName`/`GetClassSignature` return. [`JavaType`] renders
+//! them in Java source form (`java.lang.String`, `int[]`).
+
+use std::fmt;
+
+use super::ClassFileError;
+
+/// A Java type as written in a descriptor.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum JavaType {
+    Boolean,
+    Byte,
+    Char,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+    /// Only valid as a method return type.
+    Void,
+    /// Class or interface, by internal name (`java/lang/String`).
+    Object(String),
+    Array(Box<JavaType>),
+}
+
+impl JavaType {
+    /// Number of local-variable / operand-stack slots a value of this type
+    /// occupies: 2 for `long`/`double`, 0 for `void`, otherwise 1.
+    pub fn slots(&self) -> u16 {
+        match self {
+            JavaType::Long | JavaType::Double => 2,
+            JavaType::Void => 0,
+            _ => 1,
+        }
+    }
+
+    /// `true` for the eight primitive types (not `void`).
+    pub fn is_primitive(&self) -> bool {
+        !matches!(self, JavaType::Void | JavaType::Object(_) | JavaType::Array(_))
+    }
+
+    /// `true` for object and array types.
+    pub fn is_reference(&self) -> bool {
+        matches!(self, JavaType::Object(_) | JavaType::Array(_))
+    }
+
+    /// Java source spelling, e.g. `java.lang.String[]`. Same as `Display`.
+    pub fn to_source_string(&self) -> String {
+        self.to_string()
+    }
+
+    /// The descriptor form, e.g. `[Ljava/lang/String;`.
+    pub fn to_descriptor(&self) -> String {
+        let mut out = String::new();
+        self.write_descriptor(&mut out);
+        out
+    }
+
+    fn write_descriptor(&self, out: &mut String) {
+        match self {
+            JavaType::Boolean => out.push('Z'),
+            JavaType::Byte => out.push('B'),
+            JavaType::Char => out.push('C'),
+            JavaType::Short => out.push('S'),
+            JavaType::Int => out.push('I'),
+            JavaType::Long => out.push('J'),
+            JavaType::Float => out.push('F'),
+            JavaType::Double => out.push('D'),
+            JavaType::Void => out.push('V'),
+            JavaType::Object(name) => {
+                out.push('L');
+                out.push_str(name);
+                out.push(';');
+            }
+            JavaType::Array(element) => {
+                out.push('[');
+                element.write_descriptor(out);
+            }
+        }
+    }
+}
+
+impl fmt::Display for JavaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JavaType::Boolean => f.write_str("boolean"),
+            JavaType::Byte => f.write_str("byte"),
+            JavaType::Char => f.write_str("char"),
+            JavaType::Short => f.write_str("short"),
+            JavaType::Int => f.write_str("int"),
+            JavaType::Long => f.write_str("long"),
+            JavaType::Float => f.write_str("float"),
+            JavaType::Double => f.write_str("double"),
+            JavaType::Void => f.write_str("void"),
+            JavaType::Object(name) => {
+                for (i, part) in name.split('/').enumerate() {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    f.write_str(part)?;
+                }
+                Ok(())
+            }
+            JavaType::Array(element) => write!(f, "{element}[]"),
+        }
+    }
+}
+
+fn invalid(descriptor: &str) -> ClassFileError {
+    ClassFileError::InvalidDescriptor(descriptor.to_string())
+}
+
+/// Parses one type starting at `pos`, returning it and the position after it.
+fn parse_type(descriptor: &str, pos: usize, allow_void: bool) -> Result<(JavaType, usize), ClassFileError> {
+    let bytes = descriptor.as_bytes();
+    let ty = match bytes.get(pos).ok_or_else(|| invalid(descriptor))? {
+        b'Z' => JavaType::Boolean,
+        b'B' => JavaType::Byte,
+        b'C' => JavaType::Char,
+        b'S' => JavaType::Short,
+        b'I' => JavaType::Int,
+        b'J' => JavaType::Long,
+        b'F' => JavaType::Float,
+        b'D' => JavaType::Double,
+        b'V' if allow_void => JavaType::Void,
+        b'L' => {
+            let end = descriptor[pos + 1..].find(';').ok_or_else(|| invalid(descriptor))? + pos + 1;
+            if end == pos + 1 {
+                return Err(invalid(descriptor));
+            }
+            return Ok((JavaType::Object(descriptor[pos + 1..end].to_string()), end + 1));
+        }
+        b'[' => {
+            let (element, next) = parse_type(descriptor, pos + 1, false)?;
+            return Ok((JavaType::Array(Box::new(element)), next));
+        }
+        _ => return Err(invalid(descriptor)),
+    };
+    Ok((ty, pos + 1))
+}
+
+/// Parses a field descriptor such as `I`, `Ljava/lang/String;`, or `[[D`.
+///
+/// JVMTI class signatures (`GetClassSignature`) use the same syntax.
+pub fn parse_field_descriptor(descriptor: &str) -> Result<JavaType, ClassFileError> {
+    let (ty, end) = parse_type(descriptor, 0, false)?;
+    if end != descriptor.len() {
+        return Err(invalid(descriptor));
+    }
+    Ok(ty)
+}
+
+/// Converts a class signature to the name Java code uses for the class.
+///
+/// `Lcom/example/Foo;` becomes `com.example.Foo`, `[I` becomes `int[]`, and
+/// `[[Ljava/lang/String;` becomes `java.lang.String[][]`. Signatures that do
+/// not parse are returned unchanged.
+pub fn signature_to_java_name(signature: &str) -> String {
+    parse_field_descriptor(signature).map_or_else(|_| signature.to_string(), |ty| ty.to_string())
+}
+
+/// Parses a method descriptor such as `(Ljava/lang/String;[I)V` into
+/// `(parameter_types, return_type)`.
+pub fn parse_method_descriptor(descriptor: &str) -> Result<(Vec<JavaType>, JavaType), ClassFileError> {
+    if !descriptor.starts_with('(') {
+        return Err(invalid(descriptor));
+    }
+    let mut params = Vec::new();
+    let mut pos = 1;
+    loop {
+        match descriptor.as_bytes().get(pos) {
+            Some(b')') => break,
+            Some(_) => {
+                let (ty, next) = parse_type(descriptor, pos, false)?;
+                params.push(ty);
+                pos = next;
+            }
+            None => return Err(invalid(descriptor)),
+        }
+    }
+    let (ret, end) = parse_type(descriptor, pos + 1, true)?;
+    if end != descriptor.len() {
+        return Err(invalid(descriptor));
+    }
+    Ok((params, ret))
+}
+
+/// Number of local-variable slots taken by the parameters of a method
+/// descriptor, plus one for `this` when `is_static` is `false`.
+///
+/// This is the index of the first non-parameter local at method entry.
+pub fn argument_slots(descriptor: &str, is_static: bool) -> Result<u16, ClassFileError> {
+    let (params, _) = parse_method_descriptor(descriptor)?;
+    let this_slot = if is_static { 0 } else { 1 };
+    Ok(params.iter().map(JavaType::slots).sum::<u16>() + this_slot)
+}
@@ -0,0 +1,256 @@
+//! Bytecode instrumentation on parsed class files.
+//!
+//! [`inject_method_entry_call`] prepends an `invokestatic` to a method's
+//! `Code`, adding the constant pool entries it needs and relocating every
+//! bytecode offset in the exception table and the `Code` attributes, so the
+//! result can be written back with [`ClassFile::to_bytes`](super::ClassFile::to_bytes)
+//! and returned from a `ClassFileLoadHook`.
+//!
+//! ```rust,ignore
+//! use jvmti_bindings::classfile::{instrument, ClassFile};
+//!
+//! let mut class = ClassFile::parse(class_bytes)?;
+//! let class_name = class.this_class_name()?.to_string();
+//! let ClassFile { constant_pool, methods, .. } = &mut class;
+//! for method in methods.iter_mut() {
+//!     let label = format!("{class_name}.{}", method.name(constant_pool)?);
+//!     instrument::inject_method_entry_call_with_args(
+//!         method,
+//!         constant_pool,
+//!         "com/example/Profiler",
+//!         "enter",
+//!         "(Ljava/lang/String;)V",
+//!         &[instrument::EntryArg::String(label)],
+//!     )?;
+//! }
+//! let instrumented = class.to_bytes()?;
+//! ```
+//!
+//! The injected call runs before anything else in the method, including the
+//! `super(...)` call of a constructor, so it must not touch the object under
+//! construction. The target class must be visible from the instrumented
+//! class's loader.
+
+use super::bytecode::Opcode;
+use super::descriptor::{parse_method_descriptor, JavaType};
+use super::{
+    AttributeInfo, ClassFileError, CodeAttribute, ConstantPool, CpInfo, MethodInfo, StackMapFrame, TargetInfo,
+    VerificationTypeInfo,
+};
+
+/// A constant pushed as an argument of an injected call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryArg {
+    /// Passed to an `int`, `short`, `char`, `byte`, or `boolean` parameter.
+    Int(i32),
+    /// Passed to a `long` parameter.
+    Long(i64),
+    /// Passed to a `java.lang.String` parameter.
+    String(String),
+}
+
+/// Prepends `invokestatic owner.name descriptor` to `method`.
+///
+/// `descriptor` must take no parameters; a non-`void` result is popped.
+/// Returns `Ok(false)` without changes for methods without `Code`
+/// (abstract and native methods).
+pub fn inject_method_entry_call(
+    method: &mut MethodInfo,
+    cp: &mut ConstantPool,
+    owner: &str,
+    name: &str,
+    descriptor: &str,
+) -> Result<bool, ClassFileError> {
+    inject_method_entry_call_with_args(method, cp, owner, name, descriptor, &[])
+}
+
+/// Like [`inject_method_entry_call`], pushing `args` as the call's arguments.
+///
+/// Each argument must match the corresponding parameter of `descriptor`;
+/// otherwise `InvalidDescriptor` is returned. `owner` must be a class, not an
+/// interface (the call goes through a `CONSTANT_Methodref`).
+pub fn inject_method_entry_call_with_args(
+    method: &mut MethodInfo,
+    cp: &mut ConstantPool,
+    owner: &str,
+    name: &str,
+    descriptor: &str,
+    args: &[EntryArg],
+) -> Result<bool, ClassFileError> {
+    let (params, ret) = parse_method_descriptor(descriptor)?;
+    let matches = params.len() == args.len()
+        && params.iter().zip(args).all(|(param, arg)| match arg {
+            EntryArg::Int(_) => {
+                matches!(param, JavaType::Int | JavaType::Short | JavaType::Char | JavaType::Byte | JavaType::Boolean)
+            }
+            EntryArg::Long(_) => *param == JavaType::Long,
+            EntryArg::String(_) => *param == JavaType::Object("java/lang/String".to_string()),
+        });
+    if !matches {
+        return Err(ClassFileError::InvalidDescriptor(descriptor.to_string()));
+    }
+    let Some(code) = method.code_mut() else {
+        return Ok(false);
+    };
+
+    let mut prologue = Vec::new();
+    for arg in args {
+        push_constant(&mut prologue, cp, arg)?;
+    }
+    let method_index = cp.add_methodref(owner, name, descriptor)?;
+    prologue.push(Opcode::Invokestatic as u8);
+    prologue.extend_from_slice(&method_index.to_be_bytes());
+    match ret.slots() {
+        1 => prologue.push(Opcode::Pop as u8),
+        2 => prologue.push(Opcode::Pop2 as u8),
+        _ => {}
+    }
+
+    let arg_slots = params.iter().map(JavaType::slots).sum::<u16>();
+    prepend_bytecode(code, &prologue, arg_slots.max(ret.slots()))?;
+    Ok(true)
+}
+
+fn push_constant(out: &mut Vec<u8>, cp: &mut ConstantPool, arg: &EntryArg) -> Result<(), ClassFileError> {
+    match arg {
+        EntryArg::Int(value @ -1..=5) => out.push((Opcode::Iconst0 as i32 + value) as u8),
+        EntryArg::Int(value) if i8::try_from(*value).is_ok() => {
+            out.extend_from_slice(&[Opcode::Bipush as u8, *value as u8]);
+        }
+        EntryArg::Int(value) if i16::try_from(*value).is_ok() => {
+            out.push(Opcode::Sipush as u8);
+            out.extend_from_slice(&(*value as i16).to_be_bytes());
+        }
+        EntryArg::Int(value) => push_ldc(out, cp.add(CpInfo::Integer(*value))?),
+        EntryArg::Long(value @ 0..=1) => out.push(Opcode::Lconst0 as u8 + *value as u8),
+        EntryArg::Long(value) => {
+            out.push(Opcode::Ldc2W as u8);
+            out.extend_from_slice(&cp.add(CpInfo::Long(*value))?.to_be_bytes());
+        }
+        EntryArg::String(value) => push_ldc(out, cp.add_string(value)?),
+    }
+    Ok(())
+}
+
+fn push_ldc(out: &mut Vec<u8>, index: u16) {
+    match u8::try_from(index) {
+        Ok(index) => out.extend_from_slice(&[Opcode::Ldc as u8, index]),
+        Err(_) => {
+            out.push(Opcode::LdcW as u8);
+            out.extend_from_slice(&index.to_be_bytes());
+        }
+    }
+}
+
+/// Inserts straight-line bytecode at the start of `code`.
+///
+/// `prologue` must not branch, must leave the operand stack empty, and must
+/// need at most `prologue_max_stack` stack slots. It is padded with `nop`s to
+/// a multiple of four bytes so `tableswitch`/`lookupswitch` padding in the
+/// original code stays valid. Branches in the original code are relative and
+/// keep their targets; the exception table and the offsets in
+/// `LineNumberTable`, `LocalVariableTable`, `LocalVariableTypeTable`,
+/// `StackMapTable`, and type annotations are shifted. Locals that start at
+/// offset 0 (the parameters) are extended to cover the prologue.
+///
+/// Returns `Unencodable` if the code would exceed 65535 bytes.
+pub fn prepend_bytecode(code: &mut CodeAttribute, prologue: &[u8], prologue_max_stack: u16) -> Result<(), ClassFileError> {
+    let shift_len = (prologue.len() + 3) & !3;
+    if code.code.len() + shift_len > u16::MAX as usize {
+        return Err(ClassFileError::Unencodable("code exceeds 65535 bytes".to_string()));
+    }
+    let shift = shift_len as u16;
+
+    let mut bytes = Vec::with_capacity(shift_len + code.code.len());
+    bytes.extend_from_slice(prologue);
+    bytes.resize(shift_len, Opcode::Nop as u8);
+    bytes.extend_from_slice(&code.code);
+    code.code = bytes;
+    code.max_stack = code.max_stack.max(prologue_max_stack);
+
+    for entry in &mut code.exception_table {
+        entry.start_pc += shift;
+        entry.end_pc += shift;
+        entry.handler_pc += shift;
+    }
+    for attr in &mut code.attributes {
+        relocate_attribute(attr, shift);
+    }
+    Ok(())
+}
+
+/// Shifts a local's live range, extending ranges that start at method entry.
+fn relocate_range(start_pc: &mut u16, length: &mut u16, shift: u16) {
+    if *start_pc == 0 {
+        *length += shift;
+    } else {
+        *start_pc += shift;
+    }
+}
+
+fn relocate_attribute(attr: &mut AttributeInfo, shift: u16) {
+    match attr {
+        AttributeInfo::LineNumberTable { entries } => {
+            for entry in entries {
+                entry.start_pc += shift;
+            }
+        }
+        AttributeInfo::LocalVariableTable { entries } => {
+            for entry in entries {
+                relocate_range(&mut entry.start_pc, &mut entry.length, shift);
+            }
+        }
+        AttributeInfo::LocalVariableTypeTable { entries } => {
+            for entry in entries {
+                relocate_range(&mut entry.start_pc, &mut entry.length, shift);
+            }
+        }
+        AttributeInfo::StackMapTable(table) => {
+            // Only the first frame's offset is absolute; later deltas are relative.
+            if let Some(first) = table.entries.first_mut() {
+                *offset_delta_mut(first) += shift;
+            }
+            for frame in &mut table.entries {
+                let (locals, stack): (&mut [VerificationTypeInfo], &mut [VerificationTypeInfo]) = match frame {
+                    StackMapFrame::SameLocals1StackItem { stack, .. }
+                    | StackMapFrame::SameLocals1StackItemExtended { stack, .. } => (&mut [], std::slice::from_mut(stack)),
+                    StackMapFrame::Append { locals, .. } => (locals, &mut []),
+                    StackMapFrame::Full { locals, stack, .. } => (locals, stack),
+                    _ => (&mut [], &mut []),
+                };
+                for info in locals.iter_mut().chain(stack.iter_mut()) {
+                    if let VerificationTypeInfo::Uninitialized(offset) = info {
+                        *offset += shift;
+                    }
+                }
+            }
+        }
+        AttributeInfo::RuntimeVisibleTypeAnnotations { annotations }
+        | AttributeInfo::RuntimeInvisibleTypeAnnotations { annotations } => {
+            for annotation in annotations {
+                match &mut annotation.target_info {
+                    TargetInfo::Localvar { table } => {
+                        for target in table {
+                            relocate_range(&mut target.start_pc, &mut target.length, shift);
+                        }
+                    }
+                    TargetInfo::Offset { offset } | TargetInfo::TypeArgument { offset, .. } => *offset += shift,
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn offset_delta_mut(frame: &mut StackMapFrame) -> &mut u16 {
+    match frame {
+        StackMapFrame::Same { offset_delta }
+        | StackMapFrame::SameLocals1StackItem { offset_delta, .. }
+        | StackMapFrame::SameLocals1StackItemExtended { offset_delta, .. }
+        | StackMapFrame::Chop { offset_delta, .. }
+        | StackMapFrame::SameExtended { offset_delta }
+        | StackMapFrame::Append { offset_delta, .. }
+        | StackMapFrame::Full { offset_delta, .. } => offset_delta,
+    }
+}
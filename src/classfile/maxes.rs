@@ -0,0 +1,234 @@
+//! `max_stack` / `max_locals` computation for modified method bodies.
+
+use super::bytecode::{Instruction, Opcode, Operands};
+use super::descriptor::{argument_slots, parse_field_descriptor, parse_method_descriptor, JavaType};
+use super::{ClassFileError, CodeAttribute, ConstantPool, CpInfo};
+
+impl CodeAttribute {
+    /// Recomputes `max_stack` and `max_locals` from the instructions.
+    ///
+    /// Walks every path from the method entry and from each exception handler
+    /// (which starts with the exception on the stack), following branches,
+    /// switches, and `jsr`/`ret`, and tracks the operand stack height in slots.
+    /// `max_locals` covers the parameters of `descriptor` (plus `this` unless
+    /// `is_static`) and every local slot the code reads or writes.
+    ///
+    /// Call this after editing `code`, for example after
+    /// [`instrument::prepend_bytecode`](super::instrument::prepend_bytecode).
+    /// Returns `InvalidAttribute` if the stack underflows, a path reaches the
+    /// same instruction with two different heights, a branch leaves the code
+    /// array, or the code ends without a terminating instruction; unreachable
+    /// code is ignored.
+    pub fn recompute_maxes(&mut self, cp: &ConstantPool, is_static: bool, descriptor: &str) -> Result<(), ClassFileError> {
+        let instructions = self.instructions().collect::<Result<Vec<_>, _>>()?;
+        let mut index_at = vec![usize::MAX; self.code.len()];
+        for (index, insn) in instructions.iter().enumerate() {
+            index_at[insn.offset as usize] = index;
+        }
+        let lookup = |target: i64| -> Result<usize, ClassFileError> {
+            usize::try_from(target)
+                .ok()
+                .and_then(|target| index_at.get(target).copied())
+                .filter(|index| *index != usize::MAX)
+                .ok_or_else(|| invalid(format!("branch to invalid offset {target}")))
+        };
+
+        let mut max_locals = argument_slots(descriptor, is_static)?;
+        for insn in &instructions {
+            if let Some(end) = local_end(insn) {
+                max_locals = max_locals.max(end);
+            }
+        }
+
+        let mut depth_at: Vec<Option<u16>> = vec![None; instructions.len()];
+        let mut pending = Vec::new();
+        if !instructions.is_empty() {
+            enqueue(&mut depth_at, &mut pending, &instructions, 0, 0)?;
+        }
+        for handler in &self.exception_table {
+            enqueue(&mut depth_at, &mut pending, &instructions, lookup(handler.handler_pc as i64)?, 1)?;
+        }
+
+        let mut max_stack = 0u16;
+        while let Some(index) = pending.pop() {
+            let insn = &instructions[index];
+            let depth = depth_at[index].unwrap_or(0);
+            let (pops, pushes) = stack_effect(insn, cp)?;
+            let after = depth
+                .checked_sub(pops)
+                .ok_or_else(|| invalid(format!("operand stack underflow at offset {}", insn.offset)))?;
+            let after = after
+                .checked_add(pushes)
+                .ok_or_else(|| invalid(format!("operand stack overflow at offset {}", insn.offset)))?;
+            max_stack = max_stack.max(depth).max(after);
+
+            use Opcode::*;
+            match insn.opcode {
+                // The return address is only on the stack at the subroutine entry;
+                // execution resumes after the `jsr` once the subroutine returns.
+                Jsr | JsrW => {
+                    enqueue(&mut depth_at, &mut pending, &instructions, lookup(insn.branch_targets()[0])?, after)?;
+                    enqueue(&mut depth_at, &mut pending, &instructions, next_index(&instructions, index)?, depth)?;
+                    continue;
+                }
+                _ => {}
+            }
+            for target in insn.branch_targets() {
+                enqueue(&mut depth_at, &mut pending, &instructions, lookup(target)?, after)?;
+            }
+            if falls_through(insn.opcode) {
+                enqueue(&mut depth_at, &mut pending, &instructions, next_index(&instructions, index)?, after)?;
+            }
+        }
+
+        self.max_stack = max_stack;
+        self.max_locals = max_locals;
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> ClassFileError {
+    ClassFileError::InvalidAttribute(format!("Code: {message}"))
+}
+
+/// Records the stack height on entry to an instruction, queueing it the first
+/// time it is reached.
+fn enqueue(
+    depth_at: &mut [Option<u16>],
+    pending: &mut Vec<usize>,
+    instructions: &[Instruction],
+    index: usize,
+    depth: u16,
+) -> Result<(), ClassFileError> {
+    match depth_at[index] {
+        None => {
+            depth_at[index] = Some(depth);
+            pending.push(index);
+            Ok(())
+        }
+        Some(known) if known == depth => Ok(()),
+        Some(known) => Err(invalid(format!("stack height {depth} and {known} at offset {}", instructions[index].offset))),
+    }
+}
+
+fn next_index(instructions: &[Instruction], index: usize) -> Result<usize, ClassFileError> {
+    if index + 1 < instructions.len() {
+        Ok(index + 1)
+    } else {
+        Err(invalid("execution falls off the end of the code".to_string()))
+    }
+}
+
+fn falls_through(opcode: Opcode) -> bool {
+    use Opcode::*;
+    !matches!(
+        opcode,
+        Goto | GotoW | Ret | Tableswitch | Lookupswitch | Ireturn | Lreturn | Freturn | Dreturn | Areturn | Return | Athrow
+    )
+}
+
+/// One past the highest local slot an instruction touches.
+fn local_end(insn: &Instruction) -> Option<u16> {
+    use Opcode::*;
+    let op = insn.opcode as u8;
+    let slot = match insn.operands {
+        Operands::Local(slot) | Operands::Iinc { index: slot, .. } => slot,
+        // iload_0 ... aload_3 and istore_0 ... astore_3: four opcodes per type.
+        _ if (Iload0 as u8..=Aload3 as u8).contains(&op) => ((op - Iload0 as u8) % 4) as u16,
+        _ if (Istore0 as u8..=Astore3 as u8).contains(&op) => ((op - Istore0 as u8) % 4) as u16,
+        _ => return None,
+    };
+    let size = match insn.opcode {
+        Lload | Dload | Lstore | Dstore | Lload0 | Lload1 | Lload2 | Lload3 | Dload0 | Dload1 | Dload2 | Dload3
+        | Lstore0 | Lstore1 | Lstore2 | Lstore3 | Dstore0 | Dstore1 | Dstore2 | Dstore3 => 2,
+        _ => 1,
+    };
+    Some(slot + size)
+}
+
+fn constant_slots(cp: &ConstantPool, index: u16) -> Result<u16, ClassFileError> {
+    Ok(match cp.get(index)? {
+        CpInfo::Long(_) | CpInfo::Double(_) => 2,
+        CpInfo::Dynamic { name_and_type_index, .. } => {
+            parse_field_descriptor(cp.get_name_and_type(*name_and_type_index)?.1)?.slots()
+        }
+        _ => 1,
+    })
+}
+
+fn field_slots(cp: &ConstantPool, index: u16) -> Result<u16, ClassFileError> {
+    Ok(parse_field_descriptor(cp.get_member_ref(index)?.2)?.slots())
+}
+
+/// `(argument slots, return slots)` of a method descriptor.
+fn call_slots(descriptor: &str) -> Result<(u16, u16), ClassFileError> {
+    let (params, ret) = parse_method_descriptor(descriptor)?;
+    Ok((params.iter().map(JavaType::slots).sum(), ret.slots()))
+}
+
+/// Operand stack slots popped and pushed by an instruction.
+fn stack_effect(insn: &Instruction, cp: &ConstantPool) -> Result<(u16, u16), ClassFileError> {
+    use Opcode::*;
+    let cp_index = match insn.operands {
+        Operands::ConstantPool(index)
+        | Operands::InvokeInterface { index, .. }
+        | Operands::InvokeDynamic { index }
+        | Operands::MultiANewArray { index, .. } => index,
+        _ => 0,
+    };
+    Ok(match insn.opcode {
+        Nop | Iinc | Goto | GotoW | Ret | Return | Wide => (0, 0),
+        AconstNull | IconstM1 | Iconst0 | Iconst1 | Iconst2 | Iconst3 | Iconst4 | Iconst5 | Fconst0 | Fconst1
+        | Fconst2 | Bipush | Sipush | Iload | Fload | Aload | Iload0 | Iload1 | Iload2 | Iload3 | Fload0 | Fload1
+        | Fload2 | Fload3 | Aload0 | Aload1 | Aload2 | Aload3 | New | Jsr | JsrW => (0, 1),
+        Lconst0 | Lconst1 | Dconst0 | Dconst1 | Ldc2W | Lload | Dload | Lload0 | Lload1 | Lload2 | Lload3 | Dload0
+        | Dload1 | Dload2 | Dload3 => (0, 2),
+        Ldc | LdcW => (0, constant_slots(cp, cp_index)?),
+        Iaload | Faload | Aaload | Baload | Caload | Saload => (2, 1),
+        Laload | Daload => (2, 2),
+        Istore | Fstore | Astore | Istore0 | Istore1 | Istore2 | Istore3 | Fstore0 | Fstore1 | Fstore2 | Fstore3
+        | Astore0 | Astore1 | Astore2 | Astore3 | Pop | Ifeq | Ifne | Iflt | Ifge | Ifgt | Ifle | Ifnull | Ifnonnull
+        | Tableswitch | Lookupswitch | Ireturn | Freturn | Areturn | Athrow | Monitorenter | Monitorexit => (1, 0),
+        Lstore | Dstore | Lstore0 | Lstore1 | Lstore2 | Lstore3 | Dstore0 | Dstore1 | Dstore2 | Dstore3 | Pop2
+        | IfIcmpeq | IfIcmpne | IfIcmplt | IfIcmpge | IfIcmpgt | IfIcmple | IfAcmpeq | IfAcmpne | Lreturn
+        | Dreturn => (2, 0),
+        Iastore | Fastore | Aastore | Bastore | Castore | Sastore => (3, 0),
+        Lastore | Dastore => (4, 0),
+        Dup => (1, 2),
+        DupX1 => (2, 3),
+        DupX2 => (3, 4),
+        Dup2 => (2, 4),
+        Dup2X1 => (3, 5),
+        Dup2X2 => (4, 6),
+        Swap => (2, 2),
+        Iadd | Isub | Imul | Idiv | Irem | Ishl | Ishr | Iushr | Iand | Ior | Ixor | Fadd | Fsub | Fmul | Fdiv
+        | Frem | Fcmpl | Fcmpg => (2, 1),
+        Ladd | Lsub | Lmul | Ldiv | Lrem | Land | Lor | Lxor | Dadd | Dsub | Dmul | Ddiv | Drem => (4, 2),
+        Lshl | Lshr | Lushr => (3, 2),
+        Lcmp | Dcmpl | Dcmpg => (4, 1),
+        Ineg | Fneg | I2f | F2i | I2b | I2c | I2s | Newarray | Anewarray | Arraylength | Checkcast | Instanceof => {
+            (1, 1)
+        }
+        Lneg | Dneg | L2d | D2l => (2, 2),
+        I2l | I2d | F2l | F2d => (1, 2),
+        L2i | L2f | D2i | D2f => (2, 1),
+        Getstatic => (0, field_slots(cp, cp_index)?),
+        Putstatic => (field_slots(cp, cp_index)?, 0),
+        Getfield => (1, field_slots(cp, cp_index)?),
+        Putfield => (1 + field_slots(cp, cp_index)?, 0),
+        Invokevirtual | Invokespecial | Invokeinterface => {
+            let (args, ret) = call_slots(cp.get_member_ref(cp_index)?.2)?;
+            (args + 1, ret)
+        }
+        Invokestatic => call_slots(cp.get_member_ref(cp_index)?.2)?,
+        Invokedynamic => match cp.get(cp_index)? {
+            CpInfo::InvokeDynamic { name_and_type_index, .. } => call_slots(cp.get_name_and_type(*name_and_type_index)?.1)?,
+            _ => return Err(ClassFileError::InvalidConstantPoolIndex(cp_index)),
+        },
+        Multianewarray => match insn.operands {
+            Operands::MultiANewArray { dimensions, .. } => (dimensions as u16, 1),
+            _ => (0, 1),
+        },
+        Breakpoint | Impdep1 | Impdep2 => return Err(ClassFileError::InvalidOpcode(insn.opcode as u8)),
+    })
+}
@@ -344,10 +344,7 @@ impl JavaVm {
         if res != jni::JNI_OK {
             return Err(res);
         }
-        if env_ptr.is_null() {
-            return Err(jni::JNI_ERR);
-        }
-        Ok(unsafe { JniEnv::from_raw(env_ptr as *mut jni::JNIEnv) })
+        unsafe { JniEnv::try_from_raw(env_ptr as *mut jni::JNIEnv) }.map_err(|err| err.as_raw())
     }
 
     fn attach_current_thread_inner(&self, daemon: bool) -> Result<JniEnv, jni::jint> {
@@ -367,10 +364,7 @@ impl JavaVm {
         if res != jni::JNI_OK {
             return Err(res);
         }
-        if env_ptr.is_null() {
-            return Err(jni::JNI_ERR);
-        }
-        Ok(unsafe { JniEnv::from_raw(env_ptr as *mut jni::JNIEnv) })
+        unsafe { JniEnv::try_from_raw(env_ptr as *mut jni::JNIEnv) }.map_err(|err| err.as_raw())
     }
 
     /// Attach the current thread to the JVM and return a `JniEnv`.
@@ -76,12 +76,22 @@
 //! - **Exceptions**: Check, throw, and clear exceptions
 //! - **References**: Manage local, global, and weak references
 //!
+//! # Closure-Based Events
+//!
+//! [`EventRegistry`] lets you register one closure per event instead of
+//! implementing [`crate::Agent`], then installs itself as the global agent.
+//!
 //! # Reference Guards
 //!
 //! The module also provides RAII guards for automatic reference cleanup:
 //!
 //! - [`LocalRef`]: Automatically deletes a local reference when dropped
+//! - [`LocalFrameGuard`]: Pops a `PushLocalFrame` frame when dropped
+//! - [`MonitorEnterGuard`]: Exits a Java object monitor when dropped
 //! - [`GlobalRef`]: Automatically deletes a global reference when dropped
+//! - [`RawMonitorGuard`]: Exits a JVMTI raw monitor when dropped
+//! - [`SuspendGuard`] / [`SuspendListGuard`]: Resume suspended threads when dropped
+//! - [`DisposeGuard`]: Disposes an owned JVMTI environment when dropped
 //!
 //! ```rust,ignore
 //! use jvmti_bindings::prelude::*;
@@ -100,18 +110,41 @@
 // Re-export the JVMTI wrapper
 mod jvmti_impl {
     pub use crate::jvmti_wrapper::{
-        ExtensionEventInfo, ExtensionFunctionInfo, ExtensionParamInfo, Jvmti, LocalVariableEntry,
-        MonitorUsage, StackInfo, ThreadGroupInfo, ThreadInfo,
+        CapabilityError, ClassCount, ClassStatus, DisposeGuard, Event, ExtensionArg,
+        ExtensionEventInfo, ExtensionFunction, ExtensionFunctionInfo, ExtensionParamInfo,
+        HeapClosures, HeapFilter, HeapObject, IterationControl, JLocationFormat, JniFunctionTable,
+        Jvmti, JvmtiEnvPtr, JvmtiResult, LocalValue, LocalVariableEntry, MAX_EXTENSION_ARGS,
+        MethodSignature, MonitorUsage, Phase, PrimitiveArray, PrimitiveField, RawMonitorGuard,
+        Reference, ReferenceKind, ResourceExhaustedFlags, ReturnValue, SearchPathError,
+        SetEventsError, StackInfo, StringValue, SuspendGuard, SuspendListGuard, SymbolicFrame,
+        ThreadDump, ThreadGroupInfo, ThreadInfo, ThreadState, TimerInfo, TimerKind, VisitControl,
     };
 }
 
 // Re-export the JNI wrapper
 mod jni_impl {
-    pub use crate::jni_wrapper::{JniEnv, LocalRef, GlobalRef};
+    pub use crate::jni_wrapper::{
+        AttachGuard, GlobalRef, JavaException, JavaValue, JniEnv, JniEnvPtr, JniError,
+        LocalFrameGuard, LocalRef, MonitorEnterGuard, WeakGlobalRef,
+    };
 }
 
 pub use jvmti_impl::{
-    ExtensionEventInfo, ExtensionFunctionInfo, ExtensionParamInfo, Jvmti, LocalVariableEntry,
-    MonitorUsage, StackInfo, ThreadGroupInfo, ThreadInfo,
+    CapabilityError, ClassCount, ClassStatus, DisposeGuard, Event, ExtensionArg, ExtensionEventInfo,
+    ExtensionFunction, ExtensionFunctionInfo, ExtensionParamInfo, HeapClosures, HeapFilter,
+    HeapObject, IterationControl, JLocationFormat, JniFunctionTable, Jvmti, JvmtiEnvPtr,
+    JvmtiResult, LocalValue, LocalVariableEntry, MAX_EXTENSION_ARGS, MethodSignature, MonitorUsage,
+    Phase, PrimitiveArray, PrimitiveField, RawMonitorGuard, Reference, ReferenceKind,
+    ResourceExhaustedFlags, ReturnValue, SearchPathError, SetEventsError, StackInfo, StringValue,
+    SuspendGuard, SuspendListGuard, SymbolicFrame, ThreadDump, ThreadGroupInfo, ThreadInfo,
+    ThreadState, TimerInfo, TimerKind, VisitControl,
+};
+pub use jni_impl::{
+    AttachGuard, GlobalRef, JavaException, JavaValue, JniEnv, JniEnvPtr, JniError, LocalFrameGuard,
+    LocalRef, MonitorEnterGuard, WeakGlobalRef,
 };
-pub use jni_impl::{JniEnv, LocalRef, GlobalRef};
+pub use crate::event_registry::EventRegistry;
+pub use crate::object_tagger::ObjectTagger;
+pub use crate::class_cache::ClassCache;
+pub use crate::member_ids::{FieldId, MethodId};
+pub use crate::class_transformers::ClassTransformerRegistry;
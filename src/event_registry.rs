@@ -0,0 +1,218 @@
+//! Closure-based event registration.
+//!
+//! [`EventRegistry`] is an alternative to implementing [`Agent`] by hand: register
+//! one closure per event and install the registry as the process-wide agent.
+//!
+//! ```rust,ignore
+//! use jvmti_bindings::env::{EventRegistry, Jvmti};
+//! use jvmti_bindings::prelude::*;
+//! use std::sync::atomic::{AtomicU64, Ordering};
+//!
+//! static CALLS: AtomicU64 = AtomicU64::new(0);
+//!
+//! #[no_mangle]
+//! pub unsafe extern "system" fn Agent_OnLoad(
+//!     vm: *mut jni::JavaVM,
+//!     _options: *mut std::ffi::c_char,
+//!     _reserved: *mut std::ffi::c_void,
+//! ) -> jni::jint {
+//!     let Ok(jvmti) = Jvmti::new(vm) else { return jni::JNI_ERR };
+//!     if jvmti.add_method_trace_capabilities().is_err() {
+//!         return jni::JNI_ERR;
+//!     }
+//!
+//!     let mut classes = 0u64;
+//!     let installed = EventRegistry::new()
+//!         .on_method_entry(|_jvmti, _jni, _thread, _method| {
+//!             CALLS.fetch_add(1, Ordering::Relaxed);
+//!         })
+//!         .on_class_prepare(move |jvmti, _jni, _thread, klass| {
+//!             classes += 1;
+//!             if let Ok((sig, _)) = jvmti.get_class_signature(klass) {
+//!                 println!("#{classes}: {sig}");
+//!             }
+//!         })
+//!         .install(&jvmti);
+//!
+//!     if installed.is_ok() { jni::JNI_OK } else { jni::JNI_ERR }
+//! }
+//! ```
+//!
+//! Handlers are `FnMut`, so each one is guarded by its own mutex: deliveries of
+//! the same event are serialized across threads, and a handler must not cause
+//! its own event to fire re-entrantly on the same thread (for example a
+//! `MethodEntry` handler that calls back into Java).
+
+use std::os::raw::{c_char, c_uchar, c_void};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::jni_wrapper::JniEnv;
+use crate::jvmti_wrapper::Jvmti;
+use crate::sys::{jni, jvmti};
+use crate::Agent;
+
+fn lock<T: ?Sized>(handler: &Mutex<Box<T>>) -> MutexGuard<'_, Box<T>> {
+    handler.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+macro_rules! event_registry {
+    (
+        with_env {
+            $( $event:ident, $setter:ident, $agent_fn:ident, $id:path, ($($arg:ident: $ty:ty),*); )*
+        }
+        plain {
+            $( $pevent:ident, $psetter:ident, $pid:path, ($($parg:ident: $pty:ty),*); )*
+        }
+    ) => {
+        /// A set of per-event closures that can be installed as the global [`Agent`].
+        ///
+        /// Events that carry a `JNIEnv` receive the [`Jvmti`] and [`JniEnv`] wrappers;
+        /// the others receive the raw callback arguments. See the module docs for an
+        /// example.
+        #[derive(Default)]
+        pub struct EventRegistry {
+            $( $event: Option<Mutex<Box<dyn FnMut(&Jvmti, &JniEnv $(, $ty)*) + Send>>>, )*
+            $( $pevent: Option<Mutex<Box<dyn FnMut($($pty),*) + Send>>>, )*
+        }
+
+        impl EventRegistry {
+            $(
+                #[doc = concat!("Register the handler for `", stringify!($id), "`.")]
+                pub fn $setter<F>(mut self, handler: F) -> Self
+                where
+                    F: FnMut(&Jvmti, &JniEnv $(, $ty)*) + Send + 'static,
+                {
+                    self.$event = Some(Mutex::new(Box::new(handler)));
+                    self
+                }
+            )*
+            $(
+                #[doc = concat!("Register the handler for `", stringify!($pid), "`.")]
+                pub fn $psetter<F>(mut self, handler: F) -> Self
+                where
+                    F: FnMut($($pty),*) + Send + 'static,
+                {
+                    self.$pevent = Some(Mutex::new(Box::new(handler)));
+                    self
+                }
+            )*
+
+            /// The `JVMTI_EVENT_*` constants that have a registered handler.
+            pub fn registered_events(&self) -> Vec<u32> {
+                let mut events = Vec::new();
+                $( if self.$event.is_some() { events.push($id); } )*
+                $( if self.$pevent.is_some() { events.push($pid); } )*
+                events
+            }
+        }
+
+        impl Agent for EventRegistry {
+            fn on_load(&self, _vm: *mut jni::JavaVM, _options: &str) -> jni::jint {
+                jni::JNI_OK
+            }
+
+            $(
+                fn $agent_fn(&self, jvmti: &Jvmti, jni: &JniEnv $(, $arg: $ty)*) {
+                    if let Some(handler) = &self.$event {
+                        (lock(handler))(jvmti, jni $(, $arg)*);
+                    }
+                }
+            )*
+            $(
+                fn $pevent(&self $(, $parg: $pty)*) {
+                    if let Some(handler) = &self.$pevent {
+                        (lock(handler))($($parg),*);
+                    }
+                }
+            )*
+        }
+    };
+}
+
+event_registry! {
+    with_env {
+        vm_init, on_vm_init, vm_init_with_env, jvmti::JVMTI_EVENT_VM_INIT, (thread: jni::jthread);
+        vm_death, on_vm_death, vm_death_with_env, jvmti::JVMTI_EVENT_VM_DEATH, ();
+        vm_start, on_vm_start, vm_start_with_env, jvmti::JVMTI_EVENT_VM_START, ();
+        thread_start, on_thread_start, thread_start_with_env, jvmti::JVMTI_EVENT_THREAD_START, (thread: jni::jthread);
+        thread_end, on_thread_end, thread_end_with_env, jvmti::JVMTI_EVENT_THREAD_END, (thread: jni::jthread);
+        virtual_thread_start, on_virtual_thread_start, virtual_thread_start_with_env, jvmti::JVMTI_EVENT_VIRTUAL_THREAD_START, (thread: jni::jthread);
+        virtual_thread_end, on_virtual_thread_end, virtual_thread_end_with_env, jvmti::JVMTI_EVENT_VIRTUAL_THREAD_END, (thread: jni::jthread);
+        class_load, on_class_load, class_load_with_env, jvmti::JVMTI_EVENT_CLASS_LOAD, (thread: jni::jthread, klass: jni::jclass);
+        class_prepare, on_class_prepare, class_prepare_with_env, jvmti::JVMTI_EVENT_CLASS_PREPARE, (thread: jni::jthread, klass: jni::jclass);
+        class_file_load_hook, on_class_file_load_hook, class_file_load_hook_with_env, jvmti::JVMTI_EVENT_CLASS_FILE_LOAD_HOOK,
+            (class_being_redefined: jni::jclass, loader: jni::jobject, name: *const c_char,
+             protection_domain: jni::jobject, class_data_len: jni::jint, class_data: *const c_uchar,
+             new_class_data_len: *mut jni::jint, new_class_data: *mut *mut c_uchar);
+        method_entry, on_method_entry, method_entry_with_env, jvmti::JVMTI_EVENT_METHOD_ENTRY, (thread: jni::jthread, method: jni::jmethodID);
+        method_exit, on_method_exit, method_exit_with_env, jvmti::JVMTI_EVENT_METHOD_EXIT, (thread: jni::jthread, method: jni::jmethodID);
+        native_method_bind, on_native_method_bind, native_method_bind_with_env, jvmti::JVMTI_EVENT_NATIVE_METHOD_BIND,
+            (thread: jni::jthread, method: jni::jmethodID, address: *mut c_void, new_address_ptr: *mut *mut c_void);
+        exception, on_exception, exception_with_env, jvmti::JVMTI_EVENT_EXCEPTION,
+            (thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation, exception: jni::jobject,
+             catch_method: jni::jmethodID, catch_location: jvmti::jlocation);
+        exception_catch, on_exception_catch, exception_catch_with_env, jvmti::JVMTI_EVENT_EXCEPTION_CATCH,
+            (thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation, exception: jni::jobject);
+        single_step, on_single_step, single_step_with_env, jvmti::JVMTI_EVENT_SINGLE_STEP,
+            (thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation);
+        breakpoint, on_breakpoint, breakpoint_with_env, jvmti::JVMTI_EVENT_BREAKPOINT,
+            (thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation);
+        frame_pop, on_frame_pop, frame_pop_with_env, jvmti::JVMTI_EVENT_FRAME_POP,
+            (thread: jni::jthread, method: jni::jmethodID, was_popped_by_exception: jni::jboolean);
+        monitor_wait, on_monitor_wait, monitor_wait_with_env, jvmti::JVMTI_EVENT_MONITOR_WAIT,
+            (thread: jni::jthread, object: jni::jobject, timeout: jni::jlong);
+        monitor_waited, on_monitor_waited, monitor_waited_with_env, jvmti::JVMTI_EVENT_MONITOR_WAITED,
+            (thread: jni::jthread, object: jni::jobject, timed_out: jni::jboolean);
+        monitor_contended_enter, on_monitor_contended_enter, monitor_contended_enter_with_env, jvmti::JVMTI_EVENT_MONITOR_CONTENDED_ENTER,
+            (thread: jni::jthread, object: jni::jobject);
+        monitor_contended_entered, on_monitor_contended_entered, monitor_contended_entered_with_env, jvmti::JVMTI_EVENT_MONITOR_CONTENDED_ENTERED,
+            (thread: jni::jthread, object: jni::jobject);
+        field_access, on_field_access, field_access_with_env, jvmti::JVMTI_EVENT_FIELD_ACCESS,
+            (thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation, field_klass: jni::jclass,
+             object: jni::jobject, field: jni::jfieldID);
+        field_modification, on_field_modification, field_modification_with_env, jvmti::JVMTI_EVENT_FIELD_MODIFICATION,
+            (thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation, field_klass: jni::jclass,
+             object: jni::jobject, field: jni::jfieldID, sig_type: c_char, new_value: jni::jvalue);
+        resource_exhausted, on_resource_exhausted, resource_exhausted_with_env, jvmti::JVMTI_EVENT_RESOURCE_EXHAUSTED,
+            (flags: jni::jint, description: *const c_char);
+        vm_object_alloc, on_vm_object_alloc, vm_object_alloc_with_env, jvmti::JVMTI_EVENT_VM_OBJECT_ALLOC,
+            (thread: jni::jthread, object: jni::jobject, klass: jni::jclass, size: jni::jlong);
+        sampled_object_alloc, on_sampled_object_alloc, sampled_object_alloc_with_env, jvmti::JVMTI_EVENT_SAMPLED_OBJECT_ALLOC,
+            (thread: jni::jthread, object: jni::jobject, klass: jni::jclass, size: jni::jlong);
+    }
+    plain {
+        compiled_method_load, on_compiled_method_load, jvmti::JVMTI_EVENT_COMPILED_METHOD_LOAD,
+            (method: jni::jmethodID, code_size: jni::jint, code_addr: *const c_void, map_length: jni::jint,
+             map: *const c_void, compile_info: *const c_void);
+        compiled_method_unload, on_compiled_method_unload, jvmti::JVMTI_EVENT_COMPILED_METHOD_UNLOAD,
+            (method: jni::jmethodID, code_addr: *const c_void);
+        dynamic_code_generated, on_dynamic_code_generated, jvmti::JVMTI_EVENT_DYNAMIC_CODE_GENERATED,
+            (name: *const c_char, address: *const c_void, length: jni::jint);
+        data_dump_request, on_data_dump_request, jvmti::JVMTI_EVENT_DATA_DUMP_REQUEST, ();
+        garbage_collection_start, on_garbage_collection_start, jvmti::JVMTI_EVENT_GARBAGE_COLLECTION_START, ();
+        garbage_collection_finish, on_garbage_collection_finish, jvmti::JVMTI_EVENT_GARBAGE_COLLECTION_FINISH, ();
+        object_free, on_object_free, jvmti::JVMTI_EVENT_OBJECT_FREE, (tag: jni::jlong);
+    }
+}
+
+impl EventRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Install this registry as the process-wide agent and enable its events.
+    ///
+    /// This registers the registry in [`crate::GLOBAL_AGENT`], wires the default
+    /// callback trampolines, and enables every event that has a handler for all
+    /// threads. Capabilities required by those events must already be added.
+    ///
+    /// Returns `DUPLICATE` if an agent is already registered (for example via
+    /// [`crate::export_agent!`]).
+    pub fn install(self, jvmti: &Jvmti) -> Result<(), jvmti::jvmtiError> {
+        let events = self.registered_events();
+        crate::set_global_agent(Box::new(self)).map_err(|_| jvmti::jvmtiError::DUPLICATE)?;
+        jvmti.set_default_agent_callbacks()?;
+        jvmti.enable_events_global(&events)
+    }
+}
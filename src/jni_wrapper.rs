@@ -21,6 +21,13 @@
 //!         env.exception_describe();
 //!         env.exception_clear();
 //!     }
+//!
+//!     // Or let a checked call do it: a thrown exception comes back as `Err`
+//!     let length = env.get_method_id(string_class, "length", "()I").unwrap();
+//!     match env.call_int_method_checked(greeting, length, &[]) {
+//!         Ok(n) => println!("length = {}", n),
+//!         Err(e) => eprintln!("length() threw: {}", e),
+//!     }
 //! }
 //! ```
 //!
@@ -41,12 +48,249 @@
 //! fn test(r: GlobalRef) { assert_send(r); }
 //! ```
 
+use crate::classfile::descriptor::{parse_method_descriptor, JavaType};
+use crate::member_ids::{FieldId, MethodId};
+use crate::mutf8;
 use crate::sys::jni;
-use std::ffi::{CStr, CString};
 use std::marker::PhantomData;
-use std::ptr;
+use std::ptr::{self, NonNull};
 use std::rc::Rc;
 
+/// Generates `Vec`/slice conversions for a primitive array type.
+macro_rules! array_conversions {
+    ($( $get:ident, $new_from:ident, $ty:ty, $new_raw:ident, $get_raw:ident, $set_raw:ident; )*) => {
+        $(
+            #[doc = concat!("Copies a Java `", stringify!($ty), "` array into a `Vec`.")]
+            ///
+            /// Returns `None` for a null array or if the copy raised an exception.
+            pub fn $get(&self, array: jni::jarray) -> Option<Vec<$ty>> {
+                if array.is_null() {
+                    return None;
+                }
+                let len = self.get_array_length(array);
+                let mut buf: Vec<$ty> = vec![Default::default(); len.max(0) as usize];
+                if len > 0 {
+                    unsafe {
+                        let vtable = *self.env;
+                        ((*vtable).$get_raw)(self.env, array, 0, len, buf.as_mut_ptr());
+                    }
+                    if self.exception_check() {
+                        return None;
+                    }
+                }
+                Some(buf)
+            }
+
+            #[doc = concat!("Creates a new Java array holding a copy of `data` (`", stringify!($new_raw), "`).")]
+            pub fn $new_from(&self, data: &[$ty]) -> Option<jni::jarray> {
+                let len = jni::jsize::try_from(data.len()).ok()?;
+                unsafe {
+                    let vtable = *self.env;
+                    let arr = ((*vtable).$new_raw)(self.env, len);
+                    if arr.is_null() {
+                        return None;
+                    }
+                    if len > 0 {
+                        ((*vtable).$set_raw)(self.env, arr, 0, len, data.as_ptr());
+                        if self.exception_check() {
+                            return None;
+                        }
+                    }
+                    Some(arr)
+                }
+            }
+        )*
+    };
+}
+
+/// Generates `Call*MethodA` wrappers that surface a pending exception as `Err`.
+macro_rules! checked_calls {
+    (
+        instance { $( $iname:ident, $iraw:ident, $ity:ty, |$iv:ident| $iconv:expr; )* }
+        nonvirtual { $( $nname:ident, $nraw:ident, $nty:ty, |$nv:ident| $nconv:expr; )* }
+        static { $( $sname:ident, $sraw:ident, $sty:ty, |$sv:ident| $sconv:expr; )* }
+    ) => {
+        $(
+            #[doc = concat!("Calls `", stringify!($iraw), "`, returning `Err` if the method threw.")]
+            pub fn $iname(
+                &self,
+                obj: jni::jobject,
+                method_id: jni::jmethodID,
+                args: &[jni::jvalue],
+            ) -> Result<$ity, JavaException<'_>> {
+                let $iv = unsafe {
+                    let vtable = *self.env;
+                    ((*vtable).$iraw)(self.env, obj, method_id, args.as_ptr())
+                };
+                self.check_exception()?;
+                Ok($iconv)
+            }
+        )*
+        $(
+            #[doc = concat!("Calls `", stringify!($nraw), "`, returning `Err` if the method threw.")]
+            pub fn $nname(
+                &self,
+                obj: jni::jobject,
+                cls: jni::jclass,
+                method_id: jni::jmethodID,
+                args: &[jni::jvalue],
+            ) -> Result<$nty, JavaException<'_>> {
+                let $nv = unsafe {
+                    let vtable = *self.env;
+                    ((*vtable).$nraw)(self.env, obj, cls, method_id, args.as_ptr())
+                };
+                self.check_exception()?;
+                Ok($nconv)
+            }
+        )*
+        $(
+            #[doc = concat!("Calls `", stringify!($sraw), "`, returning `Err` if the method threw.")]
+            pub fn $sname(
+                &self,
+                cls: jni::jclass,
+                method_id: jni::jmethodID,
+                args: &[jni::jvalue],
+            ) -> Result<$sty, JavaException<'_>> {
+                let $sv = unsafe {
+                    let vtable = *self.env;
+                    ((*vtable).$sraw)(self.env, cls, method_id, args.as_ptr())
+                };
+                self.check_exception()?;
+                Ok($sconv)
+            }
+        )*
+    };
+}
+
+/// Typed JNI status code for failing `jint` results.
+///
+/// `JNI_OK` is not represented; functions return `Ok(..)` for success.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JniError {
+    /// `JNI_ERR` (-1): unknown error.
+    Err,
+    /// `JNI_EDETACHED` (-2): thread detached from the VM.
+    Detached,
+    /// `JNI_EVERSION` (-3): requested interface version is not supported.
+    Version,
+    /// `JNI_ENOMEM` (-4): not enough memory.
+    NoMem,
+    /// `JNI_EEXIST` (-5): VM already created.
+    Exists,
+    /// `JNI_EINVAL` (-6): invalid arguments.
+    Invalid,
+    /// Any other non-zero status code.
+    Other(jni::jint),
+}
+
+impl JniError {
+    /// Convert a raw JNI status code. `JNI_OK` maps to `Other(0)`.
+    pub const fn from_raw(code: jni::jint) -> Self {
+        match code {
+            jni::JNI_ERR => JniError::Err,
+            jni::JNI_EDETACHED => JniError::Detached,
+            jni::JNI_EVERSION => JniError::Version,
+            jni::JNI_ENOMEM => JniError::NoMem,
+            jni::JNI_EEXIST => JniError::Exists,
+            jni::JNI_EINVAL => JniError::Invalid,
+            other => JniError::Other(other),
+        }
+    }
+
+    /// Raw JNI status code, e.g. for returning from `Agent_OnLoad`.
+    pub const fn as_raw(self) -> jni::jint {
+        match self {
+            JniError::Err => jni::JNI_ERR,
+            JniError::Detached => jni::JNI_EDETACHED,
+            JniError::Version => jni::JNI_EVERSION,
+            JniError::NoMem => jni::JNI_ENOMEM,
+            JniError::Exists => jni::JNI_EEXIST,
+            JniError::Invalid => jni::JNI_EINVAL,
+            JniError::Other(code) => code,
+        }
+    }
+}
+
+impl From<jni::jint> for JniError {
+    fn from(code: jni::jint) -> Self {
+        JniError::from_raw(code)
+    }
+}
+
+impl From<JniError> for jni::jint {
+    fn from(err: JniError) -> Self {
+        err.as_raw()
+    }
+}
+
+impl std::fmt::Display for JniError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&jni::describe_result(self.as_raw()))
+    }
+}
+
+impl std::error::Error for JniError {}
+
+/// A Java value tagged with its type: the safe counterpart of [`jni::jvalue`].
+///
+/// Read and written by [`JniEnv::get_field`] and [`JniEnv::set_field`].
+/// `Object` holds a reference (possibly null); one read from a field is a new
+/// local reference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JavaValue {
+    Boolean(bool),
+    Byte(jni::jbyte),
+    Char(jni::jchar),
+    Short(jni::jshort),
+    Int(jni::jint),
+    Long(jni::jlong),
+    Float(jni::jfloat),
+    Double(jni::jdouble),
+    Object(jni::jobject),
+}
+
+impl From<JavaValue> for jni::jvalue {
+    fn from(value: JavaValue) -> Self {
+        match value {
+            JavaValue::Boolean(v) => jni::jvalue::boolean(v),
+            JavaValue::Byte(v) => jni::jvalue::byte(v),
+            JavaValue::Char(v) => jni::jvalue::char(v),
+            JavaValue::Short(v) => jni::jvalue::short(v),
+            JavaValue::Int(v) => jni::jvalue::int(v),
+            JavaValue::Long(v) => jni::jvalue::long(v),
+            JavaValue::Float(v) => jni::jvalue::float(v),
+            JavaValue::Double(v) => jni::jvalue::double(v),
+            JavaValue::Object(v) => jni::jvalue::object(v),
+        }
+    }
+}
+
+/// A `JNIEnv*` checked to be non-null and to point at a non-null
+/// `JNINativeInterface_` table.
+///
+/// See [`JvmtiEnvPtr`](crate::env::JvmtiEnvPtr) for the rationale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JniEnvPtr(NonNull<jni::JNIEnv>);
+
+impl JniEnvPtr {
+    /// Returns `None` if `env` or its function table is null.
+    ///
+    /// # Safety
+    /// `env` must be null or point to a readable `JNIEnv`.
+    pub unsafe fn new(env: *mut jni::JNIEnv) -> Option<Self> {
+        let env = NonNull::new(env)?;
+        if (*env.as_ptr()).is_null() {
+            return None;
+        }
+        Some(JniEnvPtr(env))
+    }
+
+    /// The raw pointer, for FFI.
+    pub fn as_ptr(self) -> *mut jni::JNIEnv {
+        self.0.as_ptr()
+    }
+}
+
 /// Safe wrapper around a JNI environment pointer.
 ///
 /// This struct provides ergonomic access to JNI functions with proper
@@ -71,6 +315,25 @@ impl JniEnv {
         JniEnv { env, _not_send_sync: PhantomData }
     }
 
+    /// Like [`JniEnv::from_raw`], but fails with `JniError::Err` if `env` or
+    /// its function table is null.
+    ///
+    /// # Safety
+    ///
+    /// `env` must be null or valid, and belong to the current thread.
+    pub unsafe fn try_from_raw(env: *mut jni::JNIEnv) -> Result<Self, JniError> {
+        JniEnvPtr::new(env).map(|env| JniEnv::from_env_ptr(env)).ok_or(JniError::Err)
+    }
+
+    /// Wraps an already validated environment.
+    ///
+    /// # Safety
+    ///
+    /// `env` must belong to the current thread.
+    pub unsafe fn from_env_ptr(env: JniEnvPtr) -> Self {
+        JniEnv::from_raw(env.as_ptr())
+    }
+
     /// Returns the raw JNI environment pointer.
     pub fn raw(&self) -> *mut jni::JNIEnv {
         self.env
@@ -86,6 +349,79 @@ impl JniEnv {
         }
     }
 
+    /// Attaches the current native thread to the VM and returns its environment.
+    ///
+    /// Needed before a thread spawned by the agent (for example with
+    /// `std::thread::spawn`) may call JNI. Attaching an already attached thread
+    /// returns its existing environment. The caller must call
+    /// [`detach_current_thread`](Self::detach_current_thread) before the thread
+    /// exits; [`AttachGuard`] does that automatically.
+    pub fn attach_current_thread(vm: *mut jni::JavaVM) -> Result<JniEnv, JniError> {
+        Self::attach(vm, false)
+    }
+
+    /// Like [`attach_current_thread`](Self::attach_current_thread), but as a
+    /// daemon thread, which does not keep the VM from shutting down.
+    pub fn attach_current_thread_as_daemon(vm: *mut jni::JavaVM) -> Result<JniEnv, JniError> {
+        Self::attach(vm, true)
+    }
+
+    fn attach(vm: *mut jni::JavaVM, daemon: bool) -> Result<JniEnv, JniError> {
+        if vm.is_null() {
+            return Err(JniError::Err);
+        }
+        let mut env_ptr: *mut std::ffi::c_void = ptr::null_mut();
+        unsafe {
+            if (*vm).is_null() {
+                return Err(JniError::Err);
+            }
+            let attach_fn = if daemon { (**vm).AttachCurrentThreadAsDaemon } else { (**vm).AttachCurrentThread };
+            let res = attach_fn(vm, &mut env_ptr, ptr::null_mut());
+            if res != jni::JNI_OK {
+                return Err(JniError::from_raw(res));
+            }
+        }
+        unsafe { JniEnv::try_from_raw(env_ptr as *mut jni::JNIEnv) }
+    }
+
+    /// Detaches the current native thread from the VM.
+    ///
+    /// Any `JniEnv` for this thread is invalid afterwards. Threads the VM
+    /// started itself (including `RunAgentThread` threads) must not be detached.
+    pub fn detach_current_thread(vm: *mut jni::JavaVM) -> Result<(), JniError> {
+        if vm.is_null() {
+            return Err(JniError::Err);
+        }
+        unsafe {
+            if (*vm).is_null() {
+                return Err(JniError::Err);
+            }
+            let res = ((**vm).DetachCurrentThread)(vm);
+            if res != jni::JNI_OK {
+                return Err(JniError::from_raw(res));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the current thread's environment if it is already attached.
+    pub fn get_current_env(vm: *mut jni::JavaVM) -> Result<JniEnv, JniError> {
+        if vm.is_null() {
+            return Err(JniError::Err);
+        }
+        let mut env_ptr: *mut std::ffi::c_void = ptr::null_mut();
+        unsafe {
+            if (*vm).is_null() {
+                return Err(JniError::Err);
+            }
+            let res = ((**vm).GetEnv)(vm, &mut env_ptr, jni::JNI_VERSION_1_6);
+            if res != jni::JNI_OK {
+                return Err(JniError::from_raw(res));
+            }
+        }
+        unsafe { JniEnv::try_from_raw(env_ptr as *mut jni::JNIEnv) }
+    }
+
     // =========================================================================
     // Version
     // =========================================================================
@@ -106,7 +442,7 @@ impl JniEnv {
     ///
     /// The name should use '/' as package separator (e.g., "java/lang/String").
     pub fn find_class(&self, name: &str) -> Option<jni::jclass> {
-        let c_name = CString::new(name).ok()?;
+        let c_name = mutf8::to_cstring(name);
         unsafe {
             let vtable = *self.env;
             let cls = ((*vtable).FindClass)(self.env, c_name.as_ptr());
@@ -114,6 +450,12 @@ impl JniEnv {
         }
     }
 
+    /// Like [`find_class`](Self::find_class), but also accepts the dotted Java
+    /// name (`java.lang.System`), converting it to the internal form.
+    pub fn find_class_dotted(&self, name: &str) -> Option<jni::jclass> {
+        self.find_class(&name.replace('.', "/"))
+    }
+
     /// Defines a class from raw classfile bytes.
     ///
     /// `name` must be the internal JVM class name, such as `com/example/Helper`.
@@ -122,7 +464,7 @@ impl JniEnv {
         if bytes.len() > jni::jsize::MAX as usize {
             return None;
         }
-        let c_name = CString::new(name).ok()?;
+        let c_name = mutf8::to_cstring(name);
         unsafe {
             let vtable = *self.env;
             let cls = ((*vtable).DefineClass)(
@@ -154,6 +496,9 @@ impl JniEnv {
     }
 
     /// Gets the class of an object.
+    ///
+    /// The result is a new local reference; [`JniEnv::get_object_class_ref`]
+    /// deletes it automatically.
     pub fn get_object_class(&self, obj: jni::jobject) -> jni::jclass {
         unsafe {
             let vtable = *self.env;
@@ -161,6 +506,11 @@ impl JniEnv {
         }
     }
 
+    /// Gets the class of an object as a [`LocalRef`], deleted when dropped.
+    pub fn get_object_class_ref(&self, obj: jni::jobject) -> LocalRef<'_> {
+        LocalRef::new(self, self.get_object_class(obj))
+    }
+
     /// Checks if an object is an instance of a class.
     pub fn is_instance_of(&self, obj: jni::jobject, cls: jni::jclass) -> bool {
         unsafe {
@@ -339,6 +689,24 @@ impl JniEnv {
         result
     }
 
+    // =========================================================================
+    // Virtual Threads
+    // =========================================================================
+
+    /// Returns true if `thread` is a `java.lang.Thread` that is a virtual thread.
+    ///
+    /// Uses `IsVirtualThread` (JNI 19+). On older VMs the function table has no
+    /// such entry and every thread is a platform thread, so this returns false.
+    pub fn is_virtual_thread(&self, thread: jni::jobject) -> bool {
+        if thread.is_null() || self.get_version() < jni::JNI_VERSION_19 {
+            return false;
+        }
+        unsafe {
+            let vtable = *self.env;
+            ((*vtable).IsVirtualThread)(self.env, thread) != 0
+        }
+    }
+
     // =========================================================================
     // Exception Handling
     // =========================================================================
@@ -387,7 +755,7 @@ impl JniEnv {
 
     /// Throws a new exception of the specified class with the given message.
     pub fn throw_new(&self, cls: jni::jclass, msg: &str) -> Result<(), jni::jint> {
-        let c_msg = CString::new(msg).map_err(|_| -1)?;
+        let c_msg = mutf8::to_cstring(msg);
         unsafe {
             let vtable = *self.env;
             let result = ((*vtable).ThrowNew)(self.env, cls, c_msg.as_ptr());
@@ -400,8 +768,11 @@ impl JniEnv {
     // =========================================================================
 
     /// Creates a new Java string from a Rust string.
+    ///
+    /// The string is passed as [modified UTF-8](crate::mutf8), so embedded
+    /// NULs and supplementary characters survive.
     pub fn new_string_utf(&self, s: &str) -> Option<jni::jstring> {
-        let c_str = CString::new(s).ok()?;
+        let c_str = mutf8::to_cstring(s);
         unsafe {
             let vtable = *self.env;
             let jstr = ((*vtable).NewStringUTF)(self.env, c_str.as_ptr());
@@ -421,11 +792,12 @@ impl JniEnv {
 
     /// Gets a Rust string from a Java string.
     ///
-    /// Returns `None` if the string is null or contains invalid modified UTF-8.
-    /// For full-fidelity Unicode (including embedded nulls), use
-    /// [`Self::get_string`].
+    /// The VM's [modified UTF-8](crate::mutf8) is decoded, so embedded NULs
+    /// and supplementary characters survive; unpaired surrogates become
+    /// U+FFFD. Returns `None` if the string is null or an exception is
+    /// pending. [`Self::get_string`] copies the UTF-16 code units instead.
     pub fn get_string_utf(&self, s: jni::jstring) -> Option<String> {
-        if s.is_null() {
+        if s.is_null() || self.exception_check() {
             return None;
         }
         unsafe {
@@ -434,17 +806,17 @@ impl JniEnv {
             if chars.is_null() {
                 return None;
             }
-            let result = CStr::from_ptr(chars).to_str().ok().map(|s| s.to_string());
+            let result = mutf8::from_cstr(chars);
             ((*vtable).ReleaseStringUTFChars)(self.env, s, chars);
-            result
+            Some(result)
         }
     }
 
     /// Gets a Rust string from a Java string using UTF-16.
     ///
-    /// Returns `None` if the string is null.
+    /// Returns `None` if the string is null or an exception is pending.
     pub fn get_string(&self, s: jni::jstring) -> Option<String> {
-        if s.is_null() {
+        if s.is_null() || self.exception_check() {
             return None;
         }
         unsafe {
@@ -461,6 +833,26 @@ impl JniEnv {
         }
     }
 
+    /// Copies `len` UTF-16 code units starting at `start` into a Rust string.
+    ///
+    /// Uses `GetStringRegion`, so no pinned buffer needs releasing. Returns
+    /// `None` if the string is null, an exception is pending, or the range is
+    /// out of bounds (the `StringIndexOutOfBoundsException` is left pending).
+    pub fn get_string_region(&self, s: jni::jstring, start: jni::jsize, len: jni::jsize) -> Option<String> {
+        if s.is_null() || start < 0 || len < 0 || self.exception_check() {
+            return None;
+        }
+        let mut buf: Vec<jni::jchar> = vec![0; len as usize];
+        unsafe {
+            let vtable = *self.env;
+            ((*vtable).GetStringRegion)(self.env, s, start, len, buf.as_mut_ptr());
+        }
+        if self.exception_check() {
+            return None;
+        }
+        Some(String::from_utf16_lossy(&buf))
+    }
+
     /// Gets the UTF-8 length of a Java string.
     pub fn get_string_utf_length(&self, s: jni::jstring) -> jni::jsize {
         unsafe {
@@ -483,8 +875,8 @@ impl JniEnv {
 
     /// Gets the method ID for an instance method.
     pub fn get_method_id(&self, cls: jni::jclass, name: &str, sig: &str) -> Option<jni::jmethodID> {
-        let c_name = CString::new(name).ok()?;
-        let c_sig = CString::new(sig).ok()?;
+        let c_name = mutf8::to_cstring(name);
+        let c_sig = mutf8::to_cstring(sig);
         unsafe {
             let vtable = *self.env;
             let mid = ((*vtable).GetMethodID)(self.env, cls, c_name.as_ptr(), c_sig.as_ptr());
@@ -494,8 +886,8 @@ impl JniEnv {
 
     /// Gets the method ID for a static method.
     pub fn get_static_method_id(&self, cls: jni::jclass, name: &str, sig: &str) -> Option<jni::jmethodID> {
-        let c_name = CString::new(name).ok()?;
-        let c_sig = CString::new(sig).ok()?;
+        let c_name = mutf8::to_cstring(name);
+        let c_sig = mutf8::to_cstring(sig);
         unsafe {
             let vtable = *self.env;
             let mid = ((*vtable).GetStaticMethodID)(self.env, cls, c_name.as_ptr(), c_sig.as_ptr());
@@ -509,8 +901,8 @@ impl JniEnv {
 
     /// Gets the field ID for an instance field.
     pub fn get_field_id(&self, cls: jni::jclass, name: &str, sig: &str) -> Option<jni::jfieldID> {
-        let c_name = CString::new(name).ok()?;
-        let c_sig = CString::new(sig).ok()?;
+        let c_name = mutf8::to_cstring(name);
+        let c_sig = mutf8::to_cstring(sig);
         unsafe {
             let vtable = *self.env;
             let fid = ((*vtable).GetFieldID)(self.env, cls, c_name.as_ptr(), c_sig.as_ptr());
@@ -520,8 +912,8 @@ impl JniEnv {
 
     /// Gets the field ID for a static field.
     pub fn get_static_field_id(&self, cls: jni::jclass, name: &str, sig: &str) -> Option<jni::jfieldID> {
-        let c_name = CString::new(name).ok()?;
-        let c_sig = CString::new(sig).ok()?;
+        let c_name = mutf8::to_cstring(name);
+        let c_sig = mutf8::to_cstring(sig);
         unsafe {
             let vtable = *self.env;
             let fid = ((*vtable).GetStaticFieldID)(self.env, cls, c_name.as_ptr(), c_sig.as_ptr());
@@ -529,6 +921,26 @@ impl JniEnv {
         }
     }
 
+    /// [`JniEnv::get_method_id`] returning a typed [`MethodId`].
+    pub fn method_id(&self, cls: jni::jclass, name: &str, sig: &str) -> Option<MethodId> {
+        self.get_method_id(cls, name, sig).and_then(MethodId::from_raw)
+    }
+
+    /// [`JniEnv::get_static_method_id`] returning a typed [`MethodId`].
+    pub fn static_method_id(&self, cls: jni::jclass, name: &str, sig: &str) -> Option<MethodId> {
+        self.get_static_method_id(cls, name, sig).and_then(MethodId::from_raw)
+    }
+
+    /// [`JniEnv::get_field_id`] returning a typed [`FieldId`].
+    pub fn field_id(&self, cls: jni::jclass, name: &str, sig: &str) -> Option<FieldId> {
+        self.get_field_id(cls, name, sig).and_then(FieldId::from_raw)
+    }
+
+    /// [`JniEnv::get_static_field_id`] returning a typed [`FieldId`].
+    pub fn static_field_id(&self, cls: jni::jclass, name: &str, sig: &str) -> Option<FieldId> {
+        self.get_static_field_id(cls, name, sig).and_then(FieldId::from_raw)
+    }
+
     // =========================================================================
     // Object Operations
     // =========================================================================
@@ -613,6 +1025,36 @@ impl JniEnv {
         }
     }
 
+    /// Returns whether `obj` is a local, global, or weak global reference.
+    ///
+    /// `JNIInvalidRefType` means the VM does not recognise it, typically a
+    /// local reference kept past the callback that created it, or one that was
+    /// already deleted. The VM may still crash on a reference it freed long
+    /// ago, so treat this as a debugging aid rather than a safety check.
+    pub fn ref_type(&self, obj: jni::jobject) -> jni::jobjectRefType {
+        unsafe {
+            let vtable = *self.env;
+            ((*vtable).GetObjectRefType)(self.env, obj)
+        }
+    }
+
+    /// In debug builds, logs a warning through the
+    /// [log callback](crate::set_log_callback) if `obj` is neither null nor a
+    /// valid reference. Returns `false` in that case.
+    ///
+    /// Compiles to nothing in release builds, so it can guard every use of a
+    /// reference stored across callbacks.
+    pub fn debug_assert_valid_ref(&self, obj: jni::jobject) -> bool {
+        if !cfg!(debug_assertions) || obj.is_null() {
+            return true;
+        }
+        if self.ref_type(obj) != jni::jobjectRefType::JNIInvalidRefType {
+            return true;
+        }
+        crate::log(crate::LogLevel::Warn, format_args!("invalid JNI reference {obj:p}: deleted, or a local reference used outside its frame"));
+        false
+    }
+
     /// Ensures capacity for the given number of local references.
     pub fn ensure_local_capacity(&self, capacity: jni::jint) -> Result<(), jni::jint> {
         unsafe {
@@ -639,6 +1081,38 @@ impl JniEnv {
         }
     }
 
+    /// Runs `f` inside a fresh local reference frame.
+    ///
+    /// Every local reference created by `f` is released when it returns, even if it
+    /// panics. Use this around loops that create many local references.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// for &class in &classes {
+    ///     jni.with_local_frame(16, |jni| {
+    ///         let name = jni.call_object_method(class, get_name, &[]);
+    ///         jni.get_string(name)
+    ///     })?;
+    /// }
+    /// ```
+    pub fn with_local_frame<T>(&self, capacity: jni::jint, f: impl FnOnce(&JniEnv) -> T) -> Result<T, jni::jint> {
+        let _frame = LocalFrameGuard::new(self, capacity)?;
+        Ok(f(self))
+    }
+
+    /// Like [`with_local_frame`](Self::with_local_frame), but keeps the reference
+    /// returned by `f` alive as a local reference in the enclosing frame.
+    pub fn with_local_frame_returning(
+        &self,
+        capacity: jni::jint,
+        f: impl FnOnce(&JniEnv) -> jni::jobject,
+    ) -> Result<jni::jobject, jni::jint> {
+        let frame = LocalFrameGuard::new(self, capacity)?;
+        let result = f(self);
+        Ok(frame.pop(result))
+    }
+
     // =========================================================================
     // Array Operations
     // =========================================================================
@@ -751,6 +1225,61 @@ impl JniEnv {
         }
     }
 
+    /// Copies a Java `byte[]` into a `Vec<u8>`.
+    ///
+    /// Returns `None` for a null array or if the copy raised an exception.
+    pub fn get_byte_array(&self, array: jni::jbyteArray) -> Option<Vec<u8>> {
+        if array.is_null() {
+            return None;
+        }
+        let len = self.get_array_length(array);
+        let mut buf = vec![0u8; len.max(0) as usize];
+        if len > 0 {
+            unsafe {
+                let vtable = *self.env;
+                ((*vtable).GetByteArrayRegion)(self.env, array, 0, len, buf.as_mut_ptr() as *mut jni::jbyte);
+            }
+            if self.exception_check() {
+                return None;
+            }
+        }
+        Some(buf)
+    }
+
+    /// Creates a new Java `byte[]` holding a copy of `data`.
+    pub fn new_byte_array_from(&self, data: &[u8]) -> Option<jni::jbyteArray> {
+        let len = jni::jsize::try_from(data.len()).ok()?;
+        let arr = self.new_byte_array(len)?;
+        if len > 0 {
+            unsafe {
+                let vtable = *self.env;
+                ((*vtable).SetByteArrayRegion)(self.env, arr, 0, len, data.as_ptr() as *const jni::jbyte);
+            }
+            if self.exception_check() {
+                return None;
+            }
+        }
+        Some(arr)
+    }
+
+    array_conversions! {
+        get_int_array, new_int_array_from, jni::jint, NewIntArray, GetIntArrayRegion, SetIntArrayRegion;
+        get_long_array, new_long_array_from, jni::jlong, NewLongArray, GetLongArrayRegion, SetLongArrayRegion;
+        get_char_array, new_char_array_from, jni::jchar, NewCharArray, GetCharArrayRegion, SetCharArrayRegion;
+        get_double_array, new_double_array_from, jni::jdouble, NewDoubleArray, GetDoubleArrayRegion, SetDoubleArrayRegion;
+    }
+
+    /// Collects the elements of a Java object array.
+    ///
+    /// Returns an empty `Vec` for a null array. Each element is a new local reference.
+    pub fn get_object_array(&self, array: jni::jobjectArray) -> Vec<jni::jobject> {
+        if array.is_null() {
+            return Vec::new();
+        }
+        let len = self.get_array_length(array);
+        (0..len).map(|i| self.get_object_array_element(array, i)).collect()
+    }
+
     // =========================================================================
     // Method Calls
     // =========================================================================
@@ -819,6 +1348,197 @@ impl JniEnv {
         }
     }
 
+    // =========================================================================
+    // Checked Method Calls
+    // =========================================================================
+
+    /// Returns the pending exception as `Err`, clearing it from the thread.
+    ///
+    /// Returns `Ok(())` when no exception is pending.
+    pub fn check_exception(&self) -> Result<(), JavaException<'_>> {
+        if !self.exception_check() {
+            return Ok(());
+        }
+        let throwable = self.exception_occurred().unwrap_or(ptr::null_mut());
+        self.exception_clear();
+        Err(JavaException { throwable: LocalRef::new(self, throwable) })
+    }
+
+    /// Calls `CallVoidMethodA`, returning `Err` if the method threw.
+    pub fn call_void_method_checked(
+        &self,
+        obj: jni::jobject,
+        method_id: jni::jmethodID,
+        args: &[jni::jvalue],
+    ) -> Result<(), JavaException<'_>> {
+        self.call_void_method(obj, method_id, args);
+        self.check_exception()
+    }
+
+    /// Calls `CallNonvirtualVoidMethodA`, returning `Err` if the method threw.
+    pub fn call_nonvirtual_void_method_checked(
+        &self,
+        obj: jni::jobject,
+        cls: jni::jclass,
+        method_id: jni::jmethodID,
+        args: &[jni::jvalue],
+    ) -> Result<(), JavaException<'_>> {
+        unsafe {
+            let vtable = *self.env;
+            ((*vtable).CallNonvirtualVoidMethodA)(self.env, obj, cls, method_id, args.as_ptr());
+        }
+        self.check_exception()
+    }
+
+    /// Calls `CallStaticVoidMethodA`, returning `Err` if the method threw.
+    pub fn call_static_void_method_checked(
+        &self,
+        cls: jni::jclass,
+        method_id: jni::jmethodID,
+        args: &[jni::jvalue],
+    ) -> Result<(), JavaException<'_>> {
+        self.call_static_void_method(cls, method_id, args);
+        self.check_exception()
+    }
+
+    checked_calls! {
+        instance {
+            call_object_method_checked, CallObjectMethodA, jni::jobject, |v| v;
+            call_boolean_method_checked, CallBooleanMethodA, bool, |v| v != 0;
+            call_byte_method_checked, CallByteMethodA, jni::jbyte, |v| v;
+            call_char_method_checked, CallCharMethodA, jni::jchar, |v| v;
+            call_short_method_checked, CallShortMethodA, jni::jshort, |v| v;
+            call_int_method_checked, CallIntMethodA, jni::jint, |v| v;
+            call_long_method_checked, CallLongMethodA, jni::jlong, |v| v;
+            call_float_method_checked, CallFloatMethodA, jni::jfloat, |v| v;
+            call_double_method_checked, CallDoubleMethodA, jni::jdouble, |v| v;
+        }
+        nonvirtual {
+            call_nonvirtual_object_method_checked, CallNonvirtualObjectMethodA, jni::jobject, |v| v;
+            call_nonvirtual_boolean_method_checked, CallNonvirtualBooleanMethodA, bool, |v| v != 0;
+            call_nonvirtual_byte_method_checked, CallNonvirtualByteMethodA, jni::jbyte, |v| v;
+            call_nonvirtual_char_method_checked, CallNonvirtualCharMethodA, jni::jchar, |v| v;
+            call_nonvirtual_short_method_checked, CallNonvirtualShortMethodA, jni::jshort, |v| v;
+            call_nonvirtual_int_method_checked, CallNonvirtualIntMethodA, jni::jint, |v| v;
+            call_nonvirtual_long_method_checked, CallNonvirtualLongMethodA, jni::jlong, |v| v;
+            call_nonvirtual_float_method_checked, CallNonvirtualFloatMethodA, jni::jfloat, |v| v;
+            call_nonvirtual_double_method_checked, CallNonvirtualDoubleMethodA, jni::jdouble, |v| v;
+        }
+        static {
+            call_static_object_method_checked, CallStaticObjectMethodA, jni::jobject, |v| v;
+            call_static_boolean_method_checked, CallStaticBooleanMethodA, bool, |v| v != 0;
+            call_static_byte_method_checked, CallStaticByteMethodA, jni::jbyte, |v| v;
+            call_static_char_method_checked, CallStaticCharMethodA, jni::jchar, |v| v;
+            call_static_short_method_checked, CallStaticShortMethodA, jni::jshort, |v| v;
+            call_static_int_method_checked, CallStaticIntMethodA, jni::jint, |v| v;
+            call_static_long_method_checked, CallStaticLongMethodA, jni::jlong, |v| v;
+            call_static_float_method_checked, CallStaticFloatMethodA, jni::jfloat, |v| v;
+            call_static_double_method_checked, CallStaticDoubleMethodA, jni::jdouble, |v| v;
+        }
+    }
+
+    // =========================================================================
+    // Calls by Name
+    // =========================================================================
+
+    /// Resolves and calls a static method in one step.
+    ///
+    /// `class` may use dots or slashes. The `CallStatic*MethodA` variant is
+    /// picked from the return type in `sig`; `void` methods return a zeroed
+    /// `jvalue`. Resolution failures surface as the Java exception the VM
+    /// raised (`NoClassDefFoundError`, `NoSuchMethodError`), and a malformed
+    /// `sig` or an argument count that does not match it as an
+    /// `IllegalArgumentException`, so the call never reads past `args`.
+    ///
+    /// ```rust,ignore
+    /// let now = jni.call_static_method("java/lang/System", "currentTimeMillis", "()J", &[])?;
+    /// let millis = unsafe { now.j };
+    /// ```
+    ///
+    /// This looks up the class and method on every call; cache the IDs (and a
+    /// [`ClassCache`](crate::env::ClassCache)) for calls on hot paths.
+    pub fn call_static_method(
+        &self,
+        class: &str,
+        name: &str,
+        sig: &str,
+        args: &[jni::jvalue],
+    ) -> Result<jni::jvalue, JavaException<'_>> {
+        let ret = self.call_return_type(sig, args)?;
+        let cls = LocalRef::new(self, self.find_class_dotted(class).ok_or_else(|| self.pending_exception())?);
+        let cls = cls.get();
+        let method = self.get_static_method_id(cls, name, sig).ok_or_else(|| self.pending_exception())?;
+        Ok(match ret {
+            JavaType::Void => self.call_static_void_method_checked(cls, method, args).map(|()| jni::jvalue::long(0))?,
+            JavaType::Boolean => self.call_static_boolean_method_checked(cls, method, args).map(jni::jvalue::boolean)?,
+            JavaType::Byte => self.call_static_byte_method_checked(cls, method, args).map(jni::jvalue::byte)?,
+            JavaType::Char => self.call_static_char_method_checked(cls, method, args).map(jni::jvalue::char)?,
+            JavaType::Short => self.call_static_short_method_checked(cls, method, args).map(jni::jvalue::short)?,
+            JavaType::Int => self.call_static_int_method_checked(cls, method, args).map(jni::jvalue::int)?,
+            JavaType::Long => self.call_static_long_method_checked(cls, method, args).map(jni::jvalue::long)?,
+            JavaType::Float => self.call_static_float_method_checked(cls, method, args).map(jni::jvalue::float)?,
+            JavaType::Double => self.call_static_double_method_checked(cls, method, args).map(jni::jvalue::double)?,
+            JavaType::Object(_) | JavaType::Array(_) => {
+                self.call_static_object_method_checked(cls, method, args).map(jni::jvalue::object)?
+            }
+        })
+    }
+
+    /// Resolves and calls an instance method of `obj` in one step.
+    ///
+    /// The method is looked up on the runtime class of `obj` and dispatched
+    /// virtually; otherwise this behaves like
+    /// [`call_static_method`](Self::call_static_method).
+    pub fn call_method(
+        &self,
+        obj: jni::jobject,
+        name: &str,
+        sig: &str,
+        args: &[jni::jvalue],
+    ) -> Result<jni::jvalue, JavaException<'_>> {
+        let ret = self.call_return_type(sig, args)?;
+        let cls = self.get_object_class_ref(obj);
+        let method = self.get_method_id(cls.get(), name, sig).ok_or_else(|| self.pending_exception())?;
+        Ok(match ret {
+            JavaType::Void => self.call_void_method_checked(obj, method, args).map(|()| jni::jvalue::long(0))?,
+            JavaType::Boolean => self.call_boolean_method_checked(obj, method, args).map(jni::jvalue::boolean)?,
+            JavaType::Byte => self.call_byte_method_checked(obj, method, args).map(jni::jvalue::byte)?,
+            JavaType::Char => self.call_char_method_checked(obj, method, args).map(jni::jvalue::char)?,
+            JavaType::Short => self.call_short_method_checked(obj, method, args).map(jni::jvalue::short)?,
+            JavaType::Int => self.call_int_method_checked(obj, method, args).map(jni::jvalue::int)?,
+            JavaType::Long => self.call_long_method_checked(obj, method, args).map(jni::jvalue::long)?,
+            JavaType::Float => self.call_float_method_checked(obj, method, args).map(jni::jvalue::float)?,
+            JavaType::Double => self.call_double_method_checked(obj, method, args).map(jni::jvalue::double)?,
+            JavaType::Object(_) | JavaType::Array(_) => {
+                self.call_object_method_checked(obj, method, args).map(jni::jvalue::object)?
+            }
+        })
+    }
+
+    /// Parses the return type of `sig`, throwing `IllegalArgumentException`
+    /// if it is malformed or takes a different number of arguments than `args`.
+    fn call_return_type(&self, sig: &str, args: &[jni::jvalue]) -> Result<JavaType, JavaException<'_>> {
+        let message = match parse_method_descriptor(sig) {
+            Ok((params, ret)) if params.len() == args.len() => return Ok(ret),
+            Ok((params, _)) => format!("{sig} takes {} arguments, {} given", params.len(), args.len()),
+            Err(_) => format!("invalid method descriptor {sig}"),
+        };
+        if let Some(cls) = self.find_class("java/lang/IllegalArgumentException") {
+            let _ = self.throw_new(cls, &message);
+            self.delete_local_ref(cls);
+        }
+        Err(self.pending_exception())
+    }
+
+    /// Takes the exception left by a failed lookup. The throwable is null if
+    /// the lookup failed without raising one (a name containing NUL).
+    fn pending_exception(&self) -> JavaException<'_> {
+        match self.check_exception() {
+            Err(exception) => exception,
+            Ok(()) => JavaException { throwable: LocalRef::new(self, ptr::null_mut()) },
+        }
+    }
+
     // =========================================================================
     // Field Access
     // =========================================================================
@@ -871,6 +1591,50 @@ impl JniEnv {
         }
     }
 
+    /// Reads an instance field of any type, choosing the `Get<Type>Field`
+    /// function from the field descriptor `sig` (`I`, `Ljava/lang/String;`, ...).
+    ///
+    /// Only the first character of `sig` is inspected; returns `None` if it
+    /// does not start a field descriptor. Pair it with
+    /// [`Jvmti::get_class_fields`](crate::env::Jvmti::get_class_fields) and
+    /// `get_field_name` to walk an object's fields generically.
+    pub fn get_field(&self, obj: jni::jobject, field_id: jni::jfieldID, sig: &str) -> Option<JavaValue> {
+        unsafe {
+            let vtable = *self.env;
+            Some(match sig.as_bytes().first()? {
+                b'Z' => JavaValue::Boolean(((*vtable).GetBooleanField)(self.env, obj, field_id) != 0),
+                b'B' => JavaValue::Byte(((*vtable).GetByteField)(self.env, obj, field_id)),
+                b'C' => JavaValue::Char(((*vtable).GetCharField)(self.env, obj, field_id)),
+                b'S' => JavaValue::Short(((*vtable).GetShortField)(self.env, obj, field_id)),
+                b'I' => JavaValue::Int(((*vtable).GetIntField)(self.env, obj, field_id)),
+                b'J' => JavaValue::Long(((*vtable).GetLongField)(self.env, obj, field_id)),
+                b'F' => JavaValue::Float(((*vtable).GetFloatField)(self.env, obj, field_id)),
+                b'D' => JavaValue::Double(((*vtable).GetDoubleField)(self.env, obj, field_id)),
+                b'L' | b'[' => JavaValue::Object(((*vtable).GetObjectField)(self.env, obj, field_id)),
+                _ => return None,
+            })
+        }
+    }
+
+    /// Writes an instance field, choosing the `Set<Type>Field` function from
+    /// the variant of `value`, which must match the field's type.
+    pub fn set_field(&self, obj: jni::jobject, field_id: jni::jfieldID, value: JavaValue) {
+        unsafe {
+            let vtable = *self.env;
+            match value {
+                JavaValue::Boolean(v) => ((*vtable).SetBooleanField)(self.env, obj, field_id, v as jni::jboolean),
+                JavaValue::Byte(v) => ((*vtable).SetByteField)(self.env, obj, field_id, v),
+                JavaValue::Char(v) => ((*vtable).SetCharField)(self.env, obj, field_id, v),
+                JavaValue::Short(v) => ((*vtable).SetShortField)(self.env, obj, field_id, v),
+                JavaValue::Int(v) => ((*vtable).SetIntField)(self.env, obj, field_id, v),
+                JavaValue::Long(v) => ((*vtable).SetLongField)(self.env, obj, field_id, v),
+                JavaValue::Float(v) => ((*vtable).SetFloatField)(self.env, obj, field_id, v),
+                JavaValue::Double(v) => ((*vtable).SetDoubleField)(self.env, obj, field_id, v),
+                JavaValue::Object(v) => ((*vtable).SetObjectField)(self.env, obj, field_id, v),
+            }
+        }
+    }
+
     /// Gets a static object field.
     pub fn get_static_object_field(&self, cls: jni::jclass, field_id: jni::jfieldID) -> jni::jobject {
         unsafe {
@@ -917,6 +1681,34 @@ impl JniEnv {
         }
     }
 
+    /// Enters the monitor of `obj` and returns a guard that exits it when dropped.
+    ///
+    /// This is the Java object lock used by `synchronized` blocks, not a JVMTI
+    /// raw monitor (see `Jvmti::enter_raw_monitor` for those).
+    pub fn enter_monitor(&self, obj: jni::jobject) -> Result<MonitorEnterGuard<'_>, jni::jint> {
+        self.monitor_enter(obj)?;
+        Ok(MonitorEnterGuard { env: self, obj })
+    }
+
+    /// Runs `f` while holding the monitor of `obj`, like a Java `synchronized` block.
+    ///
+    /// The monitor is exited on every path, including a panic in `f`. This is the
+    /// Java object lock, distinct from JVMTI raw monitors.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// jni.synchronized(shared_list, || {
+    ///     jni.call_boolean_method(shared_list, add, &jvalues![item]);
+    /// })?;
+    /// ```
+    pub fn synchronized<T>(&self, obj: jni::jobject, f: impl FnOnce() -> T) -> Result<T, jni::jint> {
+        let guard = self.enter_monitor(obj)?;
+        let value = f();
+        guard.exit()?;
+        Ok(value)
+    }
+
     // =========================================================================
     // Native Method Registration
     // =========================================================================
@@ -974,6 +1766,25 @@ impl<'a> LocalRef<'a> {
         std::mem::forget(self);
         obj
     }
+
+    /// Creates a global reference to the same object.
+    ///
+    /// The local reference stays valid (and is still deleted on drop); the
+    /// returned [`GlobalRef`] outlives the current callback and can be moved
+    /// to other threads.
+    pub fn to_global(&self) -> GlobalRef {
+        // SAFETY: `self.env` is the valid environment of the current thread;
+        // `GlobalRef` keeps only the `JavaVM`.
+        unsafe { GlobalRef::new(self.env, self.obj) }
+    }
+
+    /// Creates a weak global reference to the same object.
+    ///
+    /// The weak reference does not keep the object alive; see [`WeakGlobalRef`].
+    pub fn to_weak(&self) -> WeakGlobalRef {
+        // SAFETY: as for `to_global`.
+        unsafe { WeakGlobalRef::new(self.env, self.obj) }
+    }
 }
 
 impl<'a> Drop for LocalRef<'a> {
@@ -984,21 +1795,215 @@ impl<'a> Drop for LocalRef<'a> {
     }
 }
 
+/// A Java exception thrown by a checked JNI call.
+///
+/// Returned by [`JniEnv::check_exception`] and the `call_*_checked` methods. The
+/// exception has already been cleared; the throwable is held as a [`LocalRef`]
+/// and can be inspected or rethrown.
+pub struct JavaException<'a> {
+    throwable: LocalRef<'a>,
+}
+
+impl<'a> JavaException<'a> {
+    /// Returns the `jthrowable` for the exception.
+    pub fn throwable(&self) -> jni::jthrowable {
+        self.throwable.get()
+    }
+
+    /// Returns the owning local reference to the throwable.
+    pub fn into_local_ref(self) -> LocalRef<'a> {
+        self.throwable
+    }
+
+    /// Rethrows the exception on the current thread.
+    pub fn rethrow(self) -> Result<(), jni::jint> {
+        self.throwable.env.throw(self.throwable.get())
+    }
+}
+
+impl std::fmt::Debug for JavaException<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JavaException")
+            .field("throwable", &self.throwable.get())
+            .finish()
+    }
+}
+
+impl std::fmt::Display for JavaException<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Java exception thrown ({:p})", self.throwable.get())
+    }
+}
+
+impl std::error::Error for JavaException<'_> {}
+
+/// A guard that pops a local reference frame when dropped.
+///
+/// Created with [`LocalFrameGuard::new`], which calls `PushLocalFrame`. Dropping the
+/// guard calls `PopLocalFrame(NULL)`, freeing every local reference created since.
+/// Use [`pop`](LocalFrameGuard::pop) to carry one reference out of the frame.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let frame = LocalFrameGuard::new(&env, 32)?;
+/// let s = env.new_string_utf("hello").unwrap();
+/// let s = frame.pop(s); // `s` is now valid in the outer frame
+/// ```
+pub struct LocalFrameGuard<'a> {
+    env: &'a JniEnv,
+}
+
+impl<'a> LocalFrameGuard<'a> {
+    /// Pushes a new local frame with room for at least `capacity` references.
+    pub fn new(env: &'a JniEnv, capacity: jni::jint) -> Result<Self, jni::jint> {
+        env.push_local_frame(capacity)?;
+        Ok(LocalFrameGuard { env })
+    }
+
+    /// Pops the frame, returning `result` as a local reference in the previous frame.
+    pub fn pop(self, result: jni::jobject) -> jni::jobject {
+        let env = self.env;
+        std::mem::forget(self);
+        env.pop_local_frame(result)
+    }
+}
+
+impl<'a> Drop for LocalFrameGuard<'a> {
+    fn drop(&mut self) {
+        self.env.pop_local_frame(ptr::null_mut());
+    }
+}
+
+/// RAII guard for an entered Java object monitor.
+///
+/// Created by [`JniEnv::enter_monitor`]. Dropping the guard calls `MonitorExit`;
+/// a failure there is logged. Use [`exit`](MonitorEnterGuard::exit)
+/// to observe the result instead.
+pub struct MonitorEnterGuard<'a> {
+    env: &'a JniEnv,
+    obj: jni::jobject,
+}
+
+impl<'a> MonitorEnterGuard<'a> {
+    /// Returns the object whose monitor is held.
+    pub fn object(&self) -> jni::jobject {
+        self.obj
+    }
+
+    /// Exits the monitor now, returning the `MonitorExit` result.
+    pub fn exit(self) -> Result<(), jni::jint> {
+        let (env, obj) = (self.env, self.obj);
+        std::mem::forget(self);
+        env.monitor_exit(obj)
+    }
+}
+
+impl Drop for MonitorEnterGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.env.monitor_exit(self.obj) {
+            crate::log(crate::LogLevel::Error, format_args!("MonitorExit failed: {err}"));
+        }
+    }
+}
+
+/// RAII guard for a native thread attached to the VM.
+///
+/// Created by [`AttachGuard::new`] or [`AttachGuard::new_daemon`]; dereferences
+/// to the thread's [`JniEnv`]. If the guard attached the thread, dropping it
+/// detaches the thread (a failure is logged); a thread that was
+/// already attached stays attached.
+///
+/// ```rust,ignore
+/// std::thread::spawn(move || {
+///     let env = AttachGuard::new_daemon(vm as *mut jni::JavaVM).expect("attach");
+///     let class = env.find_class("java/lang/System");
+///     // ... detached when `env` drops
+/// });
+/// ```
+pub struct AttachGuard {
+    vm: *mut jni::JavaVM,
+    env: JniEnv,
+    detach_on_drop: bool,
+}
+
+impl AttachGuard {
+    /// Attaches the current thread unless it already is.
+    pub fn new(vm: *mut jni::JavaVM) -> Result<Self, JniError> {
+        Self::attach(vm, false)
+    }
+
+    /// Attaches the current thread as a daemon unless it already is attached.
+    pub fn new_daemon(vm: *mut jni::JavaVM) -> Result<Self, JniError> {
+        Self::attach(vm, true)
+    }
+
+    fn attach(vm: *mut jni::JavaVM, daemon: bool) -> Result<Self, JniError> {
+        match JniEnv::get_current_env(vm) {
+            Ok(env) => Ok(AttachGuard { vm, env, detach_on_drop: false }),
+            Err(JniError::Detached) => {
+                let env = JniEnv::attach(vm, daemon)?;
+                Ok(AttachGuard { vm, env, detach_on_drop: true })
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The current thread's JNI environment.
+    pub fn env(&self) -> &JniEnv {
+        &self.env
+    }
+
+    /// Returns true if dropping the guard will detach the thread.
+    pub fn detaches_on_drop(&self) -> bool {
+        self.detach_on_drop
+    }
+}
+
+impl std::ops::Deref for AttachGuard {
+    type Target = JniEnv;
+
+    fn deref(&self) -> &JniEnv {
+        &self.env
+    }
+}
+
+impl Drop for AttachGuard {
+    fn drop(&mut self) {
+        if self.detach_on_drop {
+            if let Err(err) = JniEnv::detach_current_thread(self.vm) {
+                crate::log(crate::LogLevel::Error, format_args!("DetachCurrentThread failed: {err}"));
+            }
+        }
+    }
+}
+
 /// A guard that automatically deletes a global reference when dropped.
 ///
+/// Global references are valid on every thread, so `GlobalRef` is `Send` and
+/// `Sync`; dropping it on a thread without a `JNIEnv` attaches briefly to
+/// delete the reference. To use the object through a `JniEnv`, either pass
+/// [`get`](Self::get) directly or take a [`LocalRef`] with
+/// [`as_local`](Self::as_local).
+///
 /// # Example
 ///
 /// ```rust,ignore
-/// let global_class = GlobalRef::new(&env, env.find_class("java/lang/String").unwrap());
-/// // global_class can be used across JNI calls
+/// let class = LocalRef::new(&env, env.find_class("java/lang/String").unwrap());
+/// let global_class = class.to_global();
+/// // global_class can be stored beyond the current callback
 /// // it's automatically deleted when dropped
 /// ```
 pub struct GlobalRef {
     vm: *mut jni::JavaVM,
     obj: jni::jobject,
-    _not_send_sync: PhantomData<Rc<()>>,
 }
 
+// SAFETY: a JNI global reference is valid on every thread until deleted, and
+// `Drop` obtains (or attaches) the dropping thread's own `JNIEnv`.
+unsafe impl Send for GlobalRef {}
+unsafe impl Sync for GlobalRef {}
+
 impl GlobalRef {
     /// Creates a new GlobalRef by creating a global reference from a local reference.
     ///
@@ -1012,7 +2017,6 @@ impl GlobalRef {
         GlobalRef {
             vm,
             obj: global,
-            _not_send_sync: PhantomData,
         }
     }
 
@@ -1020,41 +2024,130 @@ impl GlobalRef {
     pub fn get(&self) -> jni::jobject {
         self.obj
     }
+
+    /// Creates a local reference to the object in `env`'s current frame.
+    ///
+    /// The [`LocalRef`] is deleted when dropped; `self` is unaffected.
+    pub fn as_local<'a>(&self, env: &'a JniEnv) -> LocalRef<'a> {
+        LocalRef::new(env, env.new_local_ref(self.obj))
+    }
 }
 
 impl Drop for GlobalRef {
     fn drop(&mut self) {
-        if self.obj.is_null() || self.vm.is_null() {
+        if self.obj.is_null() {
             return;
         }
+        let obj = self.obj;
+        with_thread_env(self.vm, |env| env.delete_global_ref(obj));
+    }
+}
 
-        unsafe {
-            let get_env_fn = (**self.vm).GetEnv;
-            let attach_fn = (**self.vm).AttachCurrentThread;
-            let detach_fn = (**self.vm).DetachCurrentThread;
+/// Runs `f` with the current thread's `JNIEnv`, attaching (and detaching
+/// again) if the thread is not attached. Does nothing if `vm` is null or the
+/// thread cannot attach.
+fn with_thread_env(vm: *mut jni::JavaVM, f: impl FnOnce(&JniEnv)) {
+    if vm.is_null() {
+        return;
+    }
 
-            let mut env_ptr: *mut std::ffi::c_void = ptr::null_mut();
-            let res = get_env_fn(self.vm, &mut env_ptr, jni::JNI_VERSION_1_6);
+    unsafe {
+        let get_env_fn = (**vm).GetEnv;
+        let attach_fn = (**vm).AttachCurrentThread;
+        let detach_fn = (**vm).DetachCurrentThread;
 
-            if res == jni::JNI_OK && !env_ptr.is_null() {
-                let env = JniEnv::from_raw(env_ptr as *mut jni::JNIEnv);
-                env.delete_global_ref(self.obj);
-                return;
-            }
+        let mut env_ptr: *mut std::ffi::c_void = ptr::null_mut();
+        let res = get_env_fn(vm, &mut env_ptr, jni::JNI_VERSION_1_6);
 
-            if res == jni::JNI_EDETACHED {
-                let mut attach_env: *mut std::ffi::c_void = ptr::null_mut();
-                let ares = attach_fn(self.vm, &mut attach_env, ptr::null_mut());
-                if ares == jni::JNI_OK && !attach_env.is_null() {
-                    let env = JniEnv::from_raw(attach_env as *mut jni::JNIEnv);
-                    env.delete_global_ref(self.obj);
-                    let _ = detach_fn(self.vm);
-                }
+        if res == jni::JNI_OK && !env_ptr.is_null() {
+            f(&JniEnv::from_raw(env_ptr as *mut jni::JNIEnv));
+            return;
+        }
+
+        if res == jni::JNI_EDETACHED {
+            let mut attach_env: *mut std::ffi::c_void = ptr::null_mut();
+            let ares = attach_fn(vm, &mut attach_env, ptr::null_mut());
+            if ares == jni::JNI_OK && !attach_env.is_null() {
+                f(&JniEnv::from_raw(attach_env as *mut jni::JNIEnv));
+                let _ = detach_fn(vm);
             }
         }
     }
 }
 
-// Note: GlobalRef is NOT Send or Sync by default because JNI environments
-// are thread-local. If you need to share references across threads, you
-// need to obtain a new JNIEnv via AttachCurrentThread.
+/// A guard that deletes a weak global reference when dropped.
+///
+/// A weak global reference does not keep its object alive. Once the garbage
+/// collector decides the object is unreachable the reference is cleared and
+/// behaves like `null`; this can happen during any GC, so check with
+/// [`upgrade`](Self::upgrade), which returns a strong [`LocalRef`] that keeps
+/// the object alive while it is held, rather than testing
+/// [`is_collected`](Self::is_collected) and then using the weak reference.
+/// Like [`GlobalRef`], it is valid on every thread.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let weak = LocalRef::new(&env, obj).to_weak();
+/// // later, possibly in another callback:
+/// match weak.upgrade(&env) {
+///     Some(obj) => { /* use obj.get() */ }
+///     None => println!("collected"),
+/// }
+/// ```
+pub struct WeakGlobalRef {
+    vm: *mut jni::JavaVM,
+    obj: jni::jweak,
+}
+
+// SAFETY: as for `GlobalRef`.
+unsafe impl Send for WeakGlobalRef {}
+unsafe impl Sync for WeakGlobalRef {}
+
+impl WeakGlobalRef {
+    /// Creates a weak global reference to `obj`.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the env pointer remains valid for the lifetime of this WeakGlobalRef,
+    /// or that cleanup is handled manually.
+    pub unsafe fn new(env: &JniEnv, obj: jni::jobject) -> Self {
+        let weak = env.new_weak_global_ref(obj);
+        let vm = env.get_java_vm().unwrap_or(ptr::null_mut());
+        WeakGlobalRef { vm, obj: weak }
+    }
+
+    /// Returns the underlying `jweak`.
+    pub fn get(&self) -> jni::jweak {
+        self.obj
+    }
+
+    /// Returns true if the object has been garbage collected
+    /// (`IsSameObject(weak, NULL)`).
+    ///
+    /// A `false` result can be stale by the time it is used; prefer
+    /// [`upgrade`](Self::upgrade).
+    pub fn is_collected(&self, env: &JniEnv) -> bool {
+        self.obj.is_null() || env.is_same_object(self.obj, ptr::null_mut())
+    }
+
+    /// Returns a strong local reference to the object, or `None` if it has
+    /// been collected.
+    pub fn upgrade<'a>(&self, env: &'a JniEnv) -> Option<LocalRef<'a>> {
+        if self.obj.is_null() {
+            return None;
+        }
+        let local = env.new_local_ref(self.obj);
+        if local.is_null() { None } else { Some(LocalRef::new(env, local)) }
+    }
+}
+
+impl Drop for WeakGlobalRef {
+    fn drop(&mut self) {
+        if self.obj.is_null() {
+            return;
+        }
+        let obj = self.obj;
+        with_thread_env(self.vm, |env| env.delete_weak_global_ref(obj));
+    }
+}
@@ -1,74 +1,1816 @@
 // vliss/jvmti/src/wrapper.rs
 use crate::sys::jvmti;
 use crate::sys::jni;
-use std::ffi::{CStr, CString};
-use std::ptr;
+use crate::jni_wrapper::{JniEnv, JniError};
+use crate::class_transformers::ClassTransformerRegistry;
+use crate::classfile::{AccessFlags, ConstantPool};
+use crate::classfile::bytecode::{Instruction, InstructionIterator};
+use crate::LogLevel;
+use crate::mutf8;
+use crate::classfile::descriptor::{parse_method_descriptor, JavaType};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::ptr::{self, NonNull};
+use std::time::Duration;
+
+/// Result type returned by the [`Jvmti`] wrapper methods.
+pub type JvmtiResult<T> = Result<T, jvmti::jvmtiError>;
+
+/// Resolve a function-table slot that older JVMs leave null.
+///
+/// Functions added after JVMTI 1.2 (modules, heap sampling, virtual threads,
+/// `ClearAllFramePops`) occupy slots that were reserved on earlier VMs.
+fn fn_or_unavailable<F>(slot: Option<F>) -> Result<F, jvmti::jvmtiError> {
+    slot.ok_or(jvmti::jvmtiError::NOT_AVAILABLE)
+}
+
+/// Error returned by [`Jvmti::add_capabilities_checked`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CapabilityError {
+    /// The VM cannot provide these capabilities (names as in `jvmtiCapabilities`).
+    Unavailable(Vec<&'static str>),
+    /// A JVMTI call failed.
+    Jvmti(jvmti::jvmtiError),
+}
+
+impl std::fmt::Display for CapabilityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CapabilityError::Unavailable(names) => write!(f, "{} not available on this JVM", names.join(", ")),
+            CapabilityError::Jvmti(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for CapabilityError {}
+
+impl From<jvmti::jvmtiError> for CapabilityError {
+    fn from(err: jvmti::jvmtiError) -> Self {
+        CapabilityError::Jvmti(err)
+    }
+}
+
+/// Error returned by [`Jvmti::set_events`].
+///
+/// `toggled` lists the events that were switched before `failed` was
+/// rejected, so the caller can put them back with
+/// `set_events(!enable, &err.toggled, thread)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetEventsError {
+    /// The event whose `SetEventNotificationMode` call failed.
+    pub failed: Event,
+    /// The error returned for `failed`.
+    pub error: jvmti::jvmtiError,
+    /// Events switched successfully before the failure, in call order.
+    pub toggled: Vec<Event>,
+}
+
+impl std::fmt::Display for SetEventsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.failed.name(), self.error)?;
+        if !self.toggled.is_empty() {
+            let names: Vec<&str> = self.toggled.iter().map(|event| event.name()).collect();
+            write!(f, " (already switched: {})", names.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SetEventsError {}
+
+impl From<SetEventsError> for jvmti::jvmtiError {
+    fn from(err: SetEventsError) -> Self {
+        err.error
+    }
+}
+
+/// Error returned by [`Jvmti::add_to_bootstrap_class_loader_search_path`] and
+/// [`Jvmti::add_to_system_class_loader_search_path`].
+#[derive(Debug)]
+pub enum SearchPathError {
+    /// The path does not exist or cannot be read.
+    Io(PathBuf, std::io::Error),
+    /// A JVMTI call failed, or the path is not valid UTF-8 (`ILLEGAL_ARGUMENT`).
+    Jvmti(jvmti::jvmtiError),
+}
+
+impl std::fmt::Display for SearchPathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchPathError::Io(path, err) => write!(f, "{}: {err}", path.display()),
+            SearchPathError::Jvmti(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for SearchPathError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SearchPathError::Io(_, err) => Some(err),
+            SearchPathError::Jvmti(_) => None,
+        }
+    }
+}
+
+impl From<jvmti::jvmtiError> for SearchPathError {
+    fn from(err: jvmti::jvmtiError) -> Self {
+        SearchPathError::Jvmti(err)
+    }
+}
+
+/// Canonicalizes a class path segment and checks that it can be read.
+fn readable_segment(path: &Path) -> Result<String, SearchPathError> {
+    let io_error = |err| SearchPathError::Io(path.to_path_buf(), err);
+    let canonical = std::fs::canonicalize(path).map_err(io_error)?;
+    if canonical.is_dir() {
+        std::fs::read_dir(&canonical).map_err(io_error)?;
+    } else {
+        std::fs::File::open(&canonical).map_err(io_error)?;
+    }
+    canonical.into_os_string().into_string().map_err(|_| SearchPathError::Jvmti(jvmti::jvmtiError::ILLEGAL_ARGUMENT))
+}
+
+/// A thread's `GetThreadInfo` result.
+///
+/// `thread_group` and `context_class_loader` are JNI local references: they
+/// are valid until the current native frame returns, and must be promoted with
+/// `NewGlobalRef` to be kept longer.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThreadInfo {
+    pub name: Option<String>,
+    pub priority: jni::jint,
+    pub is_daemon: bool,
+    /// Local reference to the `ThreadGroup`; null for a terminated thread.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serde_support::ptr::serialize", skip_deserializing, default = "std::ptr::null_mut"))]
+    pub thread_group: jni::jobject,
+    /// Local reference to the context class loader; may be null.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serde_support::ptr::serialize", skip_deserializing, default = "std::ptr::null_mut"))]
+    pub context_class_loader: jni::jobject,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThreadGroupInfo {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serde_support::ptr::serialize", skip_deserializing, default = "std::ptr::null_mut"))]
+    pub parent: jni::jobject,
+    pub name: Option<String>,
+    pub max_priority: jni::jint,
+    pub is_daemon: bool,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MonitorUsage {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serde_support::ptr::serialize", skip_deserializing, default = "std::ptr::null_mut"))]
+    pub owner: jni::jthread,
+    pub entry_count: jni::jint,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serde_support::ptr_vec::serialize", skip_deserializing))]
+    pub waiters: Vec<jni::jthread>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serde_support::ptr_vec::serialize", skip_deserializing))]
+    pub notify_waiters: Vec<jni::jthread>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StackInfo {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serde_support::ptr::serialize", skip_deserializing, default = "std::ptr::null_mut"))]
+    pub thread: jni::jthread,
+    pub state: jni::jint,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::frames"))]
+    pub frames: Vec<jvmti::jvmtiFrameInfo>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtensionParamInfo {
+    pub name: Option<String>,
+    pub kind: jni::jint,
+    pub base_type: jni::jint,
+    pub null_ok: bool,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtensionFunctionInfo {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serde_support::ptr::serialize", skip_deserializing, default = "std::ptr::null_mut"))]
+    pub func: *mut std::ffi::c_void,
+    pub id: Option<String>,
+    pub short_description: Option<String>,
+    pub params: Vec<ExtensionParamInfo>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::errors"))]
+    pub errors: Vec<jvmti::jvmtiError>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtensionEventInfo {
+    pub extension_event_index: jni::jint,
+    pub id: Option<String>,
+    pub short_description: Option<String>,
+    pub params: Vec<ExtensionParamInfo>,
+}
+
+/// An argument passed to an [`ExtensionFunction`].
+///
+/// Each variant must match the corresponding `ExtensionParamInfo`: `Int`,
+/// `Long`, `Boolean`, and `Object` are `JVMTI_KIND_IN` scalars, while `Ptr`
+/// covers `JVMTI_KIND_IN` method/field IDs and every pointer kind
+/// (`IN_PTR`, `IN_BUF`, `ALLOC_BUF`, `ALLOC_ALLOC_BUF`, `OUT`, `OUT_BUF`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtensionArg {
+    /// A `jint`, `jshort`, `jchar`, or `jbyte` input.
+    Int(jni::jint),
+    /// A `jlong` input.
+    Long(jni::jlong),
+    /// A `jboolean` input.
+    Boolean(bool),
+    /// A `jobject`, `jthread`, or `jclass` input.
+    Object(jni::jobject),
+    /// A pointer, or a `jmethodID`/`jfieldID` input.
+    Ptr(*mut std::ffi::c_void),
+}
+
+impl ExtensionParamInfo {
+    /// Returns true if the parameter travels as one pointer-sized vararg word,
+    /// which excludes by-value `jfloat`/`jdouble`/`jvalue` and, on 32-bit
+    /// targets, by-value `jlong`.
+    pub(crate) fn passes_as_word(&self) -> bool {
+        if self.kind != jvmti::JVMTI_KIND_IN {
+            return true;
+        }
+        match self.base_type {
+            jvmti::JVMTI_TYPE_JFLOAT | jvmti::JVMTI_TYPE_JDOUBLE | jvmti::JVMTI_TYPE_JVALUE => false,
+            jvmti::JVMTI_TYPE_JLONG => std::mem::size_of::<usize>() >= std::mem::size_of::<jni::jlong>(),
+            _ => true,
+        }
+    }
+}
+
+impl ExtensionArg {
+    /// Decodes a vararg word received for `param`, one for which
+    /// [`passes_as_word`](ExtensionParamInfo::passes_as_word) holds.
+    pub(crate) fn from_word(param: &ExtensionParamInfo, word: usize) -> Self {
+        if param.kind != jvmti::JVMTI_KIND_IN {
+            return ExtensionArg::Ptr(word as *mut std::ffi::c_void);
+        }
+        match param.base_type {
+            jvmti::JVMTI_TYPE_JINT | jvmti::JVMTI_TYPE_JSHORT | jvmti::JVMTI_TYPE_JCHAR | jvmti::JVMTI_TYPE_JBYTE => {
+                ExtensionArg::Int(word as jni::jint)
+            }
+            jvmti::JVMTI_TYPE_JLONG => ExtensionArg::Long(word as jni::jlong),
+            jvmti::JVMTI_TYPE_JBOOLEAN => ExtensionArg::Boolean(word as u8 != 0),
+            jvmti::JVMTI_TYPE_JOBJECT | jvmti::JVMTI_TYPE_JTHREAD | jvmti::JVMTI_TYPE_JCLASS => {
+                ExtensionArg::Object(word as jni::jobject)
+            }
+            _ => ExtensionArg::Ptr(word as *mut std::ffi::c_void),
+        }
+    }
+}
+
+/// A callable vendor extension function, checked against its parameter list.
+///
+/// Built from an [`ExtensionFunctionInfo`] returned by
+/// [`Jvmti::get_extension_functions`], or looked up by id with
+/// [`Jvmti::get_extension_function`]:
+///
+/// ```rust,ignore
+/// let f = jvmti.get_extension_function("com.sun.hotspot.functions.IsClassUnloadingEnabled")?
+///     .ok_or(jvmtiError::NOT_AVAILABLE)?;
+/// let mut enabled: jni::jboolean = 0;
+/// unsafe { f.call(&jvmti, &[ExtensionArg::Ptr(&mut enabled as *mut _ as *mut _)])? };
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExtensionFunction {
+    func: jvmti::jvmtiExtensionFunction,
+    id: Option<String>,
+    params: Vec<ExtensionParamInfo>,
+}
+
+/// The largest parameter count [`ExtensionFunction::call`] can pass.
+pub const MAX_EXTENSION_ARGS: usize = 8;
+
+impl ExtensionFunction {
+    /// Wraps `info`, or returns `None` if its function pointer is null.
+    pub fn new(info: &ExtensionFunctionInfo) -> Option<Self> {
+        if info.func.is_null() {
+            return None;
+        }
+        Some(ExtensionFunction {
+            func: unsafe { std::mem::transmute::<*mut std::ffi::c_void, jvmti::jvmtiExtensionFunction>(info.func) },
+            id: info.id.clone(),
+            params: info.params.clone(),
+        })
+    }
+
+    /// The extension's identifier, e.g. `com.sun.hotspot.functions.GetVirtualThread`.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// The declared parameters, excluding the leading `jvmtiEnv*`.
+    pub fn params(&self) -> &[ExtensionParamInfo] {
+        &self.params
+    }
+
+    /// Checks `args` against the declared parameters without calling the function.
+    ///
+    /// Returns `ILLEGAL_ARGUMENT` for a count or variant mismatch, `NULL_POINTER`
+    /// for a null pointer where the parameter is not `null_ok`, and
+    /// `NOT_AVAILABLE` for shapes `call` cannot pass: `jfloat`/`jdouble`/`jvalue`
+    /// inputs, `jlong` on 32-bit targets, or more than [`MAX_EXTENSION_ARGS`].
+    pub fn check_args(&self, args: &[ExtensionArg]) -> Result<(), jvmti::jvmtiError> {
+        if args.len() != self.params.len() {
+            return Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT);
+        }
+        if args.len() > MAX_EXTENSION_ARGS {
+            return Err(jvmti::jvmtiError::NOT_AVAILABLE);
+        }
+        for (param, arg) in self.params.iter().zip(args) {
+            if !param.passes_as_word() {
+                return Err(jvmti::jvmtiError::NOT_AVAILABLE);
+            }
+            let by_value = param.kind == jvmti::JVMTI_KIND_IN;
+            let matches = match (by_value, param.base_type, arg) {
+                (true, jvmti::JVMTI_TYPE_JLONG, ExtensionArg::Long(_)) => true,
+                (true, jvmti::JVMTI_TYPE_JINT | jvmti::JVMTI_TYPE_JSHORT | jvmti::JVMTI_TYPE_JCHAR | jvmti::JVMTI_TYPE_JBYTE, ExtensionArg::Int(_)) => true,
+                (true, jvmti::JVMTI_TYPE_JBOOLEAN, ExtensionArg::Boolean(_)) => true,
+                (true, jvmti::JVMTI_TYPE_JOBJECT | jvmti::JVMTI_TYPE_JTHREAD | jvmti::JVMTI_TYPE_JCLASS, ExtensionArg::Object(obj)) => {
+                    if obj.is_null() && !param.null_ok {
+                        return Err(jvmti::jvmtiError::NULL_POINTER);
+                    }
+                    true
+                }
+                (true, jvmti::JVMTI_TYPE_JMETHODID | jvmti::JVMTI_TYPE_JFIELDID, ExtensionArg::Ptr(_)) => true,
+                (false, _, ExtensionArg::Ptr(p)) => {
+                    if p.is_null() && !param.null_ok {
+                        return Err(jvmti::jvmtiError::NULL_POINTER);
+                    }
+                    true
+                }
+                _ => false,
+            };
+            if !matches {
+                return Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT);
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls the extension function with `args`, after [`check_args`](Self::check_args).
+    ///
+    /// # Safety
+    ///
+    /// The parameter metadata only describes types, so the caller must uphold
+    /// what it cannot check: every `Ptr` points to memory of the declared base
+    /// type (an array of the expected length for `IN_BUF`/`OUT_BUF`), `Object`
+    /// arguments are valid references, buffers the VM allocates for
+    /// `ALLOC_BUF`/`ALLOC_ALLOC_BUF` parameters are released with
+    /// [`Jvmti::deallocate`], and the function is called from a phase and
+    /// thread its documentation allows.
+    pub unsafe fn call(&self, jvmti: &Jvmti, args: &[ExtensionArg]) -> Result<(), jvmti::jvmtiError> {
+        self.check_args(args)?;
+        // Every supported argument fits in a pointer-sized word; the callee
+        // reads narrower types back out with `va_arg`.
+        let mut w = [0usize; MAX_EXTENSION_ARGS];
+        for (slot, arg) in w.iter_mut().zip(args) {
+            *slot = match *arg {
+                ExtensionArg::Int(v) => v as isize as usize,
+                ExtensionArg::Long(v) => v as usize,
+                ExtensionArg::Boolean(v) => usize::from(v),
+                ExtensionArg::Object(obj) => obj as usize,
+                ExtensionArg::Ptr(p) => p as usize,
+            };
+        }
+        let f = self.func;
+        let env = jvmti.raw();
+        let err = match args.len() {
+            0 => f(env),
+            1 => f(env, w[0]),
+            2 => f(env, w[0], w[1]),
+            3 => f(env, w[0], w[1], w[2]),
+            4 => f(env, w[0], w[1], w[2], w[3]),
+            5 => f(env, w[0], w[1], w[2], w[3], w[4]),
+            6 => f(env, w[0], w[1], w[2], w[3], w[4], w[5]),
+            7 => f(env, w[0], w[1], w[2], w[3], w[4], w[5], w[6]),
+            _ => f(env, w[0], w[1], w[2], w[3], w[4], w[5], w[6], w[7]),
+        };
+        if err != jvmti::jvmtiError::NONE {
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LocalVariableEntry {
+    pub start_location: jvmti::jlocation,
+    pub length: jni::jint,
+    pub name: Option<String>,
+    pub signature: Option<String>,
+    pub generic_signature: Option<String>,
+    pub slot: jni::jint,
+}
+
+impl LocalVariableEntry {
+    /// Returns true if the variable is in scope at `location`
+    /// (`start_location <= location < start_location + length`).
+    pub fn is_live_at(&self, location: jvmti::jlocation) -> bool {
+        location >= self.start_location && location < self.start_location + jvmti::jlocation::from(self.length)
+    }
+}
+
+/// A local variable value read by [`Jvmti::read_locals`].
+///
+/// `Object` holds a local reference, valid until the current native method
+/// or callback returns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocalValue {
+    Boolean(bool),
+    Byte(jni::jbyte),
+    Char(jni::jchar),
+    Short(jni::jshort),
+    Int(jni::jint),
+    Long(jni::jlong),
+    Float(jni::jfloat),
+    Double(jni::jdouble),
+    Object(jni::jobject),
+}
+
+/// The value a frame returns when forced with [`Jvmti::force_early_return`].
+///
+/// `boolean`, `byte`, `char`, and `short` methods take `Int`, as in the
+/// `ForceEarlyReturnInt` function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReturnValue {
+    Void,
+    Int(jni::jint),
+    Long(jni::jlong),
+    Float(jni::jfloat),
+    Double(jni::jdouble),
+    /// A reference (may be null) for methods returning an object or array.
+    Object(jni::jobject),
+}
+
+/// A stack frame resolved to class, method, and source line.
+///
+/// Produced by [`Jvmti::symbolize_frames`]. Names that could not be resolved
+/// (for example because the class was unloaded) are reported as `"<unknown>"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SymbolicFrame {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serde_support::ptr::serialize", skip_deserializing, default = "std::ptr::null_mut"))]
+    pub method: jni::jmethodID,
+    pub location: jvmti::jlocation,
+    pub class_signature: String,
+    pub method_name: String,
+    pub method_signature: String,
+    pub line_number: Option<jni::jint>,
+}
+
+impl SymbolicFrame {
+    /// Looks up the source line for `location` in a `GetLineNumberTable` result.
+    ///
+    /// Picks the entry with the largest `start_location <= location`; returns
+    /// `None` for native frames (`location == -1`) or when no entry applies.
+    pub fn line_number_at(table: &[jvmti::jvmtiLineNumberEntry], location: jvmti::jlocation) -> Option<jni::jint> {
+        if location < 0 {
+            return None;
+        }
+        table
+            .iter()
+            .filter(|entry| entry.start_location <= location)
+            .max_by_key(|entry| entry.start_location)
+            .map(|entry| entry.line_number)
+    }
+}
+
+/// `java.lang.String.indexOf`: the frame's class and method, without location.
+pub(crate) fn frame_label(frame: &SymbolicFrame) -> String {
+    let class = frame
+        .class_signature
+        .strip_prefix('L')
+        .and_then(|name| name.strip_suffix(';'))
+        .unwrap_or(&frame.class_signature);
+    format!("{}.{}", class.replace('/', "."), frame.method_name)
+}
+
+/// Formats the frame like a Java stack trace element:
+/// `com.example.Foo.bar(Foo.java:42)`.
+///
+/// `SymbolicFrame` does not carry the `SourceFile` attribute, so the file is
+/// derived from the outermost class name, which is what javac emits for all
+/// but unusual sources. Native frames print `(Native Method)`, frames of
+/// unresolved classes `(Unknown Source)`.
+impl std::fmt::Display for SymbolicFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&frame_label(self))?;
+        if self.location == -1 {
+            return f.write_str("(Native Method)");
+        }
+        let source_file = self
+            .class_signature
+            .strip_prefix('L')
+            .and_then(|name| name.strip_suffix(';'))
+            .map(|name| name.rsplit('/').next().unwrap_or(name))
+            .and_then(|simple| simple.split('$').next())
+            .filter(|outer| !outer.is_empty());
+        match (source_file, self.line_number) {
+            (Some(file), Some(line)) => write!(f, "({file}.java:{line})"),
+            (Some(file), None) => write!(f, "({file}.java)"),
+            (None, _) => f.write_str("(Unknown Source)"),
+        }
+    }
+}
+
+/// One thread of a [`Jvmti::thread_dump`], with its frames symbolized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThreadDump {
+    /// The thread name, or `"<unknown>"` if `GetThreadInfo` failed.
+    pub name: String,
+    pub state: ThreadState,
+    /// Innermost frame first.
+    pub frames: Vec<SymbolicFrame>,
+}
+
+/// Formats the thread like a `jstack` entry: a `"name" STATE` line, with the
+/// state as in `java.lang.Thread.State`, followed by one `\tat frame` line per
+/// frame. There is no trailing newline.
+impl std::fmt::Display for ThreadDump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\" {}", self.name, self.state.java_thread_state())?;
+        for frame in &self.frames {
+            write!(f, "\n\tat {frame}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A saved JNI function table, returned by [`Jvmti::with_intercepted_jni`].
+pub struct JniFunctionTable {
+    table: Box<jni::JNINativeInterface_>,
+}
+
+// SAFETY: the table holds only function pointers and reserved slots, which
+// are valid on every thread and never written through.
+unsafe impl Send for JniFunctionTable {}
+unsafe impl Sync for JniFunctionTable {}
+
+impl JniFunctionTable {
+    /// The saved table; interceptors call through it to reach the original functions.
+    pub fn table(&self) -> &jni::JNINativeInterface_ {
+        &self.table
+    }
+
+    /// Reinstalls the saved table for every thread.
+    pub fn restore(&self, jvmti: &Jvmti) -> Result<(), jvmti::jvmtiError> {
+        jvmti.set_jni_function_table(&*self.table)
+    }
+}
+
+impl std::fmt::Debug for JniFunctionTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JniFunctionTable").field("table", &(&*self.table as *const jni::JNINativeInterface_)).finish()
+    }
+}
+
+/// A method's name and parsed descriptor, from [`Jvmti::get_method_signature`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodSignature {
+    pub name: String,
+    /// The raw descriptor, e.g. `(IJLjava/lang/String;)V`.
+    pub descriptor: String,
+    pub arg_types: Vec<JavaType>,
+    pub return_type: JavaType,
+    pub is_static: bool,
+    /// Local slots taken by the arguments, including `this` for instance
+    /// methods; `long` and `double` take two.
+    pub arg_slot_count: u16,
+}
+
+impl MethodSignature {
+    /// The local variable slot of each argument at method entry, for
+    /// `get_local_*` in a `MethodEntry` callback.
+    pub fn arg_slots(&self) -> Vec<jni::jint> {
+        let mut slot = if self.is_static { 0 } else { 1 };
+        self.arg_types
+            .iter()
+            .map(|arg| {
+                let this_slot = slot;
+                slot += jni::jint::from(arg.slots());
+                this_slot
+            })
+            .collect()
+    }
+}
+
+/// The VM's execution phase, from [`Jvmti::current_phase`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Inside `Agent_OnLoad` (or `Agent_OnAttach` for static agents).
+    OnLoad,
+    /// Between `Agent_OnLoad` returning and `VMStart`.
+    Primordial,
+    /// After `VMStart`, before `VMInit`.
+    Start,
+    /// After `VMInit`; all functions are available.
+    Live,
+    /// After `VMDeath`.
+    Dead,
+}
+
+impl Phase {
+    /// Decodes a `JVMTI_PHASE_*` value.
+    pub fn from_raw(raw: jni::jint) -> Option<Self> {
+        match raw {
+            jvmti::JVMTI_PHASE_ONLOAD => Some(Phase::OnLoad),
+            jvmti::JVMTI_PHASE_PRIMORDIAL => Some(Phase::Primordial),
+            jvmti::JVMTI_PHASE_START => Some(Phase::Start),
+            jvmti::JVMTI_PHASE_LIVE => Some(Phase::Live),
+            jvmti::JVMTI_PHASE_DEAD => Some(Phase::Dead),
+            _ => None,
+        }
+    }
+
+    pub fn as_raw(self) -> jni::jint {
+        match self {
+            Phase::OnLoad => jvmti::JVMTI_PHASE_ONLOAD,
+            Phase::Primordial => jvmti::JVMTI_PHASE_PRIMORDIAL,
+            Phase::Start => jvmti::JVMTI_PHASE_START,
+            Phase::Live => jvmti::JVMTI_PHASE_LIVE,
+            Phase::Dead => jvmti::JVMTI_PHASE_DEAD,
+        }
+    }
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Phase::OnLoad => "ONLOAD",
+            Phase::Primordial => "PRIMORDIAL",
+            Phase::Start => "START",
+            Phase::Live => "LIVE",
+            Phase::Dead => "DEAD",
+        })
+    }
+}
+
+/// How the VM encodes `jlocation` values, from [`Jvmti::get_jlocation_format_decoded`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JLocationFormat {
+    /// Bytecode indices, as used by line number and local variable tables (HotSpot).
+    JvmBci,
+    /// Native program counters.
+    MachinePc,
+    /// A VM-specific encoding.
+    Other,
+}
+
+impl JLocationFormat {
+    /// Decodes a `JVMTI_JLOCATION_*` value; unknown values map to `Other`.
+    pub fn from_raw(raw: jni::jint) -> Self {
+        match raw {
+            jvmti::JVMTI_JLOCATION_JVMBCI => JLocationFormat::JvmBci,
+            jvmti::JVMTI_JLOCATION_MACHINEPC => JLocationFormat::MachinePc,
+            _ => JLocationFormat::Other,
+        }
+    }
+
+    pub fn as_raw(self) -> jni::jint {
+        match self {
+            JLocationFormat::JvmBci => jvmti::JVMTI_JLOCATION_JVMBCI,
+            JLocationFormat::MachinePc => jvmti::JVMTI_JLOCATION_MACHINEPC,
+            JLocationFormat::Other => jvmti::JVMTI_JLOCATION_OTHER,
+        }
+    }
+
+    /// Returns true if a `jlocation` is a bytecode index that can be mapped to a line.
+    pub fn is_bytecode_index(self) -> bool {
+        self == JLocationFormat::JvmBci
+    }
+}
+
+/// What a JVMTI timer measures, from [`TimerInfo::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimerKind {
+    /// CPU time spent in user mode.
+    UserCpu,
+    /// CPU time spent in user and system mode.
+    TotalCpu,
+    /// Elapsed wall-clock time.
+    Elapsed,
+}
+
+impl TimerKind {
+    /// Decodes a `JVMTI_TIMER_*` value.
+    pub fn from_raw(raw: jni::jint) -> Option<Self> {
+        match raw {
+            jvmti::JVMTI_TIMER_USER_CPU => Some(TimerKind::UserCpu),
+            jvmti::JVMTI_TIMER_TOTAL_CPU => Some(TimerKind::TotalCpu),
+            jvmti::JVMTI_TIMER_ELAPSED => Some(TimerKind::Elapsed),
+            _ => None,
+        }
+    }
+
+    pub fn as_raw(self) -> jni::jint {
+        match self {
+            TimerKind::UserCpu => jvmti::JVMTI_TIMER_USER_CPU,
+            TimerKind::TotalCpu => jvmti::JVMTI_TIMER_TOTAL_CPU,
+            TimerKind::Elapsed => jvmti::JVMTI_TIMER_ELAPSED,
+        }
+    }
+}
+
+/// A decoded `jvmtiTimerInfo`, from [`Jvmti::get_timer_info_decoded`].
+///
+/// Converts from the raw struct, so the thread CPU timer infos decode the
+/// same way: `TimerInfo::from(jvmti.get_thread_cpu_timer_info()?)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerInfo {
+    /// The largest value the timer reaches before wrapping to its minimum.
+    pub max_value: jni::jlong,
+    /// The timer may jump forward, e.g. when the system clock is set.
+    pub may_skip_forward: bool,
+    /// The timer may jump backward, e.g. when the system clock is set.
+    pub may_skip_backward: bool,
+    /// What the timer measures; `None` for values this crate does not know.
+    pub kind: Option<TimerKind>,
+}
+
+impl TimerInfo {
+    /// Returns true if the timer never goes backward, so the difference of
+    /// two readings is never negative. HotSpot's `GetTime` is monotonic.
+    pub fn is_monotonic(&self) -> bool {
+        !self.may_skip_backward
+    }
+}
+
+impl From<jvmti::jvmtiTimerInfo> for TimerInfo {
+    fn from(info: jvmti::jvmtiTimerInfo) -> Self {
+        TimerInfo {
+            max_value: info.max_value,
+            may_skip_forward: info.may_skip_forward != 0,
+            may_skip_backward: info.may_skip_backward != 0,
+            kind: TimerKind::from_raw(info.kind),
+        }
+    }
+}
+
+/// One row of a [`Jvmti::class_histogram`] report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClassCount {
+    pub class_signature: String,
+    pub instance_count: u64,
+    pub total_bytes: u64,
+}
+
+/// An object reported by [`Jvmti::iterate_heap_with`].
+///
+/// Only valid for the duration of the closure call.
+#[derive(Debug)]
+pub struct HeapObject<'a> {
+    class_tag: jni::jlong,
+    size: jni::jlong,
+    length: jni::jint,
+    tag: &'a mut jni::jlong,
+}
+
+impl HeapObject<'_> {
+    /// Tag of the object's class, or `0` if the class is untagged.
+    pub fn class_tag(&self) -> jni::jlong {
+        self.class_tag
+    }
+
+    /// Object size in bytes (see `GetObjectSize`).
+    pub fn size(&self) -> jni::jlong {
+        self.size
+    }
+
+    /// Array length for array objects, `-1` otherwise.
+    pub fn length(&self) -> jni::jint {
+        self.length
+    }
+
+    /// Current tag of the object, or `0` if untagged.
+    pub fn tag(&self) -> jni::jlong {
+        *self.tag
+    }
+
+    /// Sets the object's tag; `0` removes it.
+    pub fn set_tag(&mut self, tag: jni::jlong) {
+        *self.tag = tag;
+    }
+}
+
+/// Whether a closure-driven heap iteration should keep going.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IterationControl {
+    Continue,
+    Abort,
+}
+
+/// What [`Jvmti::follow_references_with`] should do after a reference closure returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisitControl {
+    /// Visit the referenced object's own references.
+    Follow,
+    /// Do not descend into the referenced object.
+    Skip,
+    /// Stop the whole traversal.
+    Abort,
+}
+
+/// The `heap_filter` of `FollowReferences` and `IterateThroughHeap`.
+///
+/// Each flag excludes objects: `HeapFilter::TAGGED | HeapFilter::CLASS_UNTAGGED`
+/// reports only untagged objects of tagged classes. [`HeapFilter::NONE`]
+/// reports everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct HeapFilter(pub jni::jint);
+
+impl HeapFilter {
+    pub const NONE: HeapFilter = HeapFilter(0);
+    /// Skip tagged objects.
+    pub const TAGGED: HeapFilter = HeapFilter(jvmti::JVMTI_HEAP_FILTER_TAGGED);
+    /// Skip untagged objects.
+    pub const UNTAGGED: HeapFilter = HeapFilter(jvmti::JVMTI_HEAP_FILTER_UNTAGGED);
+    /// Skip objects whose class is tagged.
+    pub const CLASS_TAGGED: HeapFilter = HeapFilter(jvmti::JVMTI_HEAP_FILTER_CLASS_TAGGED);
+    /// Skip objects whose class is untagged.
+    pub const CLASS_UNTAGGED: HeapFilter = HeapFilter(jvmti::JVMTI_HEAP_FILTER_CLASS_UNTAGGED);
+
+    /// The raw value to pass as `heap_filter`.
+    pub const fn bits(self) -> jni::jint {
+        self.0
+    }
+
+    /// Returns `true` if every flag in `other` is set.
+    pub const fn contains(self, other: HeapFilter) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for HeapFilter {
+    type Output = HeapFilter;
+
+    fn bitor(self, rhs: HeapFilter) -> HeapFilter {
+        HeapFilter(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for HeapFilter {
+    fn bitor_assign(&mut self, rhs: HeapFilter) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// A decoded `jvmtiHeapReferenceKind`.
+///
+/// The first ten kinds are references from an object or class; the rest are
+/// GC roots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReferenceKind {
+    Class,
+    Field,
+    ArrayElement,
+    ClassLoader,
+    Signers,
+    ProtectionDomain,
+    Interface,
+    StaticField,
+    ConstantPool,
+    Superclass,
+    JniGlobal,
+    SystemClass,
+    Monitor,
+    StackLocal,
+    JniLocal,
+    Thread,
+    Other,
+}
+
+impl ReferenceKind {
+    const KINDS: [(jvmti::jvmtiHeapReferenceKind, ReferenceKind); 17] = [
+        (jvmti::JVMTI_HEAP_REFERENCE_CLASS, ReferenceKind::Class),
+        (jvmti::JVMTI_HEAP_REFERENCE_FIELD, ReferenceKind::Field),
+        (jvmti::JVMTI_HEAP_REFERENCE_ARRAY_ELEMENT, ReferenceKind::ArrayElement),
+        (jvmti::JVMTI_HEAP_REFERENCE_CLASS_LOADER, ReferenceKind::ClassLoader),
+        (jvmti::JVMTI_HEAP_REFERENCE_SIGNERS, ReferenceKind::Signers),
+        (jvmti::JVMTI_HEAP_REFERENCE_PROTECTION_DOMAIN, ReferenceKind::ProtectionDomain),
+        (jvmti::JVMTI_HEAP_REFERENCE_INTERFACE, ReferenceKind::Interface),
+        (jvmti::JVMTI_HEAP_REFERENCE_STATIC_FIELD, ReferenceKind::StaticField),
+        (jvmti::JVMTI_HEAP_REFERENCE_CONSTANT_POOL, ReferenceKind::ConstantPool),
+        (jvmti::JVMTI_HEAP_REFERENCE_SUPERCLASS, ReferenceKind::Superclass),
+        (jvmti::JVMTI_HEAP_REFERENCE_JNI_GLOBAL, ReferenceKind::JniGlobal),
+        (jvmti::JVMTI_HEAP_REFERENCE_SYSTEM_CLASS, ReferenceKind::SystemClass),
+        (jvmti::JVMTI_HEAP_REFERENCE_MONITOR, ReferenceKind::Monitor),
+        (jvmti::JVMTI_HEAP_REFERENCE_STACK_LOCAL, ReferenceKind::StackLocal),
+        (jvmti::JVMTI_HEAP_REFERENCE_JNI_LOCAL, ReferenceKind::JniLocal),
+        (jvmti::JVMTI_HEAP_REFERENCE_THREAD, ReferenceKind::Thread),
+        (jvmti::JVMTI_HEAP_REFERENCE_OTHER, ReferenceKind::Other),
+    ];
+
+    /// Decodes a `JVMTI_HEAP_REFERENCE_*` value; `None` for unknown kinds.
+    pub fn from_raw(raw: jvmti::jvmtiHeapReferenceKind) -> Option<Self> {
+        Self::KINDS.iter().find(|(value, _)| *value == raw).map(|(_, kind)| *kind)
+    }
+
+    pub fn as_raw(self) -> jvmti::jvmtiHeapReferenceKind {
+        Self::KINDS.iter().find(|(_, kind)| *kind == self).map_or(jvmti::JVMTI_HEAP_REFERENCE_OTHER, |(value, _)| *value)
+    }
+
+    /// Returns true for GC root kinds (`JNI_GLOBAL` through `OTHER`).
+    pub fn is_root(self) -> bool {
+        self.as_raw() >= jvmti::JVMTI_HEAP_REFERENCE_JNI_GLOBAL
+    }
+}
+
+/// A reference reported by [`Jvmti::follow_references_with`].
+///
+/// Describes an edge from a referrer (or a GC root) to an object. Only valid
+/// for the duration of the closure call.
+pub struct Reference<'a> {
+    kind: jvmti::jvmtiHeapReferenceKind,
+    info: Option<&'a jvmti::jvmtiHeapReferenceInfo>,
+    class_tag: jni::jlong,
+    referrer_class_tag: jni::jlong,
+    size: jni::jlong,
+    length: jni::jint,
+    // Raw because the VM passes the same pointer twice for self-references.
+    tag_ptr: *mut jni::jlong,
+    referrer_tag_ptr: *mut jni::jlong,
+    _marker: std::marker::PhantomData<&'a mut jni::jlong>,
+}
+
+impl Reference<'_> {
+    /// The `JVMTI_HEAP_REFERENCE_*` kind of this reference.
+    pub fn kind(&self) -> jvmti::jvmtiHeapReferenceKind {
+        self.kind
+    }
+
+    /// [`kind`](Self::kind), decoded.
+    pub fn reference_kind(&self) -> Option<ReferenceKind> {
+        ReferenceKind::from_raw(self.kind)
+    }
+
+    /// Returns true for references from a GC root (no referrer object).
+    pub fn is_root(&self) -> bool {
+        self.referrer_tag_ptr.is_null()
+    }
+
+    /// Field, array, or constant-pool index for `FIELD`, `STATIC_FIELD`,
+    /// `ARRAY_ELEMENT`, and `CONSTANT_POOL` references.
+    pub fn index(&self) -> Option<jni::jint> {
+        let info = self.info?;
+        // SAFETY: the VM fills the union member matching `kind`; these three
+        // members share the same single-`jint` layout.
+        unsafe {
+            match self.kind {
+                jvmti::JVMTI_HEAP_REFERENCE_FIELD | jvmti::JVMTI_HEAP_REFERENCE_STATIC_FIELD => Some(info.field.index),
+                jvmti::JVMTI_HEAP_REFERENCE_ARRAY_ELEMENT => Some(info.array.index),
+                jvmti::JVMTI_HEAP_REFERENCE_CONSTANT_POOL => Some(info.constant_pool.index),
+                _ => None,
+            }
+        }
+    }
+
+    /// Thread, frame, and slot details for `STACK_LOCAL` roots.
+    pub fn stack_local(&self) -> Option<&jvmti::jvmtiHeapReferenceInfoStackLocal> {
+        match (self.kind, self.info) {
+            // SAFETY: `stack_local` is the member the VM fills for this kind.
+            (jvmti::JVMTI_HEAP_REFERENCE_STACK_LOCAL, Some(info)) => Some(unsafe { &info.stack_local }),
+            _ => None,
+        }
+    }
+
+    /// Thread and frame details for `JNI_LOCAL` roots.
+    pub fn jni_local(&self) -> Option<&jvmti::jvmtiHeapReferenceInfoJniLocal> {
+        match (self.kind, self.info) {
+            // SAFETY: `jni_local` is the member the VM fills for this kind.
+            (jvmti::JVMTI_HEAP_REFERENCE_JNI_LOCAL, Some(info)) => Some(unsafe { &info.jni_local }),
+            _ => None,
+        }
+    }
+
+    /// Tag of the referenced object's class, or `0` if untagged.
+    pub fn class_tag(&self) -> jni::jlong {
+        self.class_tag
+    }
+
+    /// Tag of the referrer's class, or `0` for roots and untagged classes.
+    pub fn referrer_class_tag(&self) -> jni::jlong {
+        self.referrer_class_tag
+    }
+
+    /// Size of the referenced object in bytes.
+    pub fn size(&self) -> jni::jlong {
+        self.size
+    }
+
+    /// Array length if the referenced object is an array, `-1` otherwise.
+    pub fn length(&self) -> jni::jint {
+        self.length
+    }
+
+    /// Current tag of the referenced object, or `0` if untagged.
+    pub fn tag(&self) -> jni::jlong {
+        // SAFETY: the VM guarantees `tag_ptr` is valid during the callback.
+        unsafe { *self.tag_ptr }
+    }
+
+    /// Sets the referenced object's tag; `0` removes it.
+    pub fn set_tag(&mut self, tag: jni::jlong) {
+        // SAFETY: as in `tag`.
+        unsafe { *self.tag_ptr = tag }
+    }
+
+    /// Current tag of the referrer, or `None` for roots.
+    pub fn referrer_tag(&self) -> Option<jni::jlong> {
+        // SAFETY: non-null `referrer_tag_ptr` is valid during the callback.
+        (!self.referrer_tag_ptr.is_null()).then(|| unsafe { *self.referrer_tag_ptr })
+    }
+
+    /// Sets the referrer's tag. Ignored for roots.
+    pub fn set_referrer_tag(&mut self, tag: jni::jlong) {
+        if !self.referrer_tag_ptr.is_null() {
+            // SAFETY: as in `referrer_tag`.
+            unsafe { *self.referrer_tag_ptr = tag }
+        }
+    }
+}
+
+impl std::fmt::Debug for Reference<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reference")
+            .field("kind", &self.kind)
+            .field("index", &self.index())
+            .field("class_tag", &self.class_tag)
+            .field("referrer_class_tag", &self.referrer_class_tag)
+            .field("size", &self.size)
+            .field("length", &self.length)
+            .field("tag", &self.tag())
+            .field("referrer_tag", &self.referrer_tag())
+            .finish()
+    }
+}
+
+/// A primitive field value reported by [`Jvmti::follow_references_with`] or
+/// [`Jvmti::iterate_through_heap_with`].
+///
+/// Only valid for the duration of the closure call.
+pub struct PrimitiveField<'a> {
+    kind: jvmti::jvmtiHeapReferenceKind,
+    index: jni::jint,
+    object_class_tag: jni::jlong,
+    object_tag: &'a mut jni::jlong,
+    value: jni::jvalue,
+    value_type: jvmti::jvmtiPrimitiveType,
+}
+
+impl PrimitiveField<'_> {
+    /// `JVMTI_HEAP_REFERENCE_FIELD` or `JVMTI_HEAP_REFERENCE_STATIC_FIELD`.
+    pub fn kind(&self) -> jvmti::jvmtiHeapReferenceKind {
+        self.kind
+    }
+
+    /// Field index, in the order used by `GetClassFields` across the class hierarchy.
+    pub fn index(&self) -> jni::jint {
+        self.index
+    }
+
+    /// Tag of the object's class (the class itself for static fields).
+    pub fn object_class_tag(&self) -> jni::jlong {
+        self.object_class_tag
+    }
+
+    /// Current tag of the object holding the field.
+    pub fn object_tag(&self) -> jni::jlong {
+        *self.object_tag
+    }
+
+    /// Sets the tag of the object holding the field.
+    pub fn set_object_tag(&mut self, tag: jni::jlong) {
+        *self.object_tag = tag;
+    }
+
+    /// The raw field value; read the member matching [`value_type`](Self::value_type).
+    pub fn value(&self) -> jni::jvalue {
+        self.value
+    }
+
+    /// The `JVMTI_PRIMITIVE_TYPE_*` of the value.
+    pub fn value_type(&self) -> jvmti::jvmtiPrimitiveType {
+        self.value_type
+    }
+}
+
+impl std::fmt::Debug for PrimitiveField<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrimitiveField")
+            .field("kind", &self.kind)
+            .field("index", &self.index)
+            .field("object_class_tag", &self.object_class_tag)
+            .field("object_tag", &*self.object_tag)
+            .field("value_type", &self.value_type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The contents of a primitive array, reported by the heap closures.
+///
+/// Only valid for the duration of the closure call.
+pub struct PrimitiveArray<'a> {
+    class_tag: jni::jlong,
+    size: jni::jlong,
+    tag: &'a mut jni::jlong,
+    element_count: jni::jint,
+    element_type: jvmti::jvmtiPrimitiveType,
+    elements: *const std::os::raw::c_void,
+}
+
+impl PrimitiveArray<'_> {
+    /// Tag of the array's class, or `0` if the class is untagged.
+    pub fn class_tag(&self) -> jni::jlong {
+        self.class_tag
+    }
+
+    /// Array size in bytes (see `GetObjectSize`).
+    pub fn size(&self) -> jni::jlong {
+        self.size
+    }
+
+    /// Current tag of the array, or `0` if untagged.
+    pub fn tag(&self) -> jni::jlong {
+        *self.tag
+    }
+
+    /// Sets the array's tag; `0` removes it.
+    pub fn set_tag(&mut self, tag: jni::jlong) {
+        *self.tag = tag;
+    }
+
+    /// Number of elements.
+    pub fn len(&self) -> usize {
+        self.element_count.max(0) as usize
+    }
+
+    /// Returns true if the array has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `JVMTI_PRIMITIVE_TYPE_*` of the elements.
+    pub fn element_type(&self) -> jvmti::jvmtiPrimitiveType {
+        self.element_type
+    }
+
+    fn elements<T>(&self, element_type: jvmti::jvmtiPrimitiveType) -> Option<&[T]> {
+        if self.element_type != element_type {
+            return None;
+        }
+        if self.elements.is_null() || self.element_count <= 0 {
+            return Some(&[]);
+        }
+        // SAFETY: the VM passes `element_count` packed elements of `element_type`,
+        // valid for the duration of the callback.
+        Some(unsafe { std::slice::from_raw_parts(self.elements as *const T, self.len()) })
+    }
+
+    /// The elements of a `boolean[]`, `None` for other element types.
+    pub fn as_booleans(&self) -> Option<&[jni::jboolean]> {
+        self.elements(jvmti::JVMTI_PRIMITIVE_TYPE_BOOLEAN)
+    }
+
+    /// The elements of a `byte[]`, `None` for other element types.
+    pub fn as_bytes(&self) -> Option<&[jni::jbyte]> {
+        self.elements(jvmti::JVMTI_PRIMITIVE_TYPE_BYTE)
+    }
+
+    /// The elements of a `char[]`, `None` for other element types.
+    pub fn as_chars(&self) -> Option<&[jni::jchar]> {
+        self.elements(jvmti::JVMTI_PRIMITIVE_TYPE_CHAR)
+    }
+
+    /// The elements of a `short[]`, `None` for other element types.
+    pub fn as_shorts(&self) -> Option<&[jni::jshort]> {
+        self.elements(jvmti::JVMTI_PRIMITIVE_TYPE_SHORT)
+    }
+
+    /// The elements of an `int[]`, `None` for other element types.
+    pub fn as_ints(&self) -> Option<&[jni::jint]> {
+        self.elements(jvmti::JVMTI_PRIMITIVE_TYPE_INT)
+    }
+
+    /// The elements of a `long[]`, `None` for other element types.
+    pub fn as_longs(&self) -> Option<&[jni::jlong]> {
+        self.elements(jvmti::JVMTI_PRIMITIVE_TYPE_LONG)
+    }
+
+    /// The elements of a `float[]`, `None` for other element types.
+    pub fn as_floats(&self) -> Option<&[jni::jfloat]> {
+        self.elements(jvmti::JVMTI_PRIMITIVE_TYPE_FLOAT)
+    }
+
+    /// The elements of a `double[]`, `None` for other element types.
+    pub fn as_doubles(&self) -> Option<&[jni::jdouble]> {
+        self.elements(jvmti::JVMTI_PRIMITIVE_TYPE_DOUBLE)
+    }
+}
+
+impl std::fmt::Debug for PrimitiveArray<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrimitiveArray")
+            .field("class_tag", &self.class_tag)
+            .field("size", &self.size)
+            .field("tag", &self.tag())
+            .field("element_count", &self.element_count)
+            .field("element_type", &self.element_type)
+            .finish()
+    }
+}
+
+/// The value of a `java.lang.String`, reported by the heap closures.
+///
+/// Only valid for the duration of the closure call.
+#[derive(Debug)]
+pub struct StringValue<'a> {
+    class_tag: jni::jlong,
+    size: jni::jlong,
+    tag: &'a mut jni::jlong,
+    chars: &'a [jni::jchar],
+}
+
+impl StringValue<'_> {
+    /// Tag of `java.lang.String`, or `0` if it is untagged.
+    pub fn class_tag(&self) -> jni::jlong {
+        self.class_tag
+    }
+
+    /// Size of the string object in bytes (see `GetObjectSize`).
+    pub fn size(&self) -> jni::jlong {
+        self.size
+    }
+
+    /// Current tag of the string, or `0` if untagged.
+    pub fn tag(&self) -> jni::jlong {
+        *self.tag
+    }
+
+    /// Sets the string's tag; `0` removes it.
+    pub fn set_tag(&mut self, tag: jni::jlong) {
+        *self.tag = tag;
+    }
+
+    /// The UTF-16 code units of the string.
+    pub fn chars(&self) -> &[jni::jchar] {
+        self.chars
+    }
+
+    /// The string, with unpaired surrogates replaced by U+FFFD.
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(self.chars)
+    }
+}
+
+type ReferenceClosure<'c> = Box<dyn FnMut(Reference<'_>) -> VisitControl + 'c>;
+type PrimitiveFieldClosure<'c> = Box<dyn FnMut(PrimitiveField<'_>) -> IterationControl + 'c>;
+type PrimitiveArrayClosure<'c> = Box<dyn FnMut(PrimitiveArray<'_>) -> IterationControl + 'c>;
+type StringValueClosure<'c> = Box<dyn FnMut(StringValue<'_>) -> IterationControl + 'c>;
+
+/// Closures for [`Jvmti::follow_references_with`] and [`Jvmti::iterate_through_heap_with`].
+///
+/// Unset closures leave the matching `jvmtiHeapCallbacks` slot empty. With no
+/// reference closure the VM still walks the graph but reports nothing; the
+/// reference closure is not used by `iterate_through_heap_with`.
+///
+/// ```rust,ignore
+/// let mut roots = 0usize;
+/// let mut edges = 0usize;
+/// jvmti.follow_references_with(0, ptr::null_mut(), ptr::null_mut(),
+///     HeapClosures::new().on_reference(|r| {
+///         if r.is_root() { roots += 1 } else { edges += 1 }
+///         VisitControl::Follow
+///     }))?;
+/// ```
+#[derive(Default)]
+pub struct HeapClosures<'c> {
+    reference: Option<ReferenceClosure<'c>>,
+    primitive_field: Option<PrimitiveFieldClosure<'c>>,
+    array_primitive_value: Option<PrimitiveArrayClosure<'c>>,
+    string_value: Option<StringValueClosure<'c>>,
+}
+
+impl<'c> HeapClosures<'c> {
+    /// Creates an empty set of closures.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Called for every reference, including GC roots and stack locals.
+    pub fn on_reference<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(Reference<'_>) -> VisitControl + 'c,
+    {
+        self.reference = Some(Box::new(f));
+        self
+    }
+
+    /// Called for every primitive instance or static field of a visited object.
+    pub fn on_primitive_field<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(PrimitiveField<'_>) -> IterationControl + 'c,
+    {
+        self.primitive_field = Some(Box::new(f));
+        self
+    }
+
+    /// Called with the contents of every visited primitive array.
+    pub fn on_array_primitive_value<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(PrimitiveArray<'_>) -> IterationControl + 'c,
+    {
+        self.array_primitive_value = Some(Box::new(f));
+        self
+    }
+
+    /// Called with the value of every visited `java.lang.String`.
+    pub fn on_string_value<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(StringValue<'_>) -> IterationControl + 'c,
+    {
+        self.string_value = Some(Box::new(f));
+        self
+    }
+
+    /// The `jvmtiHeapCallbacks` slots for the value closures that are set.
+    fn value_callbacks(&self, callbacks: jvmti::jvmtiHeapCallbacks) -> jvmti::jvmtiHeapCallbacks {
+        let mut callbacks = callbacks;
+        if self.primitive_field.is_some() {
+            callbacks = callbacks.primitive_field(follow_primitive_field_trampoline);
+        }
+        if self.array_primitive_value.is_some() {
+            callbacks = callbacks.array_primitive_value(array_primitive_value_trampoline);
+        }
+        if self.string_value.is_some() {
+            callbacks = callbacks.string_primitive_value(string_primitive_value_trampoline);
+        }
+        callbacks
+    }
+}
+
+/// Decoded `GetThreadState` bitmask.
+///
+/// Returned by [`Jvmti::get_thread_state_decoded`]. The raw bits are kept
+/// as-is; the predicates test the `JVMTI_THREAD_STATE_*` flags and
+/// `Display` prints the set flags joined by `" | "`, e.g. `RUNNABLE | ALIVE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThreadState(pub jni::jint);
+
+impl ThreadState {
+    const FLAG_NAMES: [(jni::jint, &'static str); 16] = [
+        (jvmti::JVMTI_THREAD_STATE_TERMINATED, "TERMINATED"),
+        (jvmti::JVMTI_THREAD_STATE_RUNNABLE, "RUNNABLE"),
+        (jvmti::JVMTI_THREAD_STATE_BLOCKED_ON_MONITOR_ENTER, "BLOCKED_ON_MONITOR_ENTER"),
+        (jvmti::JVMTI_THREAD_STATE_WAITING, "WAITING"),
+        (jvmti::JVMTI_THREAD_STATE_WAITING_INDEFINITELY, "WAITING_INDEFINITELY"),
+        (jvmti::JVMTI_THREAD_STATE_WAITING_WITH_TIMEOUT, "WAITING_WITH_TIMEOUT"),
+        (jvmti::JVMTI_THREAD_STATE_SLEEPING, "SLEEPING"),
+        (jvmti::JVMTI_THREAD_STATE_IN_OBJECT_WAIT, "IN_OBJECT_WAIT"),
+        (jvmti::JVMTI_THREAD_STATE_PARKED, "PARKED"),
+        (jvmti::JVMTI_THREAD_STATE_SUSPENDED, "SUSPENDED"),
+        (jvmti::JVMTI_THREAD_STATE_INTERRUPTED, "INTERRUPTED"),
+        (jvmti::JVMTI_THREAD_STATE_IN_NATIVE, "IN_NATIVE"),
+        (jvmti::JVMTI_THREAD_STATE_VENDOR_1, "VENDOR_1"),
+        (jvmti::JVMTI_THREAD_STATE_VENDOR_2, "VENDOR_2"),
+        (jvmti::JVMTI_THREAD_STATE_VENDOR_3, "VENDOR_3"),
+        (jvmti::JVMTI_THREAD_STATE_ALIVE, "ALIVE"),
+    ];
+
+    /// Wraps a raw `GetThreadState` value.
+    pub const fn from_raw(bits: jni::jint) -> Self {
+        Self(bits)
+    }
+
+    /// The raw bitmask.
+    pub const fn bits(self) -> jni::jint {
+        self.0
+    }
+
+    /// Returns `true` if every bit in `flag` is set.
+    pub const fn contains(self, flag: jni::jint) -> bool {
+        self.0 & flag == flag
+    }
+
+    /// The thread has been started and has not yet terminated.
+    pub const fn is_alive(self) -> bool {
+        self.contains(jvmti::JVMTI_THREAD_STATE_ALIVE)
+    }
+
+    /// The thread has completed execution.
+    pub const fn is_terminated(self) -> bool {
+        self.contains(jvmti::JVMTI_THREAD_STATE_TERMINATED)
+    }
+
+    /// The thread is runnable.
+    pub const fn is_runnable(self) -> bool {
+        self.contains(jvmti::JVMTI_THREAD_STATE_RUNNABLE)
+    }
+
+    /// The thread is waiting to enter or re-enter a monitor.
+    pub const fn is_blocked_on_monitor_enter(self) -> bool {
+        self.contains(jvmti::JVMTI_THREAD_STATE_BLOCKED_ON_MONITOR_ENTER)
+    }
+
+    /// The thread is waiting (`Object.wait`, `LockSupport.park`, `Thread.sleep`, ...).
+    pub const fn is_waiting(self) -> bool {
+        self.contains(jvmti::JVMTI_THREAD_STATE_WAITING)
+    }
+
+    /// The thread is sleeping in `Thread.sleep`.
+    pub const fn is_sleeping(self) -> bool {
+        self.contains(jvmti::JVMTI_THREAD_STATE_SLEEPING)
+    }
+
+    /// The thread is parked (`LockSupport.park`).
+    pub const fn is_parked(self) -> bool {
+        self.contains(jvmti::JVMTI_THREAD_STATE_PARKED)
+    }
+
+    /// The thread is executing native code.
+    pub const fn is_in_native(self) -> bool {
+        self.contains(jvmti::JVMTI_THREAD_STATE_IN_NATIVE)
+    }
+
+    /// The thread is suspended (`SuspendThread` or `Thread.suspend`).
+    pub const fn is_suspended(self) -> bool {
+        self.contains(jvmti::JVMTI_THREAD_STATE_SUSPENDED)
+    }
+
+    /// The thread has been interrupted.
+    pub const fn is_interrupted(self) -> bool {
+        self.contains(jvmti::JVMTI_THREAD_STATE_INTERRUPTED)
+    }
+
+    /// The `java.lang.Thread.State` name for this state: `NEW`, `RUNNABLE`,
+    /// `BLOCKED`, `WAITING`, `TIMED_WAITING`, or `TERMINATED` (`UNKNOWN` for
+    /// combinations the specification does not define).
+    pub const fn java_thread_state(self) -> &'static str {
+        match self.0 & jvmti::JVMTI_JAVA_LANG_THREAD_STATE_MASK {
+            jvmti::JVMTI_JAVA_LANG_THREAD_STATE_NEW => "NEW",
+            jvmti::JVMTI_JAVA_LANG_THREAD_STATE_TERMINATED => "TERMINATED",
+            jvmti::JVMTI_JAVA_LANG_THREAD_STATE_RUNNABLE => "RUNNABLE",
+            jvmti::JVMTI_JAVA_LANG_THREAD_STATE_BLOCKED => "BLOCKED",
+            jvmti::JVMTI_JAVA_LANG_THREAD_STATE_WAITING => "WAITING",
+            jvmti::JVMTI_JAVA_LANG_THREAD_STATE_TIMED_WAITING => "TIMED_WAITING",
+            _ => "UNKNOWN",
+        }
+    }
+}
 
-#[derive(Debug, Clone)]
-pub struct ThreadInfo {
-    pub name: Option<String>,
-    pub priority: jni::jint,
-    pub is_daemon: bool,
-    pub thread_group: jni::jobject,
-    pub context_class_loader: jni::jobject,
+impl From<jni::jint> for ThreadState {
+    fn from(bits: jni::jint) -> Self {
+        Self(bits)
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct ThreadGroupInfo {
-    pub parent: jni::jobject,
-    pub name: Option<String>,
-    pub max_priority: jni::jint,
-    pub is_daemon: bool,
+/// Writes the names of the flags set in `bits` joined by `" | "`, followed by
+/// any bits no name covers in hex.
+fn fmt_flags(f: &mut std::fmt::Formatter<'_>, bits: jni::jint, names: &[(jni::jint, &str)]) -> std::fmt::Result {
+    let mut known = 0;
+    let mut first = true;
+    for &(flag, name) in names {
+        known |= flag;
+        if bits & flag == flag {
+            if !first {
+                f.write_str(" | ")?;
+            }
+            f.write_str(name)?;
+            first = false;
+        }
+    }
+    let unknown = bits & !known;
+    if unknown != 0 {
+        if !first {
+            f.write_str(" | ")?;
+        }
+        write!(f, "{unknown:#x}")?;
+    }
+    Ok(())
 }
 
-#[derive(Debug, Clone)]
-pub struct MonitorUsage {
-    pub owner: jni::jthread,
-    pub entry_count: jni::jint,
-    pub waiters: Vec<jni::jthread>,
-    pub notify_waiters: Vec<jni::jthread>,
+impl std::fmt::Display for ThreadState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 == 0 {
+            return f.write_str("NEW");
+        }
+        fmt_flags(f, self.0, &Self::FLAG_NAMES)
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct StackInfo {
-    pub thread: jni::jthread,
-    pub state: jni::jint,
-    pub frames: Vec<jvmti::jvmtiFrameInfo>,
+/// A decoded `GetClassStatus` bitmask.
+///
+/// Returned by [`Jvmti::get_class_status_decoded`]. `Display` prints the set
+/// flags joined by `" | "`, e.g. `VERIFIED | PREPARED | INITIALIZED`, or
+/// `LOADED` when none is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClassStatus(pub jni::jint);
+
+impl ClassStatus {
+    const FLAG_NAMES: [(jni::jint, &'static str); 6] = [
+        (jvmti::JVMTI_CLASS_STATUS_VERIFIED, "VERIFIED"),
+        (jvmti::JVMTI_CLASS_STATUS_PREPARED, "PREPARED"),
+        (jvmti::JVMTI_CLASS_STATUS_INITIALIZED, "INITIALIZED"),
+        (jvmti::JVMTI_CLASS_STATUS_ERROR, "ERROR"),
+        (jvmti::JVMTI_CLASS_STATUS_ARRAY, "ARRAY"),
+        (jvmti::JVMTI_CLASS_STATUS_PRIMITIVE, "PRIMITIVE"),
+    ];
+
+    /// Wraps a raw `GetClassStatus` value.
+    pub const fn from_raw(bits: jni::jint) -> Self {
+        Self(bits)
+    }
+
+    /// The raw bitmask.
+    pub const fn bits(self) -> jni::jint {
+        self.0
+    }
+
+    /// Returns `true` if every bit in `flag` is set.
+    pub const fn contains(self, flag: jni::jint) -> bool {
+        self.0 & flag == flag
+    }
+
+    /// Bytecodes have been verified.
+    pub const fn is_verified(self) -> bool {
+        self.contains(jvmti::JVMTI_CLASS_STATUS_VERIFIED)
+    }
+
+    /// Preparation is complete; static fields have their default values.
+    pub const fn is_prepared(self) -> bool {
+        self.contains(jvmti::JVMTI_CLASS_STATUS_PREPARED)
+    }
+
+    /// The static initializer has run.
+    pub const fn is_initialized(self) -> bool {
+        self.contains(jvmti::JVMTI_CLASS_STATUS_INITIALIZED)
+    }
+
+    /// Initialization failed; the class is unusable.
+    pub const fn is_error(self) -> bool {
+        self.contains(jvmti::JVMTI_CLASS_STATUS_ERROR)
+    }
+
+    /// The class is an array class (the other flags are then undefined).
+    pub const fn is_array(self) -> bool {
+        self.contains(jvmti::JVMTI_CLASS_STATUS_ARRAY)
+    }
+
+    /// The class is a primitive class such as `int.class` (the other flags are then undefined).
+    pub const fn is_primitive(self) -> bool {
+        self.contains(jvmti::JVMTI_CLASS_STATUS_PRIMITIVE)
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct ExtensionParamInfo {
-    pub name: Option<String>,
-    pub kind: jni::jint,
-    pub base_type: jni::jint,
-    pub null_ok: bool,
+impl From<jni::jint> for ClassStatus {
+    fn from(bits: jni::jint) -> Self {
+        Self(bits)
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct ExtensionFunctionInfo {
-    pub func: *mut std::ffi::c_void,
-    pub id: Option<String>,
-    pub short_description: Option<String>,
-    pub params: Vec<ExtensionParamInfo>,
-    pub errors: Vec<jvmti::jvmtiError>,
+impl std::fmt::Display for ClassStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 == 0 {
+            return f.write_str("LOADED");
+        }
+        fmt_flags(f, self.0, &Self::FLAG_NAMES)
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct ExtensionEventInfo {
-    pub extension_event_index: jni::jint,
-    pub id: Option<String>,
-    pub short_description: Option<String>,
-    pub params: Vec<ExtensionParamInfo>,
+/// A decoded `ResourceExhausted` event bitmask.
+///
+/// Passed to [`Agent::resource_exhausted_typed`](crate::Agent::resource_exhausted_typed).
+/// `Display` prints the set flags joined by `" | "`, e.g.
+/// `OOM_ERROR | JAVA_HEAP`, or `NONE` when none is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResourceExhaustedFlags(pub jni::jint);
+
+impl ResourceExhaustedFlags {
+    const FLAG_NAMES: [(jni::jint, &'static str); 3] = [
+        (jvmti::JVMTI_RESOURCE_EXHAUSTED_OOM_ERROR, "OOM_ERROR"),
+        (jvmti::JVMTI_RESOURCE_EXHAUSTED_JAVA_HEAP, "JAVA_HEAP"),
+        (jvmti::JVMTI_RESOURCE_EXHAUSTED_THREADS, "THREADS"),
+    ];
+
+    /// Wraps a raw `ResourceExhausted` flags value.
+    pub const fn from_raw(bits: jni::jint) -> Self {
+        Self(bits)
+    }
+
+    /// The raw bitmask.
+    pub const fn bits(self) -> jni::jint {
+        self.0
+    }
+
+    /// Returns `true` if every bit in `flag` is set.
+    pub const fn contains(self, flag: jni::jint) -> bool {
+        self.0 & flag == flag
+    }
+
+    /// The VM will throw an `OutOfMemoryError` after the event returns.
+    pub const fn is_oom_error(self) -> bool {
+        self.contains(jvmti::JVMTI_RESOURCE_EXHAUSTED_OOM_ERROR)
+    }
+
+    /// The Java heap could not satisfy an allocation.
+    pub const fn is_java_heap(self) -> bool {
+        self.contains(jvmti::JVMTI_RESOURCE_EXHAUSTED_JAVA_HEAP)
+    }
+
+    /// A thread could not be created.
+    pub const fn is_threads(self) -> bool {
+        self.contains(jvmti::JVMTI_RESOURCE_EXHAUSTED_THREADS)
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct LocalVariableEntry {
-    pub start_location: jvmti::jlocation,
-    pub length: jni::jint,
-    pub name: Option<String>,
-    pub signature: Option<String>,
-    pub generic_signature: Option<String>,
-    pub slot: jni::jint,
+impl From<jni::jint> for ResourceExhaustedFlags {
+    fn from(bits: jni::jint) -> Self {
+        Self(bits)
+    }
+}
+
+impl std::fmt::Display for ResourceExhaustedFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 == 0 {
+            return f.write_str("NONE");
+        }
+        fmt_flags(f, self.0, &Self::FLAG_NAMES)
+    }
+}
+
+macro_rules! events {
+    ($( $variant:ident = $raw:ident, $name:literal; )*) => {
+        /// A JVMTI event type, the typed form of the `JVMTI_EVENT_*` constants.
+        ///
+        /// Accepted by [`Jvmti::enable`] and [`Jvmti::disable`], and convertible
+        /// into the raw `u32` with [`Event::to_raw`] or `u32::from`.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[repr(u32)]
+        pub enum Event {
+            $(
+                #[doc = concat!("`", $name, "` (`", stringify!($raw), "`).")]
+                $variant = jvmti::$raw,
+            )*
+        }
+
+        impl Event {
+            /// Every event, in event-number order.
+            pub const ALL: &'static [Event] = &[$(Event::$variant),*];
+
+            /// Converts a raw `JVMTI_EVENT_*` value; `None` for reserved or unknown numbers.
+            pub const fn from_raw(raw: u32) -> Option<Event> {
+                match raw {
+                    $( jvmti::$raw => Some(Event::$variant), )*
+                    _ => None,
+                }
+            }
+
+            /// Returns the event name used by the JVMTI specification, e.g. `MethodEntry`.
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $( Event::$variant => $name, )*
+                }
+            }
+        }
+    };
+}
+
+events! {
+    VmInit = JVMTI_EVENT_VM_INIT, "VMInit";
+    VmDeath = JVMTI_EVENT_VM_DEATH, "VMDeath";
+    ThreadStart = JVMTI_EVENT_THREAD_START, "ThreadStart";
+    ThreadEnd = JVMTI_EVENT_THREAD_END, "ThreadEnd";
+    ClassFileLoadHook = JVMTI_EVENT_CLASS_FILE_LOAD_HOOK, "ClassFileLoadHook";
+    ClassLoad = JVMTI_EVENT_CLASS_LOAD, "ClassLoad";
+    ClassPrepare = JVMTI_EVENT_CLASS_PREPARE, "ClassPrepare";
+    VmStart = JVMTI_EVENT_VM_START, "VMStart";
+    Exception = JVMTI_EVENT_EXCEPTION, "Exception";
+    ExceptionCatch = JVMTI_EVENT_EXCEPTION_CATCH, "ExceptionCatch";
+    SingleStep = JVMTI_EVENT_SINGLE_STEP, "SingleStep";
+    FramePop = JVMTI_EVENT_FRAME_POP, "FramePop";
+    Breakpoint = JVMTI_EVENT_BREAKPOINT, "Breakpoint";
+    FieldAccess = JVMTI_EVENT_FIELD_ACCESS, "FieldAccess";
+    FieldModification = JVMTI_EVENT_FIELD_MODIFICATION, "FieldModification";
+    MethodEntry = JVMTI_EVENT_METHOD_ENTRY, "MethodEntry";
+    MethodExit = JVMTI_EVENT_METHOD_EXIT, "MethodExit";
+    NativeMethodBind = JVMTI_EVENT_NATIVE_METHOD_BIND, "NativeMethodBind";
+    CompiledMethodLoad = JVMTI_EVENT_COMPILED_METHOD_LOAD, "CompiledMethodLoad";
+    CompiledMethodUnload = JVMTI_EVENT_COMPILED_METHOD_UNLOAD, "CompiledMethodUnload";
+    DynamicCodeGenerated = JVMTI_EVENT_DYNAMIC_CODE_GENERATED, "DynamicCodeGenerated";
+    DataDumpRequest = JVMTI_EVENT_DATA_DUMP_REQUEST, "DataDumpRequest";
+    MonitorWait = JVMTI_EVENT_MONITOR_WAIT, "MonitorWait";
+    MonitorWaited = JVMTI_EVENT_MONITOR_WAITED, "MonitorWaited";
+    MonitorContendedEnter = JVMTI_EVENT_MONITOR_CONTENDED_ENTER, "MonitorContendedEnter";
+    MonitorContendedEntered = JVMTI_EVENT_MONITOR_CONTENDED_ENTERED, "MonitorContendedEntered";
+    ResourceExhausted = JVMTI_EVENT_RESOURCE_EXHAUSTED, "ResourceExhausted";
+    GarbageCollectionStart = JVMTI_EVENT_GARBAGE_COLLECTION_START, "GarbageCollectionStart";
+    GarbageCollectionFinish = JVMTI_EVENT_GARBAGE_COLLECTION_FINISH, "GarbageCollectionFinish";
+    ObjectFree = JVMTI_EVENT_OBJECT_FREE, "ObjectFree";
+    VmObjectAlloc = JVMTI_EVENT_VM_OBJECT_ALLOC, "VMObjectAlloc";
+    SampledObjectAlloc = JVMTI_EVENT_SAMPLED_OBJECT_ALLOC, "SampledObjectAlloc";
+    VirtualThreadStart = JVMTI_EVENT_VIRTUAL_THREAD_START, "VirtualThreadStart";
+    VirtualThreadEnd = JVMTI_EVENT_VIRTUAL_THREAD_END, "VirtualThreadEnd";
+}
+
+impl Event {
+    /// Returns the raw `JVMTI_EVENT_*` value.
+    pub const fn to_raw(self) -> u32 {
+        self as u32
+    }
+
+    /// Returns a setter for the capability this event needs, if any.
+    ///
+    /// | Event | Capability |
+    /// |-------|------------|
+    /// | `SingleStep` | `can_generate_single_step_events` |
+    /// | `Exception`, `ExceptionCatch` | `can_generate_exception_events` |
+    /// | `FramePop` | `can_generate_frame_pop_events` |
+    /// | `Breakpoint` | `can_generate_breakpoint_events` |
+    /// | `FieldAccess` | `can_generate_field_access_events` |
+    /// | `FieldModification` | `can_generate_field_modification_events` |
+    /// | `MethodEntry` | `can_generate_method_entry_events` |
+    /// | `MethodExit` | `can_generate_method_exit_events` |
+    /// | `NativeMethodBind` | `can_generate_native_method_bind_events` |
+    /// | `CompiledMethodLoad`, `CompiledMethodUnload` | `can_generate_compiled_method_load_events` |
+    /// | `MonitorWait`, `MonitorWaited`, `MonitorContendedEnter`, `MonitorContendedEntered` | `can_generate_monitor_events` |
+    /// | `GarbageCollectionStart`, `GarbageCollectionFinish` | `can_generate_garbage_collection_events` |
+    /// | `ObjectFree` | `can_generate_object_free_events` |
+    /// | `VMObjectAlloc` | `can_generate_vm_object_alloc_events` |
+    /// | `SampledObjectAlloc` | `can_generate_sampled_object_alloc_events` |
+    /// | `VirtualThreadStart`, `VirtualThreadEnd` | `can_support_virtual_threads` |
+    ///
+    /// The remaining events need no capability. `ClassFileLoadHook` only sees
+    /// non-retransformable classes without `can_generate_all_class_hook_events`,
+    /// and `ResourceExhausted` reports heap/thread exhaustion only with the
+    /// matching `can_generate_resource_exhaustion_*` capability; both still
+    /// fire without them, so they return `None` here.
+    pub fn required_capability(self) -> Option<fn(&mut jvmti::jvmtiCapabilities)> {
+        let setter: fn(&mut jvmti::jvmtiCapabilities) = match self {
+            Event::SingleStep => |c| c.set_can_generate_single_step_events(true),
+            Event::Exception | Event::ExceptionCatch => |c| c.set_can_generate_exception_events(true),
+            Event::FramePop => |c| c.set_can_generate_frame_pop_events(true),
+            Event::Breakpoint => |c| c.set_can_generate_breakpoint_events(true),
+            Event::FieldAccess => |c| c.set_can_generate_field_access_events(true),
+            Event::FieldModification => |c| c.set_can_generate_field_modification_events(true),
+            Event::MethodEntry => |c| c.set_can_generate_method_entry_events(true),
+            Event::MethodExit => |c| c.set_can_generate_method_exit_events(true),
+            Event::NativeMethodBind => |c| c.set_can_generate_native_method_bind_events(true),
+            Event::CompiledMethodLoad | Event::CompiledMethodUnload => {
+                |c| c.set_can_generate_compiled_method_load_events(true)
+            }
+            Event::MonitorWait
+            | Event::MonitorWaited
+            | Event::MonitorContendedEnter
+            | Event::MonitorContendedEntered => |c| c.set_can_generate_monitor_events(true),
+            Event::GarbageCollectionStart | Event::GarbageCollectionFinish => {
+                |c| c.set_can_generate_garbage_collection_events(true)
+            }
+            Event::ObjectFree => |c| c.set_can_generate_object_free_events(true),
+            Event::VmObjectAlloc => |c| c.set_can_generate_vm_object_alloc_events(true),
+            Event::SampledObjectAlloc => |c| c.set_can_generate_sampled_object_alloc_events(true),
+            Event::VirtualThreadStart | Event::VirtualThreadEnd => |c| c.set_can_support_virtual_threads(true),
+            _ => return None,
+        };
+        Some(setter)
+    }
+
+    /// The capabilities this event needs, empty if none (see [`required_capability`](Self::required_capability)).
+    pub fn required_capabilities(self) -> jvmti::jvmtiCapabilities {
+        let mut caps = jvmti::jvmtiCapabilities::default();
+        if let Some(set) = self.required_capability() {
+            set(&mut caps);
+        }
+        caps
+    }
+}
+
+impl From<Event> for u32 {
+    fn from(event: Event) -> Self {
+        event.to_raw()
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 fn ptr_in_range(ptr: *const u8, base: *const u8, len: usize) -> bool {
@@ -100,27 +1842,246 @@ fn cstr_to_string(ptr: *const std::os::raw::c_char) -> Option<String> {
     if ptr.is_null() {
         return None;
     }
-    unsafe { CStr::from_ptr(ptr).to_str().ok().map(|s| s.to_string()) }
+    unsafe { Some(mutf8::from_cstr(ptr)) }
+}
+
+struct FollowState<'c> {
+    closures: HeapClosures<'c>,
+    panic: Option<Box<dyn std::any::Any + Send>>,
+}
+
+/// Runs `f` for a `FollowReferences` or `IterateThroughHeap` callback,
+/// recording a panic and aborting.
+unsafe fn with_follow_state(user_data: *mut std::os::raw::c_void, f: impl FnOnce(&mut HeapClosures<'_>) -> jni::jint) -> jni::jint {
+    if user_data.is_null() {
+        return jvmti::JVMTI_VISIT_ABORT;
+    }
+    // SAFETY: `user_data` is the `FollowState` owned by `follow_references_with`
+    // or `iterate_through_heap_with`, which outlives the heap walk on this thread.
+    let state = unsafe { &mut *(user_data as *mut FollowState<'_>) };
+    if state.panic.is_some() {
+        return jvmti::JVMTI_VISIT_ABORT;
+    }
+    let closures = &mut state.closures;
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(closures))) {
+        Ok(flags) => flags,
+        Err(payload) => {
+            state.panic = Some(payload);
+            jvmti::JVMTI_VISIT_ABORT
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "system" fn follow_reference_trampoline(
+    reference_kind: jvmti::jvmtiHeapReferenceKind,
+    reference_info: *const jvmti::jvmtiHeapReferenceInfo,
+    class_tag: jni::jlong,
+    referrer_class_tag: jni::jlong,
+    size: jni::jlong,
+    tag_ptr: *mut jni::jlong,
+    referrer_tag_ptr: *mut jni::jlong,
+    length: jni::jint,
+    user_data: *mut std::os::raw::c_void,
+) -> jni::jint {
+    if tag_ptr.is_null() {
+        return jvmti::JVMTI_VISIT_ABORT;
+    }
+    with_follow_state(user_data, |closures| {
+        let Some(f) = closures.reference.as_mut() else { return jvmti::JVMTI_VISIT_OBJECTS };
+        let reference = Reference {
+            kind: reference_kind,
+            info: unsafe { reference_info.as_ref() },
+            class_tag,
+            referrer_class_tag,
+            size,
+            length,
+            tag_ptr,
+            referrer_tag_ptr,
+            _marker: std::marker::PhantomData,
+        };
+        match f(reference) {
+            VisitControl::Follow => jvmti::JVMTI_VISIT_OBJECTS,
+            VisitControl::Skip => 0,
+            VisitControl::Abort => jvmti::JVMTI_VISIT_ABORT,
+        }
+    })
+}
+
+unsafe extern "system" fn follow_primitive_field_trampoline(
+    kind: jvmti::jvmtiHeapReferenceKind,
+    info: *const jvmti::jvmtiHeapReferenceInfo,
+    object_class_tag: jni::jlong,
+    object_tag_ptr: *mut jni::jlong,
+    value: jni::jvalue,
+    value_type: jvmti::jvmtiPrimitiveType,
+    user_data: *mut std::os::raw::c_void,
+) -> jni::jint {
+    if object_tag_ptr.is_null() || info.is_null() {
+        return jvmti::JVMTI_VISIT_ABORT;
+    }
+    with_follow_state(user_data, |closures| {
+        let Some(f) = closures.primitive_field.as_mut() else { return 0 };
+        let field = PrimitiveField {
+            kind,
+            // SAFETY: the VM passes field info for primitive-field callbacks.
+            index: unsafe { (*info).field.index },
+            object_class_tag,
+            object_tag: unsafe { &mut *object_tag_ptr },
+            value,
+            value_type,
+        };
+        iteration_flags(f(field))
+    })
+}
+
+fn iteration_flags(control: IterationControl) -> jni::jint {
+    match control {
+        IterationControl::Continue => 0,
+        IterationControl::Abort => jvmti::JVMTI_VISIT_ABORT,
+    }
+}
+
+unsafe extern "system" fn array_primitive_value_trampoline(
+    class_tag: jni::jlong,
+    size: jni::jlong,
+    tag_ptr: *mut jni::jlong,
+    element_count: jni::jint,
+    element_type: jvmti::jvmtiPrimitiveType,
+    elements: *const std::os::raw::c_void,
+    user_data: *mut std::os::raw::c_void,
+) -> jni::jint {
+    if tag_ptr.is_null() {
+        return jvmti::JVMTI_VISIT_ABORT;
+    }
+    with_follow_state(user_data, |closures| {
+        let Some(f) = closures.array_primitive_value.as_mut() else { return 0 };
+        iteration_flags(f(PrimitiveArray {
+            class_tag,
+            size,
+            tag: unsafe { &mut *tag_ptr },
+            element_count,
+            element_type,
+            elements,
+        }))
+    })
+}
+
+unsafe extern "system" fn string_primitive_value_trampoline(
+    class_tag: jni::jlong,
+    size: jni::jlong,
+    tag_ptr: *mut jni::jlong,
+    value: *const jni::jchar,
+    value_length: jni::jint,
+    user_data: *mut std::os::raw::c_void,
+) -> jni::jint {
+    if tag_ptr.is_null() {
+        return jvmti::JVMTI_VISIT_ABORT;
+    }
+    with_follow_state(user_data, |closures| {
+        let Some(f) = closures.string_value.as_mut() else { return 0 };
+        iteration_flags(f(StringValue {
+            class_tag,
+            size,
+            tag: unsafe { &mut *tag_ptr },
+            chars: if value.is_null() || value_length <= 0 {
+                &[]
+            } else {
+                unsafe { std::slice::from_raw_parts(value, value_length as usize) }
+            },
+        }))
+    })
+}
+
+struct HeapIterationState<F> {
+    f: F,
+    panic: Option<Box<dyn std::any::Any + Send>>,
+}
+
+unsafe extern "system" fn heap_iteration_trampoline<F>(
+    class_tag: jni::jlong,
+    size: jni::jlong,
+    tag_ptr: *mut jni::jlong,
+    length: jni::jint,
+    user_data: *mut std::os::raw::c_void,
+) -> jni::jint
+where
+    F: FnMut(HeapObject<'_>) -> IterationControl,
+{
+    if tag_ptr.is_null() || user_data.is_null() {
+        return jvmti::JVMTI_VISIT_ABORT;
+    }
+    // SAFETY: `user_data` is the `HeapIterationState<F>` owned by
+    // `iterate_heap_with`, which outlives the IterateThroughHeap call, and the
+    // VM invokes this callback from the calling thread only.
+    let state = unsafe { &mut *(user_data as *mut HeapIterationState<F>) };
+    if state.panic.is_some() {
+        return jvmti::JVMTI_VISIT_ABORT;
+    }
+    let object = HeapObject {
+        class_tag,
+        size,
+        length,
+        tag: unsafe { &mut *tag_ptr },
+    };
+    let f = &mut state.f;
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(object))) {
+        Ok(IterationControl::Continue) => 0,
+        Ok(IterationControl::Abort) => jvmti::JVMTI_VISIT_ABORT,
+        Err(payload) => {
+            state.panic = Some(payload);
+            jvmti::JVMTI_VISIT_ABORT
+        }
+    }
+}
+
+/// A `jvmtiEnv*` checked to be non-null and to have a function table.
+///
+/// Validating the pointer once where it enters the crate turns a null
+/// environment from a misbehaving VM into an error instead of a crash on the
+/// first JVMTI call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JvmtiEnvPtr(NonNull<jvmti::jvmtiEnv>);
+
+impl JvmtiEnvPtr {
+    /// Returns `None` if `env` or its function table is null.
+    ///
+    /// # Safety
+    /// `env` must be null or point to a readable `jvmtiEnv`.
+    pub unsafe fn new(env: *mut jvmti::jvmtiEnv) -> Option<Self> {
+        let env = NonNull::new(env)?;
+        if (*env.as_ptr()).functions.is_null() {
+            return None;
+        }
+        Some(JvmtiEnvPtr(env))
+    }
+
+    /// The raw pointer, for FFI.
+    pub fn as_ptr(self) -> *mut jvmti::jvmtiEnv {
+        self.0.as_ptr()
+    }
 }
 
 /// A safe wrapper around the raw JVMTI Environment pointer.
 pub struct Jvmti {
     // We keep this private so the user can't mess with raw pointers directly.
     env: *mut jvmti::jvmtiEnv,
+    // `(*env).functions`, read once: the VM never swaps an environment's table.
+    functions: *const jvmti::jvmtiInterface_1_,
 }
 
 impl Jvmti {
     /// Connects to the JVM and retrieves the JVMTI environment.
-    pub fn new(vm: *mut jni::JavaVM) -> Result<Self, jni::jint> {
+    pub fn new(vm: *mut jni::JavaVM) -> Result<Self, JniError> {
         if vm.is_null() {
-            return Err(jni::JNI_ERR);
+            return Err(JniError::Err);
         }
 
         let mut env_ptr: *mut std::ffi::c_void = ptr::null_mut();
 
         unsafe {
             if (*vm).is_null() {
-                return Err(jni::JNI_ERR);
+                return Err(JniError::Err);
             }
 
             // Access GetEnv directly from the vtable
@@ -132,17 +2093,12 @@ impl Jvmti {
             let res = get_env_fn(vm, &mut env_ptr, jvmti::JVMTI_VERSION_1_2);
 
             if res != jni::JNI_OK {
-                return Err(res);
+                return Err(JniError::from_raw(res));
             }
         }
 
-        if env_ptr.is_null() {
-            return Err(jni::JNI_ERR);
-        }
-
-        Ok(Jvmti {
-            env: env_ptr as *mut jvmti::jvmtiEnv,
-        })
+        // SAFETY: `GetEnv` succeeded, so the pointer is null or an environment.
+        unsafe { Jvmti::try_from_raw(env_ptr as *mut jvmti::jvmtiEnv) }
     }
 
     /// Create a Jvmti wrapper from a raw jvmtiEnv pointer
@@ -150,7 +2106,35 @@ impl Jvmti {
     /// # Safety
     /// The caller must ensure the pointer is valid for the duration of use.
     pub unsafe fn from_raw(env: *mut jvmti::jvmtiEnv) -> Self {
-        Jvmti { env }
+        let functions = if env.is_null() { ptr::null() } else { (*env).functions };
+        Jvmti { env, functions }
+    }
+
+    /// Like [`Jvmti::from_raw`], but fails with `JniError::Err` if `env` or
+    /// its function table is null.
+    ///
+    /// # Safety
+    /// `env` must be null or valid for the duration of use.
+    pub unsafe fn try_from_raw(env: *mut jvmti::jvmtiEnv) -> Result<Self, JniError> {
+        JvmtiEnvPtr::new(env).map(Jvmti::from_env_ptr).ok_or(JniError::Err)
+    }
+
+    /// Wraps an already validated environment.
+    pub fn from_env_ptr(env: JvmtiEnvPtr) -> Self {
+        // SAFETY: `JvmtiEnvPtr::new` checked the environment and its table.
+        unsafe { Jvmti::from_raw(env.as_ptr()) }
+    }
+
+    /// Creates a new JVMTI environment that is disposed when the guard drops.
+    ///
+    /// Every `GetEnv` call yields a separate environment with its own
+    /// capabilities, callbacks, and event settings. Use this for a secondary
+    /// environment (for example, one holding expensive capabilities only for a
+    /// short time). Do not use it for the primary agent environment: disposing
+    /// that one while callbacks are installed silently stops event delivery.
+    /// [`Jvmti::new`] and [`Jvmti::from_raw`] never dispose.
+    pub fn new_owned(vm: *mut jni::JavaVM) -> Result<DisposeGuard, JniError> {
+        Ok(DisposeGuard { jvmti: Jvmti::new(vm)? })
     }
 
     /// Get the raw jvmtiEnv pointer
@@ -158,11 +2142,37 @@ impl Jvmti {
         self.env
     }
 
+    /// Returns the environment whose event callbacks were installed, if any.
+    ///
+    /// Lets callbacks without a `jvmtiEnv` argument (or code far from
+    /// `on_load`) reach JVMTI without threading the wrapper through agent state.
+    /// JVMTI environments may be used from any thread.
+    ///
+    /// Only the first environment to install callbacks is recorded, for the
+    /// whole process; a later environment, including one from
+    /// [`new_owned`](Self::new_owned), does not replace it. Disposing the
+    /// recorded environment clears it again.
+    ///
+    /// ```rust,ignore
+    /// fn object_free(&self, tag: jni::jlong) {
+    ///     if let Some(jvmti) = Jvmti::current() {
+    ///         let _ = jvmti.get_time();
+    ///     }
+    /// }
+    /// ```
+    pub fn current() -> Option<Jvmti> {
+        match crate::GLOBAL_JVMTI.load(Ordering::Acquire) {
+            0 => None,
+            // SAFETY: only live environments that installed callbacks are recorded.
+            env => Some(unsafe { Jvmti::from_raw(env as *mut jvmti::jvmtiEnv) }),
+        }
+    }
+
     pub fn get_capabilities(&self) -> Result<jvmti::jvmtiCapabilities, jvmti::jvmtiError> {
         let mut caps = jvmti::jvmtiCapabilities::default();
 
         unsafe {
-            let get_caps_fn = (*(*self.env).functions).GetCapabilities.unwrap();
+            let get_caps_fn = (*self.functions).GetCapabilities.unwrap();
             let err = get_caps_fn(self.env, &mut caps);
 
             if err != jvmti::jvmtiError::NONE {
@@ -178,19 +2188,37 @@ impl Jvmti {
             // 1. Retrieve the function pointer from the VTable (Index 142)
             // If this panics, it means AddCapabilities is null (unlikely on a valid JVM)
             // or jvmti.rs has the wrong type definition (missing Option).
-            let add_caps_fn = (*(*self.env).functions).AddCapabilities.unwrap();
+            let add_caps_fn = (*self.functions).AddCapabilities.unwrap();
 
             // 2. Call the C function
             let err = add_caps_fn(self.env, new_caps);
 
             // 3. Check for success
             if err != jvmti::jvmtiError::NONE {
+                crate::log(LogLevel::Warn, format_args!("AddCapabilities({}) failed: {err}", new_caps.enabled_names().join(", ")));
                 return Err(err);
             }
         }
         Ok(())
     }
 
+    /// Adds `caps` after checking them against `GetPotentialCapabilities`.
+    ///
+    /// If the VM cannot provide some of the requested capabilities, nothing is
+    /// added and the error names them, e.g.
+    /// `can_generate_sampled_object_alloc_events not available on this JVM`.
+    pub fn add_capabilities_checked(&self, caps: &jvmti::jvmtiCapabilities) -> Result<(), CapabilityError> {
+        let potential = self.get_potential_capabilities()?;
+        let missing = caps.difference(&potential);
+        if !missing.is_empty() {
+            let names = missing.enabled_names();
+            crate::log(LogLevel::Warn, format_args!("capabilities not available on this JVM: {}", names.join(", ")));
+            return Err(CapabilityError::Unavailable(names));
+        }
+        self.add_capabilities(caps)?;
+        Ok(())
+    }
+
     /// Convenience helper to build and add capabilities in one step.
     pub fn add_capabilities_with<F>(&self, f: F) -> Result<jvmti::jvmtiCapabilities, jvmti::jvmtiError>
     where
@@ -232,7 +2260,7 @@ impl Jvmti {
     
     pub fn set_event_callbacks(&self, callbacks: jvmti::jvmtiEventCallbacks) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_callbacks_fn = (*(*self.env).functions).SetEventCallbacks.unwrap();
+            let set_callbacks_fn = (*self.functions).SetEventCallbacks.unwrap();
             let size = std::mem::size_of::<jvmti::jvmtiEventCallbacks>() as i32;
 
             let err = set_callbacks_fn(self.env, &callbacks, size);
@@ -240,25 +2268,109 @@ impl Jvmti {
                 return Err(err);
             }
         }
+        let _ = crate::GLOBAL_JVMTI.compare_exchange(0, self.env as usize, Ordering::AcqRel, Ordering::Acquire);
+        crate::record_installed_callbacks(self.env as usize, Some(callbacks));
         Ok(())
     }
 
+    /// Returns true if the callbacks this environment installed through
+    /// [`set_event_callbacks`](Self::set_event_callbacks) route
+    /// `ClassFileLoadHook` to the crate's trampoline.
+    fn class_file_load_hook_wired(&self) -> bool {
+        let trampoline = crate::get_default_callbacks().ClassFileLoadHook.map(|hook| hook as usize);
+        crate::installed_callbacks(self.env as usize)
+            .is_some_and(|callbacks| callbacks.ClassFileLoadHook.map(|hook| hook as usize) == trampoline)
+    }
+
     /// Wire the default Rust agent trampolines from [`crate::get_default_callbacks`].
     pub fn set_default_agent_callbacks(&self) -> Result<(), jvmti::jvmtiError> {
         self.set_event_callbacks(crate::get_default_callbacks())
     }
 
+    /// Wire only the agent trampolines for `events`; see [`crate::get_callbacks_for`].
+    pub fn set_agent_callbacks_for(&self, events: &[Event]) -> Result<(), jvmti::jvmtiError> {
+        self.set_event_callbacks(crate::get_callbacks_for(events))
+    }
+
+    /// Installs `registry` as the process-wide class transformer registry and
+    /// enables `ClassFileLoadHook` for all threads.
+    ///
+    /// The crate's `ClassFileLoadHook` trampoline runs the matching
+    /// transformers after the agent's own `class_file_load_hook` and
+    /// `transform_class`, on whatever bytes those left. If the callbacks this
+    /// environment installed through
+    /// [`set_event_callbacks`](Self::set_event_callbacks) leave that slot
+    /// empty, the trampoline is added to them; if none were installed through
+    /// this crate, only the trampoline is wired. No [`Agent`](crate::Agent)
+    /// needs to be registered.
+    ///
+    /// Loading classes needs no capability; transforming classes again on
+    /// retransformation needs `can_retransform_classes`. Returns `DUPLICATE`
+    /// if a registry is already installed and `NOT_AVAILABLE` if another
+    /// `ClassFileLoadHook` callback is installed. The registry is claimed
+    /// before the hook is wired and enabled, so concurrent calls cannot both
+    /// succeed, and released again if either step fails, so a failed call can
+    /// be retried.
+    pub fn install_transformers(&self, registry: ClassTransformerRegistry) -> Result<(), jvmti::jvmtiError> {
+        crate::set_class_transformers(registry).map_err(|_| jvmti::jvmtiError::DUPLICATE)?;
+        let result = self
+            .wire_class_file_load_hook()
+            .and_then(|()| self.enable(Event::ClassFileLoadHook, ptr::null_mut()));
+        if result.is_err() {
+            crate::take_class_transformers();
+        }
+        result
+    }
+
+    /// Removes the registry installed by
+    /// [`install_transformers`](Self::install_transformers), so a new one can
+    /// be installed.
+    ///
+    /// Hooks already running finish with the old registry. Unless an
+    /// [`Agent`](crate::Agent) is registered, whose own handlers may still
+    /// need it, `ClassFileLoadHook` is disabled for all threads again; the
+    /// registry is removed even if that fails. Returns `NOT_FOUND` if no
+    /// registry is installed.
+    pub fn uninstall_transformers(&self) -> Result<(), jvmti::jvmtiError> {
+        if crate::take_class_transformers().is_none() {
+            return Err(jvmti::jvmtiError::NOT_FOUND);
+        }
+        if crate::GLOBAL_AGENT.get().is_none() {
+            self.disable(Event::ClassFileLoadHook, ptr::null_mut())?;
+        }
+        Ok(())
+    }
+
+    /// Routes `ClassFileLoadHook` to the crate's trampoline, adding it to the
+    /// callbacks this environment installed if they leave the slot empty.
+    fn wire_class_file_load_hook(&self) -> Result<(), jvmti::jvmtiError> {
+        if self.class_file_load_hook_wired() {
+            return Ok(());
+        }
+        let mut callbacks = crate::installed_callbacks(self.env as usize).unwrap_or_default();
+        if callbacks.ClassFileLoadHook.is_some() {
+            return Err(jvmti::jvmtiError::NOT_AVAILABLE);
+        }
+        callbacks.ClassFileLoadHook = crate::get_default_callbacks().ClassFileLoadHook;
+        self.set_event_callbacks(callbacks)
+    }
+
     pub fn set_event_notification_mode(&self, enable: bool, event_type: u32, thread: jni::jthread) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_mode_fn = (*(*self.env).functions).SetEventNotificationMode.unwrap(); // Index 1
+            let set_mode_fn = (*self.functions).SetEventNotificationMode.unwrap(); // Index 1
             let mode = if enable { 1 } else { 0 }; // JVMTI_ENABLE = 1, DISABLE = 0
 
             // thread can be null (all threads)
             let err = set_mode_fn(self.env, mode, event_type, thread);
 
+            let action = if enable { "enable" } else { "disable" };
+            let name = Event::from_raw(event_type).map_or("<unknown event>", Event::name);
+            let target = if thread.is_null() { "all threads" } else { "one thread" };
             if err != jvmti::jvmtiError::NONE {
+                crate::log(LogLevel::Warn, format_args!("cannot {action} {name} ({event_type}) for {target}: {err}"));
                 return Err(err);
             }
+            crate::log(LogLevel::Debug, format_args!("{action}d {name} ({event_type}) for {target}"));
         }
         Ok(())
     }
@@ -273,18 +2385,65 @@ impl Jvmti {
         self.set_event_notification_mode(false, event_type, thread)
     }
 
+    /// Enable an [`Event`] for a specific thread (or all threads with null).
+    pub fn enable(&self, event: Event, thread: jni::jthread) -> Result<(), jvmti::jvmtiError> {
+        self.set_event_notification_mode(true, event.to_raw(), thread)
+    }
+
+    /// Disable an [`Event`] for a specific thread (or all threads with null).
+    pub fn disable(&self, event: Event, thread: jni::jthread) -> Result<(), jvmti::jvmtiError> {
+        self.set_event_notification_mode(false, event.to_raw(), thread)
+    }
+
+    /// Adds the capability `event` needs (if not already possessed), then enables it.
+    ///
+    /// Avoids the silent "callback never fires" failure of enabling an event
+    /// without its capability. Most capabilities can only be added during
+    /// `OnLoad`; later this may fail with `NOT_AVAILABLE`.
+    pub fn enable_with_capability(&self, event: Event, thread: jni::jthread) -> Result<(), jvmti::jvmtiError> {
+        let required = event.required_capabilities();
+        if !required.is_empty() && !self.get_capabilities()?.contains(&required) {
+            self.add_capabilities(&required)?;
+        }
+        self.enable(event, thread)
+    }
+
     /// Enable multiple JVMTI events for all threads.
-    pub fn enable_events_global(&self, events: &[u32]) -> Result<(), jvmti::jvmtiError> {
+    ///
+    /// Accepts either [`Event`] values or raw `JVMTI_EVENT_*` constants.
+    pub fn enable_events_global<E: Copy + Into<u32>>(&self, events: &[E]) -> Result<(), jvmti::jvmtiError> {
         for &event_type in events {
-            self.enable_event(event_type, ptr::null_mut())?;
+            self.enable_event(event_type.into(), ptr::null_mut())?;
         }
         Ok(())
     }
 
     /// Disable multiple JVMTI events for all threads.
-    pub fn disable_events_global(&self, events: &[u32]) -> Result<(), jvmti::jvmtiError> {
+    ///
+    /// Accepts either [`Event`] values or raw `JVMTI_EVENT_*` constants.
+    pub fn disable_events_global<E: Copy + Into<u32>>(&self, events: &[E]) -> Result<(), jvmti::jvmtiError> {
         for &event_type in events {
-            self.disable_event(event_type, ptr::null_mut())?;
+            self.disable_event(event_type.into(), ptr::null_mut())?;
+        }
+        Ok(())
+    }
+
+    /// Enables or disables `events` for `thread`, or for all threads with `None`.
+    ///
+    /// Stops at the first event the VM rejects. The error names that event
+    /// and lists the ones already switched, which are left as they are:
+    ///
+    /// ```rust,ignore
+    /// if let Err(err) = jvmti.set_events(true, &[Event::MethodEntry, Event::MethodExit], Some(thread)) {
+    ///     let _ = jvmti.set_events(false, &err.toggled, Some(thread));
+    /// }
+    /// ```
+    pub fn set_events(&self, enable: bool, events: &[Event], thread: Option<jni::jthread>) -> Result<(), SetEventsError> {
+        let thread = thread.unwrap_or(ptr::null_mut());
+        for (index, &event) in events.iter().enumerate() {
+            if let Err(error) = self.set_event_notification_mode(enable, event.to_raw(), thread) {
+                return Err(SetEventsError { failed: event, error, toggled: events[..index].to_vec() });
+            }
         }
         Ok(())
     }
@@ -315,6 +2474,13 @@ impl Jvmti {
         self.enable_events_global(&[jvmti::JVMTI_EVENT_SAMPLED_OBJECT_ALLOC])
     }
 
+    /// Enable `DataDumpRequest` events (sent on `kill -QUIT` / Ctrl-Break).
+    ///
+    /// No capability is required for this event.
+    pub fn enable_data_dump_request_events(&self) -> Result<(), jvmti::jvmtiError> {
+        self.enable_events_global(&[jvmti::JVMTI_EVENT_DATA_DUMP_REQUEST])
+    }
+
     /// Enable VM init and VM death events for all threads.
     pub fn enable_vm_lifecycle_events(&self) -> Result<(), jvmti::jvmtiError> {
         self.enable_events_global(&[
@@ -369,7 +2535,7 @@ impl Jvmti {
         let mut modules_ptr: *mut jni::jobject = ptr::null_mut();
 
         unsafe {
-            let get_all_modules_fn = (*(*self.env).functions).GetAllModules.unwrap();
+            let get_all_modules_fn = fn_or_unavailable((*self.functions).GetAllModules)?;
             let err = get_all_modules_fn(self.env, &mut module_count, &mut modules_ptr);
 
             if err != jvmti::jvmtiError::NONE {
@@ -385,12 +2551,17 @@ impl Jvmti {
         }
     }
 
+    /// Returns all live platform threads.
+    ///
+    /// Virtual threads are never included; a mounted virtual thread shows up as
+    /// its carrier. Use [`partition_virtual_threads`](Self::partition_virtual_threads)
+    /// to split thread lists obtained elsewhere (e.g. from `VirtualThreadStart`).
     pub fn get_all_threads(&self) -> Result<Vec<jni::jthread>, jvmti::jvmtiError> {
         let mut threads_count: jni::jint = 0;
         let mut threads_ptr: *mut jni::jthread = ptr::null_mut();
 
         unsafe {
-            let get_all_threads_fn = (*(*self.env).functions).GetAllThreads.unwrap();
+            let get_all_threads_fn = (*self.functions).GetAllThreads.unwrap();
             let err = get_all_threads_fn(self.env, &mut threads_count, &mut threads_ptr);
 
             if err != jvmti::jvmtiError::NONE {
@@ -406,11 +2577,28 @@ impl Jvmti {
         }
     }
 
+    /// Splits `threads` into `(platform, virtual)` using JNI `IsVirtualThread`.
+    ///
+    /// JVMTI thread functions (state, stack traces, suspend/resume, locals)
+    /// only accept virtual threads once `can_support_virtual_threads` has been
+    /// added; without it they return `UNSUPPORTED_OPERATION` for them.
+    pub fn partition_virtual_threads(
+        &self,
+        jni: &JniEnv,
+        threads: &[jni::jthread],
+    ) -> (Vec<jni::jthread>, Vec<jni::jthread>) {
+        threads.iter().copied().partition(|&thread| !jni.is_virtual_thread(thread))
+    }
+
+    /// Returns name, priority, daemon status, group, and context loader of `thread`.
+    ///
+    /// A null `thread` means the current thread. The group and loader are
+    /// local references; see [`ThreadInfo`].
     pub fn get_thread_info(&self, thread: jni::jthread) -> Result<ThreadInfo, jvmti::jvmtiError> {
         let mut info = jvmti::jvmtiThreadInfo::default();
 
         unsafe {
-            let get_thread_info_fn = (*(*self.env).functions).GetThreadInfo.unwrap();
+            let get_thread_info_fn = (*self.functions).GetThreadInfo.unwrap();
             let err = get_thread_info_fn(self.env, thread, &mut info);
 
             if err != jvmti::jvmtiError::NONE {
@@ -432,11 +2620,28 @@ impl Jvmti {
         })
     }
 
+    /// [`get_thread_info`](Self::get_thread_info) for the calling thread.
+    ///
+    /// Uses `GetThreadInfo`'s null-means-current form, so no thread reference
+    /// is created.
+    pub fn current_thread_info(&self) -> Result<ThreadInfo, jvmti::jvmtiError> {
+        self.get_thread_info(ptr::null_mut())
+    }
+
+    /// The name of `thread` (null for the current thread), as for logging.
+    ///
+    /// `None` if the VM reports no name. Like [`get_thread_info`](Self::get_thread_info),
+    /// this creates group and loader local references, which are released
+    /// when the current native frame returns.
+    pub fn thread_name(&self, thread: jni::jthread) -> Result<Option<String>, jvmti::jvmtiError> {
+        self.get_thread_info(thread).map(|info| info.name)
+    }
+
     pub fn allocate(&self, size: jni::jlong) -> Result<*mut u8, jvmti::jvmtiError> {
         let mut mem_ptr: *mut u8 = ptr::null_mut();
 
         unsafe {
-            let allocate_fn = (*(*self.env).functions).Allocate.unwrap();
+            let allocate_fn = (*self.functions).Allocate.unwrap();
             let err = allocate_fn(self.env, size, &mut mem_ptr);
 
             if err != jvmti::jvmtiError::NONE {
@@ -447,15 +2652,35 @@ impl Jvmti {
         Ok(mem_ptr)
     }
 
+    /// Allocate JVMTI memory and copy `bytes` into it.
+    ///
+    /// The returned buffer is owned by the JVM side (for example as
+    /// `new_class_data` in `ClassFileLoadHook`) or must be released with
+    /// [`Jvmti::deallocate`]. Empty input returns a null pointer.
+    pub fn allocate_bytes(&self, bytes: &[u8]) -> Result<*mut u8, jvmti::jvmtiError> {
+        if bytes.is_empty() {
+            return Ok(ptr::null_mut());
+        }
+        if bytes.len() > jni::jint::MAX as usize {
+            return Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT);
+        }
+        let mem = self.allocate(bytes.len() as jni::jlong)?;
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), mem, bytes.len());
+        }
+        Ok(mem)
+    }
+
     pub fn deallocate(&self, mem: *mut u8) -> Result<(), jvmti::jvmtiError> {
         if mem.is_null() {
             return Ok(());
         }
         unsafe {
-            let deallocate_fn = (*(*self.env).functions).Deallocate.unwrap();
+            let deallocate_fn = (*self.functions).Deallocate.unwrap();
             let err = deallocate_fn(self.env, mem);
 
             if err != jvmti::jvmtiError::NONE {
+                crate::log(LogLevel::Warn, format_args!("Deallocate({mem:p}) failed: {err}"));
                 return Err(err);
             }
         }
@@ -466,7 +2691,7 @@ impl Jvmti {
         let mut thread_state: jni::jint = 0;
 
         unsafe {
-            let get_thread_state_fn = (*(*self.env).functions).GetThreadState.unwrap();
+            let get_thread_state_fn = (*self.functions).GetThreadState.unwrap();
             let err = get_thread_state_fn(self.env, thread, &mut thread_state);
 
             if err != jvmti::jvmtiError::NONE {
@@ -477,11 +2702,17 @@ impl Jvmti {
         Ok(thread_state)
     }
 
+    /// Like [`get_thread_state`](Self::get_thread_state), but returns a decoded [`ThreadState`].
+    pub fn get_thread_state_decoded(&self, thread: jni::jthread) -> Result<ThreadState, jvmti::jvmtiError> {
+        self.get_thread_state(thread).map(ThreadState)
+    }
+
+    /// Returns the calling thread as a JNI local reference.
     pub fn get_current_thread(&self) -> Result<jni::jthread, jvmti::jvmtiError> {
         let mut thread: jni::jthread = ptr::null_mut();
 
         unsafe {
-            let get_current_thread_fn = (*(*self.env).functions).GetCurrentThread.unwrap();
+            let get_current_thread_fn = (*self.functions).GetCurrentThread.unwrap();
             let err = get_current_thread_fn(self.env, &mut thread);
 
             if err != jvmti::jvmtiError::NONE {
@@ -497,16 +2728,16 @@ impl Jvmti {
         let mut gen_ptr: *mut std::os::raw::c_char = ptr::null_mut();
 
         unsafe {
-            let get_class_sig_fn = (*(*self.env).functions).GetClassSignature.unwrap();
+            let get_class_sig_fn = (*self.functions).GetClassSignature.unwrap();
             let err = get_class_sig_fn(self.env, klass, &mut sig_ptr, &mut gen_ptr);
 
             if err != jvmti::jvmtiError::NONE {
                 return Err(err);
             }
 
-            let signature = std::ffi::CStr::from_ptr(sig_ptr).to_string_lossy().into_owned();
+            let signature = mutf8::from_cstr(sig_ptr);
             let generic = if !gen_ptr.is_null() {
-                Some(std::ffi::CStr::from_ptr(gen_ptr).to_string_lossy().into_owned())
+                Some(mutf8::from_cstr(gen_ptr))
             } else {
                 None
             };
@@ -520,23 +2751,42 @@ impl Jvmti {
         }
     }
 
+    /// Returns the class name as Java code spells it: `com.example.Foo`, `int[]`.
+    ///
+    /// Converts the `GetClassSignature` result with
+    /// [`signature_to_java_name`](crate::classfile::descriptor::signature_to_java_name).
+    pub fn get_class_name(&self, klass: jni::jclass) -> Result<String, jvmti::jvmtiError> {
+        let (signature, _) = self.get_class_signature(klass)?;
+        Ok(crate::classfile::descriptor::signature_to_java_name(&signature))
+    }
+
+    /// Combines `GetMethodName` and `GetMethodModifiers` with the descriptor
+    /// parser. Returns `INTERNAL` if the VM reports a malformed descriptor.
+    pub fn get_method_signature(&self, method: jni::jmethodID) -> Result<MethodSignature, jvmti::jvmtiError> {
+        let (name, descriptor, _) = self.get_method_name(method)?;
+        let is_static = self.get_method_access_flags(method)?.is_static();
+        let (arg_types, return_type) = parse_method_descriptor(&descriptor).map_err(|_| jvmti::jvmtiError::INTERNAL)?;
+        let arg_slot_count = arg_types.iter().map(JavaType::slots).sum::<u16>() + u16::from(!is_static);
+        Ok(MethodSignature { name, descriptor, arg_types, return_type, is_static, arg_slot_count })
+    }
+
     pub fn get_method_name(&self, method: jni::jmethodID) -> Result<(String, String, Option<String>), jvmti::jvmtiError> {
         let mut name_ptr: *mut std::os::raw::c_char = ptr::null_mut();
         let mut sig_ptr: *mut std::os::raw::c_char = ptr::null_mut();
         let mut gen_ptr: *mut std::os::raw::c_char = ptr::null_mut();
 
         unsafe {
-            let get_method_name_fn = (*(*self.env).functions).GetMethodName.unwrap();
+            let get_method_name_fn = (*self.functions).GetMethodName.unwrap();
             let err = get_method_name_fn(self.env, method, &mut name_ptr, &mut sig_ptr, &mut gen_ptr);
 
             if err != jvmti::jvmtiError::NONE {
                 return Err(err);
             }
 
-            let name = std::ffi::CStr::from_ptr(name_ptr).to_string_lossy().into_owned();
-            let signature = std::ffi::CStr::from_ptr(sig_ptr).to_string_lossy().into_owned();
+            let name = mutf8::from_cstr(name_ptr);
+            let signature = mutf8::from_cstr(sig_ptr);
             let generic = if !gen_ptr.is_null() {
-                Some(std::ffi::CStr::from_ptr(gen_ptr).to_string_lossy().into_owned())
+                Some(mutf8::from_cstr(gen_ptr))
             } else {
                 None
             };
@@ -555,20 +2805,64 @@ impl Jvmti {
         let mut caps = jvmti::jvmtiCapabilities::default();
 
         unsafe {
-            let get_pot_caps_fn = (*(*self.env).functions).GetPotentialCapabilities.unwrap();
+            let get_pot_caps_fn = (*self.functions).GetPotentialCapabilities.unwrap();
             let err = get_pot_caps_fn(self.env, &mut caps);
 
             if err != jvmti::jvmtiError::NONE {
                 return Err(err);
             }
         }
-
-        Ok(caps)
+
+        Ok(caps)
+    }
+
+    /// A table of the capabilities this environment could add (`potential`),
+    /// already has (`owned`), and could still add (`available`), one name per
+    /// row in each column.
+    ///
+    /// Meant for diagnostics, e.g. printing it when an agent is loaded with a
+    /// `diag` option or when adding a capability fails. Potential
+    /// capabilities depend on the phase: many are only offered during
+    /// `OnLoad`. A column whose query fails shows the error instead.
+    pub fn capabilities_report(&self) -> String {
+        let potential = self.get_potential_capabilities();
+        let owned = self.get_capabilities();
+        let available = match (&potential, &owned) {
+            (Ok(potential), Ok(owned)) => Ok(potential.difference(owned)),
+            (Err(err), _) | (_, Err(err)) => Err(*err),
+        };
+        let column = |caps: &Result<jvmti::jvmtiCapabilities, jvmti::jvmtiError>| -> Vec<String> {
+            match caps {
+                Ok(caps) if caps.is_empty() => vec!["(none)".to_string()],
+                Ok(caps) => caps.enabled_names().into_iter().map(str::to_string).collect(),
+                Err(err) => vec![format!("<{err}>")],
+            }
+        };
+        let columns = [("potential", column(&potential)), ("owned", column(&owned)), ("available", column(&available))];
+        let widths: Vec<usize> = columns
+            .iter()
+            .map(|(header, names)| names.iter().map(String::len).chain([header.len()]).max().unwrap_or(0))
+            .collect();
+        let rows = columns.iter().map(|(_, names)| names.len()).max().unwrap_or(0);
+
+        let mut report = String::new();
+        for row in 0..=rows {
+            let mut line = String::new();
+            for ((header, names), width) in columns.iter().zip(&widths) {
+                let cell = if row == 0 { header } else { names.get(row - 1).map_or("", String::as_str) };
+                line.push_str(&format!("{cell:<width$}  "));
+            }
+            report.push_str(line.trim_end());
+            report.push('\n');
+        }
+        report
     }
 
     pub fn dispose_environment(&self) -> Result<(), jvmti::jvmtiError> {
+        let _ = crate::GLOBAL_JVMTI.compare_exchange(self.env as usize, 0, Ordering::AcqRel, Ordering::Acquire);
+        crate::record_installed_callbacks(self.env as usize, None);
         unsafe {
-            let dispose_env_fn = (*(*self.env).functions).DisposeEnvironment.unwrap();
+            let dispose_env_fn = (*self.functions).DisposeEnvironment.unwrap();
             let err = dispose_env_fn(self.env);
 
             if err != jvmti::jvmtiError::NONE {
@@ -583,7 +2877,7 @@ impl Jvmti {
         let mut classes_ptr: *mut jni::jclass = ptr::null_mut();
 
         unsafe {
-            let get_loaded_classes_fn = (*(*self.env).functions).GetLoadedClasses.unwrap();
+            let get_loaded_classes_fn = (*self.functions).GetLoadedClasses.unwrap();
             let err = get_loaded_classes_fn(self.env, &mut class_count, &mut classes_ptr);
 
             if err != jvmti::jvmtiError::NONE {
@@ -599,9 +2893,40 @@ impl Jvmti {
         }
     }
 
+    /// Returns the loaded classes whose signature (`Lcom/example/Foo;`, `[I`)
+    /// satisfies `predicate`.
+    ///
+    /// Classes whose signature cannot be read are skipped. The local
+    /// references [`Jvmti::get_loaded_classes`] returns for rejected classes
+    /// are deleted through `jni`; the kept ones are still local references,
+    /// so call this from a callback or inside a local frame.
+    pub fn get_loaded_classes_filtered(
+        &self,
+        jni: &JniEnv,
+        predicate: impl Fn(&str) -> bool,
+    ) -> Result<Vec<jni::jclass>, jvmti::jvmtiError> {
+        let mut classes = self.get_loaded_classes()?;
+        classes.retain(|&klass| {
+            let keep = self.get_class_signature(klass).is_ok_and(|(signature, _)| predicate(&signature));
+            if !keep {
+                jni.delete_local_ref(klass);
+            }
+            keep
+        });
+        Ok(classes)
+    }
+
+    /// Returns the loaded classes in `package` (`com/example` or
+    /// `com.example`) and its subpackages; array classes are not included.
+    /// See [`get_loaded_classes_filtered`](Self::get_loaded_classes_filtered).
+    pub fn classes_in_package(&self, jni: &JniEnv, package: &str) -> Result<Vec<jni::jclass>, jvmti::jvmtiError> {
+        let prefix = format!("L{}/", package.trim_end_matches(['/', '.']).replace('.', "/"));
+        self.get_loaded_classes_filtered(jni, |signature| signature.starts_with(&prefix))
+    }
+
     pub fn redefine_classes(&self, class_definitions: &[jvmti::jvmtiClassDefinition]) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let redefine_classes_fn = (*(*self.env).functions).RedefineClasses.unwrap();
+            let redefine_classes_fn = (*self.functions).RedefineClasses.unwrap();
             let err = redefine_classes_fn(self.env, class_definitions.len() as jni::jint, class_definitions.as_ptr());
 
             if err != jvmti::jvmtiError::NONE {
@@ -611,9 +2936,42 @@ impl Jvmti {
         Ok(())
     }
 
+    /// Replaces the definition of `klass` with `new_bytes` (a complete class file).
+    ///
+    /// See [`redefine_classes_from`](Self::redefine_classes_from).
+    pub fn redefine_class(&self, klass: jni::jclass, new_bytes: &[u8]) -> Result<(), jvmti::jvmtiError> {
+        self.redefine_classes_from(&[(klass, new_bytes)])
+    }
+
+    /// Redefines several classes at once from class file bytes, e.g. the
+    /// output of [`ClassFile::to_bytes`](crate::classfile::ClassFile::to_bytes).
+    ///
+    /// The classes are redefined atomically: if any definition is rejected,
+    /// none is applied. Returns `MUST_POSSESS_CAPABILITY` unless
+    /// `can_redefine_classes` has been added, and `ILLEGAL_ARGUMENT` for an
+    /// empty or oversized class file. Schema changes HotSpot refuses come back
+    /// as the `UNSUPPORTED_REDEFINITION_*` errors; see
+    /// [`jvmtiError::is_unsupported_redefinition`](jvmti::jvmtiError::is_unsupported_redefinition).
+    pub fn redefine_classes_from(&self, classes: &[(jni::jclass, &[u8])]) -> Result<(), jvmti::jvmtiError> {
+        self.require_live_phase("redefine_classes_from")?;
+        if !self.get_capabilities()?.can_redefine_classes() {
+            return Err(jvmti::jvmtiError::MUST_POSSESS_CAPABILITY);
+        }
+        let mut definitions = Vec::with_capacity(classes.len());
+        for (klass, bytes) in classes {
+            let class_byte_count = jni::jint::try_from(bytes.len()).map_err(|_| jvmti::jvmtiError::ILLEGAL_ARGUMENT)?;
+            if class_byte_count == 0 {
+                return Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT);
+            }
+            definitions.push(jvmti::jvmtiClassDefinition { klass: *klass, class_byte_count, class_bytes: bytes.as_ptr() });
+        }
+        // `definitions` borrows from `classes`, which outlives the call.
+        self.redefine_classes(&definitions)
+    }
+
     pub fn suspend_thread(&self, thread: jni::jthread) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let suspend_fn = (*(*self.env).functions).SuspendThread.unwrap();
+            let suspend_fn = (*self.functions).SuspendThread.unwrap();
             let err = suspend_fn(self.env, thread);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -622,7 +2980,7 @@ impl Jvmti {
 
     pub fn resume_thread(&self, thread: jni::jthread) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let resume_fn = (*(*self.env).functions).ResumeThread.unwrap();
+            let resume_fn = (*self.functions).ResumeThread.unwrap();
             let err = resume_fn(self.env, thread);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -631,7 +2989,7 @@ impl Jvmti {
 
     pub fn stop_thread(&self, thread: jni::jthread, exception: jni::jobject) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let stop_fn = (*(*self.env).functions).StopThread.unwrap();
+            let stop_fn = (*self.functions).StopThread.unwrap();
             let err = stop_fn(self.env, thread, exception);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -640,7 +2998,7 @@ impl Jvmti {
 
     pub fn interrupt_thread(&self, thread: jni::jthread) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let interrupt_fn = (*(*self.env).functions).InterruptThread.unwrap();
+            let interrupt_fn = (*self.functions).InterruptThread.unwrap();
             let err = interrupt_fn(self.env, thread);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -649,17 +3007,74 @@ impl Jvmti {
 
     pub fn run_agent_thread(&self, thread: jni::jthread, proc: jvmti::jvmtiStartFunction, arg: *const std::os::raw::c_void, priority: jni::jint) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let run_fn = (*(*self.env).functions).RunAgentThread.unwrap();
+            let run_fn = (*self.functions).RunAgentThread.unwrap();
             let err = run_fn(self.env, thread, proc, arg, priority);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
+    /// Starts a JVMTI agent thread that runs `f`.
+    ///
+    /// Builds a `java.lang.Thread` named `name` in the system thread group
+    /// through `jni`, then hands it to `RunAgentThread` with a start function
+    /// that runs the closure. The thread is a daemon, is already attached (use
+    /// [`JniEnv::get_current_env`] inside `f` to reach JNI), and ends when `f`
+    /// returns. `priority` is one of the `JVMTI_THREAD_*_PRIORITY` values. A
+    /// panic in `f` is handled according to [`crate::PanicPolicy`].
+    ///
+    /// Must be called in the live phase (for example from `VMInit`). If the
+    /// thread object cannot be built, any pending Java exception is cleared
+    /// and `INTERNAL` is returned.
+    pub fn spawn_agent_thread<F>(&self, jni_env: &JniEnv, name: &str, priority: jni::jint, f: F) -> Result<(), jvmti::jvmtiError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let groups = self.get_top_thread_groups()?;
+        let group = groups.first().copied().ok_or(jvmti::jvmtiError::INTERNAL)?;
+        let thread = jni_env
+            .with_local_frame_returning(8, |jni_env| {
+                let Some(thread_class) = jni_env.find_class("java/lang/Thread") else {
+                    return ptr::null_mut();
+                };
+                let Some(constructor) =
+                    jni_env.get_method_id(thread_class, "<init>", "(Ljava/lang/ThreadGroup;Ljava/lang/String;)V")
+                else {
+                    return ptr::null_mut();
+                };
+                let Some(thread_name) = jni_env.new_string_utf(name) else {
+                    return ptr::null_mut();
+                };
+                jni_env
+                    .new_object(thread_class, constructor, &[jni::jvalue { l: group }, jni::jvalue { l: thread_name }])
+                    .unwrap_or(ptr::null_mut())
+            })
+            .unwrap_or(ptr::null_mut());
+        for &group in &groups {
+            jni_env.delete_local_ref(group);
+        }
+        if thread.is_null() {
+            if jni_env.exception_check() {
+                jni_env.exception_clear();
+            }
+            return Err(jvmti::jvmtiError::INTERNAL);
+        }
+
+        let task: Box<AgentThreadTask> = Box::new(Box::new(f));
+        let arg = Box::into_raw(task);
+        let result = self.run_agent_thread(thread, agent_thread_trampoline, arg as *const std::os::raw::c_void, priority);
+        if result.is_err() {
+            // SAFETY: the VM rejected the thread, so the trampoline never took ownership.
+            drop(unsafe { Box::from_raw(arg) });
+        }
+        jni_env.delete_local_ref(thread);
+        result
+    }
+
     pub fn suspend_thread_list(&self, request_list: &[jni::jthread]) -> Result<Vec<jvmti::jvmtiError>, jvmti::jvmtiError> {
         let mut results = vec![jvmti::jvmtiError::NONE; request_list.len()];
         unsafe {
-            let suspend_list_fn = (*(*self.env).functions).SuspendThreadList.unwrap();
+            let suspend_list_fn = (*self.functions).SuspendThreadList.unwrap();
             let err = suspend_list_fn(self.env, request_list.len() as jni::jint, request_list.as_ptr(), results.as_mut_ptr());
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -669,7 +3084,7 @@ impl Jvmti {
     pub fn resume_thread_list(&self, request_list: &[jni::jthread]) -> Result<Vec<jvmti::jvmtiError>, jvmti::jvmtiError> {
         let mut results = vec![jvmti::jvmtiError::NONE; request_list.len()];
         unsafe {
-            let resume_list_fn = (*(*self.env).functions).ResumeThreadList.unwrap();
+            let resume_list_fn = (*self.functions).ResumeThreadList.unwrap();
             let err = resume_list_fn(self.env, request_list.len() as jni::jint, request_list.as_ptr(), results.as_mut_ptr());
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -680,7 +3095,7 @@ impl Jvmti {
         let mut group_count: jni::jint = 0;
         let mut groups_ptr: *mut jni::jobject = ptr::null_mut();
         unsafe {
-            let get_groups_fn = (*(*self.env).functions).GetTopThreadGroups.unwrap();
+            let get_groups_fn = (*self.functions).GetTopThreadGroups.unwrap();
             let err = get_groups_fn(self.env, &mut group_count, &mut groups_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let groups = jvmti_array_to_vec(groups_ptr, group_count)?;
@@ -694,7 +3109,7 @@ impl Jvmti {
     pub fn get_thread_group_info(&self, group: jni::jobject) -> Result<ThreadGroupInfo, jvmti::jvmtiError> {
         let mut info = jvmti::jvmtiThreadGroupInfo::default();
         unsafe {
-            let get_info_fn = (*(*self.env).functions).GetThreadGroupInfo.unwrap();
+            let get_info_fn = (*self.functions).GetThreadGroupInfo.unwrap();
             let err = get_info_fn(self.env, group, &mut info);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -716,7 +3131,7 @@ impl Jvmti {
         let mut group_count: jni::jint = 0;
         let mut groups_ptr: *mut jni::jobject = ptr::null_mut();
         unsafe {
-            let get_children_fn = (*(*self.env).functions).GetThreadGroupChildren.unwrap();
+            let get_children_fn = (*self.functions).GetThreadGroupChildren.unwrap();
             let err = get_children_fn(self.env, group, &mut thread_count, &mut threads_ptr, &mut group_count, &mut groups_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let threads = jvmti_array_to_vec(threads_ptr, thread_count)?;
@@ -735,7 +3150,7 @@ impl Jvmti {
         let mut monitor_count: jni::jint = 0;
         let mut monitors_ptr: *mut jni::jobject = ptr::null_mut();
         unsafe {
-            let get_monitors_fn = (*(*self.env).functions).GetOwnedMonitorInfo.unwrap();
+            let get_monitors_fn = (*self.functions).GetOwnedMonitorInfo.unwrap();
             let err = get_monitors_fn(self.env, thread, &mut monitor_count, &mut monitors_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let monitors = jvmti_array_to_vec(monitors_ptr, monitor_count)?;
@@ -749,7 +3164,7 @@ impl Jvmti {
     pub fn get_current_contended_monitor(&self, thread: jni::jthread) -> Result<jni::jobject, jvmti::jvmtiError> {
         let mut monitor: jni::jobject = ptr::null_mut();
         unsafe {
-            let get_monitor_fn = (*(*self.env).functions).GetCurrentContendedMonitor.unwrap();
+            let get_monitor_fn = (*self.functions).GetCurrentContendedMonitor.unwrap();
             let err = get_monitor_fn(self.env, thread, &mut monitor);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(monitor)
@@ -757,10 +3172,10 @@ impl Jvmti {
     }
 
     pub fn create_raw_monitor(&self, name: &str) -> Result<jvmti::jrawMonitorID, jvmti::jvmtiError> {
-        let c_name = CString::new(name).map_err(|_| jvmti::jvmtiError::ILLEGAL_ARGUMENT)?;
+        let c_name = mutf8::to_cstring(name);
         let mut monitor: jvmti::jrawMonitorID = ptr::null_mut();
         unsafe {
-            let create_fn = (*(*self.env).functions).CreateRawMonitor.unwrap();
+            let create_fn = (*self.functions).CreateRawMonitor.unwrap();
             let err = create_fn(self.env, c_name.as_ptr(), &mut monitor);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(monitor)
@@ -769,7 +3184,7 @@ impl Jvmti {
 
     pub fn destroy_raw_monitor(&self, monitor: jvmti::jrawMonitorID) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let destroy_fn = (*(*self.env).functions).DestroyRawMonitor.unwrap();
+            let destroy_fn = (*self.functions).DestroyRawMonitor.unwrap();
             let err = destroy_fn(self.env, monitor);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -778,7 +3193,7 @@ impl Jvmti {
 
     pub fn raw_monitor_enter(&self, monitor: jvmti::jrawMonitorID) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let enter_fn = (*(*self.env).functions).RawMonitorEnter.unwrap();
+            let enter_fn = (*self.functions).RawMonitorEnter.unwrap();
             let err = enter_fn(self.env, monitor);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -787,7 +3202,7 @@ impl Jvmti {
 
     pub fn raw_monitor_exit(&self, monitor: jvmti::jrawMonitorID) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let exit_fn = (*(*self.env).functions).RawMonitorExit.unwrap();
+            let exit_fn = (*self.functions).RawMonitorExit.unwrap();
             let err = exit_fn(self.env, monitor);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -796,7 +3211,7 @@ impl Jvmti {
 
     pub fn raw_monitor_wait(&self, monitor: jvmti::jrawMonitorID, millis: jni::jlong) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let wait_fn = (*(*self.env).functions).RawMonitorWait.unwrap();
+            let wait_fn = (*self.functions).RawMonitorWait.unwrap();
             let err = wait_fn(self.env, monitor, millis);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -805,7 +3220,7 @@ impl Jvmti {
 
     pub fn raw_monitor_notify(&self, monitor: jvmti::jrawMonitorID) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let notify_fn = (*(*self.env).functions).RawMonitorNotify.unwrap();
+            let notify_fn = (*self.functions).RawMonitorNotify.unwrap();
             let err = notify_fn(self.env, monitor);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -814,7 +3229,7 @@ impl Jvmti {
 
     pub fn raw_monitor_notify_all(&self, monitor: jvmti::jrawMonitorID) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let notify_all_fn = (*(*self.env).functions).RawMonitorNotifyAll.unwrap();
+            let notify_all_fn = (*self.functions).RawMonitorNotifyAll.unwrap();
             let err = notify_all_fn(self.env, monitor);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -824,7 +3239,7 @@ impl Jvmti {
     pub fn get_frame_count(&self, thread: jni::jthread) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut count: jni::jint = 0;
         unsafe {
-            let get_count_fn = (*(*self.env).functions).GetFrameCount.unwrap();
+            let get_count_fn = (*self.functions).GetFrameCount.unwrap();
             let err = get_count_fn(self.env, thread, &mut count);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(count)
@@ -835,7 +3250,7 @@ impl Jvmti {
         let mut method: jni::jmethodID = ptr::null_mut();
         let mut location: jvmti::jlocation = 0;
         unsafe {
-            let get_loc_fn = (*(*self.env).functions).GetFrameLocation.unwrap();
+            let get_loc_fn = (*self.functions).GetFrameLocation.unwrap();
             let err = get_loc_fn(self.env, thread, depth, &mut method, &mut location);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok((method, location))
@@ -844,7 +3259,7 @@ impl Jvmti {
 
     pub fn notify_frame_pop(&self, thread: jni::jthread, depth: jni::jint) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let notify_fn = (*(*self.env).functions).NotifyFramePop.unwrap();
+            let notify_fn = (*self.functions).NotifyFramePop.unwrap();
             let err = notify_fn(self.env, thread, depth);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -854,17 +3269,54 @@ impl Jvmti {
     pub fn get_local_object(&self, thread: jni::jthread, depth: jni::jint, slot: jni::jint) -> Result<jni::jobject, jvmti::jvmtiError> {
         let mut value: jni::jobject = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetLocalObject.unwrap();
+            let get_fn = (*self.functions).GetLocalObject.unwrap();
             let err = get_fn(self.env, thread, depth, slot, &mut value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(value)
         }
     }
 
+    /// Reads every local variable in scope at a frame, with typed values.
+    ///
+    /// Joins `GetLocalVariableTable` for the frame's method with the
+    /// `GetLocal*` call matching each variable's signature, keeping only the
+    /// entries live at the frame's current location. Results are ordered by
+    /// slot. Requires `can_access_local_variables` and a class compiled with
+    /// local variable information (`ABSENT_INFORMATION` otherwise); the first
+    /// failing read (for example `OPAQUE_FRAME` for a native frame) is returned.
+    pub fn read_locals(&self, thread: jni::jthread, depth: jni::jint) -> Result<Vec<(LocalVariableEntry, LocalValue)>, jvmti::jvmtiError> {
+        let (method, location) = self.get_frame_location(thread, depth)?;
+        let mut entries: Vec<LocalVariableEntry> = self
+            .get_local_variable_table(method)?
+            .into_iter()
+            .filter(|entry| entry.is_live_at(location))
+            .collect();
+        entries.sort_by_key(|entry| entry.slot);
+
+        let mut locals = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let slot = entry.slot;
+            let value = match entry.signature.as_deref().and_then(|sig| sig.bytes().next()) {
+                Some(b'Z') => LocalValue::Boolean(self.get_local_int(thread, depth, slot)? != 0),
+                Some(b'B') => LocalValue::Byte(self.get_local_int(thread, depth, slot)? as jni::jbyte),
+                Some(b'C') => LocalValue::Char(self.get_local_int(thread, depth, slot)? as jni::jchar),
+                Some(b'S') => LocalValue::Short(self.get_local_int(thread, depth, slot)? as jni::jshort),
+                Some(b'I') => LocalValue::Int(self.get_local_int(thread, depth, slot)?),
+                Some(b'J') => LocalValue::Long(self.get_local_long(thread, depth, slot)?),
+                Some(b'F') => LocalValue::Float(self.get_local_float(thread, depth, slot)?),
+                Some(b'D') => LocalValue::Double(self.get_local_double(thread, depth, slot)?),
+                Some(b'L' | b'[') => LocalValue::Object(self.get_local_object(thread, depth, slot)?),
+                _ => return Err(jvmti::jvmtiError::INVALID_SLOT),
+            };
+            locals.push((entry, value));
+        }
+        Ok(locals)
+    }
+
     pub fn get_local_int(&self, thread: jni::jthread, depth: jni::jint, slot: jni::jint) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut value: jni::jint = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetLocalInt.unwrap();
+            let get_fn = (*self.functions).GetLocalInt.unwrap();
             let err = get_fn(self.env, thread, depth, slot, &mut value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(value)
@@ -874,7 +3326,7 @@ impl Jvmti {
     pub fn get_local_long(&self, thread: jni::jthread, depth: jni::jint, slot: jni::jint) -> Result<jni::jlong, jvmti::jvmtiError> {
         let mut value: jni::jlong = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetLocalLong.unwrap();
+            let get_fn = (*self.functions).GetLocalLong.unwrap();
             let err = get_fn(self.env, thread, depth, slot, &mut value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(value)
@@ -884,7 +3336,7 @@ impl Jvmti {
     pub fn get_local_float(&self, thread: jni::jthread, depth: jni::jint, slot: jni::jint) -> Result<jni::jfloat, jvmti::jvmtiError> {
         let mut value: jni::jfloat = 0.0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetLocalFloat.unwrap();
+            let get_fn = (*self.functions).GetLocalFloat.unwrap();
             let err = get_fn(self.env, thread, depth, slot, &mut value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(value)
@@ -894,7 +3346,7 @@ impl Jvmti {
     pub fn get_local_double(&self, thread: jni::jthread, depth: jni::jint, slot: jni::jint) -> Result<jni::jdouble, jvmti::jvmtiError> {
         let mut value: jni::jdouble = 0.0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetLocalDouble.unwrap();
+            let get_fn = (*self.functions).GetLocalDouble.unwrap();
             let err = get_fn(self.env, thread, depth, slot, &mut value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(value)
@@ -903,7 +3355,7 @@ impl Jvmti {
 
     pub fn set_local_object(&self, thread: jni::jthread, depth: jni::jint, slot: jni::jint, value: jni::jobject) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetLocalObject.unwrap();
+            let set_fn = (*self.functions).SetLocalObject.unwrap();
             let err = set_fn(self.env, thread, depth, slot, value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -912,7 +3364,7 @@ impl Jvmti {
 
     pub fn set_local_int(&self, thread: jni::jthread, depth: jni::jint, slot: jni::jint, value: jni::jint) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetLocalInt.unwrap();
+            let set_fn = (*self.functions).SetLocalInt.unwrap();
             let err = set_fn(self.env, thread, depth, slot, value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -921,7 +3373,7 @@ impl Jvmti {
 
     pub fn set_local_long(&self, thread: jni::jthread, depth: jni::jint, slot: jni::jint, value: jni::jlong) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetLocalLong.unwrap();
+            let set_fn = (*self.functions).SetLocalLong.unwrap();
             let err = set_fn(self.env, thread, depth, slot, value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -930,7 +3382,7 @@ impl Jvmti {
 
     pub fn set_local_float(&self, thread: jni::jthread, depth: jni::jint, slot: jni::jint, value: jni::jfloat) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetLocalFloat.unwrap();
+            let set_fn = (*self.functions).SetLocalFloat.unwrap();
             let err = set_fn(self.env, thread, depth, slot, value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -939,7 +3391,7 @@ impl Jvmti {
 
     pub fn set_local_double(&self, thread: jni::jthread, depth: jni::jint, slot: jni::jint, value: jni::jdouble) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetLocalDouble.unwrap();
+            let set_fn = (*self.functions).SetLocalDouble.unwrap();
             let err = set_fn(self.env, thread, depth, slot, value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -949,7 +3401,7 @@ impl Jvmti {
     pub fn get_local_instance(&self, thread: jni::jthread, depth: jni::jint) -> Result<jni::jobject, jvmti::jvmtiError> {
         let mut value: jni::jobject = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetLocalInstance.unwrap();
+            let get_fn = (*self.functions).GetLocalInstance.unwrap();
             let err = get_fn(self.env, thread, depth, &mut value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(value)
@@ -958,16 +3410,34 @@ impl Jvmti {
 
     pub fn pop_frame(&self, thread: jni::jthread) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let pop_fn = (*(*self.env).functions).PopFrame.unwrap();
+            let pop_fn = (*self.functions).PopFrame.unwrap();
             let err = pop_fn(self.env, thread);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
+    /// Makes the current frame of `thread` return `value` (`ForceEarlyReturn*`).
+    ///
+    /// Requires `can_force_early_return`. `thread` must be suspended (or be the
+    /// current thread), and the variant must match the method's return type,
+    /// otherwise the VM returns `TYPE_MISMATCH`; native frames give `OPAQUE_FRAME`.
+    /// The return happens when the thread resumes: `finally` blocks do not
+    /// run, and monitors the method entered are released.
+    pub fn force_early_return(&self, thread: jni::jthread, value: ReturnValue) -> Result<(), jvmti::jvmtiError> {
+        match value {
+            ReturnValue::Void => self.force_early_return_void(thread),
+            ReturnValue::Int(value) => self.force_early_return_int(thread, value),
+            ReturnValue::Long(value) => self.force_early_return_long(thread, value),
+            ReturnValue::Float(value) => self.force_early_return_float(thread, value),
+            ReturnValue::Double(value) => self.force_early_return_double(thread, value),
+            ReturnValue::Object(value) => self.force_early_return_object(thread, value),
+        }
+    }
+
     pub fn force_early_return_object(&self, thread: jni::jthread, value: jni::jobject) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let force_fn = (*(*self.env).functions).ForceEarlyReturnObject.unwrap();
+            let force_fn = (*self.functions).ForceEarlyReturnObject.unwrap();
             let err = force_fn(self.env, thread, value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -976,7 +3446,7 @@ impl Jvmti {
 
     pub fn force_early_return_int(&self, thread: jni::jthread, value: jni::jint) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let force_fn = (*(*self.env).functions).ForceEarlyReturnInt.unwrap();
+            let force_fn = (*self.functions).ForceEarlyReturnInt.unwrap();
             let err = force_fn(self.env, thread, value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -985,7 +3455,7 @@ impl Jvmti {
 
     pub fn force_early_return_long(&self, thread: jni::jthread, value: jni::jlong) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let force_fn = (*(*self.env).functions).ForceEarlyReturnLong.unwrap();
+            let force_fn = (*self.functions).ForceEarlyReturnLong.unwrap();
             let err = force_fn(self.env, thread, value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -994,7 +3464,7 @@ impl Jvmti {
 
     pub fn force_early_return_float(&self, thread: jni::jthread, value: jni::jfloat) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let force_fn = (*(*self.env).functions).ForceEarlyReturnFloat.unwrap();
+            let force_fn = (*self.functions).ForceEarlyReturnFloat.unwrap();
             let err = force_fn(self.env, thread, value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1003,7 +3473,7 @@ impl Jvmti {
 
     pub fn force_early_return_double(&self, thread: jni::jthread, value: jni::jdouble) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let force_fn = (*(*self.env).functions).ForceEarlyReturnDouble.unwrap();
+            let force_fn = (*self.functions).ForceEarlyReturnDouble.unwrap();
             let err = force_fn(self.env, thread, value);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1012,7 +3482,7 @@ impl Jvmti {
 
     pub fn force_early_return_void(&self, thread: jni::jthread) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let force_fn = (*(*self.env).functions).ForceEarlyReturnVoid.unwrap();
+            let force_fn = (*self.functions).ForceEarlyReturnVoid.unwrap();
             let err = force_fn(self.env, thread);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1020,22 +3490,47 @@ impl Jvmti {
     }
 
     pub fn get_stack_trace(&self, thread: jni::jthread, start_depth: jni::jint, max_frame_count: jni::jint) -> Result<Vec<jvmti::jvmtiFrameInfo>, jvmti::jvmtiError> {
-        let mut frame_buffer = vec![jvmti::jvmtiFrameInfo::default(); max_frame_count as usize];
+        if max_frame_count < 0 {
+            return Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT);
+        }
+        let mut frame_buffer = Vec::with_capacity(max_frame_count as usize);
+        self.get_stack_trace_into(thread, start_depth, &mut frame_buffer)?;
+        Ok(frame_buffer)
+    }
+
+    /// Like [`get_stack_trace`](Self::get_stack_trace), but fills a caller-owned buffer.
+    ///
+    /// Requests up to `buf.capacity()` frames, so reserve the sampling depth
+    /// once (`Vec::with_capacity(64)`) and pass the same buffer on every
+    /// sample to avoid per-call allocation. On success `buf` holds exactly the
+    /// returned frames and the frame count is returned; on error it is empty.
+    pub fn get_stack_trace_into(
+        &self,
+        thread: jni::jthread,
+        start_depth: jni::jint,
+        buf: &mut Vec<jvmti::jvmtiFrameInfo>,
+    ) -> Result<usize, jvmti::jvmtiError> {
+        let max_frame_count = buf.capacity().min(jni::jint::MAX as usize);
+        buf.clear();
+        buf.resize(max_frame_count, jvmti::jvmtiFrameInfo::default());
         let mut count: jni::jint = 0;
         unsafe {
-            let get_stack_fn = (*(*self.env).functions).GetStackTrace.unwrap();
-            let err = get_stack_fn(self.env, thread, start_depth, max_frame_count, frame_buffer.as_mut_ptr(), &mut count);
-            if err != jvmti::jvmtiError::NONE { return Err(err); }
-            frame_buffer.truncate(count as usize);
-            Ok(frame_buffer)
+            let get_stack_fn = (*self.functions).GetStackTrace.unwrap();
+            let err = get_stack_fn(self.env, thread, start_depth, max_frame_count as jni::jint, buf.as_mut_ptr(), &mut count);
+            if err != jvmti::jvmtiError::NONE {
+                buf.clear();
+                return Err(err);
+            }
         }
+        buf.truncate(count as usize);
+        Ok(buf.len())
     }
 
     pub fn get_all_stack_traces(&self, max_frame_count: jni::jint) -> Result<Vec<StackInfo>, jvmti::jvmtiError> {
         let mut stack_info_ptr: *mut jvmti::jvmtiStackInfo = ptr::null_mut();
         let mut thread_count: jni::jint = 0;
         unsafe {
-            let get_all_fn = (*(*self.env).functions).GetAllStackTraces.unwrap();
+            let get_all_fn = (*self.functions).GetAllStackTraces.unwrap();
             let err = get_all_fn(self.env, max_frame_count, &mut stack_info_ptr, &mut thread_count);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1070,7 +3565,7 @@ impl Jvmti {
     pub fn get_thread_list_stack_traces(&self, thread_list: &[jni::jthread], max_frame_count: jni::jint) -> Result<Vec<StackInfo>, jvmti::jvmtiError> {
         let mut stack_info_ptr: *mut jvmti::jvmtiStackInfo = ptr::null_mut();
         unsafe {
-            let get_list_fn = (*(*self.env).functions).GetThreadListStackTraces.unwrap();
+            let get_list_fn = (*self.functions).GetThreadListStackTraces.unwrap();
             let err = get_list_fn(self.env, thread_list.len() as jni::jint, thread_list.as_ptr(), max_frame_count, &mut stack_info_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1102,11 +3597,82 @@ impl Jvmti {
         Ok(out)
     }
 
+    /// Resolves stack frames to class signatures, method names, and line numbers.
+    ///
+    /// Line numbers require `can_get_line_numbers` and a VM whose `jlocation`
+    /// format is [`JLocationFormat::JvmBci`]; otherwise (or for native methods)
+    /// `line_number` is `None`. The declaring-class local references
+    /// created here are not deleted, so call this from an event callback or
+    /// inside a JNI local frame when symbolizing many frames.
+    pub fn symbolize_frames(&self, frames: &[jvmti::jvmtiFrameInfo]) -> Vec<SymbolicFrame> {
+        const UNKNOWN: &str = "<unknown>";
+        // Line number tables are keyed by bytecode index; other encodings would
+        // produce plausible-looking but wrong lines.
+        let bytecode_locations = matches!(self.get_jlocation_format_decoded(), Ok(JLocationFormat::JvmBci));
+        let mut out = Vec::with_capacity(frames.len());
+        for frame in frames {
+            let (method_name, method_signature) = match self.get_method_name(frame.method) {
+                Ok((name, sig, _)) => (name, sig),
+                Err(_) => (UNKNOWN.to_string(), UNKNOWN.to_string()),
+            };
+            let class_signature = self
+                .get_method_declaring_class(frame.method)
+                .and_then(|klass| self.get_class_signature(klass))
+                .map(|(sig, _)| sig)
+                .unwrap_or_else(|_| UNKNOWN.to_string());
+            let line_number = if bytecode_locations {
+                self.get_line_number_table(frame.method)
+                    .ok()
+                    .and_then(|table| SymbolicFrame::line_number_at(&table, frame.location))
+            } else {
+                None
+            };
+            out.push(SymbolicFrame {
+                method: frame.method,
+                location: frame.location,
+                class_signature,
+                method_name,
+                method_signature,
+                line_number,
+            });
+        }
+        out
+    }
+
+    /// Captures every live thread's stack, named and symbolized, sorted by thread name.
+    ///
+    /// Joins [`get_all_stack_traces`](Self::get_all_stack_traces) (up to
+    /// [`THREAD_DUMP_MAX_FRAMES`](Self::THREAD_DUMP_MAX_FRAMES) frames per
+    /// thread) with [`get_thread_info`](Self::get_thread_info) and
+    /// [`symbolize_frames`](Self::symbolize_frames), which is what a
+    /// `jstack`-style dump needs. Like `symbolize_frames`, this creates local
+    /// references it does not delete; call it from a callback or a JNI local frame.
+    pub fn thread_dump(&self) -> Result<Vec<ThreadDump>, jvmti::jvmtiError> {
+        let mut dump: Vec<ThreadDump> = self
+            .get_all_stack_traces(Self::THREAD_DUMP_MAX_FRAMES)?
+            .into_iter()
+            .map(|stack| ThreadDump {
+                name: self
+                    .get_thread_info(stack.thread)
+                    .ok()
+                    .and_then(|info| info.name)
+                    .unwrap_or_else(|| "<unknown>".to_string()),
+                state: ThreadState(stack.state),
+                frames: self.symbolize_frames(&stack.frames),
+            })
+            .collect();
+        dump.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(dump)
+    }
+
+    /// Maximum frames per thread captured by [`thread_dump`](Self::thread_dump).
+    pub const THREAD_DUMP_MAX_FRAMES: jni::jint = 1024;
+
     pub fn get_named_module(&self, class_loader: jni::jobject, package_name: &str) -> Result<jni::jobject, jvmti::jvmtiError> {
-        let c_package = CString::new(package_name).map_err(|_| jvmti::jvmtiError::ILLEGAL_ARGUMENT)?;
+        let c_package = mutf8::to_cstring(package_name);
         let mut module: jni::jobject = ptr::null_mut();
         unsafe {
-            let get_module_fn = (*(*self.env).functions).GetNamedModule.unwrap();
+            let get_module_fn = fn_or_unavailable((*self.functions).GetNamedModule)?;
             let err = get_module_fn(self.env, class_loader, c_package.as_ptr(), &mut module);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(module)
@@ -1116,20 +3682,25 @@ impl Jvmti {
     pub fn get_class_status(&self, klass: jni::jclass) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut status: jni::jint = 0;
         unsafe {
-            let get_status_fn = (*(*self.env).functions).GetClassStatus.unwrap();
+            let get_status_fn = (*self.functions).GetClassStatus.unwrap();
             let err = get_status_fn(self.env, klass, &mut status);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(status)
         }
     }
 
+    /// Like [`get_class_status`](Self::get_class_status), decoded as [`ClassStatus`].
+    pub fn get_class_status_decoded(&self, klass: jni::jclass) -> Result<ClassStatus, jvmti::jvmtiError> {
+        self.get_class_status(klass).map(ClassStatus)
+    }
+
     pub fn get_source_file_name(&self, klass: jni::jclass) -> Result<String, jvmti::jvmtiError> {
         let mut name_ptr: *mut std::os::raw::c_char = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetSourceFileName.unwrap();
+            let get_fn = (*self.functions).GetSourceFileName.unwrap();
             let err = get_fn(self.env, klass, &mut name_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
-            let name = std::ffi::CStr::from_ptr(name_ptr).to_string_lossy().into_owned();
+            let name = mutf8::from_cstr(name_ptr);
             self.deallocate(name_ptr as *mut u8)?;
             Ok(name)
         }
@@ -1138,18 +3709,23 @@ impl Jvmti {
     pub fn get_class_modifiers(&self, klass: jni::jclass) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut modifiers: jni::jint = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetClassModifiers.unwrap();
+            let get_fn = (*self.functions).GetClassModifiers.unwrap();
             let err = get_fn(self.env, klass, &mut modifiers);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(modifiers)
         }
     }
 
+    /// Like [`get_class_modifiers`](Self::get_class_modifiers), decoded as [`AccessFlags`].
+    pub fn get_class_access_flags(&self, klass: jni::jclass) -> Result<AccessFlags, jvmti::jvmtiError> {
+        self.get_class_modifiers(klass).map(|bits| AccessFlags::class(bits as u16))
+    }
+
     pub fn get_class_methods(&self, klass: jni::jclass) -> Result<Vec<jni::jmethodID>, jvmti::jvmtiError> {
         let mut method_count: jni::jint = 0;
         let mut methods_ptr: *mut jni::jmethodID = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetClassMethods.unwrap();
+            let get_fn = (*self.functions).GetClassMethods.unwrap();
             let err = get_fn(self.env, klass, &mut method_count, &mut methods_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let methods = jvmti_array_to_vec(methods_ptr, method_count)?;
@@ -1164,7 +3740,7 @@ impl Jvmti {
         let mut field_count: jni::jint = 0;
         let mut fields_ptr: *mut jni::jfieldID = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetClassFields.unwrap();
+            let get_fn = (*self.functions).GetClassFields.unwrap();
             let err = get_fn(self.env, klass, &mut field_count, &mut fields_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let fields = jvmti_array_to_vec(fields_ptr, field_count)?;
@@ -1179,7 +3755,7 @@ impl Jvmti {
         let mut interface_count: jni::jint = 0;
         let mut interfaces_ptr: *mut jni::jclass = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetImplementedInterfaces.unwrap();
+            let get_fn = (*self.functions).GetImplementedInterfaces.unwrap();
             let err = get_fn(self.env, klass, &mut interface_count, &mut interfaces_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let interfaces = jvmti_array_to_vec(interfaces_ptr, interface_count)?;
@@ -1193,7 +3769,7 @@ impl Jvmti {
     pub fn is_interface(&self, klass: jni::jclass) -> Result<bool, jvmti::jvmtiError> {
         let mut res: jni::jboolean = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).IsInterface.unwrap();
+            let get_fn = (*self.functions).IsInterface.unwrap();
             let err = get_fn(self.env, klass, &mut res);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(res != 0)
@@ -1203,7 +3779,7 @@ impl Jvmti {
     pub fn is_array_class(&self, klass: jni::jclass) -> Result<bool, jvmti::jvmtiError> {
         let mut res: jni::jboolean = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).IsArrayClass.unwrap();
+            let get_fn = (*self.functions).IsArrayClass.unwrap();
             let err = get_fn(self.env, klass, &mut res);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(res != 0)
@@ -1213,7 +3789,7 @@ impl Jvmti {
     pub fn get_class_loader(&self, klass: jni::jclass) -> Result<jni::jobject, jvmti::jvmtiError> {
         let mut loader: jni::jobject = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetClassLoader.unwrap();
+            let get_fn = (*self.functions).GetClassLoader.unwrap();
             let err = get_fn(self.env, klass, &mut loader);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(loader)
@@ -1225,12 +3801,12 @@ impl Jvmti {
         let mut sig_ptr: *mut std::os::raw::c_char = ptr::null_mut();
         let mut gen_ptr: *mut std::os::raw::c_char = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetFieldName.unwrap();
+            let get_fn = (*self.functions).GetFieldName.unwrap();
             let err = get_fn(self.env, klass, field, &mut name_ptr, &mut sig_ptr, &mut gen_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
-            let name = std::ffi::CStr::from_ptr(name_ptr).to_string_lossy().into_owned();
-            let sig = std::ffi::CStr::from_ptr(sig_ptr).to_string_lossy().into_owned();
-            let gen = if gen_ptr.is_null() { None } else { Some(std::ffi::CStr::from_ptr(gen_ptr).to_string_lossy().into_owned()) };
+            let name = mutf8::from_cstr(name_ptr);
+            let sig = mutf8::from_cstr(sig_ptr);
+            let gen = if gen_ptr.is_null() { None } else { Some(mutf8::from_cstr(gen_ptr)) };
             self.deallocate(name_ptr as *mut u8)?;
             self.deallocate(sig_ptr as *mut u8)?;
             if !gen_ptr.is_null() { self.deallocate(gen_ptr as *mut u8)?; }
@@ -1241,7 +3817,7 @@ impl Jvmti {
     pub fn get_field_declaring_class(&self, klass: jni::jclass, field: jni::jfieldID) -> Result<jni::jclass, jvmti::jvmtiError> {
         let mut declaring_class: jni::jclass = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetFieldDeclaringClass.unwrap();
+            let get_fn = (*self.functions).GetFieldDeclaringClass.unwrap();
             let err = get_fn(self.env, klass, field, &mut declaring_class);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(declaring_class)
@@ -1251,17 +3827,22 @@ impl Jvmti {
     pub fn get_field_modifiers(&self, klass: jni::jclass, field: jni::jfieldID) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut modifiers: jni::jint = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetFieldModifiers.unwrap();
+            let get_fn = (*self.functions).GetFieldModifiers.unwrap();
             let err = get_fn(self.env, klass, field, &mut modifiers);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(modifiers)
         }
     }
 
+    /// Like [`get_field_modifiers`](Self::get_field_modifiers), decoded as [`AccessFlags`].
+    pub fn get_field_access_flags(&self, klass: jni::jclass, field: jni::jfieldID) -> Result<AccessFlags, jvmti::jvmtiError> {
+        self.get_field_modifiers(klass, field).map(|bits| AccessFlags::field(bits as u16))
+    }
+
     pub fn is_field_synthetic(&self, klass: jni::jclass, field: jni::jfieldID) -> Result<bool, jvmti::jvmtiError> {
         let mut res: jni::jboolean = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).IsFieldSynthetic.unwrap();
+            let get_fn = (*self.functions).IsFieldSynthetic.unwrap();
             let err = get_fn(self.env, klass, field, &mut res);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(res != 0)
@@ -1271,7 +3852,7 @@ impl Jvmti {
     pub fn get_method_declaring_class(&self, method: jni::jmethodID) -> Result<jni::jclass, jvmti::jvmtiError> {
         let mut declaring_class: jni::jclass = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetMethodDeclaringClass.unwrap();
+            let get_fn = (*self.functions).GetMethodDeclaringClass.unwrap();
             let err = get_fn(self.env, method, &mut declaring_class);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(declaring_class)
@@ -1281,17 +3862,22 @@ impl Jvmti {
     pub fn get_method_modifiers(&self, method: jni::jmethodID) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut modifiers: jni::jint = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetMethodModifiers.unwrap();
+            let get_fn = (*self.functions).GetMethodModifiers.unwrap();
             let err = get_fn(self.env, method, &mut modifiers);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(modifiers)
         }
     }
 
+    /// Like [`get_method_modifiers`](Self::get_method_modifiers), decoded as [`AccessFlags`].
+    pub fn get_method_access_flags(&self, method: jni::jmethodID) -> Result<AccessFlags, jvmti::jvmtiError> {
+        self.get_method_modifiers(method).map(|bits| AccessFlags::method(bits as u16))
+    }
+
     pub fn get_max_locals(&self, method: jni::jmethodID) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut max: jni::jint = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetMaxLocals.unwrap();
+            let get_fn = (*self.functions).GetMaxLocals.unwrap();
             let err = get_fn(self.env, method, &mut max);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(max)
@@ -1301,7 +3887,7 @@ impl Jvmti {
     pub fn get_arguments_size(&self, method: jni::jmethodID) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut size: jni::jint = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetArgumentsSize.unwrap();
+            let get_fn = (*self.functions).GetArgumentsSize.unwrap();
             let err = get_fn(self.env, method, &mut size);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(size)
@@ -1312,7 +3898,7 @@ impl Jvmti {
         let mut entry_count: jni::jint = 0;
         let mut table_ptr: *mut jvmti::jvmtiLineNumberEntry = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetLineNumberTable.unwrap();
+            let get_fn = (*self.functions).GetLineNumberTable.unwrap();
             let err = get_fn(self.env, method, &mut entry_count, &mut table_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let table = jvmti_array_to_vec(table_ptr, entry_count)?;
@@ -1327,7 +3913,7 @@ impl Jvmti {
         let mut start: jvmti::jlocation = 0;
         let mut end: jvmti::jlocation = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetMethodLocation.unwrap();
+            let get_fn = (*self.functions).GetMethodLocation.unwrap();
             let err = get_fn(self.env, method, &mut start, &mut end);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok((start, end))
@@ -1338,7 +3924,7 @@ impl Jvmti {
         let mut entry_count: jni::jint = 0;
         let mut table_ptr: *mut jvmti::jvmtiLocalVariableEntry = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetLocalVariableTable.unwrap();
+            let get_fn = (*self.functions).GetLocalVariableTable.unwrap();
             let err = get_fn(self.env, method, &mut entry_count, &mut table_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1380,7 +3966,7 @@ impl Jvmti {
         let mut count: jni::jint = 0;
         let mut bytecodes_ptr: *mut u8 = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetBytecodes.unwrap();
+            let get_fn = (*self.functions).GetBytecodes.unwrap();
             let err = get_fn(self.env, method, &mut count, &mut bytecodes_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let bytecodes = jvmti_array_to_vec(bytecodes_ptr, count)?;
@@ -1391,10 +3977,27 @@ impl Jvmti {
         }
     }
 
+    /// Returns a method's bytecode, decoded into instructions.
+    ///
+    /// Operands that name the constant pool index the pool returned by
+    /// [`get_constant_pool_parsed`](Self::get_constant_pool_parsed), not the
+    /// original class file's. Needs `can_get_bytecodes`. A method without
+    /// bytecode, such as one made obsolete by a redefinition (see
+    /// [`is_method_obsolete`](Self::is_method_obsolete)), is reported as
+    /// `ABSENT_INFORMATION`; native methods fail with `NATIVE_METHOD`, and
+    /// code the decoder rejects with `INTERNAL`.
+    pub fn get_method_instructions(&self, method: jni::jmethodID) -> Result<Vec<Instruction>, jvmti::jvmtiError> {
+        let code = self.get_bytecodes(method)?;
+        if code.is_empty() {
+            return Err(jvmti::jvmtiError::ABSENT_INFORMATION);
+        }
+        InstructionIterator::new(&code).collect::<Result<Vec<_>, _>>().map_err(|_| jvmti::jvmtiError::INTERNAL)
+    }
+
     pub fn is_method_native(&self, method: jni::jmethodID) -> Result<bool, jvmti::jvmtiError> {
         let mut res: jni::jboolean = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).IsMethodNative.unwrap();
+            let get_fn = (*self.functions).IsMethodNative.unwrap();
             let err = get_fn(self.env, method, &mut res);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(res != 0)
@@ -1404,7 +4007,7 @@ impl Jvmti {
     pub fn is_method_synthetic(&self, method: jni::jmethodID) -> Result<bool, jvmti::jvmtiError> {
         let mut res: jni::jboolean = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).IsMethodSynthetic.unwrap();
+            let get_fn = (*self.functions).IsMethodSynthetic.unwrap();
             let err = get_fn(self.env, method, &mut res);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(res != 0)
@@ -1414,7 +4017,7 @@ impl Jvmti {
     pub fn is_method_obsolete(&self, method: jni::jmethodID) -> Result<bool, jvmti::jvmtiError> {
         let mut res: jni::jboolean = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).IsMethodObsolete.unwrap();
+            let get_fn = (*self.functions).IsMethodObsolete.unwrap();
             let err = get_fn(self.env, method, &mut res);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(res != 0)
@@ -1425,7 +4028,7 @@ impl Jvmti {
         let mut count: jni::jint = 0;
         let mut classes_ptr: *mut jni::jclass = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetClassLoaderClasses.unwrap();
+            let get_fn = (*self.functions).GetClassLoaderClasses.unwrap();
             let err = get_fn(self.env, initiating_loader, &mut count, &mut classes_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let classes = jvmti_array_to_vec(classes_ptr, count)?;
@@ -1439,7 +4042,7 @@ impl Jvmti {
     pub fn get_object_hash_code(&self, object: jni::jobject) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut hash: jni::jint = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetObjectHashCode.unwrap();
+            let get_fn = (*self.functions).GetObjectHashCode.unwrap();
             let err = get_fn(self.env, object, &mut hash);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(hash)
@@ -1456,7 +4059,7 @@ impl Jvmti {
             notify_waiters: ptr::null_mut(),
         };
         unsafe {
-            let get_fn = (*(*self.env).functions).GetObjectMonitorUsage.unwrap();
+            let get_fn = (*self.functions).GetObjectMonitorUsage.unwrap();
             let err = get_fn(self.env, object, &mut info);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1489,7 +4092,7 @@ impl Jvmti {
     pub fn get_tag(&self, object: jni::jobject) -> Result<jni::jlong, jvmti::jvmtiError> {
         let mut tag: jni::jlong = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetTag.unwrap();
+            let get_fn = (*self.functions).GetTag.unwrap();
             let err = get_fn(self.env, object, &mut tag);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(tag)
@@ -1498,7 +4101,7 @@ impl Jvmti {
 
     pub fn set_tag(&self, object: jni::jobject, tag: jni::jlong) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetTag.unwrap();
+            let set_fn = (*self.functions).SetTag.unwrap();
             let err = set_fn(self.env, object, tag);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1507,7 +4110,7 @@ impl Jvmti {
 
     pub fn force_garbage_collection(&self) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let force_fn = (*(*self.env).functions).ForceGarbageCollection.unwrap();
+            let force_fn = (*self.functions).ForceGarbageCollection.unwrap();
             let err = force_fn(self.env);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1516,7 +4119,7 @@ impl Jvmti {
 
     pub fn iterate_over_objects_reachable_from_object(&self, object: jni::jobject, cb: jvmti::jvmtiObjectReferenceCallback, user_data: *const std::os::raw::c_void) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let iter_fn = (*(*self.env).functions).IterateOverObjectsReachableFromObject.unwrap();
+            let iter_fn = (*self.functions).IterateOverObjectsReachableFromObject.unwrap();
             let err = iter_fn(self.env, object, cb, user_data);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1525,7 +4128,7 @@ impl Jvmti {
 
     pub fn iterate_over_reachable_objects(&self, root_cb: jvmti::jvmtiHeapRootCallback, stack_cb: jvmti::jvmtiStackReferenceCallback, obj_cb: jvmti::jvmtiObjectReferenceCallback, user_data: *const std::os::raw::c_void) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let iter_fn = (*(*self.env).functions).IterateOverReachableObjects.unwrap();
+            let iter_fn = (*self.functions).IterateOverReachableObjects.unwrap();
             let err = iter_fn(self.env, root_cb, stack_cb, obj_cb, user_data);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1534,7 +4137,7 @@ impl Jvmti {
 
     pub fn iterate_over_heap(&self, filter: jni::jint, cb: jvmti::jvmtiObjectCallback, user_data: *const std::os::raw::c_void) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let iter_fn = (*(*self.env).functions).IterateOverHeap.unwrap();
+            let iter_fn = (*self.functions).IterateOverHeap.unwrap();
             let err = iter_fn(self.env, filter, cb, user_data);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1543,7 +4146,7 @@ impl Jvmti {
 
     pub fn iterate_over_instances_of_class(&self, klass: jni::jclass, filter: jni::jint, cb: jvmti::jvmtiObjectCallback, user_data: *const std::os::raw::c_void) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let iter_fn = (*(*self.env).functions).IterateOverInstancesOfClass.unwrap();
+            let iter_fn = (*self.functions).IterateOverInstancesOfClass.unwrap();
             let err = iter_fn(self.env, klass, filter, cb, user_data);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1555,7 +4158,7 @@ impl Jvmti {
         let mut objects_ptr: *mut jni::jobject = ptr::null_mut();
         let mut tags_ptr: *mut jni::jlong = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetObjectsWithTags.unwrap();
+            let get_fn = (*self.functions).GetObjectsWithTags.unwrap();
             let err = get_fn(self.env, tags.len() as jni::jint, tags.as_ptr(), &mut count, &mut objects_ptr, &mut tags_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let objects = jvmti_array_to_vec(objects_ptr, count)?;
@@ -1572,7 +4175,7 @@ impl Jvmti {
 
     pub fn follow_references(&self, heap_filter: jni::jint, klass: jni::jclass, initial_object: jni::jobject, callbacks: &jvmti::jvmtiHeapCallbacks, user_data: *const std::os::raw::c_void) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let follow_fn = (*(*self.env).functions).FollowReferences.unwrap();
+            let follow_fn = (*self.functions).FollowReferences.unwrap();
             let err = follow_fn(self.env, heap_filter, klass, initial_object, callbacks, user_data);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1581,17 +4184,158 @@ impl Jvmti {
 
     pub fn iterate_through_heap(&self, heap_filter: jni::jint, klass: jni::jclass, callbacks: &jvmti::jvmtiHeapCallbacks, user_data: *const std::os::raw::c_void) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let iter_fn = (*(*self.env).functions).IterateThroughHeap.unwrap();
+            let iter_fn = (*self.functions).IterateThroughHeap.unwrap();
             let err = iter_fn(self.env, heap_filter, klass, callbacks, user_data);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
+    /// Iterates over all heap objects with `IterateThroughHeap`, calling `f` for each.
+    ///
+    /// `heap_filter` is the JVMTI heap filter bitmask (`0` reports every
+    /// object) and `klass` restricts the walk to instances of that class
+    /// (null for all objects).
+    /// Requires `can_tag_objects`.
+    ///
+    /// The closure runs while the VM is stopped for heap iteration: it must
+    /// not call JVMTI or JNI functions (other than through the provided
+    /// [`HeapObject`]). A panic in the closure aborts the iteration and is
+    /// resumed once `IterateThroughHeap` returns.
+    pub fn iterate_heap_with<F>(&self, heap_filter: jni::jint, klass: jni::jclass, f: F) -> Result<(), jvmti::jvmtiError>
+    where
+        F: FnMut(HeapObject<'_>) -> IterationControl,
+    {
+        self.require_live_phase("iterate_heap_with")?;
+        let mut state = HeapIterationState { f, panic: None };
+        let callbacks = jvmti::jvmtiHeapCallbacks::default().heap_iteration(heap_iteration_trampoline::<F>);
+        let user_data = &mut state as *mut HeapIterationState<F> as *const std::os::raw::c_void;
+        let result = self.iterate_through_heap(heap_filter, klass, &callbacks, user_data);
+        if let Some(payload) = state.panic.take() {
+            std::panic::resume_unwind(payload);
+        }
+        result
+    }
+
+    /// Walks the reference graph with Rust closures instead of raw callbacks.
+    ///
+    /// Wraps `FollowReferences`: starts at the GC roots (or `initial_object`
+    /// if non-null), reports each reference to the reference closure, and
+    /// descends into objects for which it returns [`VisitControl::Follow`].
+    /// `heap_filter` and `klass` restrict which objects are reported, as in
+    /// [`follow_references`](Self::follow_references). Requires `can_tag_objects`.
+    ///
+    /// The closures run while the VM is stopped: they must not call JVMTI or
+    /// JNI functions. A panic aborts the walk and is resumed once
+    /// `FollowReferences` returns.
+    pub fn follow_references_with(
+        &self,
+        heap_filter: jni::jint,
+        klass: jni::jclass,
+        initial_object: jni::jobject,
+        closures: HeapClosures<'_>,
+    ) -> Result<(), jvmti::jvmtiError> {
+        self.require_live_phase("follow_references_with")?;
+        let callbacks = closures.value_callbacks(jvmti::jvmtiHeapCallbacks::default().heap_reference(follow_reference_trampoline));
+        let mut state = FollowState { closures, panic: None };
+        let user_data = &mut state as *mut FollowState<'_> as *const std::os::raw::c_void;
+        let result = self.follow_references(heap_filter, klass, initial_object, &callbacks, user_data);
+        if let Some(payload) = state.panic.take() {
+            std::panic::resume_unwind(payload);
+        }
+        result
+    }
+
+    /// Reports primitive fields, primitive array contents, and string values
+    /// of every heap object with `IterateThroughHeap`.
+    ///
+    /// Uses the primitive-field, array and string closures of `closures`
+    /// (the reference closure is ignored); `heap_filter` and `klass` restrict
+    /// the objects as in [`iterate_heap_with`](Self::iterate_heap_with). This
+    /// is the way to read `char[]`/`byte[]` contents or string values during a
+    /// heap walk. Requires `can_tag_objects`.
+    ///
+    /// The closures run while the VM is stopped: they must not call JVMTI or
+    /// JNI functions. A panic aborts the iteration and is resumed once
+    /// `IterateThroughHeap` returns.
+    pub fn iterate_through_heap_with(
+        &self,
+        heap_filter: jni::jint,
+        klass: jni::jclass,
+        closures: HeapClosures<'_>,
+    ) -> Result<(), jvmti::jvmtiError> {
+        self.require_live_phase("iterate_through_heap_with")?;
+        let callbacks = closures.value_callbacks(jvmti::jvmtiHeapCallbacks::default());
+        let mut state = FollowState { closures, panic: None };
+        let user_data = &mut state as *mut FollowState<'_> as *const std::os::raw::c_void;
+        let result = self.iterate_through_heap(heap_filter, klass, &callbacks, user_data);
+        if let Some(payload) = state.panic.take() {
+            std::panic::resume_unwind(payload);
+        }
+        result
+    }
+
+    /// Builds a per-class instance and byte histogram of the heap, like `jmap -histo`.
+    ///
+    /// Every loaded class is temporarily tagged so `IterateThroughHeap` can
+    /// attribute objects to it; the previous class tags are restored before
+    /// returning. Rows are sorted by `total_bytes`, largest first. Requires
+    /// `can_tag_objects`.
+    pub fn class_histogram(&self) -> Result<Vec<ClassCount>, jvmti::jvmtiError> {
+        self.require_live_phase("class_histogram")?;
+        let classes = self.get_loaded_classes()?;
+        let mut saved_tags = Vec::with_capacity(classes.len());
+        let mut result = Ok(());
+        for (index, &klass) in classes.iter().enumerate() {
+            match self.get_tag(klass).and_then(|old| self.set_tag(klass, index as jni::jlong + 1).map(|_| old)) {
+                Ok(old) => saved_tags.push(old),
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+
+        let mut counts = vec![(0u64, 0u64); classes.len()];
+        if result.is_ok() {
+            result = self.iterate_heap_with(0, ptr::null_mut(), |object| {
+                let class_tag = object.class_tag();
+                if class_tag > 0 && (class_tag as usize) <= counts.len() {
+                    let entry = &mut counts[class_tag as usize - 1];
+                    entry.0 += 1;
+                    entry.1 += object.size().max(0) as u64;
+                }
+                IterationControl::Continue
+            });
+        }
+
+        for (&klass, &old) in classes.iter().zip(&saved_tags) {
+            let restored = self.set_tag(klass, old);
+            if result.is_ok() {
+                result = restored;
+            }
+        }
+        result?;
+
+        let mut histogram = Vec::new();
+        for (&klass, &(instance_count, total_bytes)) in classes.iter().zip(&counts) {
+            if instance_count == 0 {
+                continue;
+            }
+            let class_signature = self
+                .get_class_signature(klass)
+                .map(|(sig, _)| sig)
+                .unwrap_or_else(|_| "<unknown>".to_string());
+            histogram.push(ClassCount { class_signature, instance_count, total_bytes });
+        }
+        histogram.sort_by(|a, b| b.total_bytes.cmp(&a.total_bytes).then_with(|| a.class_signature.cmp(&b.class_signature)));
+        Ok(histogram)
+    }
+
     pub fn get_object_size(&self, object: jni::jobject) -> Result<jni::jlong, jvmti::jvmtiError> {
         let mut size: jni::jlong = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetObjectSize.unwrap();
+            let get_fn = (*self.functions).GetObjectSize.unwrap();
             let err = get_fn(self.env, object, &mut size);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(size)
@@ -1600,16 +4344,39 @@ impl Jvmti {
 
     pub fn set_heap_sampling_interval(&self, interval: jni::jint) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetHeapSamplingInterval.unwrap();
+            let set_fn = fn_or_unavailable((*self.functions).SetHeapSamplingInterval)?;
             let err = set_fn(self.env, interval);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
+    /// Starts the JDK 11+ allocation sampler: adds
+    /// `can_generate_sampled_object_alloc_events` if needed, sets the mean
+    /// sampling interval, and enables `SampledObjectAlloc` for all threads.
+    ///
+    /// `interval_bytes` of 0 samples every allocation; the VM default is
+    /// 512 KiB. The capability can usually only be added during `OnLoad`.
+    /// Feed the events to a `profiling::AllocSampler` (`profiling` feature)
+    /// to build an allocation profile.
+    pub fn start_allocation_sampling(&self, interval_bytes: jni::jint) -> Result<(), jvmti::jvmtiError> {
+        let required = Event::SampledObjectAlloc.required_capabilities();
+        if !self.get_capabilities()?.contains(&required) {
+            self.add_capabilities(&required)?;
+        }
+        self.set_heap_sampling_interval(interval_bytes)?;
+        self.enable(Event::SampledObjectAlloc, ptr::null_mut())
+    }
+
+    /// Disables `SampledObjectAlloc` for all threads. The capability is kept,
+    /// so sampling can be restarted later in the live phase.
+    pub fn stop_allocation_sampling(&self) -> Result<(), jvmti::jvmtiError> {
+        self.disable(Event::SampledObjectAlloc, ptr::null_mut())
+    }
+
     pub fn set_breakpoint(&self, method: jni::jmethodID, location: jvmti::jlocation) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetBreakpoint.unwrap();
+            let set_fn = (*self.functions).SetBreakpoint.unwrap();
             let err = set_fn(self.env, method, location);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1618,7 +4385,7 @@ impl Jvmti {
 
     pub fn clear_breakpoint(&self, method: jni::jmethodID, location: jvmti::jlocation) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let clear_fn = (*(*self.env).functions).ClearBreakpoint.unwrap();
+            let clear_fn = (*self.functions).ClearBreakpoint.unwrap();
             let err = clear_fn(self.env, method, location);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1627,7 +4394,7 @@ impl Jvmti {
 
     pub fn set_field_access_watch(&self, klass: jni::jclass, field: jni::jfieldID) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetFieldAccessWatch.unwrap();
+            let set_fn = (*self.functions).SetFieldAccessWatch.unwrap();
             let err = set_fn(self.env, klass, field);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1636,7 +4403,7 @@ impl Jvmti {
 
     pub fn clear_field_access_watch(&self, klass: jni::jclass, field: jni::jfieldID) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let clear_fn = (*(*self.env).functions).ClearFieldAccessWatch.unwrap();
+            let clear_fn = (*self.functions).ClearFieldAccessWatch.unwrap();
             let err = clear_fn(self.env, klass, field);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1645,7 +4412,7 @@ impl Jvmti {
 
     pub fn set_field_modification_watch(&self, klass: jni::jclass, field: jni::jfieldID) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetFieldModificationWatch.unwrap();
+            let set_fn = (*self.functions).SetFieldModificationWatch.unwrap();
             let err = set_fn(self.env, klass, field);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1654,7 +4421,7 @@ impl Jvmti {
 
     pub fn clear_field_modification_watch(&self, klass: jni::jclass, field: jni::jfieldID) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let clear_fn = (*(*self.env).functions).ClearFieldModificationWatch.unwrap();
+            let clear_fn = (*self.functions).ClearFieldModificationWatch.unwrap();
             let err = clear_fn(self.env, klass, field);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1664,7 +4431,7 @@ impl Jvmti {
     pub fn is_modifiable_class(&self, klass: jni::jclass) -> Result<bool, jvmti::jvmtiError> {
         let mut res: jni::jboolean = 0;
         unsafe {
-            let is_fn = (*(*self.env).functions).IsModifiableClass.unwrap();
+            let is_fn = (*self.functions).IsModifiableClass.unwrap();
             let err = is_fn(self.env, klass, &mut res);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(res != 0)
@@ -1673,17 +4440,60 @@ impl Jvmti {
 
     pub fn retransform_classes(&self, classes: &[jni::jclass]) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let retransform_fn = (*(*self.env).functions).RetransformClasses.unwrap();
+            let retransform_fn = (*self.functions).RetransformClasses.unwrap();
             let err = retransform_fn(self.env, classes.len() as jni::jint, classes.as_ptr());
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
+    /// Retransforms `classes`, letting `transform` rewrite their class files.
+    ///
+    /// `transform` receives each class's internal name and current bytes (as
+    /// left by earlier retransformation-capable agents and this agent's own
+    /// `ClassFileLoadHook` handlers) and returns replacement bytes, or `None`
+    /// to keep them. Transformers from
+    /// [`install_transformers`](Self::install_transformers) still run on its
+    /// output. The event is enabled for the calling thread around
+    /// `RetransformClasses` and disabled for it again afterwards, leaving the
+    /// global setting untouched. JVMTI cannot report a thread-level setting,
+    /// so one made for the calling thread beforehand is cleared too; re-enable
+    /// it afterwards if the thread still needs it.
+    ///
+    /// Requires `can_retransform_classes` (`MUST_POSSESS_CAPABILITY` otherwise)
+    /// and the crate's `ClassFileLoadHook` callback, as installed from
+    /// [`get_default_callbacks`](crate::get_default_callbacks) through
+    /// [`set_event_callbacks`](Self::set_event_callbacks) (`NOT_AVAILABLE`
+    /// otherwise). `transform` runs on the calling thread; classes it causes to
+    /// load go to the agent's handlers, and calling `retransform_with` again
+    /// from inside it returns `ILLEGAL_ARGUMENT`. A panic in `transform` keeps
+    /// the class unchanged. If `RetransformClasses` fails, its error is
+    /// returned even if disabling the event fails as well.
+    pub fn retransform_with<F>(&self, classes: &[jni::jclass], mut transform: F) -> Result<(), jvmti::jvmtiError>
+    where
+        F: FnMut(&str, &[u8]) -> Option<Vec<u8>>,
+    {
+        self.require_live_phase("retransform_with")?;
+        if !self.get_capabilities()?.can_retransform_classes() {
+            return Err(jvmti::jvmtiError::MUST_POSSESS_CAPABILITY);
+        }
+        if !self.class_file_load_hook_wired() {
+            return Err(jvmti::jvmtiError::NOT_AVAILABLE);
+        }
+        let thread = self.get_current_thread()?;
+        crate::with_one_shot_hook(&mut transform, || {
+            self.set_event_notification_mode(true, jvmti::JVMTI_EVENT_CLASS_FILE_LOAD_HOOK, thread)?;
+            let result = self.retransform_classes(classes);
+            let disabled = self.set_event_notification_mode(false, jvmti::JVMTI_EVENT_CLASS_FILE_LOAD_HOOK, thread);
+            result.and(disabled)
+        })
+        .unwrap_or(Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT))
+    }
+
     pub fn is_modifiable_module(&self, module: jni::jobject) -> Result<bool, jvmti::jvmtiError> {
         let mut res: jni::jboolean = 0;
         unsafe {
-            let is_fn = (*(*self.env).functions).IsModifiableModule.unwrap();
+            let is_fn = fn_or_unavailable((*self.functions).IsModifiableModule)?;
             let err = is_fn(self.env, module, &mut res);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(res != 0)
@@ -1692,7 +4502,7 @@ impl Jvmti {
 
     pub fn add_module_reads(&self, module: jni::jobject, source_module: jni::jobject) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let add_fn = (*(*self.env).functions).AddModuleReads.unwrap();
+            let add_fn = fn_or_unavailable((*self.functions).AddModuleReads)?;
             let err = add_fn(self.env, module, source_module);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1700,9 +4510,9 @@ impl Jvmti {
     }
 
     pub fn add_module_exports(&self, module: jni::jobject, package: &str, to_module: jni::jobject) -> Result<(), jvmti::jvmtiError> {
-        let c_package = CString::new(package).map_err(|_| jvmti::jvmtiError::ILLEGAL_ARGUMENT)?;
+        let c_package = mutf8::to_cstring(package);
         unsafe {
-            let add_fn = (*(*self.env).functions).AddModuleExports.unwrap();
+            let add_fn = fn_or_unavailable((*self.functions).AddModuleExports)?;
             let err = add_fn(self.env, module, c_package.as_ptr(), to_module);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1710,9 +4520,9 @@ impl Jvmti {
     }
 
     pub fn add_module_opens(&self, module: jni::jobject, package: &str, to_module: jni::jobject) -> Result<(), jvmti::jvmtiError> {
-        let c_package = CString::new(package).map_err(|_| jvmti::jvmtiError::ILLEGAL_ARGUMENT)?;
+        let c_package = mutf8::to_cstring(package);
         unsafe {
-            let add_fn = (*(*self.env).functions).AddModuleOpens.unwrap();
+            let add_fn = fn_or_unavailable((*self.functions).AddModuleOpens)?;
             let err = add_fn(self.env, module, c_package.as_ptr(), to_module);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1721,7 +4531,7 @@ impl Jvmti {
 
     pub fn add_module_uses(&self, module: jni::jobject, service: jni::jclass) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let add_fn = (*(*self.env).functions).AddModuleUses.unwrap();
+            let add_fn = fn_or_unavailable((*self.functions).AddModuleUses)?;
             let err = add_fn(self.env, module, service);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1730,7 +4540,7 @@ impl Jvmti {
 
     pub fn add_module_provides(&self, module: jni::jobject, service: jni::jclass, implementation: jni::jclass) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let add_fn = (*(*self.env).functions).AddModuleProvides.unwrap();
+            let add_fn = fn_or_unavailable((*self.functions).AddModuleProvides)?;
             let err = add_fn(self.env, module, service, implementation);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1740,20 +4550,28 @@ impl Jvmti {
     pub fn get_version_number(&self) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut version: jni::jint = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetVersionNumber.unwrap();
+            let get_fn = (*self.functions).GetVersionNumber.unwrap();
             let err = get_fn(self.env, &mut version);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(version)
         }
     }
 
+    /// Returns the JVMTI version as `(major, minor, micro)`, e.g. `(21, 0, 0)`.
+    ///
+    /// JVMTI 1.x reports a major version of 1; from JDK 9 on the major version
+    /// matches the JDK feature release.
+    pub fn jvmti_version(&self) -> Result<(u32, u32, u32), jvmti::jvmtiError> {
+        Ok(jvmti::version_components(self.get_version_number()?))
+    }
+
     pub fn get_source_debug_extension(&self, klass: jni::jclass) -> Result<String, jvmti::jvmtiError> {
         let mut ext_ptr: *mut std::os::raw::c_char = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetSourceDebugExtension.unwrap();
+            let get_fn = (*self.functions).GetSourceDebugExtension.unwrap();
             let err = get_fn(self.env, klass, &mut ext_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
-            let ext = std::ffi::CStr::from_ptr(ext_ptr).to_string_lossy().into_owned();
+            let ext = mutf8::from_cstr(ext_ptr);
             self.deallocate(ext_ptr as *mut u8)?;
             Ok(ext)
         }
@@ -1762,7 +4580,7 @@ impl Jvmti {
     pub fn get_thread_local_storage(&self, thread: jni::jthread) -> Result<*mut std::os::raw::c_void, jvmti::jvmtiError> {
         let mut data: *mut std::os::raw::c_void = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetThreadLocalStorage.unwrap();
+            let get_fn = (*self.functions).GetThreadLocalStorage.unwrap();
             let err = get_fn(self.env, thread, &mut data);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(data)
@@ -1771,64 +4589,150 @@ impl Jvmti {
 
     pub fn set_thread_local_storage(&self, thread: jni::jthread, data: *const std::os::raw::c_void) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetThreadLocalStorage.unwrap();
+            let set_fn = (*self.functions).SetThreadLocalStorage.unwrap();
             let err = set_fn(self.env, thread, data);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
-    pub fn suspend_all_virtual_threads(&self) -> Result<(), jvmti::jvmtiError> {
+    /// Suspends every virtual thread except those in `except`.
+    ///
+    /// Requires `can_suspend` and `can_support_virtual_threads` (JDK 21+).
+    pub fn suspend_all_virtual_threads(&self, except: &[jni::jthread]) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let suspend_fn = (*(*self.env).functions).SuspendAllVirtualThreads.unwrap();
-            let err = suspend_fn(self.env);
+            let suspend_fn = fn_or_unavailable((*self.functions).SuspendAllVirtualThreads)?;
+            let err = suspend_fn(self.env, except.len() as jni::jint, except.as_ptr());
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
-    pub fn resume_all_virtual_threads(&self) -> Result<(), jvmti::jvmtiError> {
+    /// Resumes every suspended virtual thread except those in `except`.
+    ///
+    /// Requires `can_suspend` and `can_support_virtual_threads` (JDK 21+).
+    pub fn resume_all_virtual_threads(&self, except: &[jni::jthread]) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let resume_fn = (*(*self.env).functions).ResumeAllVirtualThreads.unwrap();
-            let err = resume_fn(self.env);
+            let resume_fn = fn_or_unavailable((*self.functions).ResumeAllVirtualThreads)?;
+            let err = resume_fn(self.env, except.len() as jni::jint, except.as_ptr());
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
-    pub fn set_jni_function_table(&self, function_table: *const jni::JNIEnv) -> Result<(), jvmti::jvmtiError> {
+    /// Suspends a single virtual thread via `SuspendThread`.
+    ///
+    /// Suspension applies to the virtual thread, not its carrier: a mounted
+    /// virtual thread stops running and its carrier keeps going once it unmounts,
+    /// while an unmounted one simply cannot be scheduled until resumed. Errors
+    /// from the VM are returned unchanged, notably `UNSUPPORTED_OPERATION` when
+    /// `can_support_virtual_threads` is missing and `THREAD_SUSPENDED` when the
+    /// thread is already suspended.
+    pub fn suspend_virtual_thread(&self, vthread: jni::jthread) -> Result<(), jvmti::jvmtiError> {
+        self.suspend_thread(vthread)
+    }
+
+    /// Resumes a virtual thread suspended with [`suspend_virtual_thread`](Self::suspend_virtual_thread)
+    /// or [`suspend_all_virtual_threads`](Self::suspend_all_virtual_threads).
+    ///
+    /// Returns `THREAD_NOT_SUSPENDED` if the thread is not suspended and
+    /// `UNSUPPORTED_OPERATION` without `can_support_virtual_threads`.
+    pub fn resume_virtual_thread(&self, vthread: jni::jthread) -> Result<(), jvmti::jvmtiError> {
+        self.resume_thread(vthread)
+    }
+
+    /// Installs `function_table` as the JNI function table of every thread
+    /// (`SetJNIFunctionTable`). The VM copies the table.
+    pub fn set_jni_function_table(&self, function_table: *const jni::JNINativeInterface_) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetJNIFunctionTable.unwrap();
+            let set_fn = (*self.functions).SetJNIFunctionTable.unwrap();
             let err = set_fn(self.env, function_table);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
-    pub fn get_jni_function_table(&self) -> Result<*mut jni::JNIEnv, jvmti::jvmtiError> {
-        let mut table_ptr: *mut jni::JNIEnv = ptr::null_mut();
+    /// Returns a copy of the current JNI function table (`GetJNIFunctionTable`),
+    /// which must be released with [`deallocate`](Self::deallocate).
+    pub fn get_jni_function_table(&self) -> Result<*mut jni::JNINativeInterface_, jvmti::jvmtiError> {
+        let mut table_ptr: *mut jni::JNINativeInterface_ = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetJNIFunctionTable.unwrap();
+            let get_fn = (*self.functions).GetJNIFunctionTable.unwrap();
             let err = get_fn(self.env, &mut table_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(table_ptr)
         }
     }
 
+    /// Replaces entries of the JNI function table to intercept JNI calls.
+    ///
+    /// Copies the current table, lets `build` overwrite function pointers,
+    /// and installs the result. The returned [`JniFunctionTable`] holds the
+    /// table as it was before, for forwarding to the original functions and
+    /// for [`restore`](JniFunctionTable::restore):
+    ///
+    /// ```rust,ignore
+    /// static ORIGINAL: OnceLock<JniFunctionTable> = OnceLock::new();
+    ///
+    /// unsafe extern "system" fn traced_find_class(env: *mut jni::JNIEnv, name: *const c_char) -> jni::jclass {
+    ///     eprintln!("FindClass({:?})", CStr::from_ptr(name));
+    ///     (ORIGINAL.get().unwrap().table().FindClass)(env, name)
+    /// }
+    ///
+    /// let original = jvmti.with_intercepted_jni(|table| table.FindClass = traced_find_class)?;
+    /// let _ = ORIGINAL.set(original);
+    /// ```
+    ///
+    /// Use with extreme care. The new table applies to every thread in the
+    /// process, including JNI calls made by the JVM's own native code and by
+    /// this crate, so an interceptor runs on hot paths and must be fast,
+    /// reentrant, and must not panic (it is called across an FFI boundary).
+    /// Store the returned table before the first intercepted call can reach
+    /// it, as above. Threads keep using a table until their next JNI call, so
+    /// interceptors may still run briefly after [`restore`](JniFunctionTable::restore).
+    pub fn with_intercepted_jni(
+        &self,
+        build: impl FnOnce(&mut jni::JNINativeInterface_),
+    ) -> Result<JniFunctionTable, jvmti::jvmtiError> {
+        let current = self.get_jni_function_table()?;
+        // SAFETY: the VM returned a complete, initialized table; both copies
+        // are plain function pointers, and the VM's allocation is freed below.
+        let original = Box::new(unsafe { ptr::read(current) });
+        let mut table = Box::new(unsafe { ptr::read(current) });
+        self.deallocate(current as *mut u8)?;
+        build(&mut table);
+        self.set_jni_function_table(&*table)?;
+        Ok(JniFunctionTable { table: original })
+    }
+
     pub fn generate_events(&self, event_type: u32) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let gen_fn = (*(*self.env).functions).GenerateEvents.unwrap();
+            let gen_fn = (*self.functions).GenerateEvents.unwrap();
             let err = gen_fn(self.env, event_type);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
+    /// Replays `CompiledMethodLoad` and `DynamicCodeGenerated` for code that
+    /// already exists, e.g. after a late attach.
+    ///
+    /// Enable both events first; the replayed events are delivered to the
+    /// normal callbacks before this returns. Returns `MUST_POSSESS_CAPABILITY`
+    /// unless `can_generate_compiled_method_load_events` has been added.
+    pub fn replay_compiled_methods(&self) -> Result<(), jvmti::jvmtiError> {
+        if !self.get_capabilities()?.can_generate_compiled_method_load_events() {
+            return Err(jvmti::jvmtiError::MUST_POSSESS_CAPABILITY);
+        }
+        self.generate_events(jvmti::JVMTI_EVENT_COMPILED_METHOD_LOAD)?;
+        self.generate_events(jvmti::JVMTI_EVENT_DYNAMIC_CODE_GENERATED)
+    }
+
     pub fn get_extension_functions(&self) -> Result<Vec<ExtensionFunctionInfo>, jvmti::jvmtiError> {
         let mut count: jni::jint = 0;
         let mut ext_ptr: *mut jvmti::jvmtiExtensionFunctionInfo = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetExtensionFunctions.unwrap();
+            let get_fn = (*self.functions).GetExtensionFunctions.unwrap();
             let err = get_fn(self.env, &mut count, &mut ext_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1894,11 +4798,22 @@ impl Jvmti {
         Ok(out)
     }
 
+    /// Finds the extension function with the given id, ready to call.
+    ///
+    /// Returns `Ok(None)` if this VM does not provide it.
+    pub fn get_extension_function(&self, id: &str) -> Result<Option<ExtensionFunction>, jvmti::jvmtiError> {
+        Ok(self
+            .get_extension_functions()?
+            .iter()
+            .find(|info| info.id.as_deref() == Some(id))
+            .and_then(ExtensionFunction::new))
+    }
+
     pub fn get_extension_events(&self) -> Result<Vec<ExtensionEventInfo>, jvmti::jvmtiError> {
         let mut count: jni::jint = 0;
         let mut ext_ptr: *mut jvmti::jvmtiExtensionEventInfo = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetExtensionEvents.unwrap();
+            let get_fn = (*self.functions).GetExtensionEvents.unwrap();
             let err = get_fn(self.env, &mut count, &mut ext_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -1960,21 +4875,67 @@ impl Jvmti {
 
     pub fn set_extension_event_callback(&self, extension_event_index: jni::jint, callback: jvmti::jvmtiExtensionEventCallback) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetExtensionEventCallback.unwrap();
+            let set_fn = (*self.functions).SetExtensionEventCallback.unwrap();
             let err = set_fn(self.env, extension_event_index, callback);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
+    /// Routes an extension event to [`Agent::extension_event`](crate::Agent::extension_event).
+    ///
+    /// `extension_event_index` must come from [`get_extension_events`](Self::get_extension_events)
+    /// on this VM: indices are assigned by each JVM implementation and can
+    /// differ between vendors and releases, so look events up by `id`
+    /// (e.g. `com.sun.hotspot.events.ClassUnload`) instead of hard-coding them.
+    /// Like other events, delivery goes to the global agent.
+    ///
+    /// Extension events are variadic; their arguments are decoded from the
+    /// event's parameter list. Returns `ILLEGAL_ARGUMENT` for an unknown index
+    /// and `NOT_AVAILABLE` if the event has a by-value `jfloat`, `jdouble`, or
+    /// `jvalue` parameter (or `jlong` on 32-bit targets), more than
+    /// [`MAX_EXTENSION_ARGS`] parameters, all
+    /// [`MAX_EXTENSION_EVENTS`](crate::MAX_EXTENSION_EVENTS) dispatch slots are
+    /// taken, or the target is not x86_64 or non-Apple aarch64, the targets
+    /// known to pass integer varargs like fixed arguments.
+    pub fn enable_extension_event(&self, extension_event_index: jni::jint) -> Result<(), jvmti::jvmtiError> {
+        let event = self
+            .get_extension_events()?
+            .into_iter()
+            .find(|event| event.extension_event_index == extension_event_index)
+            .ok_or(jvmti::jvmtiError::ILLEGAL_ARGUMENT)?;
+        if event.params.len() > MAX_EXTENSION_ARGS || !event.params.iter().all(ExtensionParamInfo::passes_as_word) {
+            return Err(jvmti::jvmtiError::NOT_AVAILABLE);
+        }
+        let callback = crate::extension_event_trampoline(extension_event_index, event.params)
+            .ok_or(jvmti::jvmtiError::NOT_AVAILABLE)?;
+        self.set_extension_event_callback(extension_event_index, callback)
+    }
+
+    /// Stops delivering an extension event enabled with
+    /// [`enable_extension_event`](Self::enable_extension_event).
+    pub fn disable_extension_event(&self, extension_event_index: jni::jint) -> Result<(), jvmti::jvmtiError> {
+        type SetNullableFn = unsafe extern "system" fn(*mut jvmti::jvmtiEnv, jni::jint, Option<jvmti::jvmtiExtensionEventCallback>) -> jvmti::jvmtiError;
+        unsafe {
+            // `SetExtensionEventCallback` accepts NULL to disable the event;
+            // `Option<fn>` has the same representation as a nullable pointer.
+            let set_fn = std::mem::transmute::<jvmti::JvmtiSetExtensionEventCallbackFn, SetNullableFn>(
+                (*self.functions).SetExtensionEventCallback.unwrap(),
+            );
+            let err = set_fn(self.env, extension_event_index, None);
+            if err != jvmti::jvmtiError::NONE { return Err(err); }
+        }
+        Ok(())
+    }
+
     pub fn get_error_name(&self, error: jvmti::jvmtiError) -> Result<String, jvmti::jvmtiError> {
         let mut name_ptr: *mut std::os::raw::c_char = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetErrorName.unwrap();
+            let get_fn = (*self.functions).GetErrorName.unwrap();
             let err = get_fn(self.env, error, &mut name_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             if name_ptr.is_null() { return Err(jvmti::jvmtiError::NULL_POINTER); }
-            let name = std::ffi::CStr::from_ptr(name_ptr).to_string_lossy().into_owned();
+            let name = mutf8::from_cstr(name_ptr);
             self.deallocate(name_ptr as *mut u8)?;
             Ok(name)
         }
@@ -1994,18 +4955,27 @@ impl Jvmti {
     pub fn get_jlocation_format(&self) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut format: jni::jint = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetJLocationFormat.unwrap();
+            let get_fn = fn_or_unavailable((*self.functions).GetJLocationFormat)?;
             let err = get_fn(self.env, &mut format);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(format)
         }
     }
 
+    /// [`get_jlocation_format`](Self::get_jlocation_format), decoded.
+    pub fn get_jlocation_format_decoded(&self) -> Result<JLocationFormat, jvmti::jvmtiError> {
+        self.get_jlocation_format().map(JLocationFormat::from_raw)
+    }
+
+    /// Returns the names of the VM's system properties (`GetSystemProperties`).
+    ///
+    /// Only the names; use [`get_system_properties_map`](Self::get_system_properties_map)
+    /// for the values as well.
     pub fn get_system_properties(&self) -> Result<Vec<String>, jvmti::jvmtiError> {
         let mut count: jni::jint = 0;
         let mut props_ptr: *mut *mut std::os::raw::c_char = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetSystemProperties.unwrap();
+            let get_fn = (*self.functions).GetSystemProperties.unwrap();
             let err = get_fn(self.env, &mut count, &mut props_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let mut props = Vec::with_capacity(count as usize);
@@ -2013,7 +4983,7 @@ impl Jvmti {
             let base = props_ptr as *const u8;
             let len = (count as usize) * std::mem::size_of::<*mut std::os::raw::c_char>();
             for &p_ptr in &prop_ptrs {
-                props.push(std::ffi::CStr::from_ptr(p_ptr).to_string_lossy().into_owned());
+                props.push(mutf8::from_cstr(p_ptr));
                 if !ptr_in_range(p_ptr as *const u8, base, len) {
                     self.deallocate(p_ptr as *mut u8)?;
                 }
@@ -2024,23 +4994,50 @@ impl Jvmti {
     }
 
     pub fn get_system_property(&self, property: &str) -> Result<String, jvmti::jvmtiError> {
-        let c_property = CString::new(property).map_err(|_| jvmti::jvmtiError::ILLEGAL_ARGUMENT)?;
+        let c_property = mutf8::to_cstring(property);
         let mut value_ptr: *mut std::os::raw::c_char = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetSystemProperty.unwrap();
+            let get_fn = (*self.functions).GetSystemProperty.unwrap();
             let err = get_fn(self.env, c_property.as_ptr(), &mut value_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
-            let value = std::ffi::CStr::from_ptr(value_ptr).to_string_lossy().into_owned();
+            let value = mutf8::from_cstr(value_ptr);
             self.deallocate(value_ptr as *mut u8)?;
             Ok(value)
         }
     }
 
+    /// Returns every system property with its value.
+    ///
+    /// Fetches each name from [`get_system_properties`](Self::get_system_properties)
+    /// with [`get_system_property`](Self::get_system_property); properties that
+    /// report `NOT_AVAILABLE` (no value yet) are left out. These are the VM's
+    /// properties, which during `OnLoad` may differ from what
+    /// `System.getProperties()` later returns.
+    pub fn get_system_properties_map(&self) -> Result<HashMap<String, String>, jvmti::jvmtiError> {
+        let names = self.get_system_properties()?;
+        let mut properties = HashMap::with_capacity(names.len());
+        for name in names {
+            match self.get_system_property(&name) {
+                Ok(value) => {
+                    properties.insert(name, value);
+                }
+                Err(jvmti::jvmtiError::NOT_AVAILABLE) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(properties)
+    }
+
+    /// Sets a VM system property (`SetSystemProperty`).
+    ///
+    /// Only callable during `OnLoad`, and only for properties the VM marks
+    /// writeable; others fail with `WRONG_PHASE` or `NOT_AVAILABLE`. The set
+    /// of writeable properties is VM-specific.
     pub fn set_system_property(&self, property: &str, value: &str) -> Result<(), jvmti::jvmtiError> {
-        let c_property = CString::new(property).map_err(|_| jvmti::jvmtiError::ILLEGAL_ARGUMENT)?;
-        let c_value = CString::new(value).map_err(|_| jvmti::jvmtiError::ILLEGAL_ARGUMENT)?;
+        let c_property = mutf8::to_cstring(property);
+        let c_value = mutf8::to_cstring(value);
         unsafe {
-            let set_fn = (*(*self.env).functions).SetSystemProperty.unwrap();
+            let set_fn = (*self.functions).SetSystemProperty.unwrap();
             let err = set_fn(self.env, c_property.as_ptr(), c_value.as_ptr());
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -2050,17 +5047,41 @@ impl Jvmti {
     pub fn get_phase(&self) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut phase: jni::jint = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetPhase.unwrap();
+            let get_fn = (*self.functions).GetPhase.unwrap();
             let err = get_fn(self.env, &mut phase);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(phase)
         }
     }
 
+    /// [`get_phase`](Self::get_phase), decoded. Unknown values give `INTERNAL`.
+    pub fn current_phase(&self) -> Result<Phase, jvmti::jvmtiError> {
+        Phase::from_raw(self.get_phase()?).ok_or(jvmti::jvmtiError::INTERNAL)
+    }
+
+    /// Returns `WRONG_PHASE` unless the VM is in the live phase.
+    ///
+    /// Most JVMTI functions (heap walking, redefinition, stack traces of other
+    /// threads, ...) are only available from `VMInit` until `VMDeath`; calling
+    /// them from `Agent_OnLoad` fails with `WRONG_PHASE`. The high-level
+    /// helpers check this up front and log which `function` was called in
+    /// which phase.
+    pub fn require_live_phase(&self, function: &str) -> Result<(), jvmti::jvmtiError> {
+        let phase = self.current_phase()?;
+        if phase == Phase::Live {
+            return Ok(());
+        }
+        crate::log(
+            LogLevel::Warn,
+            format_args!("{function} requires the live phase, but the VM is in the {phase} phase; call it from VMInit or later"),
+        );
+        Err(jvmti::jvmtiError::WRONG_PHASE)
+    }
+
     pub fn get_current_thread_cpu_timer_info(&self) -> Result<jvmti::jvmtiTimerInfo, jvmti::jvmtiError> {
         let mut info = jvmti::jvmtiTimerInfo { max_value: 0, may_skip_forward: 0, may_skip_backward: 0, kind: 0 };
         unsafe {
-            let get_fn = (*(*self.env).functions).GetCurrentThreadCpuTimerInfo.unwrap();
+            let get_fn = (*self.functions).GetCurrentThreadCpuTimerInfo.unwrap();
             let err = get_fn(self.env, &mut info);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(info)
@@ -2070,7 +5091,7 @@ impl Jvmti {
     pub fn get_current_thread_cpu_time(&self) -> Result<jni::jlong, jvmti::jvmtiError> {
         let mut nanos: jni::jlong = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetCurrentThreadCpuTime.unwrap();
+            let get_fn = (*self.functions).GetCurrentThreadCpuTime.unwrap();
             let err = get_fn(self.env, &mut nanos);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(nanos)
@@ -2080,7 +5101,7 @@ impl Jvmti {
     pub fn get_thread_cpu_timer_info(&self) -> Result<jvmti::jvmtiTimerInfo, jvmti::jvmtiError> {
         let mut info = jvmti::jvmtiTimerInfo { max_value: 0, may_skip_forward: 0, may_skip_backward: 0, kind: 0 };
         unsafe {
-            let get_fn = (*(*self.env).functions).GetThreadCpuTimerInfo.unwrap();
+            let get_fn = (*self.functions).GetThreadCpuTimerInfo.unwrap();
             let err = get_fn(self.env, &mut info);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(info)
@@ -2090,17 +5111,42 @@ impl Jvmti {
     pub fn get_thread_cpu_time(&self, thread: jni::jthread) -> Result<jni::jlong, jvmti::jvmtiError> {
         let mut nanos: jni::jlong = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetThreadCpuTime.unwrap();
+            let get_fn = (*self.functions).GetThreadCpuTime.unwrap();
             let err = get_fn(self.env, thread, &mut nanos);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(nanos)
         }
     }
 
+    /// Returns the CPU time, in nanoseconds, of every live platform thread.
+    ///
+    /// Joins [`get_all_threads`](Self::get_all_threads) with
+    /// [`get_thread_cpu_time`](Self::get_thread_cpu_time). Returns
+    /// `MUST_POSSESS_CAPABILITY` unless `can_get_thread_cpu_time` has been
+    /// added; threads that terminate before they are queried are left out.
+    /// The threads are local references, as with `get_all_threads`. See
+    /// `profiling::CpuSampler` (`profiling` feature) for per-thread deltas
+    /// between calls.
+    pub fn cpu_times(&self) -> Result<Vec<(jni::jthread, jni::jlong)>, jvmti::jvmtiError> {
+        if !self.get_capabilities()?.can_get_thread_cpu_time() {
+            return Err(jvmti::jvmtiError::MUST_POSSESS_CAPABILITY);
+        }
+        let threads = self.get_all_threads()?;
+        let mut times = Vec::with_capacity(threads.len());
+        for thread in threads {
+            match self.get_thread_cpu_time(thread) {
+                Ok(nanos) => times.push((thread, nanos)),
+                Err(jvmti::jvmtiError::THREAD_NOT_ALIVE) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(times)
+    }
+
     pub fn get_timer_info(&self) -> Result<jvmti::jvmtiTimerInfo, jvmti::jvmtiError> {
         let mut info = jvmti::jvmtiTimerInfo { max_value: 0, may_skip_forward: 0, may_skip_backward: 0, kind: 0 };
         unsafe {
-            let get_fn = (*(*self.env).functions).GetTimerInfo.unwrap();
+            let get_fn = (*self.functions).GetTimerInfo.unwrap();
             let err = get_fn(self.env, &mut info);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(info)
@@ -2110,16 +5156,43 @@ impl Jvmti {
     pub fn get_time(&self) -> Result<jni::jlong, jvmti::jvmtiError> {
         let mut nanos: jni::jlong = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetTime.unwrap();
+            let get_fn = (*self.functions).GetTime.unwrap();
             let err = get_fn(self.env, &mut nanos);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(nanos)
         }
     }
 
+    /// Like [`get_timer_info`](Self::get_timer_info), decoded as [`TimerInfo`].
+    pub fn get_timer_info_decoded(&self) -> Result<TimerInfo, jvmti::jvmtiError> {
+        self.get_timer_info().map(TimerInfo::from)
+    }
+
+    /// The [`get_time`](Self::get_time) clock in nanoseconds, for stamping
+    /// events and samples.
+    ///
+    /// The origin is arbitrary, so only differences between readings mean
+    /// anything; use [`elapsed_since`](Self::elapsed_since). Whether the clock
+    /// can go backward is reported by
+    /// [`TimerInfo::is_monotonic`] for [`get_timer_info_decoded`](Self::get_timer_info_decoded).
+    /// Returns 0 if `GetTime` fails, which it only does for an invalid environment.
+    pub fn timestamp(&self) -> u64 {
+        self.get_time().map_or(0, |nanos| nanos as u64)
+    }
+
+    /// Time elapsed since `start_nanos`, a value returned by
+    /// [`timestamp`](Self::timestamp).
+    ///
+    /// Zero if the clock reads earlier than `start_nanos`, which a
+    /// non-monotonic timer may do.
+    pub fn elapsed_since(&self, start_nanos: u64) -> Duration {
+        let elapsed = self.timestamp().wrapping_sub(start_nanos) as i64;
+        Duration::from_nanos(elapsed.max(0) as u64)
+    }
+
     pub fn relinquish_capabilities(&self, caps: &jvmti::jvmtiCapabilities) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let rel_fn = (*(*self.env).functions).RelinquishCapabilities.unwrap();
+            let rel_fn = (*self.functions).RelinquishCapabilities.unwrap();
             let err = rel_fn(self.env, caps);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -2129,7 +5202,7 @@ impl Jvmti {
     pub fn get_available_processors(&self) -> Result<jni::jint, jvmti::jvmtiError> {
         let mut processors: jni::jint = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetAvailableProcessors.unwrap();
+            let get_fn = (*self.functions).GetAvailableProcessors.unwrap();
             let err = get_fn(self.env, &mut processors);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(processors)
@@ -2140,7 +5213,7 @@ impl Jvmti {
         let mut minor: jni::jint = 0;
         let mut major: jni::jint = 0;
         unsafe {
-            let get_fn = (*(*self.env).functions).GetClassVersionNumbers.unwrap();
+            let get_fn = (*self.functions).GetClassVersionNumbers.unwrap();
             let err = get_fn(self.env, klass, &mut minor, &mut major);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok((minor, major))
@@ -2148,25 +5221,41 @@ impl Jvmti {
     }
 
     pub fn get_constant_pool(&self, klass: jni::jclass) -> Result<Vec<u8>, jvmti::jvmtiError> {
+        self.get_constant_pool_with_count(klass).map(|(_, bytes)| bytes)
+    }
+
+    /// Returns a loaded class's constant pool, parsed.
+    ///
+    /// Lets an agent inspect what a class references without its original
+    /// class file. The VM may have rewritten the pool, so indices need not
+    /// match the class file's. Needs `can_get_constant_pool`; a pool the
+    /// parser rejects is reported as `INTERNAL`.
+    pub fn get_constant_pool_parsed(&self, klass: jni::jclass) -> Result<ConstantPool, jvmti::jvmtiError> {
+        let (count, bytes) = self.get_constant_pool_with_count(klass)?;
+        let count = u16::try_from(count).map_err(|_| jvmti::jvmtiError::INTERNAL)?;
+        ConstantPool::parse_entries(count, &bytes).map_err(|_| jvmti::jvmtiError::INTERNAL)
+    }
+
+    fn get_constant_pool_with_count(&self, klass: jni::jclass) -> Result<(jni::jint, Vec<u8>), jvmti::jvmtiError> {
         let mut pool_count: jni::jint = 0;
         let mut byte_count: jni::jint = 0;
         let mut bytes_ptr: *mut u8 = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetConstantPool.unwrap();
+            let get_fn = (*self.functions).GetConstantPool.unwrap();
             let err = get_fn(self.env, klass, &mut pool_count, &mut byte_count, &mut bytes_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let bytes = jvmti_array_to_vec(bytes_ptr, byte_count)?;
             if !bytes_ptr.is_null() {
                 self.deallocate(bytes_ptr)?;
             }
-            Ok(bytes)
+            Ok((pool_count, bytes))
         }
     }
 
     pub fn get_environment_local_storage(&self) -> Result<*mut std::os::raw::c_void, jvmti::jvmtiError> {
         let mut data: *mut std::os::raw::c_void = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetEnvironmentLocalStorage.unwrap();
+            let get_fn = (*self.functions).GetEnvironmentLocalStorage.unwrap();
             let err = get_fn(self.env, &mut data);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             Ok(data)
@@ -2175,17 +5264,25 @@ impl Jvmti {
 
     pub fn set_environment_local_storage(&self, data: *const std::os::raw::c_void) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetEnvironmentLocalStorage.unwrap();
+            let set_fn = (*self.functions).SetEnvironmentLocalStorage.unwrap();
             let err = set_fn(self.env, data);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
+    /// Appends `segment` to the bootstrap class loader's search path.
+    ///
+    /// In the `OnLoad` phase any platform path segment (a JAR or a directory)
+    /// is accepted. In the live phase it must be a JAR file, which should not
+    /// contain classes the bootstrap loader has already failed to find, as
+    /// the VM may have cached that failure. The VM resolves `segment` lazily,
+    /// so a bad path only shows up as a `NoClassDefFoundError` later; prefer
+    /// [`Jvmti::add_to_bootstrap_class_loader_search_path`].
     pub fn add_to_bootstrap_class_loader_search(&self, segment: &str) -> Result<(), jvmti::jvmtiError> {
         let c_segment = CString::new(segment).map_err(|_| jvmti::jvmtiError::ILLEGAL_ARGUMENT)?;
         unsafe {
-            let add_fn = (*(*self.env).functions).AddToBootstrapClassLoaderSearch.unwrap();
+            let add_fn = (*self.functions).AddToBootstrapClassLoaderSearch.unwrap();
             let err = add_fn(self.env, c_segment.as_ptr());
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -2194,28 +5291,50 @@ impl Jvmti {
 
     pub fn set_verbose_flag(&self, flag: jni::jint, value: bool) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let set_fn = (*(*self.env).functions).SetVerboseFlag.unwrap();
+            let set_fn = (*self.functions).SetVerboseFlag.unwrap();
             let err = set_fn(self.env, flag, if value { 1 } else { 0 });
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
+    /// Appends `segment` to the system class loader's search path.
+    ///
+    /// In the `OnLoad` phase any platform path segment is accepted. In the
+    /// live phase it must be a JAR file, and the system class loader must
+    /// support `appendToClassPathForInstrumentation`, otherwise this returns
+    /// `CLASS_LOADER_UNSUPPORTED`. Like the bootstrap variant, a bad path is
+    /// not reported here; prefer
+    /// [`Jvmti::add_to_system_class_loader_search_path`].
     pub fn add_to_system_class_loader_search(&self, segment: &str) -> Result<(), jvmti::jvmtiError> {
         let c_segment = CString::new(segment).map_err(|_| jvmti::jvmtiError::ILLEGAL_ARGUMENT)?;
         unsafe {
-            let add_fn = (*(*self.env).functions).AddToSystemClassLoaderSearch.unwrap();
+            let add_fn = (*self.functions).AddToSystemClassLoaderSearch.unwrap();
             let err = add_fn(self.env, c_segment.as_ptr());
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
+    /// Like [`Jvmti::add_to_bootstrap_class_loader_search`], but canonicalizes
+    /// `path` and checks that it exists and can be read first, so a relative
+    /// or misspelled path fails here with an `io::Error` rather than at class
+    /// load time.
+    pub fn add_to_bootstrap_class_loader_search_path(&self, path: &Path) -> Result<(), SearchPathError> {
+        Ok(self.add_to_bootstrap_class_loader_search(&readable_segment(path)?)?)
+    }
+
+    /// Like [`Jvmti::add_to_system_class_loader_search`], with the checks of
+    /// [`Jvmti::add_to_bootstrap_class_loader_search_path`].
+    pub fn add_to_system_class_loader_search_path(&self, path: &Path) -> Result<(), SearchPathError> {
+        Ok(self.add_to_system_class_loader_search(&readable_segment(path)?)?)
+    }
+
     pub fn get_owned_monitor_stack_depth_info(&self, thread: jni::jthread) -> Result<Vec<jvmti::jvmtiMonitorStackDepthInfo>, jvmti::jvmtiError> {
         let mut count: jni::jint = 0;
         let mut info_ptr: *mut jvmti::jvmtiMonitorStackDepthInfo = ptr::null_mut();
         unsafe {
-            let get_fn = (*(*self.env).functions).GetOwnedMonitorStackDepthInfo.unwrap();
+            let get_fn = (*self.functions).GetOwnedMonitorStackDepthInfo.unwrap();
             let err = get_fn(self.env, thread, &mut count, &mut info_ptr);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
             let info = jvmti_array_to_vec(info_ptr, count)?;
@@ -2243,9 +5362,9 @@ impl Jvmti {
     /// If prefix is "wrapped_" and native method is `native void foo()`,
     /// the JVM will first look for `wrapped_foo` before `foo`.
     pub fn set_native_method_prefix(&self, prefix: &str) -> Result<(), jvmti::jvmtiError> {
-        let c_prefix = std::ffi::CString::new(prefix).map_err(|_| jvmti::jvmtiError::NULL_POINTER)?;
+        let c_prefix = mutf8::to_cstring(prefix);
         unsafe {
-            let set_fn = (*(*self.env).functions).SetNativeMethodPrefix.unwrap();
+            let set_fn = (*self.functions).SetNativeMethodPrefix.unwrap();
             let err = set_fn(self.env, c_prefix.as_ptr() as *mut _);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -2259,16 +5378,13 @@ impl Jvmti {
     ///
     /// Requires `can_set_native_method_prefix` capability.
     pub fn set_native_method_prefixes(&self, prefixes: &[&str]) -> Result<(), jvmti::jvmtiError> {
-        let c_prefixes: Vec<std::ffi::CString> = prefixes
-            .iter()
-            .map(|p| std::ffi::CString::new(*p).map_err(|_| jvmti::jvmtiError::NULL_POINTER))
-            .collect::<Result<Vec<_>, _>>()?;
+        let c_prefixes: Vec<std::ffi::CString> = prefixes.iter().map(|p| mutf8::to_cstring(p)).collect();
         let mut prefix_ptrs: Vec<*mut std::os::raw::c_char> = c_prefixes
             .iter()
             .map(|s: &std::ffi::CString| s.as_ptr() as *mut std::os::raw::c_char)
             .collect();
         unsafe {
-            let set_fn = (*(*self.env).functions).SetNativeMethodPrefixes.unwrap();
+            let set_fn = (*self.functions).SetNativeMethodPrefixes.unwrap();
             let err = set_fn(self.env, prefixes.len() as jni::jint, prefix_ptrs.as_mut_ptr());
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
@@ -2284,17 +5400,206 @@ impl Jvmti {
     /// This removes all frame pop notifications that were requested via
     /// `notify_frame_pop` for the specified thread.
     ///
-    /// **Note**: This function was added in JDK 27. Calling it on older JVMs
-    /// will result in a null pointer dereference or undefined behavior.
+    /// **Note**: This function was added in JDK 27. On older JVMs the slot is
+    /// reserved and this returns `NOT_AVAILABLE`.
     ///
     /// Requires `can_generate_frame_pop_events` capability.
     pub fn clear_all_frame_pops(&self, thread: jni::jthread) -> Result<(), jvmti::jvmtiError> {
         unsafe {
-            let clear_fn = (*(*self.env).functions).ClearAllFramePops.unwrap();
+            let clear_fn = fn_or_unavailable((*self.functions).ClearAllFramePops)?;
             let err = clear_fn(self.env, thread);
             if err != jvmti::jvmtiError::NONE { return Err(err); }
         }
         Ok(())
     }
 
+    // =========================================================================
+    // Scoped Guards
+    // =========================================================================
+
+    /// Enters a raw monitor and returns a guard that exits it when dropped.
+    ///
+    /// The guard also exposes `wait`/`notify`/`notify_all`, so condition-variable
+    /// style code cannot forget to exit the monitor on an early return.
+    pub fn enter_raw_monitor(&self, monitor: jvmti::jrawMonitorID) -> Result<RawMonitorGuard<'_>, jvmti::jvmtiError> {
+        self.raw_monitor_enter(monitor)?;
+        Ok(RawMonitorGuard { jvmti: self, monitor })
+    }
+
+    /// Suspends a thread and returns a guard that resumes it when dropped.
+    ///
+    /// Requires `can_suspend`. Use this instead of pairing
+    /// `suspend_thread`/`resume_thread` by hand so that error paths cannot
+    /// leave the target thread suspended.
+    pub fn suspend_thread_scoped(&self, thread: jni::jthread) -> Result<SuspendGuard<'_>, jvmti::jvmtiError> {
+        self.suspend_thread(thread)?;
+        Ok(SuspendGuard { jvmti: self, thread })
+    }
+
+    /// Suspends a list of threads and returns a guard that resumes them when dropped.
+    ///
+    /// Requires `can_suspend`. Only threads whose per-thread result was
+    /// `NONE` are resumed on drop; the individual results are available via
+    /// [`SuspendListGuard::results`].
+    pub fn suspend_thread_list_scoped(&self, threads: &[jni::jthread]) -> Result<SuspendListGuard<'_>, jvmti::jvmtiError> {
+        let results = self.suspend_thread_list(threads)?;
+        let suspended = threads
+            .iter()
+            .zip(&results)
+            .filter(|(_, err)| **err == jvmti::jvmtiError::NONE)
+            .map(|(thread, _)| *thread)
+            .collect();
+        Ok(SuspendListGuard { jvmti: self, suspended, results })
+    }
+
+}
+
+/// RAII guard for an entered JVMTI raw monitor.
+///
+/// Created by [`Jvmti::enter_raw_monitor`]. Dropping the guard calls
+/// `RawMonitorExit`; a failure there is logged.
+pub struct RawMonitorGuard<'a> {
+    jvmti: &'a Jvmti,
+    monitor: jvmti::jrawMonitorID,
+}
+
+impl RawMonitorGuard<'_> {
+    /// The monitor held by this guard.
+    pub fn monitor(&self) -> jvmti::jrawMonitorID {
+        self.monitor
+    }
+
+    /// Waits on the monitor (`RawMonitorWait`). `millis <= 0` waits indefinitely.
+    ///
+    /// The monitor is released while waiting and re-entered before returning.
+    pub fn wait(&self, millis: jni::jlong) -> Result<(), jvmti::jvmtiError> {
+        self.jvmti.raw_monitor_wait(self.monitor, millis)
+    }
+
+    /// Wakes one thread waiting on the monitor.
+    pub fn notify(&self) -> Result<(), jvmti::jvmtiError> {
+        self.jvmti.raw_monitor_notify(self.monitor)
+    }
+
+    /// Wakes all threads waiting on the monitor.
+    pub fn notify_all(&self) -> Result<(), jvmti::jvmtiError> {
+        self.jvmti.raw_monitor_notify_all(self.monitor)
+    }
+}
+
+impl Drop for RawMonitorGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.jvmti.raw_monitor_exit(self.monitor) {
+            crate::log(LogLevel::Error, format_args!("RawMonitorExit failed: {err}"));
+        }
+    }
+}
+
+/// RAII guard for a suspended thread.
+///
+/// Created by [`Jvmti::suspend_thread_scoped`]. Dropping the guard calls
+/// `ResumeThread`; a failure there is logged.
+pub struct SuspendGuard<'a> {
+    jvmti: &'a Jvmti,
+    thread: jni::jthread,
+}
+
+impl SuspendGuard<'_> {
+    /// The thread held suspended by this guard.
+    pub fn thread(&self) -> jni::jthread {
+        self.thread
+    }
+}
+
+impl Drop for SuspendGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.jvmti.resume_thread(self.thread) {
+            crate::log(LogLevel::Error, format_args!("ResumeThread failed: {err}"));
+        }
+    }
+}
+
+/// RAII guard for a list of suspended threads.
+///
+/// Created by [`Jvmti::suspend_thread_list_scoped`]. Dropping the guard calls
+/// `ResumeThreadList` for the threads that were successfully suspended;
+/// failures are logged.
+pub struct SuspendListGuard<'a> {
+    jvmti: &'a Jvmti,
+    suspended: Vec<jni::jthread>,
+    results: Vec<jvmti::jvmtiError>,
+}
+
+impl SuspendListGuard<'_> {
+    /// Threads that were successfully suspended and will be resumed on drop.
+    pub fn suspended(&self) -> &[jni::jthread] {
+        &self.suspended
+    }
+
+    /// Per-thread results of `SuspendThreadList`, in request order.
+    pub fn results(&self) -> &[jvmti::jvmtiError] {
+        &self.results
+    }
+}
+
+impl Drop for SuspendListGuard<'_> {
+    fn drop(&mut self) {
+        if self.suspended.is_empty() {
+            return;
+        }
+        match self.jvmti.resume_thread_list(&self.suspended) {
+            Ok(results) => {
+                for err in results.into_iter().filter(|err| *err != jvmti::jvmtiError::NONE) {
+                    crate::log(LogLevel::Error, format_args!("ResumeThreadList failed for a thread: {err}"));
+                }
+            }
+            Err(err) => crate::log(LogLevel::Error, format_args!("ResumeThreadList failed: {err}")),
+        }
+    }
+}
+
+type AgentThreadTask = Box<dyn FnOnce() + Send>;
+
+unsafe extern "system" fn agent_thread_trampoline(
+    _jvmti_env: *mut jvmti::jvmtiEnv,
+    _jni_env: *mut jni::JNIEnv,
+    arg: *mut std::os::raw::c_void,
+) {
+    // SAFETY: `arg` is the box leaked by `spawn_agent_thread`; the VM calls
+    // the start function exactly once.
+    let task = unsafe { Box::from_raw(arg as *mut AgentThreadTask) };
+    crate::guard_agent_call("agent_thread", (), task);
+}
+
+/// An owned JVMTI environment that calls `DisposeEnvironment` when dropped.
+///
+/// Created by [`Jvmti::new_owned`]; dereferences to [`Jvmti`]. A failure to
+/// dispose is logged.
+pub struct DisposeGuard {
+    jvmti: Jvmti,
+}
+
+impl DisposeGuard {
+    /// Releases ownership without disposing the environment.
+    pub fn into_inner(self) -> Jvmti {
+        let jvmti = Jvmti { env: self.jvmti.env, functions: self.jvmti.functions };
+        std::mem::forget(self);
+        jvmti
+    }
+}
+
+impl std::ops::Deref for DisposeGuard {
+    type Target = Jvmti;
+
+    fn deref(&self) -> &Jvmti {
+        &self.jvmti
+    }
+}
+
+impl Drop for DisposeGuard {
+    fn drop(&mut self) {
+        if let Err(err) = self.jvmti.dispose_environment() {
+            crate::log(LogLevel::Error, format_args!("DisposeEnvironment failed: {err}"));
+        }
+    }
 }
@@ -168,7 +168,7 @@
 //!     // Call a method
 //!     let print_class = jni.find_class("java/io/PrintStream").unwrap();
 //!     let println_method = jni.get_method_id(print_class, "println", "(Ljava/lang/String;)V").unwrap();
-//!     jni.call_void_method(out, println_method, &[jni::jvalue { l: message }]);
+//!     jni.call_void_method(out, println_method, &jvalues![message]);
 //!
 //!     // Check for exceptions
 //!     if jni.exception_check() {
@@ -193,6 +193,9 @@ pub mod sys;
 pub mod env;
 pub mod classfile;
 pub mod prelude;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+pub mod mutf8;
 #[cfg(feature = "embed")]
 pub mod embed;
 #[cfg(feature = "advanced")]
@@ -203,8 +206,22 @@ pub mod advanced;
 pub(crate) mod jvmti_wrapper;
 #[doc(hidden)]
 pub(crate) mod jni_wrapper;
+#[doc(hidden)]
+pub(crate) mod event_registry;
+#[doc(hidden)]
+pub(crate) mod object_tagger;
+#[doc(hidden)]
+pub(crate) mod class_cache;
+#[doc(hidden)]
+pub(crate) mod member_ids;
+#[doc(hidden)]
+pub(crate) mod class_transformers;
+#[cfg(feature = "serde")]
+pub(crate) mod serde_support;
 
-use std::sync::OnceLock;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 pub use crate::sys::jni as jni;
 use crate::sys::jvmti as jvmti;
 
@@ -215,6 +232,34 @@ pub fn describe_jni_result(code: jni::jint) -> String {
     jni::describe_result(code)
 }
 
+/// Borrow the `class_data`/`class_data_len` pair of a `ClassFileLoadHook` as a byte slice.
+///
+/// A null pointer or non-positive length gives `&[]`. The slice can be handed
+/// straight to [`classfile::ClassFile::parse`] to inspect a class before
+/// deciding whether to transform it.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes that stay valid for `'a`; the
+/// VM's buffer is only valid until the callback returns.
+pub unsafe fn class_data_slice<'a>(data: *const std::os::raw::c_uchar, len: jni::jint) -> &'a [u8] {
+    raw_slice(data, len)
+}
+
+/// Borrow the `name` argument of a `ClassFileLoadHook` as an internal class name
+/// (`java/lang/String`).
+///
+/// Returns `None` if the VM passed no name or it is not valid UTF-8. The
+/// name is [modified UTF-8](mutf8), which only differs for names with
+/// supplementary characters; use [`mutf8::from_cstr`] to decode those.
+///
+/// # Safety
+///
+/// `name` must be null or a NUL-terminated string that stays valid for `'a`.
+pub unsafe fn class_name<'a>(name: *const std::os::raw::c_char) -> Option<&'a str> {
+    if name.is_null() { None } else { std::ffi::CStr::from_ptr(name).to_str().ok() }
+}
+
 /// The core trait for implementing a JVMTI agent.
 ///
 /// Implement this trait and use [`export_agent!`] to create a loadable agent library.
@@ -250,6 +295,28 @@ pub fn describe_jni_result(code: jni::jint) -> String {
 /// export_agent!(MyProfiler);
 /// ```
 ///
+/// # Safe Wrappers
+///
+/// Every event that carries a `JNIEnv*` also has a `*_with_env` variant that
+/// receives `&`[`env::Jvmti`] and `&`[`env::JniEnv`] built from the callback's
+/// own environments. The trampolines call the `*_with_env` variant, whose
+/// default forwards to the raw-pointer method, so override whichever form you
+/// prefer:
+///
+/// ```rust,ignore
+/// impl Agent for MyProfiler {
+///     fn on_load(&self, _vm: *mut jni::JavaVM, _options: &str) -> jni::jint {
+///         jni::JNI_OK
+///     }
+///
+///     fn class_prepare_with_env(&self, jvmti: &Jvmti, _jni: &JniEnv, _thread: jni::jthread, klass: jni::jclass) {
+///         if let Ok((sig, _)) = jvmti.get_class_signature(klass) {
+///             println!("prepared {sig}");
+///         }
+///     }
+/// }
+/// ```
+///
 /// # Capabilities
 ///
 /// Many events require specific JVMTI capabilities to be enabled. Use
@@ -268,16 +335,63 @@ pub trait Agent: Sync + Send {
 
     /// Called when the agent is attached to a running JVM (dynamic attach).
     ///
-    /// Default implementation returns `JNI_OK`.
-    fn on_attach(&self, _vm: *mut jni::JavaVM, _options: &str) -> jni::jint {
-        jni::JNI_OK
+    /// The JVM calls `Agent_OnAttach` instead of `Agent_OnLoad` when the library
+    /// is loaded through the Attach API (`VirtualMachine.loadAgentPath`,
+    /// `jcmd <pid> JVMTI.agent_load`). The VM is already in the live phase, so:
+    ///
+    /// - `VMStart`/`VMInit` have already fired and will not be delivered.
+    /// - `ClassFileLoadHook` only sees classes loaded (or retransformed) from now on;
+    ///   use [`env::Jvmti::get_loaded_classes`] for the ones already loaded.
+    /// - `CompiledMethodLoad`/`DynamicCodeGenerated` for existing code can be
+    ///   replayed with [`env::Jvmti::generate_events`].
+    /// - OnLoad-only capabilities (`can_generate_early_vmstart`,
+    ///   `can_generate_early_class_hook_events`, and on HotSpot those that need
+    ///   interpreter-only execution, such as `can_access_local_variables` or
+    ///   `can_generate_single_step_events`) may be missing; check
+    ///   [`env::Jvmti::get_potential_capabilities`] first.
+    ///
+    /// Default implementation delegates to [`Agent::on_load`].
+    fn on_attach(&self, vm: *mut jni::JavaVM, options: &str) -> jni::jint {
+        self.on_load(vm, options)
     }
 
     /// Called when the agent is unloaded (JVM shutdown).
     ///
-    /// Use this for cleanup: flush buffers, close files, etc.
+    /// Use this for cleanup: flush buffers, close files, etc. JNI is no longer
+    /// usable here; release JNI resources in [`Agent::shutdown`] instead.
+    ///
+    /// The agent itself is not dropped: callbacks from other threads may still
+    /// be running, and they borrow it for `'static`.
     fn on_unload(&self) {}
 
+    /// Called once, right after [`Agent::vm_death`], with the dying VM's
+    /// [`env::JniEnv`].
+    ///
+    /// This is the place to release JNI state the agent holds: drop
+    /// [`env::GlobalRef`]s, [`ClassCache::clear`](env::ClassCache::clear)
+    /// caches, delete raw global references. `on_unload` runs too late for
+    /// that, and the agent is never dropped, so anything still held afterwards
+    /// leaks, which shows up in harnesses that create and destroy many VMs.
+    ///
+    /// ```rust,ignore
+    /// fn shutdown(&self, jni: &JniEnv) {
+    ///     self.classes.clear(jni);
+    ///     self.listener.lock().unwrap().take(); // Option<GlobalRef>
+    /// }
+    /// ```
+    fn shutdown(&self, _jni: &env::JniEnv) {}
+
+    /// Called from `JNI_OnLoad` when the library is also loaded with
+    /// `System.loadLibrary`, if it was built with [`export_jni_onload!`].
+    ///
+    /// Return the JNI version the library needs, or [`jni::JNI_ERR`] to make
+    /// `loadLibrary` fail. This may run before, after, or without
+    /// [`Agent::on_load`], depending on whether the library was also passed
+    /// with `-agentpath`. Default implementation returns [`jni::JNI_VERSION_1_8`].
+    fn jni_on_load(&self, _vm: *mut jni::JavaVM) -> jni::jint {
+        jni::JNI_VERSION_1_8
+    }
+
     // =========================================================================
     // VM LIFECYCLE EVENTS
     // =========================================================================
@@ -297,6 +411,12 @@ pub trait Agent: Sync + Send {
         self.vm_init(jni, thread);
     }
 
+    /// Same as [`Agent::vm_init`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn vm_init_with_env(&self, jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread) {
+        self.vm_init_with_jvmti(jvmti.raw(), jni.raw(), thread);
+    }
+
     /// Called when the VM is about to terminate.
     ///
     /// This is your last chance to perform cleanup that requires JNI.
@@ -307,6 +427,12 @@ pub trait Agent: Sync + Send {
         self.vm_death(jni);
     }
 
+    /// Same as [`Agent::vm_death`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn vm_death_with_env(&self, jvmti: &env::Jvmti, jni: &env::JniEnv) {
+        self.vm_death_with_jvmti(jvmti.raw(), jni.raw());
+    }
+
     /// Called when the VM starts (before `vm_init`).
     ///
     /// JNI is available but limited - you cannot create new threads or load classes.
@@ -318,6 +444,12 @@ pub trait Agent: Sync + Send {
         self.vm_start(jni);
     }
 
+    /// Same as [`Agent::vm_start`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn vm_start_with_env(&self, jvmti: &env::Jvmti, jni: &env::JniEnv) {
+        self.vm_start_with_jvmti(jvmti.raw(), jni.raw());
+    }
+
     // =========================================================================
     // THREAD EVENTS
     // =========================================================================
@@ -327,9 +459,21 @@ pub trait Agent: Sync + Send {
     /// Fired for every thread including the main thread.
     fn thread_start(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread) {}
 
+    /// Same as [`Agent::thread_start`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn thread_start_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread) {
+        self.thread_start(jni.raw(), thread);
+    }
+
     /// Called when a thread is about to terminate.
     fn thread_end(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread) {}
 
+    /// Same as [`Agent::thread_end`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn thread_end_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread) {
+        self.thread_end(jni.raw(), thread);
+    }
+
     /// Called when a virtual thread starts (JDK 21+).
     ///
     /// Requires the `can_support_virtual_threads` capability. This event does
@@ -337,11 +481,23 @@ pub trait Agent: Sync + Send {
     /// version supports it.
     fn virtual_thread_start(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread) {}
 
+    /// Same as [`Agent::virtual_thread_start`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn virtual_thread_start_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread) {
+        self.virtual_thread_start(jni.raw(), thread);
+    }
+
     /// Called when a virtual thread terminates (JDK 21+).
     ///
     /// Requires the `can_support_virtual_threads` capability.
     fn virtual_thread_end(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread) {}
 
+    /// Same as [`Agent::virtual_thread_end`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn virtual_thread_end_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread) {
+        self.virtual_thread_end(jni.raw(), thread);
+    }
+
     // =========================================================================
     // CLASS EVENTS
     // =========================================================================
@@ -356,6 +512,12 @@ pub trait Agent: Sync + Send {
         self.class_load(jni, thread, klass);
     }
 
+    /// Same as [`Agent::class_load`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn class_load_with_env(&self, jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, klass: jni::jclass) {
+        self.class_load_with_jvmti(jvmti.raw(), jni.raw(), thread, klass);
+    }
+
     /// Called when a class is prepared (linked and ready to use).
     ///
     /// At this point you can query the class's methods and fields.
@@ -366,6 +528,12 @@ pub trait Agent: Sync + Send {
         self.class_prepare(jni, thread, klass);
     }
 
+    /// Same as [`Agent::class_prepare`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn class_prepare_with_env(&self, jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, klass: jni::jclass) {
+        self.class_prepare_with_jvmti(jvmti.raw(), jni.raw(), thread, klass);
+    }
+
     /// Called when class bytecode is being loaded or redefined.
     ///
     /// This is your hook for bytecode instrumentation (BCI). To modify the class:
@@ -373,6 +541,9 @@ pub trait Agent: Sync + Send {
     /// 2. Write your modified bytecode to it
     /// 3. Set `new_class_data_len` and `new_class_data`
     ///
+    /// Most transformers should implement [`Agent::transform_class`] instead,
+    /// which performs these steps for you.
+    ///
     /// Requires `can_generate_all_class_hook_events` or `can_retransform_classes`.
     fn class_file_load_hook(&self, _jni: *mut jni::JNIEnv, _class_being_redefined: jni::jclass,
                             _loader: jni::jobject, _name: *const std::os::raw::c_char,
@@ -397,6 +568,46 @@ pub trait Agent: Sync + Send {
         self.class_file_load_hook(jni, class_being_redefined, loader, name, protection_domain, class_data_len, class_data, new_class_data_len, new_class_data);
     }
 
+    /// Return replacement bytes for a class as it is loaded or redefined.
+    ///
+    /// This is the high-level alternative to [`Agent::class_file_load_hook`]:
+    /// `name` is the internal class name (`java/lang/String`, or `None` when the
+    /// VM does not supply one) and `class_data` the current class file bytes.
+    /// Returning `Some(bytes)` makes the trampoline copy them into memory from
+    /// [`env::Jvmti::allocate`] and set `new_class_data`/`new_class_data_len`;
+    /// `None` leaves the class unchanged.
+    ///
+    /// Agents that only transform some classes can parse first and decide:
+    ///
+    /// ```rust,ignore
+    /// fn transform_class(&self, name: Option<&str>, class_data: &[u8]) -> Option<Vec<u8>> {
+    ///     if !name?.starts_with("com/example/") {
+    ///         return None;
+    ///     }
+    ///     let class = ClassFile::parse(class_data).ok()?;
+    ///     // rewrite `class`, then
+    ///     class.to_bytes().ok()
+    /// }
+    /// ```
+    ///
+    /// Observers can return `None` unconditionally; this is also the simplest
+    /// way to see each class's name and bytes without touching raw pointers
+    /// (see [`class_name`] and [`class_data_slice`] for the raw hooks).
+    ///
+    /// It is only consulted when the `class_file_load_hook*` callbacks did not
+    /// already install new class data. Requires the same capabilities as
+    /// `ClassFileLoadHook`.
+    fn transform_class(&self, _name: Option<&str>, _class_data: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Same as [`Agent::class_file_load_hook`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    #[allow(clippy::too_many_arguments)]
+    fn class_file_load_hook_with_env(&self, jvmti: &env::Jvmti, jni: &env::JniEnv, class_being_redefined: jni::jclass, loader: jni::jobject, name: *const std::os::raw::c_char, protection_domain: jni::jobject, class_data_len: jni::jint, class_data: *const std::os::raw::c_uchar, new_class_data_len: *mut jni::jint, new_class_data: *mut *mut std::os::raw::c_uchar) {
+        self.class_file_load_hook_with_jvmti(jvmti.raw(), jni.raw(), class_being_redefined, loader, name, protection_domain, class_data_len, class_data, new_class_data_len, new_class_data);
+    }
+
     // =========================================================================
     // METHOD EVENTS
     // =========================================================================
@@ -412,6 +623,12 @@ pub trait Agent: Sync + Send {
         self.method_entry(jni, thread, method);
     }
 
+    /// Same as [`Agent::method_entry`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn method_entry_with_env(&self, jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, method: jni::jmethodID) {
+        self.method_entry_with_jvmti(jvmti.raw(), jni.raw(), thread, method);
+    }
+
     /// Called when a method is about to return.
     ///
     /// **Warning**: This fires for EVERY method return - extremely high overhead.
@@ -423,12 +640,24 @@ pub trait Agent: Sync + Send {
         self.method_exit(jni, thread, method);
     }
 
+    /// Same as [`Agent::method_exit`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn method_exit_with_env(&self, jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, method: jni::jmethodID) {
+        self.method_exit_with_jvmti(jvmti.raw(), jni.raw(), thread, method);
+    }
+
     /// Called when a native method is bound to its implementation.
     ///
     /// You can redirect native methods by setting `*new_address_ptr`.
     /// Requires `can_generate_native_method_bind_events` capability.
     fn native_method_bind(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _method: jni::jmethodID, _address: *mut std::os::raw::c_void, _new_address_ptr: *mut *mut std::os::raw::c_void) {}
 
+    /// Same as [`Agent::native_method_bind`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn native_method_bind_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, method: jni::jmethodID, address: *mut std::os::raw::c_void, new_address_ptr: *mut *mut std::os::raw::c_void) {
+        self.native_method_bind(jni.raw(), thread, method, address, new_address_ptr);
+    }
+
     // =========================================================================
     // COMPILED CODE EVENTS (JIT)
     // =========================================================================
@@ -439,6 +668,18 @@ pub trait Agent: Sync + Send {
     /// Requires `can_generate_compiled_method_load_events` capability.
     fn compiled_method_load(&self, _method: jni::jmethodID, _code_size: jni::jint, _code_addr: *const std::os::raw::c_void, _map_length: jni::jint, _map: *const std::os::raw::c_void, _compile_info: *const std::os::raw::c_void) {}
 
+    /// Same as [`Agent::compiled_method_load`], but with the generated code and
+    /// the `jvmtiAddrLocationMap` entries as slices.
+    ///
+    /// Each map entry gives the first native address of the code generated for
+    /// a bytecode `location`; the map may be empty. Both slices borrow VM memory
+    /// and are only valid for the duration of the callback.
+    fn compiled_method_loaded(&self, method: jni::jmethodID, code: &[u8], map: &[jvmti::jvmtiAddrLocationMap], compile_info: *const std::os::raw::c_void) {
+        let code_addr = if code.is_empty() { std::ptr::null() } else { code.as_ptr() as *const std::os::raw::c_void };
+        let map_ptr = if map.is_empty() { std::ptr::null() } else { map.as_ptr() as *const std::os::raw::c_void };
+        self.compiled_method_load(method, code.len() as jni::jint, code_addr, map.len() as jni::jint, map_ptr, compile_info);
+    }
+
     /// Called when JIT-compiled code is unloaded (deoptimized).
     fn compiled_method_unload(&self, _method: jni::jmethodID, _code_addr: *const std::os::raw::c_void) {}
 
@@ -447,9 +688,12 @@ pub trait Agent: Sync + Send {
 
     /// Called when the JVM requests that the agent dump diagnostic data.
     ///
-    /// This event can be generated by a platform-specific user signal or by
-    /// [`env::Jvmti::generate_events`]. The callback has no `JNIEnv`; keep it
-    /// bounded and defer expensive work where possible.
+    /// HotSpot sends this event when the process receives `SIGQUIT`
+    /// (`kill -QUIT <pid>` or `Ctrl-\`) on Unix or Ctrl-Break on Windows. It is
+    /// not produced by [`env::Jvmti::generate_events`]. No capability is
+    /// required; enable it with [`env::Jvmti::enable_data_dump_request_events`].
+    /// The callback has no `JNIEnv`; keep it bounded and defer expensive work
+    /// where possible.
     fn data_dump_request(&self) {}
 
     // =========================================================================
@@ -464,10 +708,23 @@ pub trait Agent: Sync + Send {
                  _location: jvmti::jlocation, _exception: jni::jobject,
                  _catch_method: jni::jmethodID, _catch_location: jvmti::jlocation) {}
 
+    /// Same as [`Agent::exception`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    #[allow(clippy::too_many_arguments)]
+    fn exception_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation, exception: jni::jobject, catch_method: jni::jmethodID, catch_location: jvmti::jlocation) {
+        self.exception(jni.raw(), thread, method, location, exception, catch_method, catch_location);
+    }
+
     /// Called when an exception is caught.
     fn exception_catch(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _method: jni::jmethodID,
                        _location: jvmti::jlocation, _exception: jni::jobject) {}
 
+    /// Same as [`Agent::exception_catch`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn exception_catch_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation, exception: jni::jobject) {
+        self.exception_catch(jni.raw(), thread, method, location, exception);
+    }
+
     // =========================================================================
     // DEBUGGING EVENTS
     // =========================================================================
@@ -478,17 +735,35 @@ pub trait Agent: Sync + Send {
     /// Requires `can_generate_single_step_events` capability.
     fn single_step(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _method: jni::jmethodID, _location: jvmti::jlocation) {}
 
+    /// Same as [`Agent::single_step`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn single_step_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation) {
+        self.single_step(jni.raw(), thread, method, location);
+    }
+
     /// Called when a breakpoint is hit.
     ///
     /// Requires `can_generate_breakpoint_events` capability.
     fn breakpoint(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _method: jni::jmethodID, _location: jvmti::jlocation) {}
 
+    /// Same as [`Agent::breakpoint`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn breakpoint_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation) {
+        self.breakpoint(jni.raw(), thread, method, location);
+    }
+
     /// Called when a frame is popped (method returns or exception thrown).
     ///
     /// Must be registered per-frame with `notify_frame_pop`.
     /// Requires `can_generate_frame_pop_events` capability.
     fn frame_pop(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _method: jni::jmethodID, _was_popped_by_exception: jni::jboolean) {}
 
+    /// Same as [`Agent::frame_pop`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn frame_pop_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, method: jni::jmethodID, was_popped_by_exception: jni::jboolean) {
+        self.frame_pop(jni.raw(), thread, method, was_popped_by_exception);
+    }
+
     // =========================================================================
     // MONITOR EVENTS
     // =========================================================================
@@ -498,17 +773,41 @@ pub trait Agent: Sync + Send {
     /// Requires `can_generate_monitor_events` capability.
     fn monitor_wait(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _object: jni::jobject, _timeout: jni::jlong) {}
 
+    /// Same as [`Agent::monitor_wait`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn monitor_wait_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, object: jni::jobject, timeout: jni::jlong) {
+        self.monitor_wait(jni.raw(), thread, object, timeout);
+    }
+
     /// Called when a thread finishes waiting on a monitor.
     ///
     /// `timed_out` indicates if the wait timed out.
     fn monitor_waited(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _object: jni::jobject, _timed_out: jni::jboolean) {}
 
+    /// Same as [`Agent::monitor_waited`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn monitor_waited_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, object: jni::jobject, timed_out: jni::jboolean) {
+        self.monitor_waited(jni.raw(), thread, object, timed_out);
+    }
+
     /// Called when a thread is about to block on a contended monitor.
     fn monitor_contended_enter(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _object: jni::jobject) {}
 
+    /// Same as [`Agent::monitor_contended_enter`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn monitor_contended_enter_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, object: jni::jobject) {
+        self.monitor_contended_enter(jni.raw(), thread, object);
+    }
+
     /// Called when a thread acquires a previously contended monitor.
     fn monitor_contended_entered(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _object: jni::jobject) {}
 
+    /// Same as [`Agent::monitor_contended_entered`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn monitor_contended_entered_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, object: jni::jobject) {
+        self.monitor_contended_entered(jni.raw(), thread, object);
+    }
+
     // =========================================================================
     // FIELD EVENTS (WATCHPOINTS)
     // =========================================================================
@@ -519,6 +818,13 @@ pub trait Agent: Sync + Send {
     fn field_access(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _method: jni::jmethodID,
                     _location: jvmti::jlocation, _field_klass: jni::jclass, _object: jni::jobject, _field: jni::jfieldID) {}
 
+    /// Same as [`Agent::field_access`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    #[allow(clippy::too_many_arguments)]
+    fn field_access_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation, field_klass: jni::jclass, object: jni::jobject, field: jni::jfieldID) {
+        self.field_access(jni.raw(), thread, method, location, field_klass, object, field);
+    }
+
     /// Called when a watched field is modified.
     ///
     /// Set up with `set_field_modification_watch`. Requires `can_generate_field_modification_events`.
@@ -526,6 +832,13 @@ pub trait Agent: Sync + Send {
                           _location: jvmti::jlocation, _field_klass: jni::jclass, _object: jni::jobject,
                           _field: jni::jfieldID, _sig_type: std::os::raw::c_char, _new_value: jni::jvalue) {}
 
+    /// Same as [`Agent::field_modification`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    #[allow(clippy::too_many_arguments)]
+    fn field_modification_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation, field_klass: jni::jclass, object: jni::jobject, field: jni::jfieldID, sig_type: std::os::raw::c_char, new_value: jni::jvalue) {
+        self.field_modification(jni.raw(), thread, method, location, field_klass, object, field, sig_type, new_value);
+    }
+
     // =========================================================================
     // GC & MEMORY EVENTS
     // =========================================================================
@@ -544,6 +857,29 @@ pub trait Agent: Sync + Send {
     /// Called when a critical resource is exhausted (heap, threads, etc.).
     fn resource_exhausted(&self, _jni: *mut jni::JNIEnv, _flags: jni::jint, _description: *const std::os::raw::c_char) {}
 
+    /// Same as [`Agent::resource_exhausted`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn resource_exhausted_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, flags: jni::jint, description: *const std::os::raw::c_char) {
+        self.resource_exhausted(jni.raw(), flags, description);
+    }
+
+    /// Typed alternative to [`Agent::resource_exhausted`]: `flags` tells heap
+    /// exhaustion from thread exhaustion, and `description` is the VM's
+    /// message, if any.
+    ///
+    /// ```rust,ignore
+    /// fn resource_exhausted_typed(&self, _jni: &JniEnv, flags: ResourceExhaustedFlags, description: Option<&str>) {
+    ///     if flags.is_java_heap() {
+    ///         alert(&format!("heap exhausted: {}", description.unwrap_or("?")));
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Called after the raw callbacks. Heap and thread exhaustion are only
+    /// reported with `can_generate_resource_exhaustion_heap_events` and
+    /// `can_generate_resource_exhaustion_threads_events`.
+    fn resource_exhausted_typed(&self, _jni: &env::JniEnv, _flags: env::ResourceExhaustedFlags, _description: Option<&str>) {}
+
     // =========================================================================
     // OBJECT EVENTS
     // =========================================================================
@@ -560,11 +896,36 @@ pub trait Agent: Sync + Send {
     /// Requires `can_generate_vm_object_alloc_events` capability.
     fn vm_object_alloc(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _object: jni::jobject, _klass: jni::jclass, _size: jni::jlong) {}
 
+    /// Same as [`Agent::vm_object_alloc`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn vm_object_alloc_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, object: jni::jobject, klass: jni::jclass, size: jni::jlong) {
+        self.vm_object_alloc(jni.raw(), thread, object, klass, size);
+    }
+
     /// Called for sampled object allocations (JDK 11+).
     ///
     /// Configure sampling rate with `set_heap_sampling_interval`.
     /// Requires `can_generate_sampled_object_alloc_events` capability.
     fn sampled_object_alloc(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _object: jni::jobject, _klass: jni::jclass, _size: jni::jlong) {}
+
+    /// Same as [`Agent::sampled_object_alloc`], but receives the safe [`env::Jvmti`] and
+    /// [`env::JniEnv`] wrappers instead of raw pointers.
+    fn sampled_object_alloc_with_env(&self, _jvmti: &env::Jvmti, jni: &env::JniEnv, thread: jni::jthread, object: jni::jobject, klass: jni::jclass, size: jni::jlong) {
+        self.sampled_object_alloc(jni.raw(), thread, object, klass, size);
+    }
+
+    // =========================================================================
+    // EXTENSION EVENTS
+    // =========================================================================
+
+    /// Called for a vendor extension event enabled with
+    /// [`env::Jvmti::enable_extension_event`].
+    ///
+    /// `index` is the VM-assigned extension event index and `params` holds the
+    /// event's arguments in declaration order, decoded from its
+    /// `ExtensionParamInfo` list. References and pointers are only valid for the
+    /// duration of the callback.
+    fn extension_event(&self, _index: jni::jint, _params: &[env::ExtensionArg]) {}
 }
 
 // 2. THE GLOBAL SINGLETON
@@ -576,205 +937,708 @@ pub fn set_global_agent(agent: Box<dyn Agent>) -> Result<(), ()> {
     GLOBAL_AGENT.set(agent).map_err(|_| ())
 }
 
+/// The registry installed by [`env::Jvmti::install_transformers`], consulted
+/// by the `ClassFileLoadHook` trampoline after the agent's own callbacks.
+/// Hooks in flight keep their own `Arc`, so uninstalling never waits on them.
+static CLASS_TRANSFORMERS: RwLock<Option<Arc<class_transformers::ClassTransformerRegistry>>> = RwLock::new(None);
+
+/// The installed transformer registry, if any.
+pub(crate) fn class_transformers() -> Option<Arc<class_transformers::ClassTransformerRegistry>> {
+    CLASS_TRANSFORMERS.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+/// Install the process-wide transformer registry; fails if one is installed.
+pub(crate) fn set_class_transformers(
+    registry: class_transformers::ClassTransformerRegistry,
+) -> Result<(), class_transformers::ClassTransformerRegistry> {
+    let mut slot = CLASS_TRANSFORMERS.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if slot.is_some() {
+        return Err(registry);
+    }
+    *slot = Some(Arc::new(registry));
+    Ok(())
+}
+
+/// Remove the process-wide transformer registry, returning it if one was installed.
+pub(crate) fn take_class_transformers() -> Option<Arc<class_transformers::ClassTransformerRegistry>> {
+    CLASS_TRANSFORMERS.write().unwrap_or_else(|poisoned| poisoned.into_inner()).take()
+}
+
+/// Set once [`Agent::shutdown`] has run, so a `VMDeath` delivered to several
+/// environments only shuts the agent down once.
+static SHUT_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// The `jvmtiEnv*` whose event callbacks were installed, stored as `usize` so
+/// the static is `Send + Sync`; zero when none is. Read it with
+/// [`env::Jvmti::current`].
+///
+/// Recorded by the first successful [`env::Jvmti::set_event_callbacks`] call
+/// (which `set_default_agent_callbacks` and `EventRegistry::install` go through)
+/// and cleared when that environment is disposed. The first environment wins
+/// for the whole process, so an agent that also creates environments with
+/// [`env::Jvmti::new_owned`] should install its main callbacks first.
+pub(crate) static GLOBAL_JVMTI: AtomicUsize = AtomicUsize::new(0);
+
+/// The callback tables installed through [`env::Jvmti::set_event_callbacks`],
+/// by environment, so helpers that rely on a trampoline can tell whether it
+/// is wired.
+static INSTALLED_CALLBACKS: Mutex<Vec<(usize, jvmti::jvmtiEventCallbacks)>> = Mutex::new(Vec::new());
+
+/// Record the callbacks `env` installed, or forget them with `None`.
+pub(crate) fn record_installed_callbacks(env: usize, callbacks: Option<jvmti::jvmtiEventCallbacks>) {
+    let mut installed = INSTALLED_CALLBACKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    installed.retain(|&(recorded, _)| recorded != env);
+    if let Some(callbacks) = callbacks {
+        installed.push((env, callbacks));
+    }
+}
+
+/// The callbacks `env` last installed through this crate, if any.
+pub(crate) fn installed_callbacks(env: usize) -> Option<jvmti::jvmtiEventCallbacks> {
+    let installed = INSTALLED_CALLBACKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    installed.iter().find(|&&(recorded, _)| recorded == env).map(|&(_, callbacks)| callbacks)
+}
+
+/// What the event trampolines do when an [`Agent`] callback panics.
+///
+/// Unwinding across the `extern "system"` boundary into the JVM is undefined
+/// behavior, so every callback is run under [`std::panic::catch_unwind`].
+/// The panic is always reported on stderr with the event name; the policy
+/// decides what happens next.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Drop the panic and return to the JVM as if the callback had finished.
+    Swallow,
+    /// Abort the process immediately.
+    Abort,
+}
+
+static PANIC_POLICY: AtomicU8 = AtomicU8::new(PanicPolicy::Swallow as u8);
+
+/// Set the process-wide [`PanicPolicy`] for agent callbacks (default: `Swallow`).
+pub fn set_panic_policy(policy: PanicPolicy) {
+    PANIC_POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+/// The currently configured [`PanicPolicy`].
+pub fn panic_policy() -> PanicPolicy {
+    if PANIC_POLICY.load(Ordering::Relaxed) == PanicPolicy::Abort as u8 {
+        PanicPolicy::Abort
+    } else {
+        PanicPolicy::Swallow
+    }
+}
+
+/// Severity of a message passed to the [`set_log_callback`] hook.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+        })
+    }
+}
+
+/// A diagnostics hook installed with [`set_log_callback`].
+pub type LogCallback = fn(LogLevel, &str);
+
+static LOG_CALLBACK: AtomicPtr<()> = AtomicPtr::new(std::ptr::null_mut());
+
+/// Routes the crate's internal diagnostics to `callback`.
+///
+/// Off by default: without a callback nothing is formatted or printed. Once
+/// set, the crate reports rejected capabilities, event mode changes, events
+/// that arrive before an agent is registered, failed `Deallocate` calls, and
+/// guards that fail to release their monitor, thread, or environment, which helps with an agent that loads but "does nothing":
+///
+/// ```rust,ignore
+/// jvmti_bindings::set_log_callback(|level, message| eprintln!("[agent] {level}: {message}"));
+/// ```
+///
+/// The callback can run on any thread, including inside event callbacks, so
+/// it should not call back into JVMTI. A panic in it is caught and dropped.
+pub fn set_log_callback(callback: LogCallback) {
+    LOG_CALLBACK.store(callback as *mut (), Ordering::Release);
+}
+
+/// Removes the [`set_log_callback`] hook.
+pub fn clear_log_callback() {
+    LOG_CALLBACK.store(std::ptr::null_mut(), Ordering::Release);
+}
+
+/// Passes a message to the log hook, formatting it only if one is installed.
+pub(crate) fn log(level: LogLevel, message: std::fmt::Arguments<'_>) {
+    let raw = LOG_CALLBACK.load(Ordering::Acquire);
+    if raw.is_null() {
+        return;
+    }
+    // SAFETY: only `set_log_callback` stores a non-null value, a `LogCallback`.
+    let callback = unsafe { std::mem::transmute::<*mut (), LogCallback>(raw) };
+    let message = message.to_string();
+    let _ = std::panic::catch_unwind(|| callback(level, &message));
+}
+
+/// The registered agent, logging events that arrive before there is one.
+fn registered_agent() -> Option<&'static dyn Agent> {
+    let agent = GLOBAL_AGENT.get().map(|agent| &**agent);
+    if agent.is_none() {
+        log(LogLevel::Warn, format_args!("event callback fired before an agent was registered; ignoring it"));
+    }
+    agent
+}
+
+/// Run an agent callback without letting a panic unwind into the JVM.
+///
+/// Returns `fallback` if `f` panicked and the policy is [`PanicPolicy::Swallow`].
+#[doc(hidden)]
+pub fn guard_agent_call<R>(event: &'static str, fallback: R, f: impl FnOnce() -> R) -> R {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(value) => value,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("<non-string panic payload>");
+            match panic_policy() {
+                PanicPolicy::Swallow => {
+                    eprintln!("[jvmti-bindings] agent callback `{event}` panicked: {message} (panic swallowed)");
+                    fallback
+                }
+                PanicPolicy::Abort => {
+                    eprintln!("[jvmti-bindings] agent callback `{event}` panicked: {message} (aborting)");
+                    std::process::abort()
+                }
+            }
+        }
+    }
+}
+
+/// Wrap the raw environments handed to a trampoline for the `*_with_env` callbacks.
+unsafe fn callback_env(jvmti_env: *mut jvmti::jvmtiEnv, jni_env: *mut jni::JNIEnv) -> (env::Jvmti, env::JniEnv) {
+    (env::Jvmti::from_raw(jvmti_env), env::JniEnv::from_raw(jni_env))
+}
+
+/// Borrow a VM-owned array for the duration of a callback; null or negative lengths give `&[]`.
+unsafe fn raw_slice<'a, T>(ptr: *const T, len: jni::jint) -> &'a [T] {
+    if ptr.is_null() || len <= 0 { &[] } else { std::slice::from_raw_parts(ptr, len as usize) }
+}
+
 unsafe extern "system" fn trampoline_method_entry(
-    jvmti_env: *mut sys::jvmti::jvmtiEnv,
-    jni_env: *mut jni::JNIEnv,
+    env: *mut jvmti::jvmtiEnv,
+    jni: *mut jni::JNIEnv,
     thread: jni::jthread,
     method: jni::jmethodID,
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() {
-        agent.method_entry_with_jvmti(jvmti_env, jni_env, thread, method);
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("MethodEntry", (), || agent.method_entry_with_env(&jvmti_env, &jni_env, thread, method));
     }
 }
 
 unsafe extern "system" fn trampoline_method_exit(
-    jvmti_env: *mut sys::jvmti::jvmtiEnv,
-    jni_env: *mut jni::JNIEnv,
+    env: *mut jvmti::jvmtiEnv,
+    jni: *mut jni::JNIEnv,
     thread: jni::jthread,
     method: jni::jmethodID,
     _was_popped: jni::jboolean,
     _ret_val: jni::jvalue,
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() {
-        agent.method_exit_with_jvmti(jvmti_env, jni_env, thread, method);
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("MethodExit", (), || agent.method_exit_with_env(&jvmti_env, &jni_env, thread, method));
     }
 }
 
 unsafe extern "system" fn trampoline_native_method_bind(
-    _env: *mut sys::jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID,
+    env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID,
     address: *mut std::os::raw::c_void, new_address_ptr: *mut *mut std::os::raw::c_void
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.native_method_bind(jni, thread, method, address, new_address_ptr); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("NativeMethodBind", (), || agent.native_method_bind_with_env(&jvmti_env, &jni_env, thread, method, address, new_address_ptr));
+    }
 }
 
 
 // --- 1. Lifecycle ---
 unsafe extern "system" fn trampoline_vm_init(env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.vm_init_with_jvmti(env, jni, thread); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("VMInit", (), || agent.vm_init_with_env(&jvmti_env, &jni_env, thread));
+    }
 }
 unsafe extern "system" fn trampoline_vm_death(env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.vm_death_with_jvmti(env, jni); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("VMDeath", (), || agent.vm_death_with_env(&jvmti_env, &jni_env));
+        if !SHUT_DOWN.swap(true, Ordering::SeqCst) {
+            guard_agent_call("VMDeath", (), || agent.shutdown(&jni_env));
+        }
+    }
 }
 unsafe extern "system" fn trampoline_vm_start(env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.vm_start_with_jvmti(env, jni); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("VMStart", (), || agent.vm_start_with_env(&jvmti_env, &jni_env));
+    }
 }
 
 // --- 2. Threads ---
-unsafe extern "system" fn trampoline_thread_start(_env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.thread_start(jni, thread); }
+unsafe extern "system" fn trampoline_thread_start(env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread) {
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("ThreadStart", (), || agent.thread_start_with_env(&jvmti_env, &jni_env, thread));
+    }
 }
-unsafe extern "system" fn trampoline_thread_end(_env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.thread_end(jni, thread); }
+unsafe extern "system" fn trampoline_thread_end(env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread) {
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("ThreadEnd", (), || agent.thread_end_with_env(&jvmti_env, &jni_env, thread));
+    }
 }
 unsafe extern "system" fn trampoline_virtual_thread_start(
-    _env: *mut jvmti::jvmtiEnv,
+    env: *mut jvmti::jvmtiEnv,
     jni: *mut jni::JNIEnv,
     thread: jni::jthread,
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() {
-        agent.virtual_thread_start(jni, thread);
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("VirtualThreadStart", (), || agent.virtual_thread_start_with_env(&jvmti_env, &jni_env, thread));
     }
 }
 unsafe extern "system" fn trampoline_virtual_thread_end(
-    _env: *mut jvmti::jvmtiEnv,
+    env: *mut jvmti::jvmtiEnv,
     jni: *mut jni::JNIEnv,
     thread: jni::jthread,
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() {
-        agent.virtual_thread_end(jni, thread);
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("VirtualThreadEnd", (), || agent.virtual_thread_end_with_env(&jvmti_env, &jni_env, thread));
     }
 }
 
 // --- 3. Classes ---
 unsafe extern "system" fn trampoline_class_load(env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, klass: jni::jclass) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.class_load_with_jvmti(env, jni, thread, klass); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("ClassLoad", (), || agent.class_load_with_env(&jvmti_env, &jni_env, thread, klass));
+    }
 }
 unsafe extern "system" fn trampoline_class_prepare(env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, klass: jni::jclass) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.class_prepare_with_jvmti(env, jni, thread, klass); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("ClassPrepare", (), || agent.class_prepare_with_env(&jvmti_env, &jni_env, thread, klass));
+    }
 }
 
 // --- 3.5 Compiled Code ---
 unsafe extern "system" fn trampoline_compiled_method_load(
     _env: *mut jvmti::jvmtiEnv, method: jni::jmethodID, code_size: jni::jint, code_addr: *const std::os::raw::c_void,
-    map_length: jni::jint, map: *const std::os::raw::c_void, compile_info: *const std::os::raw::c_void
+    map_length: jni::jint, map: *const jvmti::jvmtiAddrLocationMap, compile_info: *const std::os::raw::c_void
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.compiled_method_load(method, code_size, code_addr, map_length, map, compile_info); }
+    if let Some(agent) = registered_agent() {
+        let code = raw_slice(code_addr as *const u8, code_size);
+        let map = raw_slice(map, map_length);
+        guard_agent_call("CompiledMethodLoad", (), || agent.compiled_method_loaded(method, code, map, compile_info));
+    }
 }
 unsafe extern "system" fn trampoline_compiled_method_unload(_env: *mut jvmti::jvmtiEnv, method: jni::jmethodID, code_addr: *const std::os::raw::c_void) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.compiled_method_unload(method, code_addr); }
+    if let Some(agent) = registered_agent() { guard_agent_call("CompiledMethodUnload", (), || agent.compiled_method_unload(method, code_addr)); }
 }
 unsafe extern "system" fn trampoline_dynamic_code_generated(_env: *mut jvmti::jvmtiEnv, name: *const std::os::raw::c_char, address: *const std::os::raw::c_void, length: jni::jint) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.dynamic_code_generated(name, address, length); }
+    if let Some(agent) = registered_agent() { guard_agent_call("DynamicCodeGenerated", (), || agent.dynamic_code_generated(name, address, length)); }
 }
 unsafe extern "system" fn trampoline_data_dump_request(_env: *mut jvmti::jvmtiEnv) {
-    if let Some(agent) = GLOBAL_AGENT.get() {
-        agent.data_dump_request();
+    if let Some(agent) = registered_agent() {
+        guard_agent_call("DataDumpRequest", (), || agent.data_dump_request());
+    }
+}
+type Retransformer<'a> = dyn FnMut(&str, &[u8]) -> Option<Vec<u8>> + 'a;
+
+/// State of [`env::Jvmti::retransform_with`] on the current thread.
+#[derive(Clone, Copy)]
+enum OneShotHook {
+    Idle,
+    /// Retransforming; the pointer is the caller's closure, valid until `Idle`.
+    Armed(*mut Retransformer<'static>),
+    /// The closure is running (and must not be re-entered).
+    Running,
+}
+
+thread_local! {
+    static ONE_SHOT_HOOK: Cell<OneShotHook> = const { Cell::new(OneShotHook::Idle) };
+}
+
+/// Run `body` with `transform` handling this thread's retransformed classes.
+///
+/// Returns `None` if a transformer is already installed on this thread.
+pub(crate) fn with_one_shot_hook<R>(transform: &mut Retransformer<'_>, body: impl FnOnce() -> R) -> Option<R> {
+    struct Disarm;
+    impl Drop for Disarm {
+        fn drop(&mut self) {
+            ONE_SHOT_HOOK.with(|hook| hook.set(OneShotHook::Idle));
+        }
+    }
+
+    if !matches!(ONE_SHOT_HOOK.with(Cell::get), OneShotHook::Idle) {
+        return None;
     }
+    // SAFETY: only the lifetime is erased; `Disarm` clears the pointer,
+    // even on unwind, before `transform` goes out of scope.
+    let transform: *mut Retransformer<'static> = unsafe { std::mem::transmute(transform) };
+    ONE_SHOT_HOOK.with(|hook| hook.set(OneShotHook::Armed(transform)));
+    let _disarm = Disarm;
+    Some(body())
 }
+
+/// Hand a retransformed class to the closure of an in-progress `retransform_with`.
+///
+/// Does nothing if none is installed on this thread, or the class is being
+/// loaded rather than retransformed. Like the transformer registry, the
+/// closure starts from bytes an earlier handler already installed and releases
+/// them when it replaces them.
+unsafe fn dispatch_one_shot_hook(
+    jvmti_env: &env::Jvmti, class_being_redefined: jni::jclass, name: *const std::os::raw::c_char,
+    class_data_len: jni::jint, class_data: *const std::os::raw::c_uchar,
+    new_class_data_len: *mut jni::jint, new_class_data: *mut *mut std::os::raw::c_uchar
+) {
+    let OneShotHook::Armed(transform) = ONE_SHOT_HOOK.with(Cell::get) else {
+        return;
+    };
+    if class_being_redefined.is_null() || new_class_data.is_null() || new_class_data_len.is_null() {
+        return;
+    }
+    let name = class_name(name).unwrap_or("");
+    let previous = *new_class_data;
+    let input = if previous.is_null() {
+        class_data_slice(class_data, class_data_len)
+    } else {
+        class_data_slice(previous, *new_class_data_len)
+    };
+    ONE_SHOT_HOOK.with(|hook| hook.set(OneShotHook::Running));
+    let replacement = guard_agent_call("ClassFileLoadHook", None, || (*transform)(name, input));
+    ONE_SHOT_HOOK.with(|hook| hook.set(OneShotHook::Armed(transform)));
+    if let Some(bytes) = replacement {
+        if set_new_class_data(jvmti_env, "retransform_with", &bytes, new_class_data_len, new_class_data)
+            && !previous.is_null()
+        {
+            let _ = jvmti_env.deallocate(previous);
+        }
+    }
+}
+
+/// Hand `bytes` to the VM as a `ClassFileLoadHook`'s replacement class file.
+///
+/// Returns false, leaving the out-parameters untouched, if the bytes do not
+/// fit a `jint` length or cannot be allocated; `what` names the caller in the
+/// logged error.
+pub(crate) unsafe fn set_new_class_data(
+    jvmti_env: &env::Jvmti, what: &str, bytes: &[u8],
+    new_class_data_len: *mut jni::jint, new_class_data: *mut *mut std::os::raw::c_uchar,
+) -> bool {
+    let Ok(len) = jni::jint::try_from(bytes.len()) else {
+        log(LogLevel::Error, format_args!("{what}: {} bytes do not fit a class file; keeping the original", bytes.len()));
+        return false;
+    };
+    match jvmti_env.allocate_bytes(bytes) {
+        Ok(mem) if !mem.is_null() => {
+            *new_class_data = mem;
+            *new_class_data_len = len;
+            true
+        }
+        Ok(_) => false,
+        Err(err) => {
+            log(LogLevel::Error, format_args!("{what}: cannot allocate {} bytes: {err}", bytes.len()));
+            false
+        }
+    }
+}
+
 unsafe extern "system" fn trampoline_class_file_load_hook(
     env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv,
     class_being_redefined: jni::jclass, loader: jni::jobject, name: *const std::os::raw::c_char,
     protection_domain: jni::jobject, class_data_len: jni::jint, class_data: *const std::os::raw::c_uchar,
     new_class_data_len: *mut jni::jint, new_class_data: *mut *mut std::os::raw::c_uchar
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() {
-        agent.class_file_load_hook_with_jvmti(env, jni, class_being_redefined, loader, name, protection_domain, class_data_len, class_data, new_class_data_len, new_class_data);
+    let transformers = class_transformers();
+    // Agents that only install transformers need not register an `Agent`.
+    if transformers.is_none() || GLOBAL_AGENT.get().is_some() {
+        if let Some(agent) = registered_agent() {
+            dispatch_agent_class_file_load_hook(agent, env, jni, class_being_redefined, loader, name, protection_domain, class_data_len, class_data, new_class_data_len, new_class_data);
+        }
+    }
+    // A `retransform_with` closure rewrites the agent's output for its classes.
+    dispatch_one_shot_hook(&env::Jvmti::from_raw(env), class_being_redefined, name, class_data_len, class_data, new_class_data_len, new_class_data);
+    // Transformers run last, on whatever bytes the agent or `retransform_with` left.
+    if let Some(registry) = transformers {
+        class_transformers::dispatch(&registry, &env::Jvmti::from_raw(env), name, class_data_len, class_data, new_class_data_len, new_class_data);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn dispatch_agent_class_file_load_hook(
+    agent: &dyn Agent, env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv,
+    class_being_redefined: jni::jclass, loader: jni::jobject, name: *const std::os::raw::c_char,
+    protection_domain: jni::jobject, class_data_len: jni::jint, class_data: *const std::os::raw::c_uchar,
+    new_class_data_len: *mut jni::jint, new_class_data: *mut *mut std::os::raw::c_uchar
+) {
+    let (jvmti_env, jni_env) = callback_env(env, jni);
+    guard_agent_call("ClassFileLoadHook", (), || agent.class_file_load_hook_with_env(&jvmti_env, &jni_env, class_being_redefined, loader, name, protection_domain, class_data_len, class_data, new_class_data_len, new_class_data));
+
+    // Fall back to `transform_class` unless the raw hook already replaced the bytes.
+    if new_class_data.is_null() || new_class_data_len.is_null() || !(*new_class_data).is_null()
+        || class_data.is_null() || class_data_len < 0 {
+        return;
+    }
+    let name = class_name(name);
+    let original = class_data_slice(class_data, class_data_len);
+    if let Some(bytes) = guard_agent_call("ClassFileLoadHook", None, || agent.transform_class(name, original)) {
+        set_new_class_data(&jvmti_env, "transform_class", &bytes, new_class_data_len, new_class_data);
     }
 }
 
 // --- 4. Exceptions ---
 unsafe extern "system" fn trampoline_exception(
-    _env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID,
+    env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID,
     location: jvmti::jlocation, exception: jni::jobject, catch_method: jni::jmethodID, catch_location: jvmti::jlocation
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() {
-        agent.exception(jni, thread, method, location, exception, catch_method, catch_location);
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("Exception", (), || agent.exception_with_env(&jvmti_env, &jni_env, thread, method, location, exception, catch_method, catch_location));
     }
 }
 unsafe extern "system" fn trampoline_exception_catch(
-    _env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID,
+    env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID,
     location: jvmti::jlocation, exception: jni::jobject
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() {
-        agent.exception_catch(jni, thread, method, location, exception);
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("ExceptionCatch", (), || agent.exception_catch_with_env(&jvmti_env, &jni_env, thread, method, location, exception));
     }
 }
 
 // --- 5. Debugging ---
 unsafe extern "system" fn trampoline_single_step(
-    _env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation
+    env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.single_step(jni, thread, method, location); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("SingleStep", (), || agent.single_step_with_env(&jvmti_env, &jni_env, thread, method, location));
+    }
 }
 unsafe extern "system" fn trampoline_breakpoint(
-    _env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation
+    env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID, location: jvmti::jlocation
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.breakpoint(jni, thread, method, location); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("Breakpoint", (), || agent.breakpoint_with_env(&jvmti_env, &jni_env, thread, method, location));
+    }
 }
 unsafe extern "system" fn trampoline_frame_pop(
-    _env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID, was_popped: jni::jboolean
+    env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID, was_popped: jni::jboolean
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.frame_pop(jni, thread, method, was_popped); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("FramePop", (), || agent.frame_pop_with_env(&jvmti_env, &jni_env, thread, method, was_popped));
+    }
 }
 
 // --- 5.5 Monitors ---
-unsafe extern "system" fn trampoline_monitor_wait(_env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, object: jni::jobject, timeout: jni::jlong) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.monitor_wait(jni, thread, object, timeout); }
+unsafe extern "system" fn trampoline_monitor_wait(env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, object: jni::jobject, timeout: jni::jlong) {
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("MonitorWait", (), || agent.monitor_wait_with_env(&jvmti_env, &jni_env, thread, object, timeout));
+    }
 }
-unsafe extern "system" fn trampoline_monitor_waited(_env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, object: jni::jobject, timed_out: jni::jboolean) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.monitor_waited(jni, thread, object, timed_out); }
+unsafe extern "system" fn trampoline_monitor_waited(env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, object: jni::jobject, timed_out: jni::jboolean) {
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("MonitorWaited", (), || agent.monitor_waited_with_env(&jvmti_env, &jni_env, thread, object, timed_out));
+    }
 }
-unsafe extern "system" fn trampoline_monitor_contended_enter(_env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, object: jni::jobject) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.monitor_contended_enter(jni, thread, object); }
+unsafe extern "system" fn trampoline_monitor_contended_enter(env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, object: jni::jobject) {
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("MonitorContendedEnter", (), || agent.monitor_contended_enter_with_env(&jvmti_env, &jni_env, thread, object));
+    }
 }
-unsafe extern "system" fn trampoline_monitor_contended_entered(_env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, object: jni::jobject) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.monitor_contended_entered(jni, thread, object); }
+unsafe extern "system" fn trampoline_monitor_contended_entered(env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, object: jni::jobject) {
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("MonitorContendedEntered", (), || agent.monitor_contended_entered_with_env(&jvmti_env, &jni_env, thread, object));
+    }
 }
 
 // --- 6. Fields ---
 unsafe extern "system" fn trampoline_field_access(
-    _env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID,
+    env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID,
     location: jvmti::jlocation, field_klass: jni::jclass, object: jni::jobject, field: crate::sys::jni::jfieldID
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.field_access(jni, thread, method, location, field_klass, object, field); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("FieldAccess", (), || agent.field_access_with_env(&jvmti_env, &jni_env, thread, method, location, field_klass, object, field));
+    }
 }
 unsafe extern "system" fn trampoline_field_modification(
-    _env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID,
+    env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread, method: jni::jmethodID,
     location: jvmti::jlocation, field_klass: jni::jclass, object: jni::jobject, field: crate::sys::jni::jfieldID,
     sig_type: std::os::raw::c_char, new_value: jni::jvalue
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.field_modification(jni, thread, method, location, field_klass, object, field, sig_type, new_value); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("FieldModification", (), || agent.field_modification_with_env(&jvmti_env, &jni_env, thread, method, location, field_klass, object, field, sig_type, new_value));
+    }
 }
 
 // --- 7. GC & Resource ---
 unsafe extern "system" fn trampoline_garbage_collection_start(_env: *mut jvmti::jvmtiEnv) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.garbage_collection_start(); }
+    if let Some(agent) = registered_agent() { guard_agent_call("GarbageCollectionStart", (), || agent.garbage_collection_start()); }
 }
 unsafe extern "system" fn trampoline_garbage_collection_finish(_env: *mut jvmti::jvmtiEnv) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.garbage_collection_finish(); }
+    if let Some(agent) = registered_agent() { guard_agent_call("GarbageCollectionFinish", (), || agent.garbage_collection_finish()); }
 }
 unsafe extern "system" fn trampoline_resource_exhausted(
-    _env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, flags: jni::jint,
+    env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, flags: jni::jint,
     _reserved: *const std::os::raw::c_void, description: *const std::os::raw::c_char
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.resource_exhausted(jni, flags, description); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("ResourceExhausted", (), || agent.resource_exhausted_with_env(&jvmti_env, &jni_env, flags, description));
+        let description = (!description.is_null()).then(|| mutf8::from_cstr(description));
+        guard_agent_call("ResourceExhausted", (), || {
+            agent.resource_exhausted_typed(&jni_env, env::ResourceExhaustedFlags::from_raw(flags), description.as_deref())
+        });
+    }
 }
 
 // --- 8. Objects ---
 unsafe extern "system" fn trampoline_object_free(_env: *mut jvmti::jvmtiEnv, tag: jni::jlong) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.object_free(tag); }
+    if let Some(agent) = registered_agent() { guard_agent_call("ObjectFree", (), || agent.object_free(tag)); }
 }
 unsafe extern "system" fn trampoline_vm_object_alloc(
-    _env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread,
+    env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread,
     object: jni::jobject, klass: jni::jclass, size: jni::jlong
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.vm_object_alloc(jni, thread, object, klass, size); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("VMObjectAlloc", (), || agent.vm_object_alloc_with_env(&jvmti_env, &jni_env, thread, object, klass, size));
+    }
 }
 unsafe extern "system" fn trampoline_sampled_object_alloc(
-    _env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread,
+    env: *mut jvmti::jvmtiEnv, jni: *mut jni::JNIEnv, thread: jni::jthread,
     object: jni::jobject, klass: jni::jclass, size: jni::jlong
 ) {
-    if let Some(agent) = GLOBAL_AGENT.get() { agent.sampled_object_alloc(jni, thread, object, klass, size); }
+    if let Some(agent) = registered_agent() {
+        let (jvmti_env, jni_env) = callback_env(env, jni);
+        guard_agent_call("SampledObjectAlloc", (), || agent.sampled_object_alloc_with_env(&jvmti_env, &jni_env, thread, object, klass, size));
+    }
+}
+
+// --- 9. Extension events ---
+
+/// How many distinct extension events can be enabled at once.
+pub const MAX_EXTENSION_EVENTS: usize = 8;
+
+/// Targets whose calling convention passes integer and pointer varargs
+/// exactly like fixed arguments: x86_64 (System V and Win64) and aarch64
+/// outside Apple platforms, which put varargs on the stack.
+const FIXED_ARITY_RECEIVES_VARARGS: bool =
+    cfg!(any(target_arch = "x86_64", all(target_arch = "aarch64", not(target_vendor = "apple"))));
+
+// One trampoline per slot, so a callback knows which event fired; each slot
+// holds the event index and parameter list it was enabled for.
+static EXTENSION_EVENT_SLOTS: Mutex<Vec<(jni::jint, Vec<env::ExtensionParamInfo>)>> = Mutex::new(Vec::new());
+
+/// Reserve (or reuse) the dispatch slot for `index` and return its trampoline.
+///
+/// Returns `None` unless every parameter arrives as one integer word on this
+/// target, since the trampolines are fixed-arity receivers of a variadic call.
+pub(crate) fn extension_event_trampoline(
+    index: jni::jint,
+    params: Vec<env::ExtensionParamInfo>,
+) -> Option<jvmti::jvmtiExtensionEventCallback> {
+    if !FIXED_ARITY_RECEIVES_VARARGS
+        || params.len() > env::MAX_EXTENSION_ARGS
+        || !params.iter().all(env::ExtensionParamInfo::passes_as_word)
+    {
+        return None;
+    }
+    let mut slots = EXTENSION_EVENT_SLOTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let slot = match slots.iter().position(|(slot_index, _)| *slot_index == index) {
+        Some(slot) => {
+            slots[slot].1 = params;
+            slot
+        }
+        None if slots.len() < MAX_EXTENSION_EVENTS => {
+            slots.push((index, params));
+            slots.len() - 1
+        }
+        None => return None,
+    };
+    Some(EXTENSION_EVENT_TRAMPOLINES[slot])
+}
+
+type ExtensionEventTrampoline = unsafe extern "C" fn(*mut jvmti::jvmtiEnv, usize, usize, usize, usize, usize, usize, usize, usize);
+
+fn dispatch_extension_event(slot: usize, words: [usize; env::MAX_EXTENSION_ARGS]) {
+    let Some(agent) = registered_agent() else { return };
+    let Some((index, args)) = EXTENSION_EVENT_SLOTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(slot)
+        .map(|(index, params)| {
+            let args: Vec<env::ExtensionArg> =
+                params.iter().zip(words).map(|(param, word)| env::ExtensionArg::from_word(param, word)).collect();
+            (*index, args)
+        })
+    else {
+        return;
+    };
+    guard_agent_call("ExtensionEvent", (), || agent.extension_event(index, &args));
+}
+
+// The VM calls extension events as C variadic functions. On the targets in
+// `FIXED_ARITY_RECEIVES_VARARGS`, integer and pointer varargs are passed like
+// fixed arguments, so a fixed-arity receiver sees them as the leading words;
+// words past the event's parameter count are never decoded. Floating-point
+// varargs travel in other registers, so events with them are never wired.
+macro_rules! extension_event_trampolines {
+    ($($slot:literal => $name:ident),* $(,)?) => {
+        $(
+            unsafe extern "C" fn $name(
+                _env: *mut jvmti::jvmtiEnv,
+                a0: usize, a1: usize, a2: usize, a3: usize, a4: usize, a5: usize, a6: usize, a7: usize,
+            ) {
+                dispatch_extension_event($slot, [a0, a1, a2, a3, a4, a5, a6, a7]);
+            }
+        )*
+
+        static EXTENSION_EVENT_TRAMPOLINES: [jvmti::jvmtiExtensionEventCallback; MAX_EXTENSION_EVENTS] = unsafe {
+            [$(std::mem::transmute::<ExtensionEventTrampoline, jvmti::jvmtiExtensionEventCallback>($name as ExtensionEventTrampoline)),*]
+        };
+    };
+}
+
+extension_event_trampolines! {
+    0 => trampoline_extension_event_0,
+    1 => trampoline_extension_event_1,
+    2 => trampoline_extension_event_2,
+    3 => trampoline_extension_event_3,
+    4 => trampoline_extension_event_4,
+    5 => trampoline_extension_event_5,
+    6 => trampoline_extension_event_6,
+    7 => trampoline_extension_event_7,
 }
 
 
@@ -860,11 +1724,69 @@ pub fn get_default_callbacks() -> jvmti::jvmtiEventCallbacks {
     }
 }
 
+/// Like [`get_default_callbacks`], but only wires the trampolines for `events`.
+///
+/// The trait cannot tell which [`Agent`] methods are overridden, so the
+/// default set routes every event through Rust. Listing the events the agent
+/// handles leaves the other slots empty: the VM never calls into the agent
+/// for them, even if they get enabled, and the list documents what the agent
+/// expects to receive.
+///
+/// ```rust,ignore
+/// jvmti.set_event_callbacks(get_callbacks_for(&[Event::VmInit, Event::ClassPrepare]))?;
+/// ```
+///
+/// [`env::Jvmti::retransform_with`] needs `ClassFileLoadHook` in the list.
+pub fn get_callbacks_for(events: &[env::Event]) -> jvmti::jvmtiEventCallbacks {
+    let all = get_default_callbacks();
+    let mut callbacks = jvmti::jvmtiEventCallbacks::default();
+    for event in events {
+        match event {
+            env::Event::VmInit => callbacks.VMInit = all.VMInit,
+            env::Event::VmDeath => callbacks.VMDeath = all.VMDeath,
+            env::Event::ThreadStart => callbacks.ThreadStart = all.ThreadStart,
+            env::Event::ThreadEnd => callbacks.ThreadEnd = all.ThreadEnd,
+            env::Event::ClassFileLoadHook => callbacks.ClassFileLoadHook = all.ClassFileLoadHook,
+            env::Event::ClassLoad => callbacks.ClassLoad = all.ClassLoad,
+            env::Event::ClassPrepare => callbacks.ClassPrepare = all.ClassPrepare,
+            env::Event::VmStart => callbacks.VMStart = all.VMStart,
+            env::Event::Exception => callbacks.Exception = all.Exception,
+            env::Event::ExceptionCatch => callbacks.ExceptionCatch = all.ExceptionCatch,
+            env::Event::SingleStep => callbacks.SingleStep = all.SingleStep,
+            env::Event::FramePop => callbacks.FramePop = all.FramePop,
+            env::Event::Breakpoint => callbacks.Breakpoint = all.Breakpoint,
+            env::Event::FieldAccess => callbacks.FieldAccess = all.FieldAccess,
+            env::Event::FieldModification => callbacks.FieldModification = all.FieldModification,
+            env::Event::MethodEntry => callbacks.MethodEntry = all.MethodEntry,
+            env::Event::MethodExit => callbacks.MethodExit = all.MethodExit,
+            env::Event::NativeMethodBind => callbacks.NativeMethodBind = all.NativeMethodBind,
+            env::Event::CompiledMethodLoad => callbacks.CompiledMethodLoad = all.CompiledMethodLoad,
+            env::Event::CompiledMethodUnload => callbacks.CompiledMethodUnload = all.CompiledMethodUnload,
+            env::Event::DynamicCodeGenerated => callbacks.DynamicCodeGenerated = all.DynamicCodeGenerated,
+            env::Event::DataDumpRequest => callbacks.DataDumpRequest = all.DataDumpRequest,
+            env::Event::MonitorWait => callbacks.MonitorWait = all.MonitorWait,
+            env::Event::MonitorWaited => callbacks.MonitorWaited = all.MonitorWaited,
+            env::Event::MonitorContendedEnter => callbacks.MonitorContendedEnter = all.MonitorContendedEnter,
+            env::Event::MonitorContendedEntered => callbacks.MonitorContendedEntered = all.MonitorContendedEntered,
+            env::Event::ResourceExhausted => callbacks.ResourceExhausted = all.ResourceExhausted,
+            env::Event::GarbageCollectionStart => callbacks.GarbageCollectionStart = all.GarbageCollectionStart,
+            env::Event::GarbageCollectionFinish => callbacks.GarbageCollectionFinish = all.GarbageCollectionFinish,
+            env::Event::ObjectFree => callbacks.ObjectFree = all.ObjectFree,
+            env::Event::VmObjectAlloc => callbacks.VMObjectAlloc = all.VMObjectAlloc,
+            env::Event::SampledObjectAlloc => callbacks.SampledObjectAlloc = all.SampledObjectAlloc,
+            env::Event::VirtualThreadStart => callbacks.VirtualThreadStart = all.VirtualThreadStart,
+            env::Event::VirtualThreadEnd => callbacks.VirtualThreadEnd = all.VirtualThreadEnd,
+        }
+    }
+    callbacks
+}
+
 
 /// Exports your agent type as a loadable JVMTI agent library.
 ///
-/// This macro generates the required `Agent_OnLoad` and `Agent_OnUnload` FFI entry points
-/// that the JVM expects when loading an agent via `-agentpath` or `-agentlib`.
+/// This macro generates the required `Agent_OnLoad`, `Agent_OnAttach`, and `Agent_OnUnload`
+/// FFI entry points that the JVM expects when loading an agent via `-agentpath`/`-agentlib`
+/// or through the Attach API.
 ///
 /// # Requirements
 ///
@@ -875,11 +1797,15 @@ pub fn get_default_callbacks() -> jvmti::jvmtiEventCallbacks {
 ///
 /// # Generated Functions
 ///
-/// The macro generates two `extern "system"` functions:
+/// The macro generates three `extern "system"` functions:
 ///
 /// - **`Agent_OnLoad`**: Called by the JVM when the agent is loaded. Creates your agent
 ///   instance, registers it globally, and calls your [`Agent::on_load`] method.
 ///
+/// - **`Agent_OnAttach`**: Called when the agent is loaded into a running JVM via the
+///   Attach API. Registers the agent if it is not registered yet and calls
+///   [`Agent::on_attach`] (which defaults to [`Agent::on_load`]).
+///
 /// - **`Agent_OnUnload`**: Called by the JVM during shutdown. Calls your [`Agent::on_unload`]
 ///   method for cleanup.
 ///
@@ -900,7 +1826,7 @@ pub fn get_default_callbacks() -> jvmti::jvmtiEventCallbacks {
 ///     }
 /// }
 ///
-/// // This generates Agent_OnLoad and Agent_OnUnload
+/// // This generates Agent_OnLoad, Agent_OnAttach, and Agent_OnUnload
 /// export_agent!(MyAgent);
 /// ```
 ///
@@ -941,6 +1867,14 @@ pub fn get_default_callbacks() -> jvmti::jvmtiEventCallbacks {
 /// - Your agent's methods may be called concurrently from multiple JVM threads
 /// - Use interior mutability (`Mutex`, `RwLock`, `AtomicXxx`) for mutable state
 ///
+/// # Panics in Callbacks
+///
+/// `Agent_OnLoad`, `Agent_OnAttach`, `Agent_OnUnload`, and every event trampoline
+/// catch panics from your [`Agent`] methods so they never unwind into the JVM.
+/// The panic is reported on stderr; a panicking `on_load`/`on_attach` returns
+/// [`jni::JNI_ERR`]. Call [`set_panic_policy`] with [`PanicPolicy::Abort`] to
+/// abort the process instead of continuing.
+///
 /// # Return Values
 ///
 /// Your `on_load` must return:
@@ -971,7 +1905,9 @@ macro_rules! export_agent {
 
             // 3. Call the User's Logic
             if let Some(global_agent) = $crate::GLOBAL_AGENT.get() {
-                return global_agent.on_load(vm, options_str);
+                return $crate::guard_agent_call("Agent_OnLoad", $crate::sys::jni::JNI_ERR, || {
+                    global_agent.on_load(vm, options_str)
+                });
             }
 
             $crate::sys::jni::JNI_ERR
@@ -984,10 +1920,9 @@ macro_rules! export_agent {
             reserved: *mut std::ffi::c_void,
         ) -> $crate::sys::jni::jint {
 
-            // 1. Create and Register the Agent
-            let agent = Box::new(<$agent_type>::default());
-            if let Err(_) = $crate::set_global_agent(agent) {
-                return $crate::sys::jni::JNI_ERR;
+            // 1. Register the Agent, reusing it if the library was already loaded
+            if $crate::GLOBAL_AGENT.get().is_none() {
+                let _ = $crate::set_global_agent(Box::new(<$agent_type>::default()));
             }
 
             // 2. Handle Options
@@ -999,7 +1934,9 @@ macro_rules! export_agent {
 
             // 3. Call the User's Logic
             if let Some(global_agent) = $crate::GLOBAL_AGENT.get() {
-                return global_agent.on_attach(vm, options_str);
+                return $crate::guard_agent_call("Agent_OnAttach", $crate::sys::jni::JNI_ERR, || {
+                    global_agent.on_attach(vm, options_str)
+                });
             }
 
             $crate::sys::jni::JNI_ERR
@@ -1008,8 +1945,54 @@ macro_rules! export_agent {
         #[no_mangle]
         pub unsafe extern "system" fn Agent_OnUnload(vm: *mut $crate::sys::jni::JavaVM) {
              if let Some(agent) = $crate::GLOBAL_AGENT.get() {
-                agent.on_unload();
+                $crate::guard_agent_call("Agent_OnUnload", (), || agent.on_unload());
+            }
+        }
+    };
+}
+
+/// Exports `JNI_OnLoad` for a library that is both a JVMTI agent and a JNI library.
+///
+/// `System.loadLibrary` calls `JNI_OnLoad` and fails to load a library that
+/// does not return a supported JNI version from it. This macro generates that
+/// entry point and delegates to [`Agent::jni_on_load`], reusing the global agent
+/// if `Agent_OnLoad` already created it. Use it alongside [`export_agent!`]
+/// with the same type:
+///
+/// ```rust,ignore
+/// use jvmti_bindings::prelude::*;
+///
+/// #[derive(Default)]
+/// struct Hybrid;
+///
+/// impl Agent for Hybrid {
+///     fn on_load(&self, _vm: *mut jni::JavaVM, _options: &str) -> jni::jint { jni::JNI_OK }
+/// }
+///
+/// export_agent!(Hybrid);
+/// export_jni_onload!(Hybrid);
+/// ```
+///
+/// It is a separate macro because a library may only define `JNI_OnLoad` once.
+#[macro_export]
+macro_rules! export_jni_onload {
+    ($agent_type:ty) => {
+        #[no_mangle]
+        pub unsafe extern "system" fn JNI_OnLoad(
+            vm: *mut $crate::sys::jni::JavaVM,
+            reserved: *mut std::ffi::c_void,
+        ) -> $crate::sys::jni::jint {
+            if $crate::GLOBAL_AGENT.get().is_none() {
+                let _ = $crate::set_global_agent(Box::new(<$agent_type>::default()));
             }
+
+            if let Some(global_agent) = $crate::GLOBAL_AGENT.get() {
+                return $crate::guard_agent_call("JNI_OnLoad", $crate::sys::jni::JNI_ERR, || {
+                    global_agent.jni_on_load(vm)
+                });
+            }
+
+            $crate::sys::jni::JNI_ERR
         }
     };
 }
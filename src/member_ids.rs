@@ -0,0 +1,71 @@
+//! Typed method and field IDs.
+//!
+//! `jmethodID`, `jfieldID`, and `jclass` are all `*mut c_void` in the raw
+//! bindings, so nothing stops a field ID from being passed where a method ID is
+//! expected. [`MethodId`] and [`FieldId`] keep them apart and, unlike object
+//! references, may be stored and shared between threads:
+//!
+//! ```rust,ignore
+//! use jvmti_bindings::env::MethodId;
+//! use std::sync::OnceLock;
+//!
+//! static LENGTH: OnceLock<MethodId> = OnceLock::new();
+//!
+//! fn string_length(jni: &JniEnv, s: jni::jstring) -> Option<jni::jint> {
+//!     let cls = jni.get_object_class_ref(s);
+//!     let method = *LENGTH.get_or_init(|| jni.method_id(cls.get(), "length", "()I").unwrap());
+//!     jni.call_int_method_checked(s, method.as_raw(), &[]).ok()
+//! }
+//! ```
+//!
+//! There is no counterpart for `jclass`: a class is an object reference, and
+//! is only valid beyond the current callback as a [`GlobalRef`](crate::env::GlobalRef).
+
+use crate::sys::jni;
+
+macro_rules! member_id {
+    ($(#[$doc:meta])* $name:ident, $raw:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct $name(jni::$raw);
+
+        // SAFETY: IDs are opaque handles that the VM keeps valid on every
+        // thread until the declaring class is unloaded.
+        unsafe impl Send for $name {}
+        unsafe impl Sync for $name {}
+
+        impl $name {
+            /// Wraps a raw ID; `None` if it is null.
+            pub fn from_raw(raw: jni::$raw) -> Option<Self> {
+                if raw.is_null() { None } else { Some(Self(raw)) }
+            }
+
+            /// The raw ID, for JNI and JVMTI calls.
+            pub const fn as_raw(self) -> jni::$raw {
+                self.0
+            }
+        }
+
+        impl From<$name> for jni::$raw {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+    };
+}
+
+member_id! {
+    /// A non-null `jmethodID`.
+    ///
+    /// Method IDs stay valid until the declaring class is unloaded, so they
+    /// can be cached in statics and used from any thread.
+    MethodId, jmethodID
+}
+
+member_id! {
+    /// A non-null `jfieldID`.
+    ///
+    /// Field IDs stay valid until the declaring class is unloaded, so they
+    /// can be cached in statics and used from any thread.
+    FieldId, jfieldID
+}
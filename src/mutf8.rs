@@ -0,0 +1,107 @@
+//! Java's "modified UTF-8".
+//!
+//! JNI (`NewStringUTF`, `GetStringUTFChars`, `FindClass`, ...) and JVMTI
+//! (class signatures, method names, properties) exchange strings in the
+//! encoding the class file format uses for `CONSTANT_Utf8`. It differs from
+//! standard UTF-8 in two ways:
+//!
+//! - NUL is encoded as `0xC0 0x80`, so an encoded string never contains a
+//!   zero byte and can be passed as a C string;
+//! - characters outside the Basic Multilingual Plane are encoded as a UTF-16
+//!   surrogate pair, three bytes per surrogate, instead of four bytes.
+//!
+//! Plain ASCII and most other text are identical in both encodings, which is
+//! why mixing them up usually goes unnoticed until a string carries an emoji
+//! or an embedded NUL:
+//!
+//! ```
+//! use jvmti_bindings::mutf8;
+//!
+//! assert_eq!(mutf8::encode("a\0b"), b"a\xC0\x80b");
+//! assert_eq!(mutf8::decode(&mutf8::encode("\u{1F600}")), "\u{1F600}");
+//! ```
+//!
+//! For full fidelity with no re-encoding at all, the UTF-16 JNI functions
+//! ([`JniEnv::new_string`](crate::env::JniEnv::new_string) and
+//! [`JniEnv::get_string`](crate::env::JniEnv::get_string)) remain available.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+/// Encodes `s` as modified UTF-8. The result never contains a zero byte.
+pub fn encode(s: &str) -> Vec<u8> {
+    // Without NULs and four-byte sequences, UTF-8 and modified UTF-8 agree.
+    if !s.bytes().any(|b| b == 0 || b >= 0xF0) {
+        return s.as_bytes().to_vec();
+    }
+    let mut out = Vec::with_capacity(s.len() + s.len() / 2);
+    for unit in s.encode_utf16() {
+        match unit {
+            0x0001..=0x007F => out.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => {
+                out.push(0xC0 | (unit >> 6) as u8);
+                out.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                out.push(0xE0 | (unit >> 12) as u8);
+                out.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                out.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    out
+}
+
+/// Decodes modified UTF-8, falling back to lossy UTF-8 for malformed input.
+///
+/// Standard UTF-8 is accepted as well, so strings from sources that do not
+/// use the modified encoding still decode.
+pub fn decode(bytes: &[u8]) -> String {
+    // Bytes below 0xED never start a surrogate or four-byte sequence, so
+    // plain UTF-8 without NULs decodes identically either way.
+    if !bytes.iter().any(|&b| b == 0 || b >= 0xED) {
+        if let Ok(s) = std::str::from_utf8(bytes) {
+            return s.to_string();
+        }
+    }
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b & 0x80 == 0 {
+            units.push(b as u16);
+            i += 1;
+        } else if b & 0xE0 == 0xC0 && i + 1 < bytes.len() && bytes[i + 1] & 0xC0 == 0x80 {
+            units.push(((b as u16 & 0x1F) << 6) | (bytes[i + 1] as u16 & 0x3F));
+            i += 2;
+        } else if b & 0xF0 == 0xE0
+            && i + 2 < bytes.len()
+            && bytes[i + 1] & 0xC0 == 0x80
+            && bytes[i + 2] & 0xC0 == 0x80
+        {
+            units.push(((b as u16 & 0x0F) << 12) | ((bytes[i + 1] as u16 & 0x3F) << 6) | (bytes[i + 2] as u16 & 0x3F));
+            i += 3;
+        } else {
+            return String::from_utf8_lossy(bytes).into_owned();
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// Encodes `s` as a NUL-terminated modified UTF-8 string for JNI and JVMTI.
+///
+/// Unlike `CString::new`, this cannot fail: embedded NULs are encoded as
+/// `0xC0 0x80`.
+pub fn to_cstring(s: &str) -> CString {
+    CString::new(encode(s)).expect("modified UTF-8 never contains a zero byte")
+}
+
+/// Decodes a NUL-terminated modified UTF-8 string returned by JNI or JVMTI.
+///
+/// # Safety
+///
+/// `ptr` must be non-null and point to a NUL-terminated string that stays
+/// valid for the duration of the call.
+pub unsafe fn from_cstr(ptr: *const c_char) -> String {
+    decode(CStr::from_ptr(ptr).to_bytes())
+}
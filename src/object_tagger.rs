@@ -0,0 +1,156 @@
+//! Typed side table for JVMTI object tags.
+//!
+//! JVMTI lets an agent attach a single `jlong` tag to any object. [`ObjectTagger`]
+//! hands out unique tags and keeps a Rust value per tag, so the tag becomes a key
+//! into agent-side metadata (allocation site, timestamp, ...). Entries are removed
+//! from the `ObjectFree` callback.
+//!
+//! ```rust,ignore
+//! use jvmti_bindings::env::{Jvmti, ObjectTagger};
+//! use std::sync::OnceLock;
+//!
+//! static TRACKED: OnceLock<ObjectTagger<String>> = OnceLock::new();
+//!
+//! fn track(jvmti: &Jvmti, obj: jni::jobject, site: String) {
+//!     let tagger = TRACKED.get_or_init(ObjectTagger::new);
+//!     let _ = tagger.tag_object(jvmti, obj, site);
+//! }
+//!
+//! impl Agent for LeakDetector {
+//!     fn object_free(&self, tag: jni::jlong) {
+//!         if let Some(site) = TRACKED.get().and_then(|t| t.object_freed(tag)) {
+//!             println!("collected object allocated at {site}");
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! The tagger assumes it owns every tag it sees: do not mix it with other
+//! `set_tag` users in the same JVMTI environment.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::jvmti_wrapper::Jvmti;
+use crate::sys::{jni, jvmti};
+
+/// Assigns unique JVMTI tags to objects and stores a value of type `T` per tag.
+///
+/// Tagging needs `can_tag_objects`; removing entries on collection needs
+/// `can_generate_object_free_events` and the `ObjectFree` event enabled.
+pub struct ObjectTagger<T> {
+    next_tag: AtomicI64,
+    values: Mutex<HashMap<jni::jlong, T>>,
+}
+
+impl<T> ObjectTagger<T> {
+    /// Creates an empty tagger. The first tag handed out is 1.
+    pub fn new() -> Self {
+        ObjectTagger {
+            next_tag: AtomicI64::new(1),
+            values: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn values(&self) -> MutexGuard<'_, HashMap<jni::jlong, T>> {
+        self.values.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Tags `obj` with a fresh tag and associates `value` with it.
+    ///
+    /// Returns the tag. If `SetTag` fails, nothing is stored.
+    pub fn tag_object(&self, jvmti: &Jvmti, obj: jni::jobject, value: T) -> Result<jni::jlong, jvmti::jvmtiError> {
+        let tag = self.next_tag.fetch_add(1, Ordering::Relaxed);
+        jvmti.set_tag(obj, tag)?;
+        self.values().insert(tag, value);
+        Ok(tag)
+    }
+
+    /// Returns a clone of the value stored for `tag`.
+    pub fn get_value(&self, tag: jni::jlong) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.values().get(&tag).cloned()
+    }
+
+    /// Runs `f` on the value stored for `tag` without cloning it.
+    pub fn with_value<R>(&self, tag: jni::jlong, f: impl FnOnce(&T) -> R) -> Option<R> {
+        self.values().get(&tag).map(f)
+    }
+
+    /// Looks up the value for `obj` by reading its current tag.
+    pub fn value_of(&self, jvmti: &Jvmti, obj: jni::jobject) -> Result<Option<T>, jvmti::jvmtiError>
+    where
+        T: Clone,
+    {
+        let tag = jvmti.get_tag(obj)?;
+        Ok(self.get_value(tag))
+    }
+
+    /// Finds the live objects carrying any of `tags`, paired with their values.
+    ///
+    /// Uses `GetObjectsWithTags`. The returned `jobject`s are local references,
+    /// valid only until the current native method or callback returns; promote
+    /// them with `JniEnv::new_global_ref` to keep them longer.
+    pub fn live_objects(&self, jvmti: &Jvmti, tags: &[jni::jlong]) -> Result<Vec<(jni::jobject, T)>, jvmti::jvmtiError>
+    where
+        T: Clone,
+    {
+        if tags.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (objects, object_tags) = jvmti.get_objects_with_tags(tags)?;
+        let values = self.values();
+        Ok(objects
+            .into_iter()
+            .zip(object_tags)
+            .filter_map(|(obj, tag)| values.get(&tag).map(|value| (obj, value.clone())))
+            .collect())
+    }
+
+    /// Finds the live tracked objects whose value matches `predicate`.
+    ///
+    /// For example, all instances tagged at one allocation site. The same
+    /// local-reference lifetime rules as [`live_objects`](Self::live_objects) apply.
+    pub fn live_objects_matching(
+        &self,
+        jvmti: &Jvmti,
+        mut predicate: impl FnMut(&T) -> bool,
+    ) -> Result<Vec<(jni::jobject, T)>, jvmti::jvmtiError>
+    where
+        T: Clone,
+    {
+        let tags: Vec<jni::jlong> = self
+            .values()
+            .iter()
+            .filter(|(_, value)| predicate(value))
+            .map(|(tag, _)| *tag)
+            .collect();
+        self.live_objects(jvmti, &tags)
+    }
+
+    /// Removes and returns the value for a collected object.
+    ///
+    /// Call this from [`Agent::object_free`](crate::Agent::object_free).
+    pub fn object_freed(&self, tag: jni::jlong) -> Option<T> {
+        self.values().remove(&tag)
+    }
+
+    /// Number of objects currently tracked.
+    pub fn len(&self) -> usize {
+        self.values().len()
+    }
+
+    /// Returns true if no objects are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.values().is_empty()
+    }
+}
+
+impl<T> Default for ObjectTagger<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -4,10 +4,14 @@
 //! agents use while avoiding over-broad re-exports.
 
 pub use crate::describe_jni_result;
+pub use crate::{class_data_slice, class_name};
 #[cfg(feature = "embed")]
 pub use crate::embed::{find_libjvm, find_libjvm_verbose, AttachedThread, JavaVm, JavaVmBuilder};
-pub use crate::env::{GlobalRef, JniEnv, Jvmti, LocalRef};
+pub use crate::env::{GlobalRef, JniEnv, JniError, Jvmti, JvmtiResult, LocalRef};
 pub use crate::export_agent;
+pub use crate::export_jni_onload;
 pub use crate::get_default_callbacks;
+pub use crate::get_callbacks_for;
+pub use crate::jvalues;
 pub use crate::sys::{jni, jvmti};
 pub use crate::Agent;
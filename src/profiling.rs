@@ -0,0 +1,354 @@
+//! Output formats for sampling profilers.
+//!
+//! Requires the `profiling` feature.
+//!
+//! [`FoldedStacks`] accumulates symbolized stack samples in the collapsed
+//! ("folded") format read by Brendan Gregg's `flamegraph.pl`, speedscope, and
+//! inferno: one `root;caller;callee <count>` line per distinct stack.
+//!
+//! ```rust,ignore
+//! use jvmti_bindings::profiling::FoldedStacks;
+//!
+//! let mut stacks = FoldedStacks::new();
+//! for _ in 0..1000 {
+//!     stacks.sample_all_threads(&jvmti, 64)?;
+//!     std::thread::sleep(std::time::Duration::from_millis(10));
+//! }
+//! stacks.write(std::fs::File::create("profile.folded")?)?;
+//! ```
+//!
+//! [`AllocSampler`] does the same for the JDK 11+ allocation sampler, fed
+//! from `SampledObjectAlloc` events after
+//! [`Jvmti::start_allocation_sampling`]:
+//!
+//! ```rust,ignore
+//! use jvmti_bindings::profiling::AllocSampler;
+//! use std::sync::OnceLock;
+//!
+//! static ALLOCS: OnceLock<AllocSampler> = OnceLock::new();
+//!
+//! impl Agent for Profiler {
+//!     fn sampled_object_alloc_with_env(&self, jvmti: &Jvmti, _jni: &JniEnv, thread: jni::jthread, _object: jni::jobject, klass: jni::jclass, size: jni::jlong) {
+//!         let _ = ALLOCS.get_or_init(|| AllocSampler::with_stacks(64)).record(jvmti, thread, klass, size);
+//!     }
+//! }
+//! ```
+//!
+//! [`CpuSampler`] turns successive [`Jvmti::cpu_times`] snapshots into
+//! per-thread CPU deltas; comparing them with the elapsed wall-clock time
+//! shows which threads were running and which were waiting.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::classfile::descriptor::signature_to_java_name;
+use crate::env::{GlobalRef, JniEnv, Jvmti, SymbolicFrame};
+use crate::sys::{jni, jvmti};
+
+/// Counts of identical stacks, keyed by their folded representation.
+#[derive(Debug, Clone, Default)]
+pub struct FoldedStacks {
+    counts: HashMap<String, u64>,
+}
+
+impl FoldedStacks {
+    /// Creates an empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records one sample of `frames`, innermost frame first as returned by
+    /// `GetStackTrace` and [`Jvmti::symbolize_frames`]. Empty stacks are ignored.
+    pub fn record(&mut self, frames: &[SymbolicFrame]) {
+        self.record_weighted(frames, 1);
+    }
+
+    /// Records `frames` with an explicit sample count, e.g. bytes allocated.
+    pub fn record_weighted(&mut self, frames: &[SymbolicFrame], weight: u64) {
+        if frames.is_empty() || weight == 0 {
+            return;
+        }
+        *self.counts.entry(fold(frames)).or_insert(0) += weight;
+    }
+
+    /// Records `frames` with `leaf` as an extra innermost frame, e.g. the
+    /// allocated class in an allocation profile. `frames` may be empty.
+    pub fn record_with_leaf(&mut self, frames: &[SymbolicFrame], leaf: &str, weight: u64) {
+        if weight == 0 {
+            return;
+        }
+        let mut folded = fold(frames);
+        if !folded.is_empty() {
+            folded.push(';');
+        }
+        folded.push_str(leaf);
+        *self.counts.entry(folded).or_insert(0) += weight;
+    }
+
+    /// Samples every live thread with `GetAllStackTraces`, symbolizes the
+    /// frames, and records one sample per non-empty stack.
+    ///
+    /// Returns the number of stacks recorded.
+    pub fn sample_all_threads(&mut self, jvmti_env: &Jvmti, max_frame_count: jni::jint) -> Result<usize, jvmti::jvmtiError> {
+        let mut recorded = 0;
+        for stack in jvmti_env.get_all_stack_traces(max_frame_count)? {
+            if stack.frames.is_empty() {
+                continue;
+            }
+            self.record(&jvmti_env.symbolize_frames(&stack.frames));
+            recorded += 1;
+        }
+        Ok(recorded)
+    }
+
+    /// Writes one `a;b;c <count>` line per distinct stack, sorted by stack.
+    pub fn write<W: Write>(&self, w: W) -> io::Result<()> {
+        let mut out = io::BufWriter::new(w);
+        let mut lines: Vec<(&String, &u64)> = self.counts.iter().collect();
+        lines.sort_unstable();
+        for (stack, count) in lines {
+            writeln!(out, "{stack} {count}")?;
+        }
+        out.flush()
+    }
+
+    /// Sample count recorded for an exact folded stack string.
+    pub fn count(&self, folded: &str) -> u64 {
+        self.counts.get(folded).copied().unwrap_or(0)
+    }
+
+    /// Total number of samples recorded.
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Number of distinct stacks.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Returns true if nothing has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Discards all recorded samples.
+    pub fn clear(&mut self) {
+        self.counts.clear();
+    }
+}
+
+fn fold(frames: &[SymbolicFrame]) -> String {
+    let mut folded = String::new();
+    for (index, frame) in frames.iter().rev().enumerate() {
+        if index > 0 {
+            folded.push(';');
+        }
+        folded.push_str(&frame_label(frame));
+    }
+    folded
+}
+
+/// Sampled allocations of one class.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClassAllocations {
+    /// Number of sampled allocations.
+    pub samples: u64,
+    /// Total size of the sampled objects, in bytes.
+    pub bytes: u64,
+}
+
+#[derive(Default)]
+struct AllocState {
+    classes: HashMap<String, ClassAllocations>,
+    stacks: FoldedStacks,
+}
+
+/// Accumulates `SampledObjectAlloc` events by allocated class and, optionally,
+/// by allocating stack.
+///
+/// Events arrive on the allocating threads, so recording takes `&self` and
+/// the sampler can live in a static. The sampler only sees one allocation per
+/// sampling interval, so counts are samples, not totals: compare classes and
+/// stacks with each other rather than with the heap size.
+pub struct AllocSampler {
+    max_frame_count: jni::jint,
+    state: Mutex<AllocState>,
+}
+
+impl AllocSampler {
+    /// Creates a sampler that counts by class only.
+    pub fn new() -> Self {
+        Self::with_stacks(0)
+    }
+
+    /// Creates a sampler that also records up to `max_frame_count` frames of
+    /// each allocating stack, with the allocated class as the leaf frame.
+    pub fn with_stacks(max_frame_count: jni::jint) -> Self {
+        AllocSampler { max_frame_count: max_frame_count.max(0), state: Mutex::new(AllocState::default()) }
+    }
+
+    fn state(&self) -> MutexGuard<'_, AllocState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Records one `SampledObjectAlloc` event.
+    ///
+    /// Call from [`Agent::sampled_object_alloc_with_env`](crate::Agent::sampled_object_alloc_with_env)
+    /// with the event's arguments. Looks up the class signature and, if
+    /// stacks are recorded, the current stack of `thread`; nothing is
+    /// recorded if either lookup fails.
+    pub fn record(&self, jvmti_env: &Jvmti, thread: jni::jthread, klass: jni::jclass, size: jni::jlong) -> Result<(), jvmti::jvmtiError> {
+        let (signature, _) = jvmti_env.get_class_signature(klass)?;
+        let frames = if self.max_frame_count > 0 {
+            jvmti_env.symbolize_frames(&jvmti_env.get_stack_trace(thread, 0, self.max_frame_count)?)
+        } else {
+            Vec::new()
+        };
+        self.record_sample(&signature, &frames, size);
+        Ok(())
+    }
+
+    /// Records one sampled allocation of `size` bytes of the class with
+    /// `class_signature`, allocated by `frames` (innermost first).
+    ///
+    /// `frames` is ignored unless the sampler was created with
+    /// [`with_stacks`](Self::with_stacks).
+    pub fn record_sample(&self, class_signature: &str, frames: &[SymbolicFrame], size: jni::jlong) {
+        let class = signature_to_java_name(class_signature);
+        let bytes = size.max(0) as u64;
+        let mut state = self.state();
+        if self.max_frame_count > 0 {
+            state.stacks.record_with_leaf(frames, &class, bytes);
+        }
+        let entry = state.classes.entry(class).or_default();
+        entry.samples += 1;
+        entry.bytes += bytes;
+    }
+
+    /// Per-class totals by Java class name (`java.lang.String`, `int[]`),
+    /// largest byte count first.
+    pub fn classes(&self) -> Vec<(String, ClassAllocations)> {
+        let mut classes: Vec<_> = self.state().classes.iter().map(|(name, allocs)| (name.clone(), *allocs)).collect();
+        classes.sort_unstable_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then_with(|| a.0.cmp(&b.0)));
+        classes
+    }
+
+    /// Sampled bytes by allocating stack, weighted by object size.
+    ///
+    /// Empty unless the sampler was created with [`with_stacks`](Self::with_stacks).
+    pub fn stacks(&self) -> FoldedStacks {
+        self.state().stacks.clone()
+    }
+
+    /// Writes the allocation flame graph in the folded format.
+    ///
+    /// With stacks, one line per allocating stack ending in the allocated
+    /// class; without, one single-frame line per class. Counts are bytes.
+    pub fn write_folded<W: Write>(&self, w: W) -> io::Result<()> {
+        if self.max_frame_count > 0 {
+            return self.stacks().write(w);
+        }
+        let mut stacks = FoldedStacks::new();
+        for (class, allocs) in self.classes() {
+            stacks.record_with_leaf(&[], &class, allocs.bytes);
+        }
+        stacks.write(w)
+    }
+
+    /// Total sampled bytes across all classes.
+    pub fn total_bytes(&self) -> u64 {
+        self.state().classes.values().map(|allocs| allocs.bytes).sum()
+    }
+
+    /// Discards everything recorded.
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.classes.clear();
+        state.stacks.clear();
+    }
+}
+
+impl Default for AllocSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// CPU time one thread used between two [`CpuSampler::sample`] calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadCpuDelta {
+    /// The thread, as a local reference from the current sample.
+    pub thread: jni::jthread,
+    /// CPU nanoseconds since the previous sample, or since the thread
+    /// started if the previous sample did not include it.
+    pub cpu_nanos: jni::jlong,
+    /// CPU nanoseconds since the thread started.
+    pub total_nanos: jni::jlong,
+}
+
+/// Diffs successive [`Jvmti::cpu_times`] snapshots into per-thread CPU deltas.
+///
+/// JVMTI has no stable thread id, so threads are matched between samples
+/// with `IsSameObject` against [`GlobalRef`]s the sampler keeps. A sample
+/// therefore costs up to one JNI call per pair of previous and current
+/// threads, quadratic in the thread count; matched threads are not compared
+/// again. The references are deleted when replaced, on
+/// [`clear`](Self::clear), and when the sampler is dropped.
+#[derive(Default)]
+pub struct CpuSampler {
+    previous: Vec<(GlobalRef, jni::jlong)>,
+}
+
+impl std::fmt::Debug for CpuSampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CpuSampler").field("threads", &self.previous.len()).finish()
+    }
+}
+
+impl CpuSampler {
+    /// Creates a sampler with no previous sample.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a snapshot and returns each live thread's CPU time since the
+    /// previous one.
+    ///
+    /// The first call reports every thread's total. Needs
+    /// `can_get_thread_cpu_time`; like [`Jvmti::cpu_times`], the threads in
+    /// the result are local references.
+    pub fn sample(&mut self, jvmti_env: &Jvmti, jni: &JniEnv) -> Result<Vec<ThreadCpuDelta>, jvmti::jvmtiError> {
+        let times = jvmti_env.cpu_times()?;
+        let mut deltas = Vec::with_capacity(times.len());
+        let mut next = Vec::with_capacity(times.len());
+        for (thread, total_nanos) in times {
+            let before = self
+                .previous
+                .iter()
+                .position(|(previous, _)| jni.is_same_object(previous.get(), thread))
+                .map_or(0, |index| self.previous.swap_remove(index).1);
+            deltas.push(ThreadCpuDelta { thread, cpu_nanos: (total_nanos - before).max(0), total_nanos });
+            // SAFETY: `jni` is the current thread's environment; the reference
+            // is deleted through the VM, not `jni`, when dropped.
+            let global = unsafe { GlobalRef::new(jni, thread) };
+            if !global.get().is_null() {
+                next.push((global, total_nanos));
+            }
+        }
+        self.previous = next;
+        Ok(deltas)
+    }
+
+    /// Deletes the global references kept from the previous sample; the next
+    /// sample reports totals again.
+    pub fn clear(&mut self) {
+        self.previous.clear();
+    }
+}
+
+/// Formats a frame as `java.lang.String.indexOf`, the label used in folded stacks.
+pub fn frame_label(frame: &SymbolicFrame) -> String {
+    crate::jvmti_wrapper::frame_label(frame)
+}
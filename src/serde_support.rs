@@ -0,0 +1,67 @@
+//! `serde` adapters for raw JNI/JVMTI values in the wrapper structs.
+//!
+//! Object references and method/function pointers are written as their
+//! `usize` address. They are only meaningful inside the process and VM that
+//! produced them, so they are not read back: deserialized handles are null
+//! (or empty lists), never a stale or forged pointer.
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::sys::jvmti;
+
+/// A single raw pointer as its address.
+pub(crate) mod ptr {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer, T>(value: &*mut T, serializer: S) -> Result<S::Ok, S::Error> {
+        (*value as usize).serialize(serializer)
+    }
+}
+
+/// A list of raw pointers as addresses.
+pub(crate) mod ptr_vec {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer, T>(values: &[*mut T], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|&value| value as usize))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct FrameInfo {
+    method: usize,
+    location: jvmti::jlocation,
+}
+
+/// `jvmtiFrameInfo` lists as `{ method, location }` objects; methods read back as null.
+pub(crate) mod frames {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(frames: &[jvmti::jvmtiFrameInfo], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(frames.iter().map(|frame| FrameInfo { method: frame.method as usize, location: frame.location }))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<jvmti::jvmtiFrameInfo>, D::Error> {
+        Ok(Vec::<FrameInfo>::deserialize(deserializer)?
+            .into_iter()
+            .map(|frame| jvmti::jvmtiFrameInfo { method: std::ptr::null_mut(), location: frame.location })
+            .collect())
+    }
+}
+
+/// `jvmtiError` lists as numeric error codes.
+pub(crate) mod errors {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(errors: &[jvmti::jvmtiError], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(errors.iter().map(|err| err.code()))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<jvmti::jvmtiError>, D::Error> {
+        Vec::<u32>::deserialize(deserializer)?
+            .into_iter()
+            .map(|code| jvmti::jvmtiError::from_raw(code).ok_or_else(|| D::Error::custom(format!("unknown JVMTI error code {code}"))))
+            .collect()
+    }
+}
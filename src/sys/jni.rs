@@ -85,6 +85,79 @@ pub union jvalue {
     pub l: jobject,
 }
 
+impl jvalue {
+    /// A `boolean` argument.
+    pub const fn boolean(value: bool) -> jvalue {
+        jvalue { z: value as jboolean }
+    }
+
+    /// A `byte` argument.
+    pub const fn byte(value: jbyte) -> jvalue {
+        jvalue { b: value }
+    }
+
+    /// A `char` argument (a UTF-16 code unit).
+    pub const fn char(value: jchar) -> jvalue {
+        jvalue { c: value }
+    }
+
+    /// A `short` argument.
+    pub const fn short(value: jshort) -> jvalue {
+        jvalue { s: value }
+    }
+
+    /// An `int` argument.
+    pub const fn int(value: jint) -> jvalue {
+        jvalue { i: value }
+    }
+
+    /// A `long` argument.
+    pub const fn long(value: jlong) -> jvalue {
+        jvalue { j: value }
+    }
+
+    /// A `float` argument.
+    pub const fn float(value: jfloat) -> jvalue {
+        jvalue { f: value }
+    }
+
+    /// A `double` argument.
+    pub const fn double(value: jdouble) -> jvalue {
+        jvalue { d: value }
+    }
+
+    /// A reference argument; null passes Java `null`.
+    pub const fn object(value: jobject) -> jvalue {
+        jvalue { l: value }
+    }
+}
+
+// No `From<u8>`: `jboolean` is `u8`, and a conversion that silently picked
+// the boolean field for byte values would be the mistake these impls avoid.
+macro_rules! jvalue_from {
+    ($($ty:ty => $ctor:ident),* $(,)?) => {
+        $(
+            impl From<$ty> for jvalue {
+                fn from(value: $ty) -> jvalue {
+                    jvalue::$ctor(value)
+                }
+            }
+        )*
+    };
+}
+
+jvalue_from! {
+    bool => boolean,
+    jbyte => byte,
+    jchar => char,
+    jshort => short,
+    jint => int,
+    jlong => long,
+    jfloat => float,
+    jdouble => double,
+    jobject => object,
+}
+
 // =============================================================================
 // Constants
 // =============================================================================
@@ -1292,6 +1365,18 @@ pub type JNI_GetCreatedJavaVMs = unsafe extern "system" fn(
 // Helper macros and functions
 // =============================================================================
 
+/// Builds a `[jvalue; N]` argument array, converting each argument with `jvalue::from`.
+///
+/// The union field follows the Rust type, so suffix integer literals that are
+/// not `int`s: `jvalues![obj, 42, 7i64, true]` passes an object, an `int`, a
+/// `long`, and a `boolean`.
+#[macro_export]
+macro_rules! jvalues {
+    ($($arg:expr),* $(,)?) => {
+        [$($crate::sys::jni::jvalue::from($arg)),*]
+    };
+}
+
 /// Helper to call JNI functions through the vtable.
 /// env_ptr: *mut JNIEnv = *mut *const JNINativeInterface_
 /// *env_ptr: *const JNINativeInterface_ (vtable pointer)
@@ -19,7 +19,7 @@
 #![allow(non_snake_case)]
 
 use std::os::raw::{c_void, c_uchar};
-use crate::sys::jni::{jint, jlong, jthread, jclass, jobject, jfieldID, jmethodID, jboolean, JNIEnv, jvalue, jfloat, jdouble};
+use crate::sys::jni::{jint, jlong, jthread, jclass, jobject, jfieldID, jmethodID, jboolean, JNIEnv, jvalue, jfloat, jdouble, jchar};
 
 // --- Constants ---
 pub const JVMTI_VERSION_1_0: jint = 0x30010000;
@@ -30,6 +30,25 @@ pub const JVMTI_VERSION_11: jint = 0x300B0000;
 pub const JVMTI_VERSION_19: jint = 0x30130000;
 pub const JVMTI_VERSION_21: jint = 0x30150000;
 
+pub const JVMTI_VERSION_INTERFACE_JNI: jint = 0x00000000;
+pub const JVMTI_VERSION_INTERFACE_JVMTI: jint = 0x30000000;
+pub const JVMTI_VERSION_MASK_INTERFACE_TYPE: jint = 0x70000000;
+pub const JVMTI_VERSION_MASK_MAJOR: jint = 0x0FFF0000;
+pub const JVMTI_VERSION_MASK_MINOR: jint = 0x0000FF00;
+pub const JVMTI_VERSION_MASK_MICRO: jint = 0x000000FF;
+pub const JVMTI_VERSION_SHIFT_MAJOR: jint = 16;
+pub const JVMTI_VERSION_SHIFT_MINOR: jint = 8;
+pub const JVMTI_VERSION_SHIFT_MICRO: jint = 0;
+
+/// Split a `GetVersionNumber` value into `(major, minor, micro)`.
+pub const fn version_components(version: jint) -> (u32, u32, u32) {
+    (
+        ((version & JVMTI_VERSION_MASK_MAJOR) >> JVMTI_VERSION_SHIFT_MAJOR) as u32,
+        ((version & JVMTI_VERSION_MASK_MINOR) >> JVMTI_VERSION_SHIFT_MINOR) as u32,
+        ((version & JVMTI_VERSION_MASK_MICRO) >> JVMTI_VERSION_SHIFT_MICRO) as u32,
+    )
+}
+
 pub const JVMTI_EVENT_VM_INIT: u32 = 50;
 pub const JVMTI_EVENT_VM_DEATH: u32 = 51;
 pub const JVMTI_EVENT_THREAD_START: u32 = 52;
@@ -70,6 +89,12 @@ pub const JVMTI_HEAP_OBJECT_EITHER: jint = 0;
 pub const JVMTI_HEAP_OBJECT_TAGGED: jint = 1;
 pub const JVMTI_HEAP_OBJECT_UNTAGGED: jint = 2;
 
+// --- Heap Filter Flags (FollowReferences, IterateThroughHeap) ---
+pub const JVMTI_HEAP_FILTER_TAGGED: jint = 0x4;
+pub const JVMTI_HEAP_FILTER_UNTAGGED: jint = 0x8;
+pub const JVMTI_HEAP_FILTER_CLASS_TAGGED: jint = 0x10;
+pub const JVMTI_HEAP_FILTER_CLASS_UNTAGGED: jint = 0x20;
+
 
 
 // --- Phases ---
@@ -79,23 +104,201 @@ pub const JVMTI_PHASE_START: jint = 6;
 pub const JVMTI_PHASE_LIVE: jint = 4;
 pub const JVMTI_PHASE_DEAD: jint = 8;
 
+// --- Thread State Flags (GetThreadState) ---
+pub const JVMTI_THREAD_STATE_ALIVE: jint = 0x0001;
+pub const JVMTI_THREAD_STATE_TERMINATED: jint = 0x0002;
+pub const JVMTI_THREAD_STATE_RUNNABLE: jint = 0x0004;
+pub const JVMTI_THREAD_STATE_WAITING_INDEFINITELY: jint = 0x0010;
+pub const JVMTI_THREAD_STATE_WAITING_WITH_TIMEOUT: jint = 0x0020;
+pub const JVMTI_THREAD_STATE_SLEEPING: jint = 0x0040;
+pub const JVMTI_THREAD_STATE_WAITING: jint = 0x0080;
+pub const JVMTI_THREAD_STATE_IN_OBJECT_WAIT: jint = 0x0100;
+pub const JVMTI_THREAD_STATE_PARKED: jint = 0x0200;
+pub const JVMTI_THREAD_STATE_BLOCKED_ON_MONITOR_ENTER: jint = 0x0400;
+pub const JVMTI_THREAD_STATE_SUSPENDED: jint = 0x100000;
+pub const JVMTI_THREAD_STATE_INTERRUPTED: jint = 0x200000;
+pub const JVMTI_THREAD_STATE_IN_NATIVE: jint = 0x400000;
+pub const JVMTI_THREAD_STATE_VENDOR_1: jint = 0x10000000;
+pub const JVMTI_THREAD_STATE_VENDOR_2: jint = 0x20000000;
+pub const JVMTI_THREAD_STATE_VENDOR_3: jint = 0x40000000;
+
+// --- Class Status Flags (GetClassStatus) ---
+pub const JVMTI_CLASS_STATUS_VERIFIED: jint = 1;
+pub const JVMTI_CLASS_STATUS_PREPARED: jint = 2;
+pub const JVMTI_CLASS_STATUS_INITIALIZED: jint = 4;
+pub const JVMTI_CLASS_STATUS_ERROR: jint = 8;
+pub const JVMTI_CLASS_STATUS_ARRAY: jint = 16;
+pub const JVMTI_CLASS_STATUS_PRIMITIVE: jint = 32;
+
+// --- Resource Exhaustion Flags (ResourceExhausted event) ---
+pub const JVMTI_RESOURCE_EXHAUSTED_OOM_ERROR: jint = 0x0001;
+pub const JVMTI_RESOURCE_EXHAUSTED_JAVA_HEAP: jint = 0x0002;
+pub const JVMTI_RESOURCE_EXHAUSTED_THREADS: jint = 0x0004;
+
+// --- java.lang.Thread.State Conversion Masks ---
+pub const JVMTI_JAVA_LANG_THREAD_STATE_MASK: jint = JVMTI_THREAD_STATE_TERMINATED
+    | JVMTI_THREAD_STATE_ALIVE
+    | JVMTI_THREAD_STATE_RUNNABLE
+    | JVMTI_THREAD_STATE_BLOCKED_ON_MONITOR_ENTER
+    | JVMTI_THREAD_STATE_WAITING
+    | JVMTI_THREAD_STATE_WAITING_INDEFINITELY
+    | JVMTI_THREAD_STATE_WAITING_WITH_TIMEOUT;
+pub const JVMTI_JAVA_LANG_THREAD_STATE_NEW: jint = 0;
+pub const JVMTI_JAVA_LANG_THREAD_STATE_TERMINATED: jint = JVMTI_THREAD_STATE_TERMINATED;
+pub const JVMTI_JAVA_LANG_THREAD_STATE_RUNNABLE: jint = JVMTI_THREAD_STATE_ALIVE | JVMTI_THREAD_STATE_RUNNABLE;
+pub const JVMTI_JAVA_LANG_THREAD_STATE_BLOCKED: jint =
+    JVMTI_THREAD_STATE_ALIVE | JVMTI_THREAD_STATE_BLOCKED_ON_MONITOR_ENTER;
+pub const JVMTI_JAVA_LANG_THREAD_STATE_WAITING: jint =
+    JVMTI_THREAD_STATE_ALIVE | JVMTI_THREAD_STATE_WAITING | JVMTI_THREAD_STATE_WAITING_INDEFINITELY;
+pub const JVMTI_JAVA_LANG_THREAD_STATE_TIMED_WAITING: jint =
+    JVMTI_THREAD_STATE_ALIVE | JVMTI_THREAD_STATE_WAITING | JVMTI_THREAD_STATE_WAITING_WITH_TIMEOUT;
+
+// --- jlocation Formats (GetJLocationFormat) ---
+pub const JVMTI_JLOCATION_JVMBCI: jint = 1;
+pub const JVMTI_JLOCATION_MACHINEPC: jint = 2;
+pub const JVMTI_JLOCATION_OTHER: jint = 0;
+
+// --- Timer Kinds (jvmtiTimerInfo.kind) ---
+pub const JVMTI_TIMER_USER_CPU: jint = 30;
+pub const JVMTI_TIMER_TOTAL_CPU: jint = 31;
+pub const JVMTI_TIMER_ELAPSED: jint = 32;
+
 pub const JVMTI_ENABLE: jint = 1;
 pub const JVMTI_DISABLE: jint = 0;
 
 // --- Error Codes ---
+/// Error codes returned by JVMTI functions (JVMTI spec, "Error Reference").
+///
+/// Every code defined by the specification is listed so that values coming
+/// back from the VM are always valid discriminants. Use
+/// [`jvmtiError::from_raw`] when converting an untrusted integer.
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum jvmtiError {
     NONE = 0,
-    ILLEGAL_ARGUMENT = 103,
     INVALID_THREAD = 10,
+    INVALID_THREAD_GROUP = 11,
+    INVALID_PRIORITY = 12,
+    THREAD_NOT_SUSPENDED = 13,
+    THREAD_SUSPENDED = 14,
+    THREAD_NOT_ALIVE = 15,
+    INVALID_OBJECT = 20,
     INVALID_CLASS = 21,
+    CLASS_NOT_PREPARED = 22,
+    INVALID_METHODID = 23,
+    INVALID_LOCATION = 24,
+    INVALID_FIELDID = 25,
+    INVALID_MODULE = 26,
+    NO_MORE_FRAMES = 31,
+    OPAQUE_FRAME = 32,
+    TYPE_MISMATCH = 34,
+    INVALID_SLOT = 35,
+    DUPLICATE = 40,
+    NOT_FOUND = 41,
+    INVALID_MONITOR = 50,
+    NOT_MONITOR_OWNER = 51,
+    INTERRUPT = 52,
+    INVALID_CLASS_FORMAT = 60,
+    CIRCULAR_CLASS_DEFINITION = 61,
+    FAILS_VERIFICATION = 62,
+    UNSUPPORTED_REDEFINITION_METHOD_ADDED = 63,
+    UNSUPPORTED_REDEFINITION_SCHEMA_CHANGED = 64,
+    INVALID_TYPESTATE = 65,
+    UNSUPPORTED_REDEFINITION_HIERARCHY_CHANGED = 66,
+    UNSUPPORTED_REDEFINITION_METHOD_DELETED = 67,
+    UNSUPPORTED_VERSION = 68,
+    NAMES_DONT_MATCH = 69,
+    UNSUPPORTED_REDEFINITION_CLASS_MODIFIERS_CHANGED = 70,
+    UNSUPPORTED_REDEFINITION_METHOD_MODIFIERS_CHANGED = 71,
+    UNSUPPORTED_REDEFINITION_CLASS_ATTRIBUTE_CHANGED = 72,
+    UNSUPPORTED_OPERATION = 73,
+    UNMODIFIABLE_CLASS = 79,
+    UNMODIFIABLE_MODULE = 80,
     NOT_AVAILABLE = 98,
     MUST_POSSESS_CAPABILITY = 99,
     NULL_POINTER = 100,
     ABSENT_INFORMATION = 101,
     INVALID_EVENT_TYPE = 102,
-    // ...
+    ILLEGAL_ARGUMENT = 103,
+    NATIVE_METHOD = 104,
+    CLASS_LOADER_UNSUPPORTED = 106,
+    OUT_OF_MEMORY = 110,
+    ACCESS_DENIED = 111,
+    WRONG_PHASE = 112,
+    INTERNAL = 113,
+    UNATTACHED_THREAD = 115,
+    INVALID_ENVIRONMENT = 116,
+}
+
+impl jvmtiError {
+    /// Convert a raw JVMTI error code into a `jvmtiError`.
+    ///
+    /// Returns `None` for values not defined by the specification.
+    pub const fn from_raw(code: u32) -> Option<Self> {
+        let err = match code {
+            0 => jvmtiError::NONE,
+            10 => jvmtiError::INVALID_THREAD,
+            11 => jvmtiError::INVALID_THREAD_GROUP,
+            12 => jvmtiError::INVALID_PRIORITY,
+            13 => jvmtiError::THREAD_NOT_SUSPENDED,
+            14 => jvmtiError::THREAD_SUSPENDED,
+            15 => jvmtiError::THREAD_NOT_ALIVE,
+            20 => jvmtiError::INVALID_OBJECT,
+            21 => jvmtiError::INVALID_CLASS,
+            22 => jvmtiError::CLASS_NOT_PREPARED,
+            23 => jvmtiError::INVALID_METHODID,
+            24 => jvmtiError::INVALID_LOCATION,
+            25 => jvmtiError::INVALID_FIELDID,
+            26 => jvmtiError::INVALID_MODULE,
+            31 => jvmtiError::NO_MORE_FRAMES,
+            32 => jvmtiError::OPAQUE_FRAME,
+            34 => jvmtiError::TYPE_MISMATCH,
+            35 => jvmtiError::INVALID_SLOT,
+            40 => jvmtiError::DUPLICATE,
+            41 => jvmtiError::NOT_FOUND,
+            50 => jvmtiError::INVALID_MONITOR,
+            51 => jvmtiError::NOT_MONITOR_OWNER,
+            52 => jvmtiError::INTERRUPT,
+            60 => jvmtiError::INVALID_CLASS_FORMAT,
+            61 => jvmtiError::CIRCULAR_CLASS_DEFINITION,
+            62 => jvmtiError::FAILS_VERIFICATION,
+            63 => jvmtiError::UNSUPPORTED_REDEFINITION_METHOD_ADDED,
+            64 => jvmtiError::UNSUPPORTED_REDEFINITION_SCHEMA_CHANGED,
+            65 => jvmtiError::INVALID_TYPESTATE,
+            66 => jvmtiError::UNSUPPORTED_REDEFINITION_HIERARCHY_CHANGED,
+            67 => jvmtiError::UNSUPPORTED_REDEFINITION_METHOD_DELETED,
+            68 => jvmtiError::UNSUPPORTED_VERSION,
+            69 => jvmtiError::NAMES_DONT_MATCH,
+            70 => jvmtiError::UNSUPPORTED_REDEFINITION_CLASS_MODIFIERS_CHANGED,
+            71 => jvmtiError::UNSUPPORTED_REDEFINITION_METHOD_MODIFIERS_CHANGED,
+            72 => jvmtiError::UNSUPPORTED_REDEFINITION_CLASS_ATTRIBUTE_CHANGED,
+            73 => jvmtiError::UNSUPPORTED_OPERATION,
+            79 => jvmtiError::UNMODIFIABLE_CLASS,
+            80 => jvmtiError::UNMODIFIABLE_MODULE,
+            98 => jvmtiError::NOT_AVAILABLE,
+            99 => jvmtiError::MUST_POSSESS_CAPABILITY,
+            100 => jvmtiError::NULL_POINTER,
+            101 => jvmtiError::ABSENT_INFORMATION,
+            102 => jvmtiError::INVALID_EVENT_TYPE,
+            103 => jvmtiError::ILLEGAL_ARGUMENT,
+            104 => jvmtiError::NATIVE_METHOD,
+            106 => jvmtiError::CLASS_LOADER_UNSUPPORTED,
+            110 => jvmtiError::OUT_OF_MEMORY,
+            111 => jvmtiError::ACCESS_DENIED,
+            112 => jvmtiError::WRONG_PHASE,
+            113 => jvmtiError::INTERNAL,
+            115 => jvmtiError::UNATTACHED_THREAD,
+            116 => jvmtiError::INVALID_ENVIRONMENT,
+            _ => return None,
+        };
+        Some(err)
+    }
+
+    /// Raw numeric value of this error code.
+    pub const fn code(self) -> u32 {
+        self as u32
+    }
 }
 
 /// Return the standard JVMTI error constant name.
@@ -103,16 +306,95 @@ pub const fn error_name(error: jvmtiError) -> &'static str {
     match error {
         jvmtiError::NONE => "JVMTI_ERROR_NONE",
         jvmtiError::INVALID_THREAD => "JVMTI_ERROR_INVALID_THREAD",
+        jvmtiError::INVALID_THREAD_GROUP => "JVMTI_ERROR_INVALID_THREAD_GROUP",
+        jvmtiError::INVALID_PRIORITY => "JVMTI_ERROR_INVALID_PRIORITY",
+        jvmtiError::THREAD_NOT_SUSPENDED => "JVMTI_ERROR_THREAD_NOT_SUSPENDED",
+        jvmtiError::THREAD_SUSPENDED => "JVMTI_ERROR_THREAD_SUSPENDED",
+        jvmtiError::THREAD_NOT_ALIVE => "JVMTI_ERROR_THREAD_NOT_ALIVE",
+        jvmtiError::INVALID_OBJECT => "JVMTI_ERROR_INVALID_OBJECT",
         jvmtiError::INVALID_CLASS => "JVMTI_ERROR_INVALID_CLASS",
+        jvmtiError::CLASS_NOT_PREPARED => "JVMTI_ERROR_CLASS_NOT_PREPARED",
+        jvmtiError::INVALID_METHODID => "JVMTI_ERROR_INVALID_METHODID",
+        jvmtiError::INVALID_LOCATION => "JVMTI_ERROR_INVALID_LOCATION",
+        jvmtiError::INVALID_FIELDID => "JVMTI_ERROR_INVALID_FIELDID",
+        jvmtiError::INVALID_MODULE => "JVMTI_ERROR_INVALID_MODULE",
+        jvmtiError::NO_MORE_FRAMES => "JVMTI_ERROR_NO_MORE_FRAMES",
+        jvmtiError::OPAQUE_FRAME => "JVMTI_ERROR_OPAQUE_FRAME",
+        jvmtiError::TYPE_MISMATCH => "JVMTI_ERROR_TYPE_MISMATCH",
+        jvmtiError::INVALID_SLOT => "JVMTI_ERROR_INVALID_SLOT",
+        jvmtiError::DUPLICATE => "JVMTI_ERROR_DUPLICATE",
+        jvmtiError::NOT_FOUND => "JVMTI_ERROR_NOT_FOUND",
+        jvmtiError::INVALID_MONITOR => "JVMTI_ERROR_INVALID_MONITOR",
+        jvmtiError::NOT_MONITOR_OWNER => "JVMTI_ERROR_NOT_MONITOR_OWNER",
+        jvmtiError::INTERRUPT => "JVMTI_ERROR_INTERRUPT",
+        jvmtiError::INVALID_CLASS_FORMAT => "JVMTI_ERROR_INVALID_CLASS_FORMAT",
+        jvmtiError::CIRCULAR_CLASS_DEFINITION => "JVMTI_ERROR_CIRCULAR_CLASS_DEFINITION",
+        jvmtiError::FAILS_VERIFICATION => "JVMTI_ERROR_FAILS_VERIFICATION",
+        jvmtiError::UNSUPPORTED_REDEFINITION_METHOD_ADDED => "JVMTI_ERROR_UNSUPPORTED_REDEFINITION_METHOD_ADDED",
+        jvmtiError::UNSUPPORTED_REDEFINITION_SCHEMA_CHANGED => "JVMTI_ERROR_UNSUPPORTED_REDEFINITION_SCHEMA_CHANGED",
+        jvmtiError::INVALID_TYPESTATE => "JVMTI_ERROR_INVALID_TYPESTATE",
+        jvmtiError::UNSUPPORTED_REDEFINITION_HIERARCHY_CHANGED => "JVMTI_ERROR_UNSUPPORTED_REDEFINITION_HIERARCHY_CHANGED",
+        jvmtiError::UNSUPPORTED_REDEFINITION_METHOD_DELETED => "JVMTI_ERROR_UNSUPPORTED_REDEFINITION_METHOD_DELETED",
+        jvmtiError::UNSUPPORTED_VERSION => "JVMTI_ERROR_UNSUPPORTED_VERSION",
+        jvmtiError::NAMES_DONT_MATCH => "JVMTI_ERROR_NAMES_DONT_MATCH",
+        jvmtiError::UNSUPPORTED_REDEFINITION_CLASS_MODIFIERS_CHANGED => "JVMTI_ERROR_UNSUPPORTED_REDEFINITION_CLASS_MODIFIERS_CHANGED",
+        jvmtiError::UNSUPPORTED_REDEFINITION_METHOD_MODIFIERS_CHANGED => "JVMTI_ERROR_UNSUPPORTED_REDEFINITION_METHOD_MODIFIERS_CHANGED",
+        jvmtiError::UNSUPPORTED_REDEFINITION_CLASS_ATTRIBUTE_CHANGED => "JVMTI_ERROR_UNSUPPORTED_REDEFINITION_CLASS_ATTRIBUTE_CHANGED",
+        jvmtiError::UNSUPPORTED_OPERATION => "JVMTI_ERROR_UNSUPPORTED_OPERATION",
+        jvmtiError::UNMODIFIABLE_CLASS => "JVMTI_ERROR_UNMODIFIABLE_CLASS",
+        jvmtiError::UNMODIFIABLE_MODULE => "JVMTI_ERROR_UNMODIFIABLE_MODULE",
         jvmtiError::NOT_AVAILABLE => "JVMTI_ERROR_NOT_AVAILABLE",
         jvmtiError::MUST_POSSESS_CAPABILITY => "JVMTI_ERROR_MUST_POSSESS_CAPABILITY",
         jvmtiError::NULL_POINTER => "JVMTI_ERROR_NULL_POINTER",
         jvmtiError::ABSENT_INFORMATION => "JVMTI_ERROR_ABSENT_INFORMATION",
         jvmtiError::INVALID_EVENT_TYPE => "JVMTI_ERROR_INVALID_EVENT_TYPE",
         jvmtiError::ILLEGAL_ARGUMENT => "JVMTI_ERROR_ILLEGAL_ARGUMENT",
+        jvmtiError::NATIVE_METHOD => "JVMTI_ERROR_NATIVE_METHOD",
+        jvmtiError::CLASS_LOADER_UNSUPPORTED => "JVMTI_ERROR_CLASS_LOADER_UNSUPPORTED",
+        jvmtiError::OUT_OF_MEMORY => "JVMTI_ERROR_OUT_OF_MEMORY",
+        jvmtiError::ACCESS_DENIED => "JVMTI_ERROR_ACCESS_DENIED",
+        jvmtiError::WRONG_PHASE => "JVMTI_ERROR_WRONG_PHASE",
+        jvmtiError::INTERNAL => "JVMTI_ERROR_INTERNAL",
+        jvmtiError::UNATTACHED_THREAD => "JVMTI_ERROR_UNATTACHED_THREAD",
+        jvmtiError::INVALID_ENVIRONMENT => "JVMTI_ERROR_INVALID_ENVIRONMENT",
     }
 }
 
+impl jvmtiError {
+    /// Returns true for the `UNSUPPORTED_REDEFINITION_*` errors, which
+    /// `RedefineClasses`/`RetransformClasses` return when the new class file
+    /// changes something the VM cannot redefine in place (adding, removing, or
+    /// re-modifying methods, changing fields, the hierarchy, class modifiers,
+    /// or class attributes).
+    pub const fn is_unsupported_redefinition(self) -> bool {
+        matches!(
+            self,
+            jvmtiError::UNSUPPORTED_REDEFINITION_METHOD_ADDED
+                | jvmtiError::UNSUPPORTED_REDEFINITION_SCHEMA_CHANGED
+                | jvmtiError::UNSUPPORTED_REDEFINITION_HIERARCHY_CHANGED
+                | jvmtiError::UNSUPPORTED_REDEFINITION_METHOD_DELETED
+                | jvmtiError::UNSUPPORTED_REDEFINITION_CLASS_MODIFIERS_CHANGED
+                | jvmtiError::UNSUPPORTED_REDEFINITION_METHOD_MODIFIERS_CHANGED
+                | jvmtiError::UNSUPPORTED_REDEFINITION_CLASS_ATTRIBUTE_CHANGED
+        )
+    }
+}
+
+impl std::fmt::Display for jvmtiError {
+    /// Formats as the canonical name without the `JVMTI_ERROR_` prefix plus
+    /// the numeric code, e.g. `MUST_POSSESS_CAPABILITY (99)`.
+    ///
+    /// Uses a static table, so it is safe to call where JVMTI functions are
+    /// forbidden (for example inside `GarbageCollectionStart`).
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = error_name(*self);
+        let short = name.strip_prefix("JVMTI_ERROR_").unwrap_or(name);
+        write!(f, "{} ({})", short, *self as u32)
+    }
+}
+
+impl std::error::Error for jvmtiError {}
+
 pub type jlocation = jlong;
 pub type jrawMonitorID = *mut c_void;
 
@@ -171,7 +453,12 @@ pub struct jvmtiThreadGroupInfo {
     pub is_daemon: jboolean,
 }
 
-pub type jvmtiStartFunction = unsafe extern "system" fn(env: *mut jvmtiEnv, arg: *mut c_void);
+pub type jvmtiStartFunction = unsafe extern "system" fn(jvmti_env: *mut jvmtiEnv, jni_env: *mut JNIEnv, arg: *mut c_void);
+
+/// Priorities for `RunAgentThread`, as in `java.lang.Thread`.
+pub const JVMTI_THREAD_MIN_PRIORITY: jint = 1;
+pub const JVMTI_THREAD_NORM_PRIORITY: jint = 5;
+pub const JVMTI_THREAD_MAX_PRIORITY: jint = 10;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Default)]
@@ -249,20 +536,216 @@ pub type jvmtiObjectCallback = unsafe extern "system" fn(
     user_data: *mut c_void,
 ) -> jint;
 
+// --- Heap Callbacks (FollowReferences / IterateThroughHeap) ---
+pub type jvmtiHeapReferenceKind = jint;
+pub type jvmtiPrimitiveType = jint;
+
+// Heap visit control flags returned by jvmtiHeapCallbacks functions.
+pub const JVMTI_VISIT_OBJECTS: jint = 0x100;
+pub const JVMTI_VISIT_ABORT: jint = 0x8000;
+
+// jvmtiHeapReferenceKind values.
+pub const JVMTI_HEAP_REFERENCE_CLASS: jvmtiHeapReferenceKind = 1;
+pub const JVMTI_HEAP_REFERENCE_FIELD: jvmtiHeapReferenceKind = 2;
+pub const JVMTI_HEAP_REFERENCE_ARRAY_ELEMENT: jvmtiHeapReferenceKind = 3;
+pub const JVMTI_HEAP_REFERENCE_CLASS_LOADER: jvmtiHeapReferenceKind = 4;
+pub const JVMTI_HEAP_REFERENCE_SIGNERS: jvmtiHeapReferenceKind = 5;
+pub const JVMTI_HEAP_REFERENCE_PROTECTION_DOMAIN: jvmtiHeapReferenceKind = 6;
+pub const JVMTI_HEAP_REFERENCE_INTERFACE: jvmtiHeapReferenceKind = 7;
+pub const JVMTI_HEAP_REFERENCE_STATIC_FIELD: jvmtiHeapReferenceKind = 8;
+pub const JVMTI_HEAP_REFERENCE_CONSTANT_POOL: jvmtiHeapReferenceKind = 9;
+pub const JVMTI_HEAP_REFERENCE_SUPERCLASS: jvmtiHeapReferenceKind = 10;
+pub const JVMTI_HEAP_REFERENCE_JNI_GLOBAL: jvmtiHeapReferenceKind = 21;
+pub const JVMTI_HEAP_REFERENCE_SYSTEM_CLASS: jvmtiHeapReferenceKind = 22;
+pub const JVMTI_HEAP_REFERENCE_MONITOR: jvmtiHeapReferenceKind = 23;
+pub const JVMTI_HEAP_REFERENCE_STACK_LOCAL: jvmtiHeapReferenceKind = 24;
+pub const JVMTI_HEAP_REFERENCE_JNI_LOCAL: jvmtiHeapReferenceKind = 25;
+pub const JVMTI_HEAP_REFERENCE_THREAD: jvmtiHeapReferenceKind = 26;
+pub const JVMTI_HEAP_REFERENCE_OTHER: jvmtiHeapReferenceKind = 27;
+
+// jvmtiPrimitiveType values (the descriptor characters).
+pub const JVMTI_PRIMITIVE_TYPE_BOOLEAN: jvmtiPrimitiveType = 90;
+pub const JVMTI_PRIMITIVE_TYPE_BYTE: jvmtiPrimitiveType = 66;
+pub const JVMTI_PRIMITIVE_TYPE_CHAR: jvmtiPrimitiveType = 67;
+pub const JVMTI_PRIMITIVE_TYPE_SHORT: jvmtiPrimitiveType = 83;
+pub const JVMTI_PRIMITIVE_TYPE_INT: jvmtiPrimitiveType = 73;
+pub const JVMTI_PRIMITIVE_TYPE_LONG: jvmtiPrimitiveType = 74;
+pub const JVMTI_PRIMITIVE_TYPE_FLOAT: jvmtiPrimitiveType = 70;
+pub const JVMTI_PRIMITIVE_TYPE_DOUBLE: jvmtiPrimitiveType = 68;
+
 pub type jvmtiHeapIterationCallback = unsafe extern "system" fn(
     class_tag: jlong,
     size: jlong,
     tag_ptr: *mut jlong,
+    length: jint,
+    user_data: *mut c_void,
+) -> jint;
+
+pub type jvmtiHeapReferenceCallback = unsafe extern "system" fn(
+    reference_kind: jvmtiHeapReferenceKind,
+    reference_info: *const jvmtiHeapReferenceInfo,
+    class_tag: jlong,
+    referrer_class_tag: jlong,
+    size: jlong,
+    tag_ptr: *mut jlong,
+    referrer_tag_ptr: *mut jlong,
+    length: jint,
+    user_data: *mut c_void,
+) -> jint;
+
+pub type jvmtiPrimitiveFieldCallback = unsafe extern "system" fn(
+    kind: jvmtiHeapReferenceKind,
+    info: *const jvmtiHeapReferenceInfo,
+    object_class_tag: jlong,
+    object_tag_ptr: *mut jlong,
+    value: jvalue,
+    value_type: jvmtiPrimitiveType,
     user_data: *mut c_void,
 ) -> jint;
 
+pub type jvmtiArrayPrimitiveValueCallback = unsafe extern "system" fn(
+    class_tag: jlong,
+    size: jlong,
+    tag_ptr: *mut jlong,
+    element_count: jint,
+    element_type: jvmtiPrimitiveType,
+    elements: *const c_void,
+    user_data: *mut c_void,
+) -> jint;
+
+pub type jvmtiStringPrimitiveValueCallback = unsafe extern "system" fn(
+    class_tag: jlong,
+    size: jlong,
+    tag_ptr: *mut jlong,
+    value: *const jchar,
+    value_length: jint,
+    user_data: *mut c_void,
+) -> jint;
+
+pub type jvmtiReservedCallback = unsafe extern "system" fn() -> jint;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct jvmtiHeapReferenceInfoField {
+    pub index: jint,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct jvmtiHeapReferenceInfoArray {
+    pub index: jint,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct jvmtiHeapReferenceInfoConstantPool {
+    pub index: jint,
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
+pub struct jvmtiHeapReferenceInfoStackLocal {
+    pub thread_tag: jlong,
+    pub thread_id: jlong,
+    pub depth: jint,
+    pub method: jmethodID,
+    pub location: jlocation,
+    pub slot: jint,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct jvmtiHeapReferenceInfoJniLocal {
+    pub thread_tag: jlong,
+    pub thread_id: jlong,
+    pub depth: jint,
+    pub method: jmethodID,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct jvmtiHeapReferenceInfoReserved {
+    pub reserved1: jlong,
+    pub reserved2: jlong,
+    pub reserved3: jlong,
+    pub reserved4: jlong,
+    pub reserved5: jlong,
+    pub reserved6: jlong,
+    pub reserved7: jlong,
+    pub reserved8: jlong,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union jvmtiHeapReferenceInfo {
+    pub field: jvmtiHeapReferenceInfoField,
+    pub array: jvmtiHeapReferenceInfoArray,
+    pub constant_pool: jvmtiHeapReferenceInfoConstantPool,
+    pub stack_local: jvmtiHeapReferenceInfoStackLocal,
+    pub jni_local: jvmtiHeapReferenceInfoJniLocal,
+    pub other: jvmtiHeapReferenceInfoReserved,
+}
+
+/// Callback table for `FollowReferences` and `IterateThroughHeap`.
+///
+/// Layout follows `jvmti.h`: five callbacks followed by eleven reserved slots
+/// that must stay `None`. Start from `Default` (no callbacks) and set the ones
+/// you need:
+///
+/// ```rust,ignore
+/// let callbacks = jvmtiHeapCallbacks::default().heap_reference(on_reference);
+/// ```
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
 pub struct jvmtiHeapCallbacks {
-    pub heap_root_callback: Option<jvmtiHeapRootCallback>,
-    pub stack_reference_callback: Option<jvmtiStackReferenceCallback>,
-    pub object_reference_callback: Option<jvmtiObjectReferenceCallback>,
-    pub object_callback: Option<jvmtiObjectCallback>,
+    pub heap_iteration_callback: Option<jvmtiHeapIterationCallback>,
+    pub heap_reference_callback: Option<jvmtiHeapReferenceCallback>,
+    pub primitive_field_callback: Option<jvmtiPrimitiveFieldCallback>,
+    pub array_primitive_value_callback: Option<jvmtiArrayPrimitiveValueCallback>,
+    pub string_primitive_value_callback: Option<jvmtiStringPrimitiveValueCallback>,
+    pub reserved5: Option<jvmtiReservedCallback>,
+    pub reserved6: Option<jvmtiReservedCallback>,
+    pub reserved7: Option<jvmtiReservedCallback>,
+    pub reserved8: Option<jvmtiReservedCallback>,
+    pub reserved9: Option<jvmtiReservedCallback>,
+    pub reserved10: Option<jvmtiReservedCallback>,
+    pub reserved11: Option<jvmtiReservedCallback>,
+    pub reserved12: Option<jvmtiReservedCallback>,
+    pub reserved13: Option<jvmtiReservedCallback>,
+    pub reserved14: Option<jvmtiReservedCallback>,
+    pub reserved15: Option<jvmtiReservedCallback>,
+}
+
+impl jvmtiHeapCallbacks {
+    /// Set the `IterateThroughHeap` object callback.
+    pub fn heap_iteration(mut self, callback: jvmtiHeapIterationCallback) -> Self {
+        self.heap_iteration_callback = Some(callback);
+        self
+    }
+
+    /// Set the `FollowReferences` reference callback.
+    pub fn heap_reference(mut self, callback: jvmtiHeapReferenceCallback) -> Self {
+        self.heap_reference_callback = Some(callback);
+        self
+    }
+
+    /// Set the primitive field callback.
+    pub fn primitive_field(mut self, callback: jvmtiPrimitiveFieldCallback) -> Self {
+        self.primitive_field_callback = Some(callback);
+        self
+    }
+
+    /// Set the primitive array callback.
+    pub fn array_primitive_value(mut self, callback: jvmtiArrayPrimitiveValueCallback) -> Self {
+        self.array_primitive_value_callback = Some(callback);
+        self
+    }
+
+    /// Set the `java.lang.String` value callback.
+    pub fn string_primitive_value(mut self, callback: jvmtiStringPrimitiveValueCallback) -> Self {
+        self.string_primitive_value_callback = Some(callback);
+        self
+    }
 }
 
 #[repr(C)]
@@ -307,6 +790,40 @@ pub struct jvmtiExtensionEventInfo {
 
 pub type jvmtiExtensionEventCallback = unsafe extern "system" fn();
 
+/// Signature of the functions returned by `GetExtensionFunctions`.
+///
+/// Extension functions are C variadic functions; the arguments after the
+/// environment follow the function's `jvmtiExtensionParamInfo` list.
+pub type jvmtiExtensionFunction = unsafe extern "C" fn(jvmti_env: *mut jvmtiEnv, ...) -> jvmtiError;
+
+// jvmtiParamKind values.
+pub const JVMTI_KIND_IN: jint = 91;
+pub const JVMTI_KIND_IN_PTR: jint = 92;
+pub const JVMTI_KIND_IN_BUF: jint = 93;
+pub const JVMTI_KIND_ALLOC_BUF: jint = 94;
+pub const JVMTI_KIND_ALLOC_ALLOC_BUF: jint = 95;
+pub const JVMTI_KIND_OUT: jint = 96;
+pub const JVMTI_KIND_OUT_BUF: jint = 97;
+
+// jvmtiParamTypes values.
+pub const JVMTI_TYPE_JBYTE: jint = 101;
+pub const JVMTI_TYPE_JCHAR: jint = 102;
+pub const JVMTI_TYPE_JSHORT: jint = 103;
+pub const JVMTI_TYPE_JINT: jint = 104;
+pub const JVMTI_TYPE_JLONG: jint = 105;
+pub const JVMTI_TYPE_JFLOAT: jint = 106;
+pub const JVMTI_TYPE_JDOUBLE: jint = 107;
+pub const JVMTI_TYPE_JBOOLEAN: jint = 108;
+pub const JVMTI_TYPE_JOBJECT: jint = 109;
+pub const JVMTI_TYPE_JTHREAD: jint = 110;
+pub const JVMTI_TYPE_JCLASS: jint = 111;
+pub const JVMTI_TYPE_JVALUE: jint = 112;
+pub const JVMTI_TYPE_JFIELDID: jint = 113;
+pub const JVMTI_TYPE_JMETHODID: jint = 114;
+pub const JVMTI_TYPE_CCHAR: jint = 115;
+pub const JVMTI_TYPE_CVOID: jint = 116;
+pub const JVMTI_TYPE_JNIENV: jint = 117;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct jvmtiMonitorStackDepthInfo {
@@ -324,8 +841,57 @@ pub struct jvmtiStackInfo {
 }
 
 // --- Capabilities ---
+/// Capability names indexed by their bit position in `jvmtiCapabilities`.
+const CAPABILITY_NAMES: [&str; 45] = [
+    "can_tag_objects",
+    "can_generate_field_modification_events",
+    "can_generate_field_access_events",
+    "can_get_bytecodes",
+    "can_get_synthetic_attribute",
+    "can_get_owned_monitor_info",
+    "can_get_current_contended_monitor",
+    "can_get_monitor_info",
+    "can_pop_frame",
+    "can_redefine_classes",
+    "can_signal_thread",
+    "can_get_source_file_name",
+    "can_get_line_numbers",
+    "can_get_source_debug_extension",
+    "can_access_local_variables",
+    "can_maintain_original_method_order",
+    "can_generate_single_step_events",
+    "can_generate_exception_events",
+    "can_generate_frame_pop_events",
+    "can_generate_breakpoint_events",
+    "can_suspend",
+    "can_redefine_any_class",
+    "can_get_current_thread_cpu_time",
+    "can_get_thread_cpu_time",
+    "can_generate_method_entry_events",
+    "can_generate_method_exit_events",
+    "can_generate_all_class_hook_events",
+    "can_generate_compiled_method_load_events",
+    "can_generate_monitor_events",
+    "can_generate_vm_object_alloc_events",
+    "can_generate_native_method_bind_events",
+    "can_generate_garbage_collection_events",
+    "can_generate_object_free_events",
+    "can_force_early_return",
+    "can_get_owned_monitor_stack_depth_info",
+    "can_get_constant_pool",
+    "can_set_native_method_prefix",
+    "can_retransform_classes",
+    "can_retransform_any_class",
+    "can_generate_resource_exhaustion_heap_events",
+    "can_generate_resource_exhaustion_threads_events",
+    "can_generate_early_vmstart",
+    "can_generate_early_class_hook_events",
+    "can_generate_sampled_object_alloc_events",
+    "can_support_virtual_threads",
+];
+
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct jvmtiCapabilities {
     bits: [u32; 4],
 }
@@ -352,6 +918,56 @@ impl jvmtiCapabilities {
         (self.bits[word_index] & (1 << bit_index)) != 0
     }
 
+    /// Names of the set capabilities, e.g. `"can_tag_objects"`, in bit order.
+    pub fn enabled_names(&self) -> Vec<&'static str> {
+        CAPABILITY_NAMES
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| self.get_bit(*bit))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    /// Capabilities set in `self` but not in `other`.
+    ///
+    /// For example, `potential.difference(&expensive)` requests everything the
+    /// VM offers except a chosen set.
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut bits = self.bits;
+        for (word, other) in bits.iter_mut().zip(other.bits) {
+            *word &= !other;
+        }
+        Self { bits }
+    }
+
+    /// Capabilities set in both `self` and `other`.
+    pub fn intersect(&self, other: &Self) -> Self {
+        let mut bits = self.bits;
+        for (word, other) in bits.iter_mut().zip(other.bits) {
+            *word &= other;
+        }
+        Self { bits }
+    }
+
+    /// Capabilities set in either `self` or `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        let mut bits = self.bits;
+        for (word, other) in bits.iter_mut().zip(other.bits) {
+            *word |= other;
+        }
+        Self { bits }
+    }
+
+    /// Returns true if every capability in `other` is also set in `self`.
+    pub fn contains(&self, other: &Self) -> bool {
+        other.difference(self).is_empty()
+    }
+
+    /// Returns true if no capability is set.
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|word| *word == 0)
+    }
+
     /// Capabilities required for `ClassFileLoadHook`.
     pub fn for_class_file_load_hook() -> Self {
         let mut caps = Self::default();
@@ -589,11 +1205,7 @@ impl jvmtiCapabilities {
 use std::fmt;
 impl fmt::Display for jvmtiCapabilities {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Capabilities [")?;
-        if self.can_generate_method_entry_events() { write!(f, "MethodEntry ")?; }
-        if self.can_generate_method_exit_events() { write!(f, "MethodExit ")?; }
-        // ... add others
-        write!(f, "]")
+        write!(f, "Capabilities [{}]", self.enabled_names().join(", "))
     }
 }
 
@@ -713,10 +1325,10 @@ pub type JvmtiIterateOverInstancesOfClassFn = unsafe extern "system" fn(env: *mu
 pub type JvmtiGetObjectsWithTagsFn = unsafe extern "system" fn(env: *mut jvmtiEnv, tag_count: jint, tags: *const jlong, count_ptr: *mut jint, object_result_ptr: *mut *mut jobject, tag_result_ptr: *mut *mut jlong) -> jvmtiError;
 pub type JvmtiFollowReferencesFn = unsafe extern "system" fn(env: *mut jvmtiEnv, heap_filter: jint, klass: jclass, initial_object: jobject, callbacks: *const jvmtiHeapCallbacks, user_data: *const c_void) -> jvmtiError;
 pub type JvmtiIterateThroughHeapFn = unsafe extern "system" fn(env: *mut jvmtiEnv, heap_filter: jint, klass: jclass, callbacks: *const jvmtiHeapCallbacks, user_data: *const c_void) -> jvmtiError;
-pub type JvmtiSuspendAllVirtualThreadsFn = unsafe extern "system" fn(env: *mut jvmtiEnv) -> jvmtiError;
-pub type JvmtiResumeAllVirtualThreadsFn = unsafe extern "system" fn(env: *mut jvmtiEnv) -> jvmtiError;
-pub type JvmtiSetJNIFunctionTableFn = unsafe extern "system" fn(env: *mut jvmtiEnv, function_table: *const crate::sys::jni::JNIEnv) -> jvmtiError;
-pub type JvmtiGetJNIFunctionTableFn = unsafe extern "system" fn(env: *mut jvmtiEnv, function_table_ptr: *mut *mut crate::sys::jni::JNIEnv) -> jvmtiError;
+pub type JvmtiSuspendAllVirtualThreadsFn = unsafe extern "system" fn(env: *mut jvmtiEnv, except_count: jint, except_list: *const jthread) -> jvmtiError;
+pub type JvmtiResumeAllVirtualThreadsFn = unsafe extern "system" fn(env: *mut jvmtiEnv, except_count: jint, except_list: *const jthread) -> jvmtiError;
+pub type JvmtiSetJNIFunctionTableFn = unsafe extern "system" fn(env: *mut jvmtiEnv, function_table: *const crate::sys::jni::JNINativeInterface_) -> jvmtiError;
+pub type JvmtiGetJNIFunctionTableFn = unsafe extern "system" fn(env: *mut jvmtiEnv, function_table_ptr: *mut *mut crate::sys::jni::JNINativeInterface_) -> jvmtiError;
 pub type JvmtiSetEventCallbacksFn = unsafe extern "system" fn(env: *mut jvmtiEnv, callbacks: *const jvmtiEventCallbacks, size_of_callbacks: jint) -> jvmtiError;
 pub type JvmtiGenerateEventsFn = unsafe extern "system" fn(env: *mut jvmtiEnv, event_type: u32) -> jvmtiError;
 pub type JvmtiGetExtensionFunctionsFn = unsafe extern "system" fn(env: *mut jvmtiEnv, extension_count_ptr: *mut jint, extensions_ptr: *mut *mut jvmtiExtensionFunctionInfo) -> jvmtiError;
@@ -913,13 +1525,22 @@ pub type JvmtiNativeMethodBindFn = unsafe extern "system" fn(
 );
 
 // 8. Compiled Code (JIT)
+/// One entry of the native-address to bytecode-location map passed to
+/// `CompiledMethodLoad`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct jvmtiAddrLocationMap {
+    pub start_address: *const std::os::raw::c_void,
+    pub location: jlocation,
+}
+
 pub type JvmtiCompiledMethodLoadFn = unsafe extern "system" fn(
     jvmti_env: *mut jvmtiEnv,
     method: jmethodID,
     code_size: jint,
     code_addr: *const std::os::raw::c_void,
     map_length: jint,
-    map: *const std::os::raw::c_void, // jvmtiAddrLocationMap
+    map: *const jvmtiAddrLocationMap,
     compile_info: *const std::os::raw::c_void
 );
 
@@ -36,28 +36,9 @@ impl Agent for MyAgent {
         jni::JNI_OK
     }
 
-    fn class_file_load_hook(
-        &self,
-        _jni: *mut jni::JNIEnv,
-        _class_being_redefined: jni::jclass,
-        _loader: jni::jobject,
-        name: *const std::os::raw::c_char,
-        _protection_domain: jni::jobject,
-        class_data_len: jni::jint,
-        _class_data: *const u8,
-        _new_class_data_len: *mut jni::jint,
-        _new_class_data: *mut *mut u8,
-    ) {
-        let class_name = if name.is_null() {
-            "<unknown>".to_string()
-        } else {
-            unsafe { std::ffi::CStr::from_ptr(name) }
-                .to_str()
-                .unwrap_or("<invalid>")
-                .to_string()
-        };
-
-        eprintln!("[agent] Loaded: {} ({} bytes)", class_name, class_data_len);
+    fn transform_class(&self, name: Option<&str>, class_data: &[u8]) -> Option<Vec<u8>> {
+        eprintln!("[agent] Loaded: {} ({} bytes)", name.unwrap_or("<unknown>"), class_data.len());
+        None
     }
 }
 
@@ -1,33 +1,1301 @@
+mod common;
+
 use std::ptr;
 
-use jvmti_bindings::env::{JniEnv, Jvmti};
-use jvmti_bindings::sys::jvmti;
-use jvmti_bindings::{describe_jni_result, jni};
+use jvmti_bindings::env::{
+    AttachGuard, CapabilityError, ClassCache, ClassStatus, DisposeGuard, Event, ExtensionArg,
+    ExtensionFunction, ExtensionFunctionInfo, ExtensionParamInfo, FieldId, GlobalRef, HeapClosures,
+    HeapFilter, IterationControl, JLocationFormat, JavaException, JavaValue, JniEnv, JniEnvPtr,
+    JniError, JniFunctionTable, Jvmti, JvmtiEnvPtr, JvmtiResult, LocalFrameGuard, LocalRef,
+    LocalValue, LocalVariableEntry, MethodId, MethodSignature, MonitorEnterGuard, ObjectTagger,
+    Phase, PrimitiveArray, PrimitiveField, Reference, ReferenceKind, ResourceExhaustedFlags,
+    ReturnValue, SearchPathError, SetEventsError, StringValue, SymbolicFrame, ThreadDump,
+    ThreadState, TimerInfo, TimerKind, VisitControl, WeakGlobalRef,
+};
+use jvmti_bindings::sys::jvmti;
+use jvmti_bindings::{describe_jni_result, get_callbacks_for, get_default_callbacks, jni, PanicPolicy};
+
+#[test]
+fn jvmti_new_rejects_null_vm_pointer() {
+    let err = match Jvmti::new(ptr::null_mut()) {
+        Ok(_) => panic!("null JavaVM must be rejected"),
+        Err(err) => err,
+    };
+    assert_eq!(err, JniError::Err);
+    assert_eq!(err.as_raw(), jni::JNI_ERR);
+}
+
+#[test]
+fn thread_attach_rejects_null_vm_pointer() {
+    assert!(matches!(JniEnv::attach_current_thread(ptr::null_mut()), Err(JniError::Err)));
+    assert!(matches!(JniEnv::attach_current_thread_as_daemon(ptr::null_mut()), Err(JniError::Err)));
+    assert!(matches!(JniEnv::get_current_env(ptr::null_mut()), Err(JniError::Err)));
+    assert_eq!(JniEnv::detach_current_thread(ptr::null_mut()), Err(JniError::Err));
+    assert!(matches!(AttachGuard::new(ptr::null_mut()), Err(JniError::Err)));
+    assert!(matches!(AttachGuard::new_daemon(ptr::null_mut()), Err(JniError::Err)));
+    let _ = AttachGuard::env as fn(&AttachGuard) -> &JniEnv;
+}
+
+#[test]
+fn agent_threads_take_closures() {
+    fn _compiles(jvmti_env: &Jvmti, jni_env: &JniEnv) -> JvmtiResult<()> {
+        let counter = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        jvmti_env.spawn_agent_thread(jni_env, "reporter", jvmti::JVMTI_THREAD_NORM_PRIORITY, move || {
+            counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        })
+    }
+}
+
+// Held by tests that install callbacks, which `Jvmti::current` sees process-wide.
+static INSTALLED_CALLBACKS: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+unsafe extern "system" fn accept_event_callbacks(
+    _env: *mut jvmti::jvmtiEnv,
+    _callbacks: *const jvmti::jvmtiEventCallbacks,
+    _size: jni::jint,
+) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn dispose(_env: *mut jvmti::jvmtiEnv) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn current_jvmti_tracks_first_environment_with_callbacks() {
+    let _installed = INSTALLED_CALLBACKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    assert!(Jvmti::current().is_none());
+
+    let configure = |functions: &mut jvmti::jvmtiInterface_1_| {
+        functions.SetEventCallbacks = Some(accept_event_callbacks);
+        functions.DisposeEnvironment = Some(dispose);
+    };
+    let (first, second) = (common::stub_jvmti(configure), common::stub_jvmti(configure));
+    let (first_env, second_env) = (first.jvmti(), second.jvmti());
+
+    first_env.set_event_callbacks(jvmti::jvmtiEventCallbacks::default()).unwrap();
+    second_env.set_event_callbacks(jvmti::jvmtiEventCallbacks::default()).unwrap();
+    assert_eq!(Jvmti::current().map(|env| env.raw()), Some(first_env.raw()));
+
+    second_env.dispose_environment().unwrap();
+    assert_eq!(Jvmti::current().map(|env| env.raw()), Some(first_env.raw()));
+    first_env.dispose_environment().unwrap();
+    assert!(Jvmti::current().is_none());
+}
+
+#[test]
+fn owned_jvmti_rejects_null_vm_pointer() {
+    assert!(matches!(Jvmti::new_owned(ptr::null_mut()), Err(JniError::Err)));
+    let _ = DisposeGuard::into_inner as fn(DisposeGuard) -> Jvmti;
+}
+
+#[test]
+fn jni_errors_round_trip_raw_codes() {
+    assert_eq!(JniError::from_raw(jni::JNI_EDETACHED), JniError::Detached);
+    assert_eq!(JniError::from_raw(jni::JNI_EINVAL), JniError::Invalid);
+    assert_eq!(JniError::from_raw(-42), JniError::Other(-42));
+    assert_eq!(jni::jint::from(JniError::NoMem), jni::JNI_ENOMEM);
+    assert_eq!(JniError::Version.to_string(), "JNI_EVERSION (-3)");
+
+    let result: JvmtiResult<()> = Err(jvmti::jvmtiError::WRONG_PHASE);
+    assert!(result.is_err());
+}
+
+#[test]
+fn jni_results_have_display_helpers() {
+    assert_eq!(
+        jni::describe_result(jni::JNI_EDETACHED),
+        "JNI_EDETACHED (-2)"
+    );
+    assert_eq!(describe_jni_result(jni::JNI_EVERSION), "JNI_EVERSION (-3)");
+}
+
+#[test]
+fn jvmti_errors_have_static_names() {
+    assert_eq!(
+        jvmti::error_name(jvmti::jvmtiError::MUST_POSSESS_CAPABILITY),
+        "JVMTI_ERROR_MUST_POSSESS_CAPABILITY"
+    );
+}
+
+#[test]
+fn jvmti_error_names_cover_phase_errors() {
+    assert_eq!(
+        jvmti::error_name(jvmti::jvmtiError::WRONG_PHASE),
+        "JVMTI_ERROR_WRONG_PHASE"
+    );
+}
+
+#[test]
+fn compiled_method_replay_is_public_api() {
+    assert_eq!(jvmti::JVMTI_EVENT_COMPILED_METHOD_LOAD, 68);
+    assert_eq!(jvmti::JVMTI_EVENT_DYNAMIC_CODE_GENERATED, 70);
+    let _ = Jvmti::replay_compiled_methods as fn(&Jvmti) -> JvmtiResult<()>;
+}
+
+#[test]
+fn typed_events_round_trip_raw_constants() {
+    assert_eq!(Event::ALL.len(), 34);
+    for &event in Event::ALL {
+        assert_eq!(Event::from_raw(event.to_raw()), Some(event));
+        assert_eq!(u32::from(event), event.to_raw());
+    }
+    assert_eq!(Event::MethodEntry.to_raw(), jvmti::JVMTI_EVENT_METHOD_ENTRY);
+    assert_eq!(Event::VirtualThreadEnd.to_raw(), jvmti::JVMTI_EVENT_VIRTUAL_THREAD_END);
+    assert_eq!(Event::from_raw(72), None);
+    assert_eq!(Event::VmInit.to_string(), "VMInit");
+    let _ = Jvmti::enable as fn(&Jvmti, Event, jni::jthread) -> JvmtiResult<()>;
+    let _ = Jvmti::enable_events_global::<Event> as fn(&Jvmti, &[Event]) -> JvmtiResult<()>;
+    let _ = Jvmti::enable_events_global::<u32> as fn(&Jvmti, &[u32]) -> JvmtiResult<()>;
+}
+
+#[test]
+fn events_map_to_required_capabilities() {
+    assert!(Event::MethodEntry.required_capabilities().can_generate_method_entry_events());
+    assert!(Event::ExceptionCatch.required_capabilities().can_generate_exception_events());
+    assert!(Event::MonitorWaited.required_capabilities().can_generate_monitor_events());
+    assert!(Event::SampledObjectAlloc.required_capabilities().can_generate_sampled_object_alloc_events());
+    assert!(Event::VirtualThreadStart.required_capabilities().can_support_virtual_threads());
+    assert!(Event::VmInit.required_capability().is_none());
+    assert!(Event::ClassFileLoadHook.required_capabilities().is_empty());
+    for &event in Event::ALL {
+        assert!(event.required_capabilities().enabled_names().len() <= 1, "{event}");
+    }
+    let _ = Jvmti::enable_with_capability as fn(&Jvmti, Event, jni::jthread) -> JvmtiResult<()>;
+}
+
+#[test]
+fn follow_references_closures_are_public_api() {
+    let mut roots = 0;
+    let closures = HeapClosures::new()
+        .on_reference(|r: Reference<'_>| {
+            if r.is_root() {
+                roots += 1;
+            }
+            VisitControl::Follow
+        })
+        .on_primitive_field(|_: PrimitiveField<'_>| IterationControl::Continue);
+    let _ = Jvmti::follow_references_with
+        as fn(&Jvmti, jni::jint, jni::jclass, jni::jobject, HeapClosures<'_>) -> JvmtiResult<()>;
+    drop(closures);
+    assert_eq!(roots, 0);
+    assert_eq!(jvmti::JVMTI_HEAP_REFERENCE_STACK_LOCAL, 24);
+    assert_eq!(jvmti::JVMTI_PRIMITIVE_TYPE_INT, b'I' as jni::jint);
+}
+
+#[test]
+fn jvmti_version_numbers_decode() {
+    assert_eq!(jvmti::version_components(jvmti::JVMTI_VERSION_1_2), (1, 2, 0));
+    assert_eq!(jvmti::version_components(jvmti::JVMTI_VERSION_21), (21, 0, 0));
+    assert_eq!(jvmti::version_components(0x3011_0203), (17, 2, 3));
+    assert_eq!(
+        jvmti::JVMTI_VERSION_11 & jvmti::JVMTI_VERSION_MASK_INTERFACE_TYPE,
+        jvmti::JVMTI_VERSION_INTERFACE_JVMTI
+    );
+    let _ = Jvmti::jvmti_version as fn(&Jvmti) -> JvmtiResult<(u32, u32, u32)>;
+}
+
+#[test]
+fn jvmti_error_codes_match_spec() {
+    assert_eq!(jvmti::jvmtiError::INVALID_METHODID.code(), 23);
+    assert_eq!(jvmti::jvmtiError::OPAQUE_FRAME.code(), 32);
+    assert_eq!(jvmti::jvmtiError::OUT_OF_MEMORY.code(), 110);
+    assert_eq!(jvmti::jvmtiError::INVALID_ENVIRONMENT.code(), 116);
+    assert_eq!(jvmti::jvmtiError::from_raw(112), Some(jvmti::jvmtiError::WRONG_PHASE));
+    assert_eq!(jvmti::jvmtiError::from_raw(15), Some(jvmti::jvmtiError::THREAD_NOT_ALIVE));
+    assert_eq!(jvmti::jvmtiError::from_raw(33), None);
+    assert_eq!(jvmti::jvmtiError::from_raw(105), None);
+}
+
+#[test]
+fn jvmti_errors_implement_std_error() {
+    let err = jvmti::jvmtiError::MUST_POSSESS_CAPABILITY;
+    assert_eq!(err.to_string(), "MUST_POSSESS_CAPABILITY (99)");
+
+    let boxed: Box<dyn std::error::Error> = Box::new(jvmti::jvmtiError::WRONG_PHASE);
+    assert_eq!(boxed.to_string(), "WRONG_PHASE (112)");
+}
+
+#[test]
+fn heap_filter_and_reference_kind_decode() {
+    let filter = HeapFilter::TAGGED | HeapFilter::CLASS_UNTAGGED;
+    assert_eq!(filter.bits(), jvmti::JVMTI_HEAP_FILTER_TAGGED | jvmti::JVMTI_HEAP_FILTER_CLASS_UNTAGGED);
+    assert!(filter.contains(HeapFilter::TAGGED));
+    assert!(!filter.contains(HeapFilter::UNTAGGED));
+    assert_eq!(HeapFilter::default(), HeapFilter::NONE);
+
+    assert_eq!(ReferenceKind::from_raw(jvmti::JVMTI_HEAP_REFERENCE_FIELD), Some(ReferenceKind::Field));
+    assert_eq!(ReferenceKind::from_raw(jvmti::JVMTI_HEAP_REFERENCE_STACK_LOCAL), Some(ReferenceKind::StackLocal));
+    assert_eq!(ReferenceKind::from_raw(11), None);
+    assert_eq!(ReferenceKind::StaticField.as_raw(), 8);
+    assert_eq!(ReferenceKind::Superclass.as_raw(), 10);
+    assert!(!ReferenceKind::ArrayElement.is_root());
+    assert!(ReferenceKind::JniGlobal.is_root());
+    assert!(ReferenceKind::Other.is_root());
+}
+
+#[test]
+fn thread_state_decodes_flags() {
+    let state = ThreadState::from_raw(jvmti::JVMTI_JAVA_LANG_THREAD_STATE_RUNNABLE | jvmti::JVMTI_THREAD_STATE_IN_NATIVE);
+    assert!(state.is_alive());
+    assert!(state.is_runnable());
+    assert!(state.is_in_native());
+    assert!(!state.is_waiting());
+    assert!(!state.is_suspended());
+    assert_eq!(state.to_string(), "RUNNABLE | IN_NATIVE | ALIVE");
+
+    let parked = ThreadState::from(0x0291 | jvmti::JVMTI_THREAD_STATE_INTERRUPTED);
+    assert!(parked.is_waiting() && parked.is_parked() && parked.is_interrupted());
+    assert_eq!(parked.to_string(), "WAITING | WAITING_INDEFINITELY | PARKED | INTERRUPTED | ALIVE");
+
+    assert_eq!(ThreadState::default().to_string(), "NEW");
+    assert_eq!(ThreadState::from_raw(0x0800 | 0x1).to_string(), "ALIVE | 0x800");
+    let _ = Jvmti::get_thread_state_decoded as fn(&Jvmti, jni::jthread) -> JvmtiResult<ThreadState>;
+}
+
+#[test]
+fn class_status_decodes_flags() {
+    let status = ClassStatus::from_raw(
+        jvmti::JVMTI_CLASS_STATUS_VERIFIED | jvmti::JVMTI_CLASS_STATUS_PREPARED | jvmti::JVMTI_CLASS_STATUS_INITIALIZED,
+    );
+    assert!(status.is_verified() && status.is_prepared() && status.is_initialized());
+    assert!(!status.is_error() && !status.is_array() && !status.is_primitive());
+    assert_eq!(status.to_string(), "VERIFIED | PREPARED | INITIALIZED");
+
+    assert!(ClassStatus::from(jvmti::JVMTI_CLASS_STATUS_ARRAY).is_array());
+    assert_eq!(ClassStatus::from_raw(jvmti::JVMTI_CLASS_STATUS_ERROR | 0x40).to_string(), "ERROR | 0x40");
+    assert_eq!(ClassStatus::default().to_string(), "LOADED");
+    let _ = Jvmti::get_class_status_decoded as fn(&Jvmti, jni::jclass) -> JvmtiResult<ClassStatus>;
+}
+
+#[test]
+fn resource_exhausted_flags_decode() {
+    let heap = ResourceExhaustedFlags::from_raw(
+        jvmti::JVMTI_RESOURCE_EXHAUSTED_OOM_ERROR | jvmti::JVMTI_RESOURCE_EXHAUSTED_JAVA_HEAP,
+    );
+    assert!(heap.is_oom_error() && heap.is_java_heap() && !heap.is_threads());
+    assert_eq!(heap.to_string(), "OOM_ERROR | JAVA_HEAP");
+
+    assert!(ResourceExhaustedFlags::from(jvmti::JVMTI_RESOURCE_EXHAUSTED_THREADS).is_threads());
+    assert_eq!(ResourceExhaustedFlags::from_raw(jvmti::JVMTI_RESOURCE_EXHAUSTED_THREADS | 0x10).to_string(), "THREADS | 0x10");
+    assert_eq!(ResourceExhaustedFlags::default().to_string(), "NONE");
+}
+
+#[test]
+fn symbolic_frames_resolve_line_numbers() {
+    let table = [
+        jvmti::jvmtiLineNumberEntry { start_location: 0, line_number: 10 },
+        jvmti::jvmtiLineNumberEntry { start_location: 8, line_number: 12 },
+        jvmti::jvmtiLineNumberEntry { start_location: 4, line_number: 11 },
+    ];
+    assert_eq!(SymbolicFrame::line_number_at(&table, 0), Some(10));
+    assert_eq!(SymbolicFrame::line_number_at(&table, 6), Some(11));
+    assert_eq!(SymbolicFrame::line_number_at(&table, 100), Some(12));
+    assert_eq!(SymbolicFrame::line_number_at(&table, -1), None);
+    assert_eq!(SymbolicFrame::line_number_at(&[], 3), None);
+    let _ = Jvmti::symbolize_frames as fn(&Jvmti, &[jvmti::jvmtiFrameInfo]) -> Vec<SymbolicFrame>;
+}
+
+unsafe extern "system" fn three_frame_stack(
+    _env: *mut jvmti::jvmtiEnv,
+    _thread: jni::jthread,
+    start_depth: jni::jint,
+    max_frame_count: jni::jint,
+    frame_buffer: *mut jvmti::jvmtiFrameInfo,
+    count_ptr: *mut jni::jint,
+) -> jvmti::jvmtiError {
+    let count = max_frame_count.min(3);
+    for i in 0..count {
+        (*frame_buffer.add(i as usize)).location = (start_depth + i) as jvmti::jlocation;
+    }
+    *count_ptr = count;
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn unknown_method_name(
+    _env: *mut jvmti::jvmtiEnv,
+    _method: jni::jmethodID,
+    _name_ptr: *mut *mut std::os::raw::c_char,
+    _signature_ptr: *mut *mut std::os::raw::c_char,
+    _generic_ptr: *mut *mut std::os::raw::c_char,
+) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::INVALID_METHODID
+}
+
+unsafe extern "system" fn unknown_declaring_class(
+    _env: *mut jvmti::jvmtiEnv,
+    _method: jni::jmethodID,
+    _declaring_class_ptr: *mut jni::jclass,
+) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::INVALID_METHODID
+}
+
+unsafe extern "system" fn two_line_table(
+    _env: *mut jvmti::jvmtiEnv,
+    _method: jni::jmethodID,
+    entry_count_ptr: *mut jni::jint,
+    table_ptr: *mut *mut jvmti::jvmtiLineNumberEntry,
+) -> jvmti::jvmtiError {
+    let table = Box::leak(Box::new([
+        jvmti::jvmtiLineNumberEntry { start_location: 0, line_number: 10 },
+        jvmti::jvmtiLineNumberEntry { start_location: 5, line_number: 11 },
+    ]));
+    *entry_count_ptr = 2;
+    *table_ptr = table.as_mut_ptr();
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn ignore_deallocate(_env: *mut jvmti::jvmtiEnv, _mem: *mut u8) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn bci_locations(_env: *mut jvmti::jvmtiEnv, format_ptr: *mut jni::jint) -> jvmti::jvmtiError {
+    *format_ptr = jvmti::JVMTI_JLOCATION_JVMBCI;
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn pc_locations(_env: *mut jvmti::jvmtiEnv, format_ptr: *mut jni::jint) -> jvmti::jvmtiError {
+    *format_ptr = jvmti::JVMTI_JLOCATION_MACHINEPC;
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn symbolizer_only_maps_bytecode_locations_to_lines() {
+    assert_eq!(JLocationFormat::from_raw(jvmti::JVMTI_JLOCATION_JVMBCI), JLocationFormat::JvmBci);
+    assert_eq!(JLocationFormat::from_raw(jvmti::JVMTI_JLOCATION_MACHINEPC), JLocationFormat::MachinePc);
+    assert_eq!(JLocationFormat::from_raw(42), JLocationFormat::Other);
+    assert_eq!(JLocationFormat::Other.as_raw(), jvmti::JVMTI_JLOCATION_OTHER);
+    assert!(JLocationFormat::JvmBci.is_bytecode_index());
+    assert!(!JLocationFormat::MachinePc.is_bytecode_index());
+
+    let mut stub = common::stub_jvmti(|functions| {
+        functions.GetMethodName = Some(unknown_method_name);
+        functions.GetMethodDeclaringClass = Some(unknown_declaring_class);
+        functions.GetLineNumberTable = Some(two_line_table);
+        functions.Deallocate = Some(ignore_deallocate);
+        functions.GetJLocationFormat = Some(bci_locations);
+    });
+    let frame = jvmti::jvmtiFrameInfo { method: ptr::null_mut(), location: 7 };
+    let jvmti_env = stub.jvmti();
+    assert_eq!(jvmti_env.get_jlocation_format_decoded(), Ok(JLocationFormat::JvmBci));
+    let frames = jvmti_env.symbolize_frames(&[frame]);
+    assert_eq!(frames[0].line_number, Some(11));
+    assert_eq!(frames[0].method_name, "<unknown>");
+
+    stub.configure(|functions| functions.GetJLocationFormat = Some(pc_locations));
+    let jvmti_env = stub.jvmti();
+    assert_eq!(jvmti_env.get_jlocation_format_decoded(), Ok(JLocationFormat::MachinePc));
+    assert_eq!(jvmti_env.symbolize_frames(&[frame])[0].line_number, None);
+}
+
+unsafe extern "system" fn three_thread_stacks(
+    _env: *mut jvmti::jvmtiEnv,
+    _max_frame_count: jni::jint,
+    stack_info_ptr: *mut *mut jvmti::jvmtiStackInfo,
+    thread_count_ptr: *mut jni::jint,
+) -> jvmti::jvmtiError {
+    let frames = Box::leak(Box::new([jvmti::jvmtiFrameInfo { method: ptr::null_mut(), location: 7 }; 2]));
+    let state = jvmti::JVMTI_THREAD_STATE_ALIVE | jvmti::JVMTI_THREAD_STATE_RUNNABLE;
+    let stacks = Box::leak(Box::new([
+        jvmti::jvmtiStackInfo { frame_buffer: frames.as_mut_ptr(), thread: 2 as jni::jthread, state, frame_count: 1 },
+        jvmti::jvmtiStackInfo { frame_buffer: frames.as_mut_ptr(), thread: 1 as jni::jthread, state, frame_count: 2 },
+        jvmti::jvmtiStackInfo { frame_buffer: ptr::null_mut(), thread: 3 as jni::jthread, state: 0, frame_count: 0 },
+    ]));
+    *stack_info_ptr = stacks.as_mut_ptr();
+    *thread_count_ptr = 3;
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn thread_names(
+    _env: *mut jvmti::jvmtiEnv,
+    thread: jni::jthread,
+    info_ptr: *mut jvmti::jvmtiThreadInfo,
+) -> jvmti::jvmtiError {
+    // A null thread is the current thread, which these tests run as "main".
+    let name = match thread as usize {
+        0 | 1 => "main",
+        2 => "worker",
+        _ => return jvmti::jvmtiError::INVALID_THREAD,
+    };
+    (*info_ptr).name = std::ffi::CString::new(name).unwrap().into_raw();
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn thread_dump_names_and_symbolizes_every_thread() {
+    let stub = common::stub_jvmti(|functions| {
+        functions.GetAllStackTraces = Some(three_thread_stacks);
+        functions.GetThreadInfo = Some(thread_names);
+        functions.GetMethodName = Some(unknown_method_name);
+        functions.GetMethodDeclaringClass = Some(unknown_declaring_class);
+        functions.GetLineNumberTable = Some(two_line_table);
+        functions.GetJLocationFormat = Some(bci_locations);
+        functions.Deallocate = Some(ignore_deallocate);
+    });
+    let jvmti_env = stub.jvmti();
+
+    let dump: Vec<ThreadDump> = jvmti_env.thread_dump().unwrap();
+    let names: Vec<&str> = dump.iter().map(|thread| thread.name.as_str()).collect();
+    assert_eq!(names, ["<unknown>", "main", "worker"]);
+    assert_eq!(dump[1].frames.len(), 2);
+    assert_eq!(dump[1].frames[0].line_number, Some(11));
+    assert!(dump[1].state.is_runnable());
+    assert_eq!(dump[2].frames.len(), 1);
+    assert!(dump[0].frames.is_empty());
+}
+
+#[test]
+fn current_thread_info_uses_the_null_thread() {
+    let stub = common::stub_jvmti(|functions| {
+        functions.GetThreadInfo = Some(thread_names);
+        functions.Deallocate = Some(ignore_deallocate);
+    });
+    let jvmti_env = stub.jvmti();
+
+    assert_eq!(jvmti_env.current_thread_info().unwrap().name.as_deref(), Some("main"));
+    assert_eq!(jvmti_env.thread_name(2 as jni::jthread), Ok(Some("worker".to_string())));
+    assert_eq!(jvmti_env.thread_name(3 as jni::jthread), Err(jvmti::jvmtiError::INVALID_THREAD));
+}
+
+unsafe extern "system" fn method_name_with_descriptor(
+    _env: *mut jvmti::jvmtiEnv,
+    _method: jni::jmethodID,
+    name_ptr: *mut *mut std::os::raw::c_char,
+    signature_ptr: *mut *mut std::os::raw::c_char,
+    _generic_ptr: *mut *mut std::os::raw::c_char,
+) -> jvmti::jvmtiError {
+    *name_ptr = std::ffi::CString::new("update").unwrap().into_raw();
+    *signature_ptr = std::ffi::CString::new("(IJLjava/lang/String;D)Z").unwrap().into_raw();
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn public_instance_method(
+    _env: *mut jvmti::jvmtiEnv,
+    _method: jni::jmethodID,
+    modifiers_ptr: *mut jni::jint,
+) -> jvmti::jvmtiError {
+    *modifiers_ptr = 0x0001;
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn method_signature_counts_argument_slots() {
+    use jvmti_bindings::classfile::descriptor::JavaType;
+
+    let stub = common::stub_jvmti(|functions| {
+        functions.GetMethodName = Some(method_name_with_descriptor);
+        functions.GetMethodModifiers = Some(public_instance_method);
+        functions.Deallocate = Some(ignore_deallocate);
+    });
+    let jvmti_env = stub.jvmti();
+
+    let sig = jvmti_env.get_method_signature(ptr::null_mut()).unwrap();
+    assert_eq!(sig.name, "update");
+    assert_eq!(sig.descriptor, "(IJLjava/lang/String;D)Z");
+    assert_eq!(
+        sig.arg_types,
+        vec![JavaType::Int, JavaType::Long, JavaType::Object("java/lang/String".to_string()), JavaType::Double]
+    );
+    assert_eq!(sig.return_type, JavaType::Boolean);
+    assert!(!sig.is_static);
+    assert_eq!(sig.arg_slot_count, 7);
+    assert_eq!(sig.arg_slots(), vec![1, 2, 4, 5]);
+
+    let static_sig = MethodSignature { is_static: true, arg_slot_count: 6, ..sig };
+    assert_eq!(static_sig.arg_slots(), vec![0, 1, 3, 4]);
+}
+
+#[test]
+fn stack_trace_into_reuses_the_callers_buffer() {
+    // SAFETY: every slot is a nullable pointer or `Option<fn>`, so all-zero is valid.
+    let stub = common::stub_jvmti(|functions| {
+        functions.GetStackTrace = Some(three_frame_stack);
+    });
+    let jvmti_env = stub.jvmti();
+
+    let mut buf = Vec::with_capacity(8);
+    assert_eq!(jvmti_env.get_stack_trace_into(ptr::null_mut(), 1, &mut buf), Ok(3));
+    assert_eq!(buf.iter().map(|frame| frame.location).collect::<Vec<_>>(), vec![1, 2, 3]);
+    let capacity = buf.capacity();
+    assert_eq!(jvmti_env.get_stack_trace_into(ptr::null_mut(), 0, &mut buf), Ok(3));
+    assert_eq!(buf.capacity(), capacity);
+
+    let mut shallow = Vec::with_capacity(2);
+    assert_eq!(jvmti_env.get_stack_trace_into(ptr::null_mut(), 0, &mut shallow), Ok(2));
+    assert_eq!(jvmti_env.get_stack_trace(ptr::null_mut(), 0, 16).map(|frames| frames.len()), Ok(3));
+    assert_eq!(
+        jvmti_env.get_stack_trace(ptr::null_mut(), 0, -1).map(|frames| frames.len()),
+        Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT)
+    );
+}
+
+unsafe extern "system" fn live_phase(_env: *mut jvmti::jvmtiEnv, phase_ptr: *mut jni::jint) -> jvmti::jvmtiError {
+    *phase_ptr = jvmti::JVMTI_PHASE_LIVE;
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn onload_phase(_env: *mut jvmti::jvmtiEnv, phase_ptr: *mut jni::jint) -> jvmti::jvmtiError {
+    *phase_ptr = jvmti::JVMTI_PHASE_ONLOAD;
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn phase_guard_rejects_calls_before_vm_init() {
+    assert_eq!(Phase::from_raw(jvmti::JVMTI_PHASE_START), Some(Phase::Start));
+    assert_eq!(Phase::from_raw(3), None);
+    assert_eq!(Phase::Dead.as_raw(), jvmti::JVMTI_PHASE_DEAD);
+    assert_eq!(Phase::OnLoad.to_string(), "ONLOAD");
+
+    let mut stub = common::stub_jvmti(|functions| {
+        functions.GetPhase = Some(onload_phase);
+        functions.GetCapabilities = Some(redefine_capable);
+    });
+    let jvmti_env = stub.jvmti();
+    assert_eq!(jvmti_env.current_phase(), Ok(Phase::OnLoad));
+    assert_eq!(jvmti_env.require_live_phase("test"), Err(jvmti::jvmtiError::WRONG_PHASE));
+    assert_eq!(jvmti_env.redefine_class(ptr::null_mut(), &[0xCA]), Err(jvmti::jvmtiError::WRONG_PHASE));
+
+    stub.configure(|functions| {
+        functions.GetPhase = Some(live_phase);
+    });
+    let jvmti_env = stub.jvmti();
+    assert_eq!(jvmti_env.require_live_phase("test"), Ok(()));
+}
+
+unsafe extern "system" fn redefine_capable(_env: *mut jvmti::jvmtiEnv, caps: *mut jvmti::jvmtiCapabilities) -> jvmti::jvmtiError {
+    (*caps).set_can_redefine_classes(true);
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn no_capabilities(_env: *mut jvmti::jvmtiEnv, caps: *mut jvmti::jvmtiCapabilities) -> jvmti::jvmtiError {
+    *caps = jvmti::jvmtiCapabilities::default();
+    jvmti::jvmtiError::NONE
+}
+
+// Accepts only class files starting with the magic number.
+unsafe extern "system" fn redefine_magic_only(
+    _env: *mut jvmti::jvmtiEnv,
+    class_count: jni::jint,
+    class_definitions: *const jvmti::jvmtiClassDefinition,
+) -> jvmti::jvmtiError {
+    for def in std::slice::from_raw_parts(class_definitions, class_count as usize) {
+        let bytes = std::slice::from_raw_parts(def.class_bytes, def.class_byte_count as usize);
+        if !bytes.starts_with(&[0xCA, 0xFE, 0xBA, 0xBE]) {
+            return jvmti::jvmtiError::UNSUPPORTED_REDEFINITION_SCHEMA_CHANGED;
+        }
+    }
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn redefine_class_from_bytes() {
+    let mut stub = common::stub_jvmti(|functions| {
+        functions.GetPhase = Some(live_phase);
+        functions.GetCapabilities = Some(no_capabilities);
+        functions.RedefineClasses = Some(redefine_magic_only);
+    });
+    let jvmti_env = stub.jvmti();
+    let class_bytes = [0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 61];
+    assert_eq!(
+        jvmti_env.redefine_class(ptr::null_mut(), &class_bytes),
+        Err(jvmti::jvmtiError::MUST_POSSESS_CAPABILITY)
+    );
+
+    stub.configure(|functions| {
+        functions.GetCapabilities = Some(redefine_capable);
+    });
+    let jvmti_env = stub.jvmti();
+    assert_eq!(jvmti_env.redefine_class(ptr::null_mut(), &class_bytes), Ok(()));
+    assert_eq!(jvmti_env.redefine_class(ptr::null_mut(), &[]), Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT));
+
+    let err = jvmti_env
+        .redefine_classes_from(&[(ptr::null_mut(), &class_bytes[..]), (ptr::null_mut(), &class_bytes[4..])])
+        .unwrap_err();
+    assert_eq!(err, jvmti::jvmtiError::UNSUPPORTED_REDEFINITION_SCHEMA_CHANGED);
+    assert!(err.is_unsupported_redefinition());
+    assert!(!jvmti::jvmtiError::INVALID_CLASS_FORMAT.is_unsupported_redefinition());
+}
+
+static HOOK_MODES: std::sync::Mutex<Vec<(jni::jint, usize)>> = std::sync::Mutex::new(Vec::new());
+static RETRANSFORMED: std::sync::Mutex<Vec<Vec<u8>>> = std::sync::Mutex::new(Vec::new());
+
+unsafe extern "system" fn retransform_capable(_env: *mut jvmti::jvmtiEnv, caps: *mut jvmti::jvmtiCapabilities) -> jvmti::jvmtiError {
+    (*caps).set_can_retransform_classes(true);
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn current_thread(_env: *mut jvmti::jvmtiEnv, thread_ptr: *mut jni::jthread) -> jvmti::jvmtiError {
+    *thread_ptr = 0x7 as jni::jthread;
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn record_mode(_env: *mut jvmti::jvmtiEnv, mode: jni::jint, event_type: u32, thread: jni::jthread) -> jvmti::jvmtiError {
+    assert_eq!(event_type, jvmti::JVMTI_EVENT_CLASS_FILE_LOAD_HOOK);
+    HOOK_MODES.lock().unwrap().push((mode, thread as usize));
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn leak_allocate(_env: *mut jvmti::jvmtiEnv, size: jni::jlong, mem_ptr: *mut *mut u8) -> jvmti::jvmtiError {
+    *mem_ptr = Box::leak(vec![0u8; size as usize].into_boxed_slice()).as_mut_ptr();
+    jvmti::jvmtiError::NONE
+}
+
+// Runs the crate's ClassFileLoadHook for each class, as HotSpot does.
+unsafe extern "system" fn retransform_through_hook(
+    env: *mut jvmti::jvmtiEnv,
+    class_count: jni::jint,
+    classes: *const jni::jclass,
+) -> jvmti::jvmtiError {
+    let hook = jvmti_bindings::get_default_callbacks().ClassFileLoadHook.unwrap();
+    for &klass in std::slice::from_raw_parts(classes, class_count as usize) {
+        let original = [0xCA, 0xFE, klass as u8];
+        let mut new_len: jni::jint = 0;
+        let mut new_data: *mut u8 = ptr::null_mut();
+        let name = b"com/example/Foo\0".as_ptr() as *const std::os::raw::c_char;
+        hook(env, ptr::null_mut(), klass, ptr::null_mut(), name, ptr::null_mut(), 3, original.as_ptr(), &mut new_len, &mut new_data);
+        let bytes = if new_data.is_null() { Vec::new() } else { std::slice::from_raw_parts(new_data, new_len as usize).to_vec() };
+        RETRANSFORMED.lock().unwrap().push(bytes);
+    }
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn reject_disable(_env: *mut jvmti::jvmtiEnv, mode: jni::jint, _event_type: u32, _thread: jni::jthread) -> jvmti::jvmtiError {
+    if mode == 0 { jvmti::jvmtiError::ILLEGAL_ARGUMENT } else { jvmti::jvmtiError::NONE }
+}
+
+unsafe extern "system" fn reject_retransform(
+    _env: *mut jvmti::jvmtiEnv,
+    _class_count: jni::jint,
+    _classes: *const jni::jclass,
+) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::INVALID_CLASS
+}
+
+#[test]
+fn retransform_with_runs_a_one_shot_hook() {
+    let _installed = INSTALLED_CALLBACKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut stub = common::stub_jvmti(|functions| {
+        functions.GetPhase = Some(live_phase);
+        functions.GetCapabilities = Some(no_capabilities);
+    });
+    let jvmti_env = stub.jvmti();
+    assert_eq!(jvmti_env.retransform_with(&[], |_, _| None), Err(jvmti::jvmtiError::MUST_POSSESS_CAPABILITY));
+
+    stub.configure(|functions| {
+        functions.GetCapabilities = Some(retransform_capable);
+        functions.GetCurrentThread = Some(current_thread);
+        functions.SetEventNotificationMode = Some(record_mode);
+        functions.SetEventCallbacks = Some(accept_event_callbacks);
+        functions.DisposeEnvironment = Some(dispose);
+        functions.Allocate = Some(leak_allocate);
+        functions.RetransformClasses = Some(retransform_through_hook);
+    });
+    let jvmti_env = stub.jvmti();
+    jvmti_env.set_agent_callbacks_for(&[Event::VmInit]).unwrap();
+    assert_eq!(jvmti_env.retransform_with(&[], |_, _| None), Err(jvmti::jvmtiError::NOT_AVAILABLE));
+    assert!(HOOK_MODES.lock().unwrap().is_empty());
+    jvmti_env.set_agent_callbacks_for(&[Event::VmInit, Event::ClassFileLoadHook]).unwrap();
+
+    let mut seen = Vec::new();
+    let mut nested = None;
+    let classes = [0x10 as jni::jclass, 0x20 as jni::jclass];
+    let result = jvmti_env.retransform_with(&classes, |name, bytes| {
+        seen.push((name.to_string(), bytes.to_vec()));
+        nested.get_or_insert_with(|| jvmti_env.retransform_with(&[], |_, _| None));
+        (bytes[2] == 0x20).then(|| vec![1, 2, 3, 4])
+    });
+    assert_eq!(result, Ok(()));
+    assert_eq!(nested, Some(Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT)));
+    assert_eq!(
+        seen,
+        vec![("com/example/Foo".to_string(), vec![0xCA, 0xFE, 0x10]), ("com/example/Foo".to_string(), vec![0xCA, 0xFE, 0x20])]
+    );
+    assert_eq!(*RETRANSFORMED.lock().unwrap(), vec![Vec::new(), vec![1, 2, 3, 4]]);
+    assert_eq!(*HOOK_MODES.lock().unwrap(), vec![(1, 0x7), (0, 0x7)]);
+    jvmti_env.dispose_environment().unwrap();
+
+    stub.configure(|functions| {
+        functions.SetEventNotificationMode = Some(reject_disable);
+        functions.RetransformClasses = Some(reject_retransform);
+    });
+    let jvmti_env = stub.jvmti();
+    jvmti_env.set_agent_callbacks_for(&[Event::ClassFileLoadHook]).unwrap();
+    assert_eq!(jvmti_env.retransform_with(&classes, |_, _| None), Err(jvmti::jvmtiError::INVALID_CLASS));
+    jvmti_env.dispose_environment().unwrap();
+}
+
+#[test]
+fn local_variable_scope_and_typed_values() {
+    let entry = LocalVariableEntry {
+        start_location: 4,
+        length: 10,
+        name: Some("count".to_string()),
+        signature: Some("I".to_string()),
+        generic_signature: None,
+        slot: 1,
+    };
+    assert!(!entry.is_live_at(3));
+    assert!(entry.is_live_at(4));
+    assert!(entry.is_live_at(13));
+    assert!(!entry.is_live_at(14));
+    assert!(!entry.is_live_at(-1));
+
+    let _ = Jvmti::read_locals as fn(&Jvmti, jni::jthread, jni::jint) -> JvmtiResult<Vec<(LocalVariableEntry, LocalValue)>>;
+    assert_ne!(LocalValue::Int(1), LocalValue::Long(1));
+    assert_eq!(LocalValue::Object(ptr::null_mut()), LocalValue::Object(ptr::null_mut()));
+}
+
+fn extension_param(kind: jni::jint, base_type: jni::jint, null_ok: bool) -> ExtensionParamInfo {
+    ExtensionParamInfo { name: None, kind, base_type, null_ok }
+}
+
+#[test]
+fn extension_function_checks_args_against_params() {
+    let mut out: jni::jint = 0;
+    let out_ptr = &mut out as *mut jni::jint as *mut std::ffi::c_void;
+    let info = ExtensionFunctionInfo {
+        func: ptr::null_mut(),
+        id: Some("com.example.Scale".to_string()),
+        short_description: None,
+        params: vec![
+            extension_param(jvmti::JVMTI_KIND_IN, jvmti::JVMTI_TYPE_JINT, false),
+            extension_param(jvmti::JVMTI_KIND_OUT, jvmti::JVMTI_TYPE_JINT, false),
+        ],
+        errors: Vec::new(),
+    };
+    assert!(ExtensionFunction::new(&info).is_none());
+
+    unsafe extern "C" fn never_called(_: *mut jvmti::jvmtiEnv) -> jvmti::jvmtiError {
+        jvmti::jvmtiError::INTERNAL
+    }
+    let info = ExtensionFunctionInfo { func: never_called as *mut std::ffi::c_void, ..info };
+    let f = ExtensionFunction::new(&info).unwrap();
+    assert_eq!(f.id(), Some("com.example.Scale"));
+    assert_eq!(f.params().len(), 2);
+    assert_eq!(f.check_args(&[ExtensionArg::Int(2), ExtensionArg::Ptr(out_ptr)]), Ok(()));
+    assert_eq!(f.check_args(&[ExtensionArg::Int(2)]), Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT));
+    assert_eq!(
+        f.check_args(&[ExtensionArg::Long(2), ExtensionArg::Ptr(out_ptr)]),
+        Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT)
+    );
+    assert_eq!(
+        f.check_args(&[ExtensionArg::Int(2), ExtensionArg::Ptr(ptr::null_mut())]),
+        Err(jvmti::jvmtiError::NULL_POINTER)
+    );
+
+    let info = ExtensionFunctionInfo {
+        params: vec![extension_param(jvmti::JVMTI_KIND_IN, jvmti::JVMTI_TYPE_JDOUBLE, false)],
+        ..info
+    };
+    let f = ExtensionFunction::new(&info).unwrap();
+    assert_eq!(f.check_args(&[ExtensionArg::Long(0)]), Err(jvmti::jvmtiError::NOT_AVAILABLE));
+}
+
+// A non-variadic stub only matches the variadic calling convention for
+// integer arguments on x86_64.
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn extension_function_call_passes_args_in_order() {
+    unsafe extern "C" fn scale(_: *mut jvmti::jvmtiEnv, factor: jni::jint, value: jni::jlong, out: *mut jni::jlong) -> jvmti::jvmtiError {
+        *out = value * jni::jlong::from(factor);
+        jvmti::jvmtiError::NONE
+    }
+    unsafe extern "C" fn fail(_: *mut jvmti::jvmtiEnv) -> jvmti::jvmtiError {
+        jvmti::jvmtiError::WRONG_PHASE
+    }
+
+    let stub = common::stub_jvmti(|_| {});
+    let jvmti_env = stub.jvmti();
+
+    let info = ExtensionFunctionInfo {
+        func: scale as *mut std::ffi::c_void,
+        id: Some("com.example.Scale".to_string()),
+        short_description: None,
+        params: vec![
+            extension_param(jvmti::JVMTI_KIND_IN, jvmti::JVMTI_TYPE_JINT, false),
+            extension_param(jvmti::JVMTI_KIND_IN, jvmti::JVMTI_TYPE_JLONG, false),
+            extension_param(jvmti::JVMTI_KIND_OUT, jvmti::JVMTI_TYPE_JLONG, false),
+        ],
+        errors: Vec::new(),
+    };
+    let mut out: jni::jlong = 0;
+    let args = [
+        ExtensionArg::Int(-3),
+        ExtensionArg::Long(1 << 40),
+        ExtensionArg::Ptr(&mut out as *mut jni::jlong as *mut std::ffi::c_void),
+    ];
+    let f = ExtensionFunction::new(&info).unwrap();
+    assert_eq!(unsafe { f.call(&jvmti_env, &args) }, Ok(()));
+    assert_eq!(out, -3 << 40);
+
+    let info = ExtensionFunctionInfo { func: fail as *mut std::ffi::c_void, params: Vec::new(), ..info };
+    let f = ExtensionFunction::new(&info).unwrap();
+    assert_eq!(unsafe { f.call(&jvmti_env, &[]) }, Err(jvmti::jvmtiError::WRONG_PHASE));
+}
+
+#[test]
+fn closure_heap_iteration_is_public_api() {
+    use jvmti_bindings::env::{HeapObject, IterationControl};
+
+    fn count_objects(jvmti_env: &Jvmti) -> JvmtiResult<usize> {
+        let mut count = 0;
+        jvmti_env.iterate_heap_with(0, ptr::null_mut(), |mut object: HeapObject<'_>| {
+            count += 1;
+            object.set_tag(object.size());
+            IterationControl::Continue
+        })?;
+        Ok(count)
+    }
+    let _ = count_objects as fn(&Jvmti) -> JvmtiResult<usize>;
+    let _ = Jvmti::class_histogram as fn(&Jvmti) -> JvmtiResult<Vec<jvmti_bindings::env::ClassCount>>;
+    assert_ne!(IterationControl::Continue, IterationControl::Abort);
+}
+
+#[test]
+fn jvmti_access_flag_helpers_are_public_api() {
+    use jvmti_bindings::classfile::AccessFlags;
+
+    let _ = Jvmti::get_class_access_flags as fn(&Jvmti, jni::jclass) -> JvmtiResult<AccessFlags>;
+    let _ = Jvmti::get_field_access_flags as fn(&Jvmti, jni::jclass, jni::jfieldID) -> JvmtiResult<AccessFlags>;
+    let _ = Jvmti::get_method_access_flags as fn(&Jvmti, jni::jmethodID) -> JvmtiResult<AccessFlags>;
+}
+
+#[test]
+fn jni_string_helpers_are_public_api() {
+    let _ = JniEnv::get_string as fn(&JniEnv, jni::jstring) -> Option<String>;
+    let _ = JniEnv::get_string_utf as fn(&JniEnv, jni::jstring) -> Option<String>;
+    let _ = JniEnv::get_string_region as fn(&JniEnv, jni::jstring, jni::jsize, jni::jsize) -> Option<String>;
+}
+
+#[test]
+fn local_frame_helpers_are_public_api() {
+    fn _compiles(jni: &JniEnv) -> Result<(), jni::jint> {
+        let frame = LocalFrameGuard::new(jni, 16)?;
+        let _: jni::jobject = frame.pop(ptr::null_mut());
+        let _: u32 = jni.with_local_frame(16, |_| 1)?;
+        let _: jni::jobject = jni.with_local_frame_returning(16, |_| ptr::null_mut())?;
+        Ok(())
+    }
+    let _ = _compiles as fn(&JniEnv) -> Result<(), jni::jint>;
+}
+
+#[test]
+fn checked_jni_calls_return_java_exceptions() {
+    fn _compiles(jni: &JniEnv, obj: jni::jobject, cls: jni::jclass, m: jni::jmethodID) -> Result<(), JavaException<'_>> {
+        jni.check_exception()?;
+        jni.call_void_method_checked(obj, m, &[])?;
+        let _: bool = jni.call_boolean_method_checked(obj, m, &[])?;
+        let _: jni::jdouble = jni.call_nonvirtual_double_method_checked(obj, cls, m, &[])?;
+        let _: jni::jobject = jni.call_static_object_method_checked(cls, m, &[])?;
+        Ok(())
+    }
+    let _ = _compiles as for<'a> fn(&'a JniEnv, jni::jobject, jni::jclass, jni::jmethodID) -> Result<(), JavaException<'a>>;
+}
+
+#[test]
+fn jni_array_conversions_are_public_api() {
+    let _ = JniEnv::get_byte_array as fn(&JniEnv, jni::jbyteArray) -> Option<Vec<u8>>;
+    let _ = JniEnv::new_byte_array_from as fn(&JniEnv, &[u8]) -> Option<jni::jbyteArray>;
+    let _ = JniEnv::get_int_array as fn(&JniEnv, jni::jintArray) -> Option<Vec<jni::jint>>;
+    let _ = JniEnv::new_long_array_from as fn(&JniEnv, &[jni::jlong]) -> Option<jni::jlongArray>;
+    let _ = JniEnv::get_char_array as fn(&JniEnv, jni::jcharArray) -> Option<Vec<jni::jchar>>;
+    let _ = JniEnv::new_double_array_from as fn(&JniEnv, &[jni::jdouble]) -> Option<jni::jdoubleArray>;
+    let _ = JniEnv::get_object_array as fn(&JniEnv, jni::jobjectArray) -> Vec<jni::jobject>;
+}
+
+#[test]
+fn virtual_thread_helpers_are_public_api() {
+    let _ = JniEnv::is_virtual_thread as fn(&JniEnv, jni::jobject) -> bool;
+    let _ = Jvmti::partition_virtual_threads
+        as fn(&Jvmti, &JniEnv, &[jni::jthread]) -> (Vec<jni::jthread>, Vec<jni::jthread>);
+    let _ = Jvmti::suspend_virtual_thread as fn(&Jvmti, jni::jthread) -> JvmtiResult<()>;
+    let _ = Jvmti::resume_virtual_thread as fn(&Jvmti, jni::jthread) -> JvmtiResult<()>;
+    let _ = Jvmti::suspend_all_virtual_threads as fn(&Jvmti, &[jni::jthread]) -> JvmtiResult<()>;
+    let _ = Jvmti::resume_all_virtual_threads as fn(&Jvmti, &[jni::jthread]) -> JvmtiResult<()>;
+}
+
+// jvmtiError SuspendAllVirtualThreads(jvmtiEnv* env, jint except_count, const jthread* except_list)
+unsafe extern "system" fn count_except_list(
+    _env: *mut jvmti::jvmtiEnv,
+    except_count: jni::jint,
+    except_list: *const jni::jthread,
+) -> jvmti::jvmtiError {
+    let except = std::slice::from_raw_parts(except_list, except_count as usize);
+    if except == [7 as jni::jthread, 9 as jni::jthread] {
+        jvmti::jvmtiError::NONE
+    } else {
+        jvmti::jvmtiError::ILLEGAL_ARGUMENT
+    }
+}
+
+#[test]
+fn all_virtual_thread_ops_pass_the_except_list() {
+    let _: jvmti::JvmtiSuspendAllVirtualThreadsFn = count_except_list;
+    let _: jvmti::JvmtiResumeAllVirtualThreadsFn = count_except_list;
+
+    let mut stub = common::stub_jvmti(|_| {});
+    let jvmti_env = stub.jvmti();
+    assert_eq!(jvmti_env.suspend_all_virtual_threads(&[]), Err(jvmti::jvmtiError::NOT_AVAILABLE));
+
+    stub.configure(|functions| {
+        functions.SuspendAllVirtualThreads = Some(count_except_list);
+        functions.ResumeAllVirtualThreads = Some(count_except_list);
+    });
+    let jvmti_env = stub.jvmti();
+    let except = [7 as jni::jthread, 9 as jni::jthread];
+    assert_eq!(jvmti_env.suspend_all_virtual_threads(&except), Ok(()));
+    assert_eq!(jvmti_env.resume_all_virtual_threads(&except), Ok(()));
+    assert_eq!(jvmti_env.resume_all_virtual_threads(&except[..1]), Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT));
+}
+
+unsafe extern "system" fn unreachable_jni_function() {
+    unreachable!("placeholder JNI function called")
+}
+
+unsafe extern "system" fn intercepted_find_class(
+    _env: *mut jni::JNIEnv,
+    _name: *const std::os::raw::c_char,
+) -> jni::jclass {
+    ptr::null_mut()
+}
+
+static INSTALLED_FIND_CLASS: std::sync::Mutex<Vec<usize>> = std::sync::Mutex::new(Vec::new());
+
+unsafe extern "system" fn placeholder_jni_table(
+    _env: *mut jvmti::jvmtiEnv,
+    table_ptr: *mut *mut jni::JNINativeInterface_,
+) -> jvmti::jvmtiError {
+    let slots = std::mem::size_of::<jni::JNINativeInterface_>() / std::mem::size_of::<usize>();
+    let placeholder = unreachable_jni_function as unsafe extern "system" fn() as usize;
+    let table = vec![placeholder; slots].leak();
+    *table_ptr = table.as_mut_ptr().cast();
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn record_jni_table(
+    _env: *mut jvmti::jvmtiEnv,
+    table: *const jni::JNINativeInterface_,
+) -> jvmti::jvmtiError {
+    INSTALLED_FIND_CLASS.lock().unwrap().push((*table).FindClass as usize);
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn intercepted_jni_table_can_be_restored() {
+    let _: jvmti::JvmtiSetJNIFunctionTableFn = record_jni_table;
+    let _: jvmti::JvmtiGetJNIFunctionTableFn = placeholder_jni_table;
+
+    let stub = common::stub_jvmti(|functions| {
+        functions.GetJNIFunctionTable = Some(placeholder_jni_table);
+        functions.SetJNIFunctionTable = Some(record_jni_table);
+        functions.Deallocate = Some(ignore_deallocate);
+    });
+    let jvmti_env = stub.jvmti();
+
+    let placeholder = unreachable_jni_function as unsafe extern "system" fn() as usize;
+    let interceptor: unsafe extern "system" fn(*mut jni::JNIEnv, *const std::os::raw::c_char) -> jni::jclass =
+        intercepted_find_class;
+    let original: JniFunctionTable = jvmti_env.with_intercepted_jni(|table| table.FindClass = interceptor).unwrap();
+    assert_eq!(original.table().FindClass as usize, placeholder);
+    original.restore(&jvmti_env).unwrap();
+    assert_eq!(*INSTALLED_FIND_CLASS.lock().unwrap(), [interceptor as usize, placeholder]);
+}
+
+unsafe extern "system" fn return_long_mismatch(
+    _env: *mut jvmti::jvmtiEnv,
+    _thread: jni::jthread,
+    _value: jni::jlong,
+) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::TYPE_MISMATCH
+}
+
+unsafe extern "system" fn return_void_ok(_env: *mut jvmti::jvmtiEnv, _thread: jni::jthread) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn force_early_return_dispatches_on_the_value() {
+    let stub = common::stub_jvmti(|functions| {
+        functions.ForceEarlyReturnLong = Some(return_long_mismatch);
+        functions.ForceEarlyReturnVoid = Some(return_void_ok);
+    });
+    let jvmti_env = stub.jvmti();
+
+    let thread = 1 as jni::jthread;
+    assert_eq!(jvmti_env.force_early_return(thread, ReturnValue::Void), Ok(()));
+    assert_eq!(jvmti_env.force_early_return(thread, ReturnValue::Long(7)), Err(jvmti::jvmtiError::TYPE_MISMATCH));
+}
+
+unsafe extern "system" fn heap_with_values(
+    _env: *mut jvmti::jvmtiEnv,
+    _heap_filter: jni::jint,
+    _klass: jni::jclass,
+    callbacks: *const jvmti::jvmtiHeapCallbacks,
+    user_data: *const std::os::raw::c_void,
+) -> jvmti::jvmtiError {
+    let callbacks = &*callbacks;
+    let user_data = user_data as *mut std::os::raw::c_void;
+    assert!(callbacks.heap_iteration_callback.is_none());
+    assert!(callbacks.primitive_field_callback.is_none());
+    let mut tag = 0;
+    let chars: Vec<jni::jchar> = "secret".encode_utf16().collect();
+    if let Some(array) = callbacks.array_primitive_value_callback {
+        let flags = array(1, 40, &mut tag, 6, jvmti::JVMTI_PRIMITIVE_TYPE_CHAR, chars.as_ptr().cast(), user_data);
+        assert_eq!(flags, 0);
+    }
+    if let Some(string) = callbacks.string_primitive_value_callback {
+        let flags = string(2, 24, &mut tag, chars.as_ptr(), 3, user_data);
+        assert_eq!(flags, jvmti::JVMTI_VISIT_ABORT);
+    }
+    assert_eq!(tag, 9);
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn iterate_through_heap_with_reports_array_and_string_values() {
+    let stub = common::stub_jvmti(|functions| {
+        functions.GetPhase = Some(live_phase);
+        functions.IterateThroughHeap = Some(heap_with_values);
+    });
+    let jvmti_env = stub.jvmti();
+
+    let mut arrays = Vec::new();
+    let mut strings = Vec::new();
+    let closures = HeapClosures::new()
+        .on_array_primitive_value(|mut array: PrimitiveArray<'_>| {
+            assert!(array.as_ints().is_none());
+            arrays.push(String::from_utf16_lossy(array.as_chars().unwrap()));
+            array.set_tag(9);
+            IterationControl::Continue
+        })
+        .on_string_value(|string: StringValue<'_>| {
+            strings.push((string.to_string_lossy(), string.tag()));
+            IterationControl::Abort
+        });
+    jvmti_env.iterate_through_heap_with(0, ptr::null_mut(), closures).unwrap();
+    assert_eq!(arrays, ["secret"]);
+    assert_eq!(strings, [("sec".to_string(), 9)]);
+}
+
+#[test]
+fn object_tagger_starts_empty() {
+    let tagger: ObjectTagger<String> = ObjectTagger::new();
+    assert!(tagger.is_empty());
+    assert_eq!(tagger.get_value(1), None);
+    assert_eq!(tagger.with_value(1, |v| v.len()), None);
+    assert_eq!(tagger.object_freed(1), None);
+    let _ = ObjectTagger::<String>::tag_object as fn(&ObjectTagger<String>, &Jvmti, jni::jobject, String) -> JvmtiResult<jni::jlong>;
+    let _ = ObjectTagger::<String>::live_objects
+        as fn(&ObjectTagger<String>, &Jvmti, &[jni::jlong]) -> JvmtiResult<Vec<(jni::jobject, String)>>;
+}
+
+#[test]
+fn class_cache_starts_empty() {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<ClassCache>();
+
+    let cache = ClassCache::default();
+    assert!(cache.is_empty());
+    assert_eq!(cache.len(), 0);
+    assert_eq!(cache.get_cached("java.lang.System"), None);
+    let _ = ClassCache::get as fn(&ClassCache, &JniEnv, &str) -> Option<jni::jclass>;
+    let _ = ClassCache::clear as fn(&ClassCache, &JniEnv);
+    let _ = JniEnv::find_class_dotted as fn(&JniEnv, &str) -> Option<jni::jclass>;
+}
 
 #[test]
-fn jvmti_new_rejects_null_vm_pointer() {
-    let err = match Jvmti::new(ptr::null_mut()) {
-        Ok(_) => panic!("null JavaVM must be rejected"),
-        Err(err) => err,
+fn global_refs_are_shareable_and_convert_to_local() {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<GlobalRef>();
+
+    fn _round_trip<'a>(jni: &'a JniEnv, obj: jni::jobject) -> LocalRef<'a> {
+        let global = LocalRef::new(jni, obj).to_global();
+        let handle = std::thread::spawn(move || global.get() as usize);
+        let _ = handle.join();
+        let global = LocalRef::new(jni, obj).to_global();
+        global.as_local(jni)
+    }
+}
+
+#[test]
+fn weak_global_refs_upgrade_to_local() {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<WeakGlobalRef>();
+
+    let _ = WeakGlobalRef::is_collected as fn(&WeakGlobalRef, &JniEnv) -> bool;
+    fn _upgrade<'a>(jni: &'a JniEnv, obj: jni::jobject) -> Option<LocalRef<'a>> {
+        let weak = LocalRef::new(jni, obj).to_weak();
+        weak.upgrade(jni)
+    }
+}
+
+unsafe extern "system" fn reject_method_exit(
+    _env: *mut jvmti::jvmtiEnv,
+    _mode: jni::jint,
+    event_type: u32,
+    _thread: jni::jthread,
+) -> jvmti::jvmtiError {
+    if event_type == jvmti::JVMTI_EVENT_METHOD_EXIT {
+        jvmti::jvmtiError::MUST_POSSESS_CAPABILITY
+    } else {
+        jvmti::jvmtiError::NONE
+    }
+}
+
+#[test]
+fn set_events_reports_what_was_toggled_before_a_failure() {
+    let stub = common::stub_jvmti(|functions| {
+        functions.SetEventNotificationMode = Some(reject_method_exit);
+    });
+    let jvmti_env = stub.jvmti();
+
+    assert_eq!(jvmti_env.set_events(true, &[Event::VmInit, Event::MethodEntry], None), Ok(()));
+    let err = jvmti_env
+        .set_events(true, &[Event::VmInit, Event::MethodEntry, Event::MethodExit, Event::VmDeath], None)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        SetEventsError {
+            failed: Event::MethodExit,
+            error: jvmti::jvmtiError::MUST_POSSESS_CAPABILITY,
+            toggled: vec![Event::VmInit, Event::MethodEntry],
+        }
+    );
+    assert!(err.to_string().starts_with("MethodExit: "));
+    assert_eq!(jvmti::jvmtiError::from(err.clone()), jvmti::jvmtiError::MUST_POSSESS_CAPABILITY);
+    assert_eq!(jvmti_env.set_events(false, &err.toggled, Some(ptr::null_mut())), Ok(()));
+}
+
+#[test]
+fn jvalue_helpers_set_the_matching_union_field() {
+    let obj = 0x10 as jni::jobject;
+    let args = jvmti_bindings::jvalues![obj, 42, 7i64, true, 1.5f32, 2.5f64, 3i8, 4i16, 'x' as u16];
+    unsafe {
+        assert_eq!(args[0].l, obj);
+        assert_eq!(args[1].i, 42);
+        assert_eq!(args[2].j, 7);
+        assert_eq!(args[3].z, jni::JNI_TRUE);
+        assert_eq!(args[4].f, 1.5);
+        assert_eq!(args[5].d, 2.5);
+        assert_eq!(args[6].b, 3);
+        assert_eq!(args[7].s, 4);
+        assert_eq!(args[8].c, 'x' as u16);
+        assert_eq!(jni::jvalue::int(-1).i, -1);
+        assert_eq!(jni::jvalue::boolean(false).z, jni::JNI_FALSE);
+        assert!(jni::jvalue::object(ptr::null_mut()).l.is_null());
+        assert_eq!(jni::jvalue::from(9i64).j, 9);
+    }
+    let none: [jni::jvalue; 0] = jvmti_bindings::jvalues![];
+    assert!(none.is_empty());
+}
+
+#[test]
+fn jni_calls_by_name_are_public_api() {
+    fn _compiles(jni: &JniEnv, obj: jni::jobject) -> Result<i64, JavaException<'_>> {
+        let now = jni.call_static_method("java.lang.System", "currentTimeMillis", "()J", &[])?;
+        let hash = jni.call_method(obj, "hashCode", "()I", &[])?;
+        let key = jvmti_bindings::jvalues![obj];
+        let _ = jni.call_static_method("java/lang/System", "getProperty", "(Ljava/lang/String;)Ljava/lang/String;", &key)?;
+        Ok(unsafe { now.j + hash.i as i64 })
+    }
+    let _ = _compiles as fn(&JniEnv, jni::jobject) -> Result<i64, JavaException<'_>>;
+}
+
+unsafe extern "system" fn three_property_names(
+    _env: *mut jvmti::jvmtiEnv,
+    count_ptr: *mut jni::jint,
+    property_ptr: *mut *mut *mut std::os::raw::c_char,
+) -> jvmti::jvmtiError {
+    let names = ["java.version", "user.dir", "unset.property"]
+        .map(|name| std::ffi::CString::new(name).unwrap().into_raw());
+    *property_ptr = Box::leak(Box::new(names)).as_mut_ptr();
+    *count_ptr = 3;
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn property_values(
+    _env: *mut jvmti::jvmtiEnv,
+    property: *const std::os::raw::c_char,
+    value_ptr: *mut *mut std::os::raw::c_char,
+) -> jvmti::jvmtiError {
+    let value = match std::ffi::CStr::from_ptr(property).to_str().unwrap() {
+        "java.version" => "21",
+        "user.dir" => "/srv/app",
+        _ => return jvmti::jvmtiError::NOT_AVAILABLE,
     };
-    assert_eq!(err, jni::JNI_ERR);
+    *value_ptr = std::ffi::CString::new(value).unwrap().into_raw();
+    jvmti::jvmtiError::NONE
 }
 
 #[test]
-fn jni_results_have_display_helpers() {
+fn system_properties_map_fetches_values() {
+    let stub = common::stub_jvmti(|functions| {
+        functions.GetSystemProperties = Some(three_property_names);
+        functions.GetSystemProperty = Some(property_values);
+        functions.Deallocate = Some(ignore_deallocate);
+    });
+    let jvmti_env = stub.jvmti();
+
+    assert_eq!(jvmti_env.get_system_properties().unwrap().len(), 3);
+    let properties = jvmti_env.get_system_properties_map().unwrap();
+    assert_eq!(properties.len(), 2);
+    assert_eq!(properties["java.version"], "21");
+    assert_eq!(properties["user.dir"], "/srv/app");
+    assert!(!properties.contains_key("unset.property"));
+}
+
+#[test]
+fn jni_monitor_helpers_are_public_api() {
+    fn _compiles(jni: &JniEnv, obj: jni::jobject) -> Result<(), jni::jint> {
+        let guard: MonitorEnterGuard<'_> = jni.enter_monitor(obj)?;
+        assert_eq!(guard.object(), obj);
+        guard.exit()?;
+        let _: u32 = jni.synchronized(obj, || 1)?;
+        Ok(())
+    }
+    let _ = _compiles as fn(&JniEnv, jni::jobject) -> Result<(), jni::jint>;
+}
+
+#[test]
+fn capabilities_support_set_operations() {
+    let trace = jvmti::jvmtiCapabilities::for_method_trace();
+    let mut all = trace;
+    all.set_can_tag_objects(true);
+    all.set_can_support_virtual_threads(true);
+
     assert_eq!(
-        jni::describe_result(jni::JNI_EDETACHED),
-        "JNI_EDETACHED (-2)"
+        all.enabled_names(),
+        vec![
+            "can_tag_objects",
+            "can_generate_method_entry_events",
+            "can_generate_method_exit_events",
+            "can_support_virtual_threads",
+        ]
+    );
+    let rest = all.difference(&trace);
+    assert!(!rest.can_generate_method_entry_events());
+    assert!(rest.can_tag_objects());
+    assert_eq!(all.intersect(&trace), trace);
+    assert_eq!(rest.union(&trace), all);
+    assert!(all.contains(&trace));
+    assert!(!trace.contains(&all));
+    assert!(jvmti::jvmtiCapabilities::default().is_empty());
+    assert_eq!(
+        trace.to_string(),
+        "Capabilities [can_generate_method_entry_events, can_generate_method_exit_events]"
     );
-    assert_eq!(describe_jni_result(jni::JNI_EVERSION), "JNI_EVERSION (-3)");
 }
 
 #[test]
-fn jvmti_errors_have_static_names() {
+fn capability_errors_name_missing_capabilities() {
+    let err = CapabilityError::Unavailable(vec!["can_generate_sampled_object_alloc_events"]);
+    assert_eq!(err.to_string(), "can_generate_sampled_object_alloc_events not available on this JVM");
     assert_eq!(
-        jvmti::error_name(jvmti::jvmtiError::MUST_POSSESS_CAPABILITY),
-        "JVMTI_ERROR_MUST_POSSESS_CAPABILITY"
+        CapabilityError::from(jvmti::jvmtiError::WRONG_PHASE),
+        CapabilityError::Jvmti(jvmti::jvmtiError::WRONG_PHASE)
     );
+    let _ = Jvmti::add_capabilities_checked
+        as fn(&Jvmti, &jvmti::jvmtiCapabilities) -> Result<(), CapabilityError>;
 }
 
 #[test]
@@ -72,6 +1340,11 @@ fn jvmti_workflow_helpers_are_public_api() {
         as fn(&Jvmti, jvmti::jvmtiError) -> Result<String, jvmti::jvmtiError>;
 }
 
+#[test]
+fn data_dump_request_helper_is_public_api() {
+    let _ = Jvmti::enable_data_dump_request_events as fn(&Jvmti) -> Result<(), jvmti::jvmtiError>;
+}
+
 #[test]
 fn jni_classloader_and_module_helpers_are_public_api() {
     let _ = JniEnv::define_class as fn(&JniEnv, &str, jni::jobject, &[u8]) -> Option<jni::jclass>;
@@ -85,6 +1358,118 @@ fn jni_classloader_and_module_helpers_are_public_api() {
     let _ = JniEnv::module_is_open_to as fn(&JniEnv, jni::jobject, &str, jni::jobject) -> bool;
 }
 
+#[test]
+fn jvmti_scoped_guards_are_public_api() {
+    use jvmti_bindings::env::{RawMonitorGuard, SuspendGuard, SuspendListGuard};
+
+    let _ = Jvmti::enter_raw_monitor
+        as fn(&Jvmti, jvmti::jrawMonitorID) -> Result<RawMonitorGuard<'_>, jvmti::jvmtiError>;
+    let _ = RawMonitorGuard::<'static>::wait as fn(&RawMonitorGuard<'static>, jni::jlong) -> Result<(), jvmti::jvmtiError>;
+    let _ = RawMonitorGuard::<'static>::notify as fn(&RawMonitorGuard<'static>) -> Result<(), jvmti::jvmtiError>;
+    let _ = RawMonitorGuard::<'static>::notify_all as fn(&RawMonitorGuard<'static>) -> Result<(), jvmti::jvmtiError>;
+    let _ = Jvmti::suspend_thread_scoped
+        as fn(&Jvmti, jni::jthread) -> Result<SuspendGuard<'_>, jvmti::jvmtiError>;
+    let _ = SuspendGuard::<'static>::thread as fn(&SuspendGuard<'static>) -> jni::jthread;
+    let _ = Jvmti::suspend_thread_list_scoped
+        as for<'a> fn(&'a Jvmti, &[jni::jthread]) -> Result<SuspendListGuard<'a>, jvmti::jvmtiError>;
+    let _ = SuspendListGuard::<'static>::suspended as for<'a> fn(&'a SuspendListGuard<'static>) -> &'a [jni::jthread];
+    let _ = SuspendListGuard::<'static>::results as for<'a> fn(&'a SuspendListGuard<'static>) -> &'a [jvmti::jvmtiError];
+}
+
+static MONITOR_CALLS: std::sync::Mutex<Vec<(&'static str, usize)>> = std::sync::Mutex::new(Vec::new());
+
+unsafe extern "system" fn record_monitor_enter(_env: *mut jvmti::jvmtiEnv, monitor: jvmti::jrawMonitorID) -> jvmti::jvmtiError {
+    MONITOR_CALLS.lock().unwrap().push(("enter", monitor as usize));
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn record_monitor_exit(_env: *mut jvmti::jvmtiEnv, monitor: jvmti::jrawMonitorID) -> jvmti::jvmtiError {
+    MONITOR_CALLS.lock().unwrap().push(("exit", monitor as usize));
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn raw_monitor_guard_exits_once_on_drop() {
+    let stub = common::stub_jvmti(|functions| {
+        functions.RawMonitorEnter = Some(record_monitor_enter);
+        functions.RawMonitorExit = Some(record_monitor_exit);
+    });
+    let jvmti_env = stub.jvmti();
+
+    let guard = jvmti_env.enter_raw_monitor(0x40 as jvmti::jrawMonitorID).unwrap();
+    assert_eq!(*MONITOR_CALLS.lock().unwrap(), vec![("enter", 0x40)]);
+    drop(guard);
+    assert_eq!(*MONITOR_CALLS.lock().unwrap(), vec![("enter", 0x40), ("exit", 0x40)]);
+}
+
+static RESUMED: std::sync::Mutex<Vec<usize>> = std::sync::Mutex::new(Vec::new());
+
+unsafe extern "system" fn accept_suspend(_env: *mut jvmti::jvmtiEnv, _thread: jni::jthread) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn record_resume(_env: *mut jvmti::jvmtiEnv, thread: jni::jthread) -> jvmti::jvmtiError {
+    RESUMED.lock().unwrap().push(thread as usize);
+    jvmti::jvmtiError::NONE
+}
+
+// Suspends every thread except 0x20, which has already terminated.
+unsafe extern "system" fn suspend_live_threads(
+    _env: *mut jvmti::jvmtiEnv,
+    request_count: jni::jint,
+    request_list: *const jni::jthread,
+    results: *mut jvmti::jvmtiError,
+) -> jvmti::jvmtiError {
+    for i in 0..request_count as usize {
+        *results.add(i) = if *request_list.add(i) as usize == 0x20 {
+            jvmti::jvmtiError::THREAD_NOT_ALIVE
+        } else {
+            jvmti::jvmtiError::NONE
+        };
+    }
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn record_resume_list(
+    _env: *mut jvmti::jvmtiEnv,
+    request_count: jni::jint,
+    request_list: *const jni::jthread,
+    results: *mut jvmti::jvmtiError,
+) -> jvmti::jvmtiError {
+    for i in 0..request_count as usize {
+        RESUMED.lock().unwrap().push(*request_list.add(i) as usize);
+        *results.add(i) = jvmti::jvmtiError::NONE;
+    }
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn suspend_guards_resume_what_they_suspended() {
+    let stub = common::stub_jvmti(|functions| {
+        functions.SuspendThread = Some(accept_suspend);
+        functions.ResumeThread = Some(record_resume);
+        functions.SuspendThreadList = Some(suspend_live_threads);
+        functions.ResumeThreadList = Some(record_resume_list);
+    });
+    let jvmti_env = stub.jvmti();
+
+    let guard = jvmti_env.suspend_thread_scoped(0x8 as jni::jthread).unwrap();
+    assert!(RESUMED.lock().unwrap().is_empty());
+    drop(guard);
+    assert_eq!(std::mem::take(&mut *RESUMED.lock().unwrap()), vec![0x8]);
+
+    let threads = [0x10 as jni::jthread, 0x20 as jni::jthread, 0x30 as jni::jthread];
+    let guard = jvmti_env.suspend_thread_list_scoped(&threads).unwrap();
+    assert_eq!(guard.suspended(), &[0x10 as jni::jthread, 0x30 as jni::jthread]);
+    assert_eq!(
+        guard.results(),
+        &[jvmti::jvmtiError::NONE, jvmti::jvmtiError::THREAD_NOT_ALIVE, jvmti::jvmtiError::NONE]
+    );
+    assert!(RESUMED.lock().unwrap().is_empty());
+    drop(guard);
+    assert_eq!(*RESUMED.lock().unwrap(), vec![0x10, 0x30]);
+}
+
 #[test]
 fn agent_jvmti_callback_variants_are_public_api() {
     struct ApiAgent;
@@ -107,3 +1492,573 @@ fn agent_jvmti_callback_variants_are_public_api() {
     jvmti_bindings::Agent::virtual_thread_start(&agent, ptr::null_mut(), ptr::null_mut());
     jvmti_bindings::Agent::virtual_thread_end(&agent, ptr::null_mut(), ptr::null_mut());
 }
+
+#[test]
+fn transform_class_defaults_to_keeping_the_bytes() {
+    struct ApiAgent;
+    impl jvmti_bindings::Agent for ApiAgent {
+        fn on_load(&self, _vm: *mut jni::JavaVM, _options: &str) -> jni::jint {
+            jni::JNI_OK
+        }
+    }
+
+    let _ = Jvmti::allocate_bytes as fn(&Jvmti, &[u8]) -> Result<*mut u8, jvmti::jvmtiError>;
+    assert!(jvmti_bindings::Agent::transform_class(&ApiAgent, Some("java/lang/Object"), &[0xCA, 0xFE]).is_none());
+}
+
+#[test]
+fn agent_on_attach_defaults_to_on_load() {
+    struct LoadOnly;
+    impl jvmti_bindings::Agent for LoadOnly {
+        fn on_load(&self, _vm: *mut jni::JavaVM, options: &str) -> jni::jint {
+            if options == "attach" { jni::JNI_EINVAL } else { jni::JNI_OK }
+        }
+    }
+
+    let agent = LoadOnly;
+    assert_eq!(jvmti_bindings::Agent::on_attach(&agent, ptr::null_mut(), "attach"), jni::JNI_EINVAL);
+    assert_eq!(jvmti_bindings::Agent::on_attach(&agent, ptr::null_mut(), ""), jni::JNI_OK);
+}
+
+#[test]
+fn agent_env_callback_variants_delegate_to_raw_callbacks() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct CountingAgent {
+        hits: AtomicUsize,
+    }
+    impl jvmti_bindings::Agent for CountingAgent {
+        fn on_load(&self, _vm: *mut jni::JavaVM, _options: &str) -> jni::jint {
+            jni::JNI_OK
+        }
+        fn vm_init(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        fn class_load(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread, _klass: jni::jclass) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        fn thread_start(&self, _jni: *mut jni::JNIEnv, _thread: jni::jthread) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let agent = CountingAgent::default();
+    let jvmti_env = unsafe { Jvmti::from_raw(ptr::null_mut()) };
+    let jni_env = unsafe { JniEnv::from_raw(ptr::null_mut()) };
+    jvmti_bindings::Agent::vm_init_with_env(&agent, &jvmti_env, &jni_env, ptr::null_mut());
+    jvmti_bindings::Agent::class_load_with_env(&agent, &jvmti_env, &jni_env, ptr::null_mut(), ptr::null_mut());
+    jvmti_bindings::Agent::thread_start_with_env(&agent, &jvmti_env, &jni_env, ptr::null_mut());
+    assert_eq!(agent.hits.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn event_registry_dispatches_to_registered_closures() {
+    use jvmti_bindings::env::EventRegistry;
+    use jvmti_bindings::Agent;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let entries = Arc::new(AtomicUsize::new(0));
+    let seen = Arc::clone(&entries);
+    let mut freed = Vec::new();
+    let registry = EventRegistry::new()
+        .on_method_entry(move |_jvmti, _jni, _thread, _method| {
+            seen.fetch_add(1, Ordering::Relaxed);
+        })
+        .on_object_free(move |tag| {
+            freed.push(tag);
+            assert_eq!(freed.len() as i64, tag);
+        });
+
+    assert_eq!(
+        registry.registered_events(),
+        vec![jvmti::JVMTI_EVENT_METHOD_ENTRY, jvmti::JVMTI_EVENT_OBJECT_FREE]
+    );
+
+    let jvmti_env = unsafe { Jvmti::from_raw(ptr::null_mut()) };
+    let jni_env = unsafe { JniEnv::from_raw(ptr::null_mut()) };
+    registry.method_entry_with_env(&jvmti_env, &jni_env, ptr::null_mut(), ptr::null_mut());
+    registry.method_entry_with_env(&jvmti_env, &jni_env, ptr::null_mut(), ptr::null_mut());
+    registry.object_free(1);
+    registry.object_free(2);
+    registry.class_load_with_env(&jvmti_env, &jni_env, ptr::null_mut(), ptr::null_mut());
+    assert_eq!(entries.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn agent_callback_panics_are_contained() {
+    assert_eq!(jvmti_bindings::panic_policy(), PanicPolicy::Swallow);
+
+    let value = jvmti_bindings::guard_agent_call("Test", jni::JNI_ERR, || -> jni::jint {
+        panic!("boom")
+    });
+    assert_eq!(value, jni::JNI_ERR);
+    assert_eq!(jvmti_bindings::guard_agent_call("Test", 0, || 7), 7);
+
+    jvmti_bindings::set_panic_policy(PanicPolicy::Abort);
+    assert_eq!(jvmti_bindings::panic_policy(), PanicPolicy::Abort);
+    jvmti_bindings::set_panic_policy(PanicPolicy::Swallow);
+}
+
+static CONSTANT_POOL: [u8; 17] = [
+    1, 0, 3, b'F', b'o', b'o', // #1 Utf8 "Foo"
+    7, 0, 1, // #2 Class #1
+    5, 0, 0, 0, 0, 0, 0, 0, // #3 Long (and #4), truncated below
+];
+
+unsafe extern "system" fn small_constant_pool(
+    _env: *mut jvmti::jvmtiEnv,
+    _klass: jni::jclass,
+    count_ptr: *mut jni::jint,
+    byte_count_ptr: *mut jni::jint,
+    bytes_ptr: *mut *mut u8,
+) -> jvmti::jvmtiError {
+    *count_ptr = 3;
+    *byte_count_ptr = 9;
+    *bytes_ptr = CONSTANT_POOL.as_ptr() as *mut u8;
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn truncated_constant_pool(
+    _env: *mut jvmti::jvmtiEnv,
+    _klass: jni::jclass,
+    count_ptr: *mut jni::jint,
+    byte_count_ptr: *mut jni::jint,
+    bytes_ptr: *mut *mut u8,
+) -> jvmti::jvmtiError {
+    *count_ptr = 5;
+    *byte_count_ptr = CONSTANT_POOL.len() as jni::jint;
+    *bytes_ptr = CONSTANT_POOL.as_ptr() as *mut u8;
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn get_constant_pool_parsed_uses_the_returned_entry_count() {
+    let mut stub = common::stub_jvmti(|functions| {
+        functions.GetConstantPool = Some(small_constant_pool);
+        functions.Deallocate = Some(ignore_deallocate);
+    });
+    let jvmti_env = stub.jvmti();
+
+    let pool = jvmti_env.get_constant_pool_parsed(ptr::null_mut()).unwrap();
+    assert_eq!(pool.len(), 3);
+    assert_eq!(pool.get_class_name(2).unwrap(), "Foo");
+    assert_eq!(jvmti_env.get_constant_pool(ptr::null_mut()).unwrap(), &CONSTANT_POOL[..9]);
+
+    stub.configure(|functions| {
+        functions.GetConstantPool = Some(truncated_constant_pool);
+    });
+    let jvmti_env = stub.jvmti();
+    assert_eq!(jvmti_env.get_constant_pool_parsed(ptr::null_mut()).unwrap_err(), jvmti::jvmtiError::INTERNAL);
+}
+
+static SAMPLING_CALLS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+unsafe extern "system" fn record_add_capabilities(_env: *mut jvmti::jvmtiEnv, caps: *const jvmti::jvmtiCapabilities) -> jvmti::jvmtiError {
+    SAMPLING_CALLS.lock().unwrap().push(format!("add {:?}", (*caps).enabled_names()));
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn record_sampling_interval(_env: *mut jvmti::jvmtiEnv, interval: jni::jint) -> jvmti::jvmtiError {
+    SAMPLING_CALLS.lock().unwrap().push(format!("interval {interval}"));
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn record_sampling_mode(_env: *mut jvmti::jvmtiEnv, mode: jni::jint, event_type: u32, _thread: jni::jthread) -> jvmti::jvmtiError {
+    assert_eq!(event_type, jvmti::JVMTI_EVENT_SAMPLED_OBJECT_ALLOC);
+    SAMPLING_CALLS.lock().unwrap().push(format!("mode {mode}"));
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn allocation_sampling_adds_capability_sets_interval_and_enables_event() {
+    let mut stub = common::stub_jvmti(|functions| {
+        functions.GetCapabilities = Some(no_capabilities);
+        functions.AddCapabilities = Some(record_add_capabilities);
+        functions.SetHeapSamplingInterval = Some(record_sampling_interval);
+        functions.SetEventNotificationMode = Some(record_sampling_mode);
+    });
+    let jvmti_env = stub.jvmti();
+
+    assert_eq!(jvmti_env.start_allocation_sampling(256 * 1024), Ok(()));
+    assert_eq!(jvmti_env.stop_allocation_sampling(), Ok(()));
+    assert_eq!(
+        std::mem::take(&mut *SAMPLING_CALLS.lock().unwrap()),
+        [
+            "add [\"can_generate_sampled_object_alloc_events\"]".to_string(),
+            "interval 262144".to_string(),
+            format!("mode {}", jvmti::JVMTI_ENABLE),
+            format!("mode {}", jvmti::JVMTI_DISABLE),
+        ]
+    );
+
+    stub.configure(|functions| {
+        functions.SetHeapSamplingInterval = None;
+    });
+    let jvmti_env = stub.jvmti();
+    assert_eq!(jvmti_env.start_allocation_sampling(0), Err(jvmti::jvmtiError::NOT_AVAILABLE));
+}
+
+unsafe extern "system" fn cpu_time_capable(_env: *mut jvmti::jvmtiEnv, caps: *mut jvmti::jvmtiCapabilities) -> jvmti::jvmtiError {
+    (*caps).set_can_get_thread_cpu_time(true);
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn three_threads(_env: *mut jvmti::jvmtiEnv, count_ptr: *mut jni::jint, threads_ptr: *mut *mut jni::jthread) -> jvmti::jvmtiError {
+    let threads: Box<[jni::jthread]> = Box::new([0x1 as jni::jthread, 0x2 as jni::jthread, 0x3 as jni::jthread]);
+    *count_ptr = 3;
+    *threads_ptr = Box::leak(threads).as_mut_ptr();
+    jvmti::jvmtiError::NONE
+}
+
+// Thread 0x2 terminates between GetAllThreads and GetThreadCpuTime.
+unsafe extern "system" fn thread_cpu_time(_env: *mut jvmti::jvmtiEnv, thread: jni::jthread, nanos_ptr: *mut jni::jlong) -> jvmti::jvmtiError {
+    if thread as usize == 0x2 {
+        return jvmti::jvmtiError::THREAD_NOT_ALIVE;
+    }
+    *nanos_ptr = thread as jni::jlong * 1_000;
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn cpu_times_skips_threads_that_died() {
+    let mut stub = common::stub_jvmti(|functions| {
+        functions.GetCapabilities = Some(no_capabilities);
+    });
+    let jvmti_env = stub.jvmti();
+    assert_eq!(jvmti_env.cpu_times(), Err(jvmti::jvmtiError::MUST_POSSESS_CAPABILITY));
+
+    stub.configure(|functions| {
+        functions.GetCapabilities = Some(cpu_time_capable);
+        functions.GetAllThreads = Some(three_threads);
+        functions.GetThreadCpuTime = Some(thread_cpu_time);
+        functions.Deallocate = Some(ignore_deallocate);
+    });
+    let jvmti_env = stub.jvmti();
+    assert_eq!(jvmti_env.cpu_times(), Ok(vec![(0x1 as jni::jthread, 1_000), (0x3 as jni::jthread, 3_000)]));
+}
+
+unsafe extern "system" fn fixed_time(_env: *mut jvmti::jvmtiEnv, nanos_ptr: *mut jni::jlong) -> jvmti::jvmtiError {
+    *nanos_ptr = 5_000_000_000;
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn elapsed_timer(_env: *mut jvmti::jvmtiEnv, info_ptr: *mut jvmti::jvmtiTimerInfo) -> jvmti::jvmtiError {
+    *info_ptr = jvmti::jvmtiTimerInfo { max_value: -1, may_skip_forward: 1, may_skip_backward: 0, kind: jvmti::JVMTI_TIMER_ELAPSED };
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn timer_info_decodes_and_timestamps_measure_elapsed_time() {
+    let stub = common::stub_jvmti(|functions| {
+        functions.GetTime = Some(fixed_time);
+        functions.GetTimerInfo = Some(elapsed_timer);
+    });
+    let jvmti_env = stub.jvmti();
+
+    let info = jvmti_env.get_timer_info_decoded().unwrap();
+    assert_eq!(info.kind, Some(TimerKind::Elapsed));
+    assert!(info.may_skip_forward && info.is_monotonic());
+    assert_eq!(TimerKind::from_raw(jvmti::JVMTI_TIMER_USER_CPU), Some(TimerKind::UserCpu));
+    assert_eq!(TimerKind::TotalCpu.as_raw(), jvmti::JVMTI_TIMER_TOTAL_CPU);
+    assert_eq!(TimerInfo::from(jvmti::jvmtiTimerInfo { max_value: 0, may_skip_forward: 0, may_skip_backward: 1, kind: 7 }).kind, None);
+
+    assert_eq!(jvmti_env.timestamp(), 5_000_000_000);
+    assert_eq!(jvmti_env.elapsed_since(3_500_000_000), std::time::Duration::from_millis(1_500));
+    assert_eq!(jvmti_env.elapsed_since(6_000_000_000), std::time::Duration::ZERO);
+}
+
+unsafe extern "system" fn tagging_and_redefine(_env: *mut jvmti::jvmtiEnv, caps: *mut jvmti::jvmtiCapabilities) -> jvmti::jvmtiError {
+    (*caps).set_can_tag_objects(true);
+    (*caps).set_can_redefine_classes(true);
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn capabilities_report_lists_potential_owned_and_available() {
+    let mut stub = common::stub_jvmti(|functions| {
+        functions.GetPotentialCapabilities = Some(tagging_and_redefine);
+        functions.GetCapabilities = Some(redefine_capable);
+    });
+    let jvmti_env = stub.jvmti();
+
+    assert_eq!(
+        jvmti_env.capabilities_report(),
+        "potential             owned                 available\n\
+         can_tag_objects       can_redefine_classes  can_tag_objects\n\
+         can_redefine_classes\n"
+    );
+
+    stub.configure(|functions| {
+        functions.GetCapabilities = Some(tagging_and_redefine);
+    });
+    let jvmti_env = stub.jvmti();
+    assert!(jvmti_env.capabilities_report().lines().nth(1).unwrap().ends_with("(none)"));
+}
+
+// bipush 42; ireturn. The method ID selects how much of it GetBytecodes returns.
+static BYTECODES: [u8; 3] = [0x10, 42, 0xAC];
+
+unsafe extern "system" fn method_bytecodes(
+    _env: *mut jvmti::jvmtiEnv,
+    method: jni::jmethodID,
+    count_ptr: *mut jni::jint,
+    bytes_ptr: *mut *mut u8,
+) -> jvmti::jvmtiError {
+    *count_ptr = method as jni::jint;
+    *bytes_ptr = BYTECODES.as_ptr() as *mut u8;
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn get_method_instructions_decodes_bytecodes() {
+    use jvmti_bindings::classfile::bytecode::{Opcode, Operands};
+
+    let stub = common::stub_jvmti(|functions| {
+        functions.GetBytecodes = Some(method_bytecodes);
+        functions.Deallocate = Some(ignore_deallocate);
+    });
+    let jvmti_env = stub.jvmti();
+
+    let instructions = jvmti_env.get_method_instructions(3 as jni::jmethodID).unwrap();
+    assert_eq!(instructions.len(), 2);
+    assert_eq!(instructions[0].opcode, Opcode::Bipush);
+    assert_eq!(instructions[0].operands, Operands::Byte(42));
+    assert_eq!((instructions[1].offset, instructions[1].opcode), (2, Opcode::Ireturn));
+
+    assert_eq!(jvmti_env.get_method_instructions(ptr::null_mut()).unwrap_err(), jvmti::jvmtiError::ABSENT_INFORMATION);
+    assert_eq!(jvmti_env.get_method_instructions(1 as jni::jmethodID).unwrap_err(), jvmti::jvmtiError::INTERNAL);
+}
+
+#[test]
+fn class_file_load_hook_arguments_borrow_safely() {
+    let bytes = [0xCA, 0xFE, 0xBA, 0xBE];
+    unsafe {
+        assert_eq!(jvmti_bindings::class_data_slice(bytes.as_ptr(), 4), &bytes);
+        assert!(jvmti_bindings::class_data_slice(ptr::null(), 4).is_empty());
+        assert!(jvmti_bindings::class_data_slice(bytes.as_ptr(), -1).is_empty());
+        assert_eq!(jvmti_bindings::class_name(b"java/lang/String\0".as_ptr().cast()), Some("java/lang/String"));
+        assert_eq!(jvmti_bindings::class_name(ptr::null()), None);
+    }
+}
+
+#[test]
+fn env_pointers_reject_null_environments_and_tables() {
+    unsafe {
+        assert!(JvmtiEnvPtr::new(ptr::null_mut()).is_none());
+        assert!(Jvmti::try_from_raw(ptr::null_mut()).is_err());
+        let mut without_table = jvmti::jvmtiEnv { functions: ptr::null() };
+        assert!(JvmtiEnvPtr::new(&mut without_table).is_none());
+
+        let stub = common::stub_jvmti(|_| {});
+        let checked = JvmtiEnvPtr::new(stub.raw()).unwrap();
+        assert_eq!(Jvmti::from_env_ptr(checked).raw(), checked.as_ptr());
+
+        assert!(JniEnvPtr::new(ptr::null_mut()).is_none());
+        let mut no_table: jni::JNIEnv = ptr::null();
+        assert!(JniEnvPtr::new(&mut no_table).is_none());
+        assert_eq!(JniEnv::try_from_raw(&mut no_table).err(), Some(JniError::Err));
+        // Only the table pointer is checked, never the table.
+        let mut jni_env: jni::JNIEnv = ptr::NonNull::dangling().as_ptr();
+        assert_eq!(JniEnv::try_from_raw(&mut jni_env).unwrap().raw(), &mut jni_env as *mut jni::JNIEnv);
+    }
+}
+
+unsafe extern "system" fn five_loaded_classes(
+    _env: *mut jvmti::jvmtiEnv,
+    count_ptr: *mut jni::jint,
+    classes_ptr: *mut *mut jni::jclass,
+) -> jvmti::jvmtiError {
+    let classes = Box::leak((1..=5).map(|n| n as jni::jclass).collect::<Vec<_>>().into_boxed_slice());
+    *count_ptr = classes.len() as jni::jint;
+    *classes_ptr = classes.as_mut_ptr();
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn numbered_class_signature(
+    _env: *mut jvmti::jvmtiEnv,
+    klass: jni::jclass,
+    signature_ptr: *mut *mut std::os::raw::c_char,
+    generic_ptr: *mut *mut std::os::raw::c_char,
+) -> jvmti::jvmtiError {
+    let signature: &[u8] = match klass as usize {
+        1 => b"Ljava/lang/String;\0",
+        2 => b"Lcom/example/Foo;\0",
+        3 => b"Lcom/example/deep/Bar;\0",
+        4 => b"Lcom/examples/Baz;\0",
+        _ => return jvmti::jvmtiError::INVALID_CLASS,
+    };
+    *signature_ptr = signature.as_ptr() as *mut std::os::raw::c_char;
+    *generic_ptr = ptr::null_mut();
+    jvmti::jvmtiError::NONE
+}
+
+static DELETED_LOCAL_REFS: std::sync::Mutex<Vec<usize>> = std::sync::Mutex::new(Vec::new());
+
+unsafe extern "system" fn record_delete_local_ref(_env: *mut jni::JNIEnv, obj: jni::jobject) {
+    DELETED_LOCAL_REFS.lock().unwrap().push(obj as usize);
+}
+
+#[test]
+fn loaded_classes_can_be_filtered_by_signature_and_package() {
+    let stub = common::stub_jvmti(|functions| {
+        functions.GetLoadedClasses = Some(five_loaded_classes);
+        functions.GetClassSignature = Some(numbered_class_signature);
+        functions.Deallocate = Some(ignore_deallocate);
+    });
+    let jvmti_env = stub.jvmti();
+    let slots = std::mem::size_of::<jni::JNINativeInterface_>() / std::mem::size_of::<usize>();
+    let placeholder = unreachable_jni_function as unsafe extern "system" fn() as usize;
+    let mut table = vec![placeholder; slots];
+    let functions = table.as_mut_ptr().cast::<jni::JNINativeInterface_>();
+    unsafe { (*functions).DeleteLocalRef = record_delete_local_ref };
+    let mut raw_jni: jni::JNIEnv = functions;
+    let jni_env = unsafe { JniEnv::from_raw(&mut raw_jni) };
+
+    let handles = |classes: Vec<jni::jclass>| classes.into_iter().map(|klass| klass as usize).collect::<Vec<_>>();
+    let jdk = jvmti_env.get_loaded_classes_filtered(&jni_env, |signature| signature.starts_with("Ljava/")).unwrap();
+    assert_eq!(handles(jdk), [1]);
+    // Rejected classes, including the one without a signature, are released.
+    assert_eq!(std::mem::take(&mut *DELETED_LOCAL_REFS.lock().unwrap()), [2, 3, 4, 5]);
+    assert_eq!(handles(jvmti_env.classes_in_package(&jni_env, "com/example").unwrap()), [2, 3]);
+    assert_eq!(std::mem::take(&mut *DELETED_LOCAL_REFS.lock().unwrap()), [1, 4, 5]);
+    assert_eq!(handles(jvmti_env.classes_in_package(&jni_env, "com.example.").unwrap()), [2, 3]);
+    assert_eq!(handles(jvmti_env.get_loaded_classes_filtered(&jni_env, |_| true).unwrap()), [1, 2, 3, 4]);
+}
+
+#[test]
+fn symbolic_frames_and_thread_dumps_print_like_jstack() {
+    let frame = |class: &str, method: &str, location, line| SymbolicFrame {
+        method: ptr::null_mut(),
+        location,
+        class_signature: class.to_string(),
+        method_name: method.to_string(),
+        method_signature: "()V".to_string(),
+        line_number: line,
+    };
+    let dump = ThreadDump {
+        name: "main".to_string(),
+        state: ThreadState(jvmti::JVMTI_JAVA_LANG_THREAD_STATE_TIMED_WAITING | jvmti::JVMTI_THREAD_STATE_SLEEPING),
+        frames: vec![
+            frame("Ljava/lang/Thread;", "sleep0", -1, None),
+            frame("Lcom/example/Foo$Worker;", "run", 12, Some(42)),
+            frame("LMain;", "main", 3, None),
+            frame("<unknown>", "<unknown>", 0, None),
+        ],
+    };
+    assert_eq!(
+        dump.to_string(),
+        "\"main\" TIMED_WAITING\n\
+         \tat java.lang.Thread.sleep0(Native Method)\n\
+         \tat com.example.Foo$Worker.run(Foo.java:42)\n\
+         \tat Main.main(Main.java)\n\
+         \tat <unknown>.<unknown>(Unknown Source)"
+    );
+    assert_eq!(ThreadState(0).java_thread_state(), "NEW");
+    assert_eq!(ThreadState(jvmti::JVMTI_JAVA_LANG_THREAD_STATE_BLOCKED).java_thread_state(), "BLOCKED");
+    assert_eq!(ThreadState(jvmti::JVMTI_THREAD_STATE_RUNNABLE).java_thread_state(), "UNKNOWN");
+}
+
+#[test]
+fn typed_field_access_uses_java_values() {
+    let _ = JniEnv::get_field as fn(&JniEnv, jni::jobject, jni::jfieldID, &str) -> Option<JavaValue>;
+    let _ = JniEnv::set_field as fn(&JniEnv, jni::jobject, jni::jfieldID, JavaValue);
+
+    unsafe {
+        assert_eq!(jni::jvalue::from(JavaValue::Boolean(true)).z, 1);
+        assert_eq!(jni::jvalue::from(JavaValue::Char(0x263A)).c, 0x263A);
+        assert_eq!(jni::jvalue::from(JavaValue::Long(-7)).j, -7);
+        assert_eq!(jni::jvalue::from(JavaValue::Double(0.5)).d, 0.5);
+        assert!(jni::jvalue::from(JavaValue::Object(ptr::null_mut())).l.is_null());
+    }
+}
+
+#[test]
+fn object_identity_and_type_checks_are_wrapped() {
+    let _ = JniEnv::is_same_object as fn(&JniEnv, jni::jobject, jni::jobject) -> bool;
+    let _ = JniEnv::is_instance_of as fn(&JniEnv, jni::jobject, jni::jclass) -> bool;
+    let _ = JniEnv::get_object_class as fn(&JniEnv, jni::jobject) -> jni::jclass;
+    let _ = JniEnv::get_object_class_ref as for<'a> fn(&'a JniEnv, jni::jobject) -> LocalRef<'a>;
+}
+
+#[test]
+fn reference_types_can_be_queried() {
+    let _ = JniEnv::ref_type as fn(&JniEnv, jni::jobject) -> jni::jobjectRefType;
+    let _ = JniEnv::debug_assert_valid_ref as fn(&JniEnv, jni::jobject) -> bool;
+
+    // Null is always accepted without calling into the VM.
+    let jni_env = unsafe { JniEnv::from_raw(ptr::null_mut()) };
+    assert!(jni_env.debug_assert_valid_ref(ptr::null_mut()));
+}
+
+#[test]
+fn callbacks_can_be_limited_to_the_events_an_agent_handles() {
+    let callbacks = get_callbacks_for(&[Event::VmInit, Event::ClassPrepare]);
+    let all = get_default_callbacks();
+    assert_eq!(callbacks.VMInit.map(|f| f as usize), all.VMInit.map(|f| f as usize));
+    assert_eq!(callbacks.ClassPrepare.map(|f| f as usize), all.ClassPrepare.map(|f| f as usize));
+    assert!(callbacks.VMDeath.is_none());
+    assert!(callbacks.SingleStep.is_none());
+
+    for &event in Event::ALL {
+        let one = get_callbacks_for(&[event]);
+        let wired = [one.VMInit.is_some(), one.SampledObjectAlloc.is_some(), one.VirtualThreadEnd.is_some(), one.MethodEntry.is_some()];
+        let expected = [Event::VmInit, Event::SampledObjectAlloc, Event::VirtualThreadEnd, Event::MethodEntry].map(|e| e == event);
+        assert_eq!(wired, expected, "{event:?}");
+    }
+}
+
+static SEARCH_SEGMENTS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+unsafe extern "system" fn record_system_segment(_env: *mut jvmti::jvmtiEnv, segment: *const std::os::raw::c_char) -> jvmti::jvmtiError {
+    SEARCH_SEGMENTS.lock().unwrap().push(std::ffi::CStr::from_ptr(segment).to_string_lossy().into_owned());
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn class_loader_search_paths_are_checked_before_the_vm_sees_them() {
+    let stub = common::stub_jvmti(|functions| {
+        functions.AddToSystemClassLoaderSearch = Some(record_system_segment);
+    });
+    let jvmti_env = stub.jvmti();
+
+    let dir = std::env::temp_dir().join(format!("jvmti-search-path-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let jar = dir.join("agent.jar");
+    std::fs::write(&jar, b"PK").unwrap();
+
+    let missing = dir.join("missing.jar");
+    match jvmti_env.add_to_system_class_loader_search_path(&missing) {
+        Err(SearchPathError::Io(path, err)) => {
+            assert_eq!(path, missing);
+            assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        }
+        other => panic!("{other:?}"),
+    }
+    assert!(SEARCH_SEGMENTS.lock().unwrap().is_empty());
+
+    jvmti_env.add_to_system_class_loader_search_path(&dir.join(".").join("agent.jar")).unwrap();
+    let canonical = std::fs::canonicalize(&jar).unwrap();
+    assert_eq!(*SEARCH_SEGMENTS.lock().unwrap(), [canonical.to_str().unwrap()]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn member_ids_are_typed_and_storable() {
+    fn assert_send_sync<T: Send + Sync + Copy>() {}
+    assert_send_sync::<MethodId>();
+    assert_send_sync::<FieldId>();
+
+    assert!(MethodId::from_raw(ptr::null_mut()).is_none());
+    let method = MethodId::from_raw(0x10 as jni::jmethodID).unwrap();
+    assert_eq!(method.as_raw() as usize, 0x10);
+    assert_eq!(jni::jmethodID::from(method) as usize, 0x10);
+    let field = FieldId::from_raw(0x20 as jni::jfieldID).unwrap();
+    assert_eq!(jni::jfieldID::from(field) as usize, 0x20);
+
+    let _ = JniEnv::method_id as fn(&JniEnv, jni::jclass, &str, &str) -> Option<MethodId>;
+    let _ = JniEnv::static_method_id as fn(&JniEnv, jni::jclass, &str, &str) -> Option<MethodId>;
+    let _ = JniEnv::field_id as fn(&JniEnv, jni::jclass, &str, &str) -> Option<FieldId>;
+    let _ = JniEnv::static_field_id as fn(&JniEnv, jni::jclass, &str, &str) -> Option<FieldId>;
+}
@@ -0,0 +1,211 @@
+//! Name-keyed `ClassFileLoadHook` dispatch.
+//!
+//! Kept in its own test binary because the transformer registry is
+//! process-wide and no agent may be registered.
+
+mod common;
+
+use std::ptr;
+use std::sync::Mutex;
+
+use jvmti_bindings::env::{ClassTransformerRegistry, Event};
+use jvmti_bindings::get_default_callbacks;
+use jvmti_bindings::sys::{jni, jvmti};
+
+static ENABLED: Mutex<Vec<(jni::jint, u32)>> = Mutex::new(Vec::new());
+static CALLBACKS: Mutex<Vec<jvmti::jvmtiEventCallbacks>> = Mutex::new(Vec::new());
+
+unsafe extern "system" fn record_callbacks(
+    _env: *mut jvmti::jvmtiEnv,
+    callbacks: *const jvmti::jvmtiEventCallbacks,
+    _size: jni::jint,
+) -> jvmti::jvmtiError {
+    CALLBACKS.lock().unwrap().push(*callbacks);
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn record_mode(_env: *mut jvmti::jvmtiEnv, mode: jni::jint, event_type: u32, _thread: jni::jthread) -> jvmti::jvmtiError {
+    ENABLED.lock().unwrap().push((mode, event_type));
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn reject_mode(_env: *mut jvmti::jvmtiEnv, _mode: jni::jint, _event_type: u32, _thread: jni::jthread) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::WRONG_PHASE
+}
+
+unsafe extern "system" fn live_phase(_env: *mut jvmti::jvmtiEnv, phase_ptr: *mut jni::jint) -> jvmti::jvmtiError {
+    *phase_ptr = jvmti::JVMTI_PHASE_LIVE;
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn retransform_capable(_env: *mut jvmti::jvmtiEnv, caps: *mut jvmti::jvmtiCapabilities) -> jvmti::jvmtiError {
+    (*caps).set_can_retransform_classes(true);
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn current_thread(_env: *mut jvmti::jvmtiEnv, thread_ptr: *mut jni::jthread) -> jvmti::jvmtiError {
+    *thread_ptr = 0x7 as jni::jthread;
+    jvmti::jvmtiError::NONE
+}
+
+static RETRANSFORMED: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+// Runs the crate's ClassFileLoadHook for `com/example/Foo`, as HotSpot does.
+unsafe extern "system" fn retransform_through_hook(
+    env: *mut jvmti::jvmtiEnv,
+    _class_count: jni::jint,
+    _classes: *const jni::jclass,
+) -> jvmti::jvmtiError {
+    let hook = get_default_callbacks().ClassFileLoadHook.unwrap();
+    let original = [0xCA, 0xFE];
+    let mut new_len: jni::jint = 0;
+    let mut new_data: *mut u8 = ptr::null_mut();
+    hook(env, ptr::null_mut(), 0x10 as jni::jclass, ptr::null_mut(), b"com/example/Foo\0".as_ptr().cast(), ptr::null_mut(),
+         2, original.as_ptr(), &mut new_len, &mut new_data);
+    RETRANSFORMED.lock().unwrap().push(std::slice::from_raw_parts(new_data, new_len as usize).to_vec());
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn leak_allocate(_env: *mut jvmti::jvmtiEnv, size: jni::jlong, mem_ptr: *mut *mut u8) -> jvmti::jvmtiError {
+    *mem_ptr = Box::leak(vec![0u8; size as usize].into_boxed_slice()).as_mut_ptr();
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn ignore_deallocate(_env: *mut jvmti::jvmtiEnv, _mem: *mut u8) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::NONE
+}
+
+fn append(byte: u8) -> impl Fn(&str, &[u8]) -> Option<Vec<u8>> + Send + Sync {
+    move |_, bytes| Some([bytes, &[byte]].concat())
+}
+
+#[test]
+fn patterns_match_internal_names() {
+    let registry = ClassTransformerRegistry::new()
+        .register("com/example/*Handler", |_, _| None)
+        .register("org.acme.**", |_, _| None)
+        .register("java/lang/Strin?", |_, _| None);
+    assert_eq!(registry.len(), 3);
+
+    assert!(registry.matches("com/example/OrderHandler"));
+    assert!(!registry.matches("com/example/sub/OrderHandler"));
+    assert!(registry.matches("org/acme/deep/Nested$Inner"));
+    assert!(!registry.matches("org/acmeX/Foo"));
+    assert!(registry.matches("java/lang/String"));
+    assert!(!registry.matches("java/lang/Strings"));
+    assert!(!ClassTransformerRegistry::new().matches("java/lang/String"));
+}
+
+// The matching rules, spelled out recursively.
+fn reference_match(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern {
+        [] => name.is_empty(),
+        [b'*', b'*', rest @ ..] => (0..=name.len()).any(|skip| reference_match(rest, &name[skip..])),
+        [b'*', rest @ ..] => {
+            let segment = name.iter().position(|&b| b == b'/').unwrap_or(name.len());
+            (0..=segment).any(|skip| reference_match(rest, &name[skip..]))
+        }
+        [b'?', rest @ ..] => matches!(name, [first, tail @ ..] if *first != b'/' && reference_match(rest, tail)),
+        [expected, rest @ ..] => matches!(name, [first, tail @ ..] if first == expected && reference_match(rest, tail)),
+    }
+}
+
+fn strings_over(alphabet: &[u8], max_len: usize) -> Vec<Vec<u8>> {
+    let mut all = vec![Vec::new()];
+    let mut last = vec![Vec::new()];
+    for _ in 0..max_len {
+        last = last
+            .iter()
+            .flat_map(|prefix| alphabet.iter().map(move |&b| [prefix.as_slice(), &[b]].concat()))
+            .collect();
+        all.extend(last.iter().cloned());
+    }
+    all
+}
+
+#[test]
+fn patterns_match_like_the_reference_rules() {
+    let names = strings_over(b"ab/", 5);
+    for pattern in strings_over(b"a/*?", 5) {
+        let text = String::from_utf8(pattern.clone()).unwrap();
+        let registry = ClassTransformerRegistry::new().register(&text, |_, _| None);
+        for name in &names {
+            let name_text = std::str::from_utf8(name).unwrap();
+            assert_eq!(registry.matches(name_text), reference_match(&pattern, name), "{text:?} vs {name_text:?}");
+        }
+    }
+
+    // Long names with many stars stay cheap.
+    let name = format!("{}/{}", "a".repeat(5_000), "a".repeat(5_000));
+    let registry = ClassTransformerRegistry::new().register("*a*a*a*a*a*a*a*b/**a*a*a*a*a*a*a*b", |_, _| None);
+    assert!(!registry.matches(&name));
+}
+
+#[test]
+fn matching_transformers_chain_and_are_installed_on_the_hook() {
+    let registry = ClassTransformerRegistry::new()
+        .register("com/example/**", append(1))
+        .register("com/example/Skip", |_, _| None)
+        .register("com/example/*", append(2))
+        .register("com/other/*", append(9));
+    assert_eq!(registry.transform("com/example/Foo", &[0]), Some(vec![0, 1, 2]));
+    assert_eq!(registry.transform("com/unrelated/Foo", &[0]), None);
+
+    let mut stub = common::stub_jvmti(|functions| {
+        functions.SetEventCallbacks = Some(record_callbacks);
+        functions.SetEventNotificationMode = Some(reject_mode);
+        functions.Allocate = Some(leak_allocate);
+        functions.Deallocate = Some(ignore_deallocate);
+    });
+    let jvmti_env = stub.jvmti();
+    jvmti_env.set_agent_callbacks_for(&[Event::VmInit]).unwrap();
+
+    // A failed install keeps nothing, so it can be retried.
+    assert_eq!(jvmti_env.install_transformers(ClassTransformerRegistry::new()), Err(jvmti::jvmtiError::WRONG_PHASE));
+
+    stub.configure(|functions| {
+        functions.SetEventNotificationMode = Some(record_mode);
+        functions.GetPhase = Some(live_phase);
+        functions.GetCapabilities = Some(retransform_capable);
+        functions.GetCurrentThread = Some(current_thread);
+        functions.RetransformClasses = Some(retransform_through_hook);
+    });
+    let jvmti_env = stub.jvmti();
+    jvmti_env.set_agent_callbacks_for(&[Event::VmInit]).unwrap();
+    assert_eq!(jvmti_env.install_transformers(registry), Ok(()));
+    assert_eq!(*ENABLED.lock().unwrap(), [(1, jvmti::JVMTI_EVENT_CLASS_FILE_LOAD_HOOK)]);
+    assert_eq!(jvmti_env.install_transformers(ClassTransformerRegistry::new()), Err(jvmti::jvmtiError::DUPLICATE));
+
+    // The hook was merged into the callbacks installed before.
+    let installed = *CALLBACKS.lock().unwrap().last().unwrap();
+    assert!(installed.VMInit.is_some());
+    assert!(installed.ClassFileLoadHook.is_some());
+
+    // Transformers run on `retransform_with` output too.
+    assert_eq!(jvmti_env.retransform_with(&[0x10 as jni::jclass], |_, bytes| Some([bytes, &[7]].concat())), Ok(()));
+    assert_eq!(*RETRANSFORMED.lock().unwrap(), [vec![0xCA, 0xFE, 7, 1, 2]]);
+
+    let hook = get_default_callbacks().ClassFileLoadHook.unwrap();
+    let load = |name: &[u8]| {
+        let original = [0xCA, 0xFE];
+        let mut new_len: jni::jint = 0;
+        let mut new_data: *mut u8 = ptr::null_mut();
+        unsafe {
+            hook(stub.raw(), ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), name.as_ptr().cast(), ptr::null_mut(),
+                 2, original.as_ptr(), &mut new_len, &mut new_data);
+        }
+        if new_data.is_null() { None } else { Some(unsafe { std::slice::from_raw_parts(new_data, new_len as usize) }.to_vec()) }
+    };
+    assert_eq!(load(b"com/example/Foo\0"), Some(vec![0xCA, 0xFE, 1, 2]));
+    assert_eq!(load(b"com/example/sub/Foo\0"), Some(vec![0xCA, 0xFE, 1]));
+    assert_eq!(load(b"java/lang/String\0"), None);
+
+    // Uninstalling stops dispatch and frees the slot for a new registry.
+    assert_eq!(jvmti_env.uninstall_transformers(), Ok(()));
+    assert_eq!(ENABLED.lock().unwrap().last(), Some(&(0, jvmti::JVMTI_EVENT_CLASS_FILE_LOAD_HOOK)));
+    assert_eq!(load(b"com/example/Foo\0"), None);
+    assert_eq!(jvmti_env.uninstall_transformers(), Err(jvmti::jvmtiError::NOT_FOUND));
+    let registry = ClassTransformerRegistry::new().register("java/lang/String", append(3));
+    assert_eq!(jvmti_env.install_transformers(registry), Ok(()));
+    assert_eq!(load(b"java/lang/String\0"), Some(vec![0xCA, 0xFE, 3]));
+}
@@ -1,4 +1,4 @@
-use jvmti_bindings::classfile::{AttributeInfo, ClassFile};
+use jvmti_bindings::classfile::{AttributeInfo, ClassFile, ClassFileError, CpInfo};
 
 struct CpBuilder {
     entries: Vec<Vec<u8>>,
@@ -434,3 +434,475 @@ fn parses_all_attributes() {
     assert!(code_attr.attributes.iter().any(|a| matches!(a, AttributeInfo::LocalVariableTypeTable { .. })));
     assert!(code_attr.attributes.iter().any(|a| matches!(a, AttributeInfo::StackMapTable { .. })));
 }
+
+#[test]
+fn round_trips_unmodified_class_bytes() {
+    let bytes = build_test_class();
+    let classfile = ClassFile::parse(&bytes).expect("parse class file");
+    assert_eq!(classfile.to_bytes().expect("serialize class file"), bytes);
+}
+
+#[test]
+fn round_trips_modified_utf8_constants() {
+    let mut cp = CpBuilder::new();
+    // "a\0b" with the NUL as 0xC0 0x80, then U+1F600 as a surrogate pair.
+    let mut entry = vec![1, 0, 10];
+    entry.extend_from_slice(&[b'a', 0xC0, 0x80, b'b', 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]);
+    let text = cp.push(entry);
+    let utf_object = cp.utf8("java/lang/Object");
+    let class_object = cp.class(utf_object);
+
+    let mut bytes = Vec::new();
+    u4(&mut bytes, 0xCAFEBABE);
+    u2(&mut bytes, 0);
+    u2(&mut bytes, 52);
+    u2(&mut bytes, cp.entries.len() as u16 + 1);
+    for entry in &cp.entries {
+        bytes.extend_from_slice(entry);
+    }
+    u2(&mut bytes, 0x0021);
+    u2(&mut bytes, class_object);
+    u2(&mut bytes, 0);
+    u2(&mut bytes, 0); // interfaces
+    u2(&mut bytes, 0); // fields
+    u2(&mut bytes, 0); // methods
+    u2(&mut bytes, 0); // attributes
+
+    let mut classfile = ClassFile::parse(&bytes).expect("parse class file");
+    assert!(matches!(classfile.constant_pool.get(text), Ok(CpInfo::Utf8(s)) if s == "a\0b\u{1F600}"));
+    assert_eq!(classfile.to_bytes().expect("serialize class file"), bytes);
+
+    classfile.attributes.push(AttributeInfo::Deprecated);
+    assert!(matches!(classfile.to_bytes(), Err(ClassFileError::Unencodable(_))));
+}
+
+#[test]
+fn resolves_constant_pool_references() {
+    let bytes = build_test_class();
+    let classfile = ClassFile::parse(&bytes).expect("parse class file");
+    let cp = &classfile.constant_pool;
+
+    assert_eq!(classfile.this_class_name().unwrap(), "Test");
+    assert_eq!(classfile.super_class_name().unwrap(), Some("java/lang/Object"));
+    assert_eq!(classfile.fields[0].name(cp).unwrap(), "value");
+    assert_eq!(classfile.fields[0].descriptor(cp).unwrap(), "I");
+
+    let (method_ref, _) = cp
+        .iter()
+        .find(|(_, entry)| matches!(entry, CpInfo::Methodref { .. }))
+        .expect("methodref");
+    assert_eq!(cp.get_member_ref(method_ref).unwrap(), ("java/lang/Object", "<init>", "()V"));
+
+    // Index 0, out-of-range indices, and the wrong entry kind are rejected.
+    assert!(matches!(cp.get_class_name(0), Err(ClassFileError::InvalidConstantPoolIndex(0))));
+    assert!(cp.get_utf8(cp.len() as u16).is_err());
+    assert!(cp.get_name_and_type(classfile.this_class).is_err());
+    assert_eq!(cp.iter().count(), cp.len() - 1);
+}
+
+#[test]
+fn long_constants_occupy_two_slots() {
+    let mut cp = CpBuilder::new();
+    let long = cp.push(vec![5, 0, 0, 0, 1, 0, 0, 0, 2]);
+    cp.entries.push(Vec::new()); // unusable second slot
+    let utf_object = cp.utf8("java/lang/Object");
+    let class_object = cp.class(utf_object);
+
+    let mut bytes = Vec::new();
+    u4(&mut bytes, 0xCAFEBABE);
+    u2(&mut bytes, 0);
+    u2(&mut bytes, 52);
+    u2(&mut bytes, cp.entries.len() as u16 + 1);
+    for entry in &cp.entries {
+        bytes.extend_from_slice(entry);
+    }
+    u2(&mut bytes, 0x0021);
+    u2(&mut bytes, class_object);
+    u2(&mut bytes, 0);
+    for _ in 0..4 {
+        u2(&mut bytes, 0);
+    }
+
+    let classfile = ClassFile::parse(&bytes).expect("parse class file");
+    let cp = &classfile.constant_pool;
+    assert!(matches!(cp.get(long), Ok(CpInfo::Long(v)) if *v == (1 << 32) | 2));
+    assert!(cp.get(long + 1).is_err());
+    assert_eq!(cp.get_class_name(class_object).unwrap(), "java/lang/Object");
+    assert_eq!(classfile.this_class_name().unwrap(), "java/lang/Object");
+    assert_eq!(classfile.super_class_name().unwrap(), None);
+}
+
+#[test]
+fn exposes_method_code_attribute() {
+    let bytes = build_test_class();
+    let mut classfile = ClassFile::parse(&bytes).expect("parse class file");
+
+    let code = classfile.methods[0].code().expect("code attribute");
+    assert!(!code.code.is_empty());
+    assert!(code.attributes.iter().any(|a| matches!(a, AttributeInfo::LineNumberTable { .. })));
+
+    let original_max_stack = code.max_stack;
+    classfile.methods[0].code_mut().expect("code attribute").max_stack = original_max_stack + 1;
+    let reparsed = ClassFile::parse(&classfile.to_bytes().expect("serialize")).expect("reparse");
+    assert_eq!(reparsed.methods[0].code().unwrap().max_stack, original_max_stack + 1);
+}
+
+#[test]
+fn decodes_variable_length_instructions() {
+    use jvmti_bindings::classfile::bytecode::{InstructionIterator, Opcode, Operands};
+
+    let code = [
+        0x10, 0xFE, // 0: bipush -2
+        0xAA, 0x00, // 2: tableswitch, padded to offset 4
+        0x00, 0x00, 0x00, 0x20, // default
+        0x00, 0x00, 0x00, 0x01, // low
+        0x00, 0x00, 0x00, 0x02, // high
+        0x00, 0x00, 0x00, 0x10, // 1
+        0x00, 0x00, 0x00, 0x18, // 2
+        0xAB, 0x00, 0x00, 0x00, // 24: lookupswitch, padded to offset 28
+        0x00, 0x00, 0x00, 0x08, // default
+        0x00, 0x00, 0x00, 0x01, // npairs
+        0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x0C, // -1 => +12
+        0xC4, 0x15, 0x01, 0x00, // 44: wide iload 256
+        0xC4, 0x84, 0x00, 0x05, 0xFF, 0x00, // 48: wide iinc 5, -256
+        0xB9, 0x00, 0x07, 0x02, 0x00, // 54: invokeinterface #7, 2
+        0xBA, 0x00, 0x09, 0x00, 0x00, // 59: invokedynamic #9
+        0x13, 0x01, 0x00, // 64: ldc_w #256
+        0xC8, 0xFF, 0xFF, 0xFF, 0xF0, // 67: goto_w -16
+        0xB1, // 72: return
+    ];
+
+    let instructions: Vec<_> = InstructionIterator::new(&code).collect::<Result<_, _>>().expect("decode");
+    let summary: Vec<_> = instructions.iter().map(|insn| (insn.offset, insn.opcode)).collect();
+    assert_eq!(
+        summary,
+        vec![
+            (0, Opcode::Bipush),
+            (2, Opcode::Tableswitch),
+            (24, Opcode::Lookupswitch),
+            (44, Opcode::Iload),
+            (48, Opcode::Iinc),
+            (54, Opcode::Invokeinterface),
+            (59, Opcode::Invokedynamic),
+            (64, Opcode::LdcW),
+            (67, Opcode::GotoW),
+            (72, Opcode::Return),
+        ]
+    );
+
+    assert_eq!(instructions[0].operands, Operands::Byte(-2));
+    assert_eq!(
+        instructions[1].operands,
+        Operands::TableSwitch { default: 0x20, low: 1, high: 2, offsets: vec![0x10, 0x18] }
+    );
+    assert_eq!(instructions[1].branch_targets(), vec![34, 18, 26]);
+    assert_eq!(instructions[2].operands, Operands::LookupSwitch { default: 8, pairs: vec![(-1, 12)] });
+    assert!(instructions[3].wide);
+    assert_eq!(instructions[3].operands, Operands::Local(256));
+    assert_eq!(instructions[4].operands, Operands::Iinc { index: 5, delta: -256 });
+    assert_eq!(instructions[5].operands, Operands::InvokeInterface { index: 7, count: 2 });
+    assert_eq!(instructions[6].operands, Operands::InvokeDynamic { index: 9 });
+    assert_eq!(instructions[7].operands, Operands::ConstantPool(256));
+    assert_eq!(instructions[8].branch_targets(), vec![51]);
+    assert_eq!(instructions[9].next_offset(), code.len() as u32);
+
+    assert_eq!(instructions[5].to_string(), "   54: invokeinterface #7, 2");
+    assert_eq!(instructions[3].to_string(), "   44: wide iload 256");
+    assert_eq!(Opcode::from_u8(0xB6), Some(Opcode::Invokevirtual));
+    assert_eq!(Opcode::from_u8(0xCB), None);
+    assert_eq!(Opcode::Invokevirtual.mnemonic(), "invokevirtual");
+
+    let truncated = [0x11, 0x00];
+    let mut iter = InstructionIterator::new(&truncated);
+    assert!(matches!(iter.next(), Some(Err(ClassFileError::UnexpectedEof))));
+    assert!(iter.next().is_none());
+    assert!(matches!(
+        InstructionIterator::new(&[0xCB]).next(),
+        Some(Err(ClassFileError::InvalidOpcode(0xCB)))
+    ));
+}
+
+#[test]
+fn iterates_method_code_instructions() {
+    let bytes = build_test_class();
+    let classfile = ClassFile::parse(&bytes).expect("parse class file");
+    let code = classfile.methods[0].code().expect("code attribute");
+    let instructions = code.instructions().collect::<Result<Vec<_>, _>>().expect("decode");
+    let total: u32 = instructions.iter().map(|insn| insn.length).sum();
+    assert_eq!(total as usize, code.code.len());
+}
+
+#[test]
+fn decodes_access_flags() {
+    use jvmti_bindings::classfile::AccessFlags;
+
+    let method = AccessFlags::method(0x0001 | 0x0008 | 0x0010 | 0x0020 | 0x1000);
+    assert!(method.is_public() && method.is_static() && method.is_final());
+    assert!(method.is_synchronized() && method.is_synthetic());
+    assert!(!method.is_abstract() && !method.is_native());
+    assert_eq!(method.to_string(), "public static final synchronized");
+
+    assert_eq!(AccessFlags::field(0x0002 | 0x0040 | 0x0080).to_string(), "private transient volatile");
+    assert_eq!(AccessFlags::class(0x0001 | 0x0020 | 0x0010).to_string(), "public final");
+    assert_eq!(AccessFlags::class(0x0001 | 0x0200 | 0x0400).to_string(), "public");
+    assert_eq!(AccessFlags::method(0).to_string(), "");
+
+    let bytes = build_test_class();
+    let classfile = ClassFile::parse(&bytes).expect("parse class file");
+    assert_eq!(classfile.flags().bits(), classfile.access_flags);
+    assert_eq!(classfile.methods[0].flags().bits(), classfile.methods[0].access_flags);
+}
+
+#[test]
+fn parses_type_descriptors() {
+    use jvmti_bindings::classfile::descriptor::{
+        argument_slots, parse_field_descriptor, parse_method_descriptor, signature_to_java_name, JavaType,
+    };
+
+    let string = JavaType::Object("java/lang/String".to_string());
+    assert_eq!(parse_field_descriptor("Ljava/lang/String;").unwrap(), string);
+    assert_eq!(parse_field_descriptor("[[D").unwrap().to_source_string(), "double[][]");
+    assert_eq!(parse_field_descriptor("[Ljava/util/Map$Entry;").unwrap().to_string(), "java.util.Map$Entry[]");
+
+    let (params, ret) = parse_method_descriptor("(Ljava/lang/String;[IJD)V").unwrap();
+    assert_eq!(
+        params,
+        vec![
+            string.clone(),
+            JavaType::Array(Box::new(JavaType::Int)),
+            JavaType::Long,
+            JavaType::Double,
+        ]
+    );
+    assert_eq!(ret, JavaType::Void);
+    assert_eq!(params[1].to_descriptor(), "[I");
+
+    assert_eq!(argument_slots("(Ljava/lang/String;[IJD)V", true).unwrap(), 6);
+    assert_eq!(argument_slots("()V", false).unwrap(), 1);
+
+    assert_eq!(signature_to_java_name("Lcom/example/Foo;"), "com.example.Foo");
+    assert_eq!(signature_to_java_name("[I"), "int[]");
+    assert_eq!(signature_to_java_name("[[Ljava/lang/String;"), "java.lang.String[][]");
+    assert_eq!(signature_to_java_name("J"), "long");
+    assert_eq!(signature_to_java_name("not a signature"), "not a signature");
+
+    for bad in ["", "V", "L;", "Ljava/lang/String", "II", "[", "(I", "(V)V", "()", "()VV", "I)V"] {
+        let parsed = if bad.starts_with('(') || bad.ends_with('V') {
+            parse_method_descriptor(bad).map(|_| ())
+        } else {
+            parse_field_descriptor(bad).map(|_| ())
+        };
+        assert!(matches!(parsed, Err(ClassFileError::InvalidDescriptor(_))), "{bad:?} should be rejected");
+    }
+}
+
+
+#[test]
+fn adds_constant_pool_entries_once() {
+    let bytes = build_test_class();
+    let mut classfile = ClassFile::parse(&bytes).expect("parse class file");
+    let cp = &mut classfile.constant_pool;
+    let original_len = cp.len();
+
+    let code_index = cp.add_utf8("Code").unwrap();
+    assert_eq!(cp.get_utf8(code_index).unwrap(), "Code");
+    assert_eq!(cp.len(), original_len);
+    let methodref = cp.add_methodref("com/example/Profiler", "enter", "()V").unwrap();
+    assert_eq!(cp.add_methodref("com/example/Profiler", "enter", "()V").unwrap(), methodref);
+    assert_eq!(cp.get_member_ref(methodref).unwrap(), ("com/example/Profiler", "enter", "()V"));
+
+    let long = cp.add(CpInfo::Long(1 << 40)).unwrap();
+    assert_eq!(cp.add_string("after").unwrap(), long + 3);
+    assert_eq!(cp.get_string(long + 3).unwrap(), "after");
+    assert!(cp.len() > original_len);
+
+    let reparsed = ClassFile::parse(&classfile.to_bytes().expect("serialize")).expect("reparse");
+    assert_eq!(reparsed.constant_pool.get_member_ref(methodref).unwrap().1, "enter");
+}
+
+#[test]
+fn injects_static_call_at_method_entry() {
+    use jvmti_bindings::classfile::bytecode::{InstructionIterator, Opcode};
+    use jvmti_bindings::classfile::instrument::{inject_method_entry_call_with_args, EntryArg};
+    use jvmti_bindings::classfile::StackMapFrame;
+
+    let bytes = build_test_class();
+    let mut classfile = ClassFile::parse(&bytes).expect("parse class file");
+    let ClassFile { constant_pool, methods, .. } = &mut classfile;
+
+    let bad_args = [EntryArg::Long(1)];
+    let err = inject_method_entry_call_with_args(&mut methods[0], constant_pool, "P", "enter", "(I)V", &bad_args);
+    assert!(matches!(err, Err(ClassFileError::InvalidDescriptor(_))));
+
+    let args = [EntryArg::Int(300), EntryArg::String("Test.<init>".to_string())];
+    let injected =
+        inject_method_entry_call_with_args(&mut methods[0], constant_pool, "com/example/Profiler", "enter", "(ILjava/lang/String;)I", &args);
+    assert!(injected.unwrap());
+
+    let reparsed = ClassFile::parse(&classfile.to_bytes().expect("serialize")).expect("reparse");
+    let cp = &reparsed.constant_pool;
+    let code = reparsed.methods[0].code().unwrap();
+    let opcodes: Vec<Opcode> = InstructionIterator::new(&code.code).map(|insn| insn.unwrap().opcode).collect();
+    // sipush(3) + ldc(2) + invokestatic(3) + pop(1), padded to 12, then the original `return`.
+    assert_eq!(
+        opcodes,
+        [Opcode::Sipush, Opcode::Ldc, Opcode::Invokestatic, Opcode::Pop, Opcode::Nop, Opcode::Nop, Opcode::Nop, Opcode::Return]
+    );
+    let method_index = u16::from_be_bytes([code.code[6], code.code[7]]);
+    assert_eq!(cp.get_member_ref(method_index).unwrap(), ("com/example/Profiler", "enter", "(ILjava/lang/String;)I"));
+    assert_eq!(cp.get_string(code.code[4] as u16).unwrap(), "Test.<init>");
+    assert_eq!(code.max_stack, 2);
+
+    for attr in &code.attributes {
+        match attr {
+            AttributeInfo::LineNumberTable { entries } => assert_eq!(entries[0].start_pc, 12),
+            AttributeInfo::LocalVariableTable { entries } => {
+                assert_eq!((entries[0].start_pc, entries[0].length), (0, 13));
+            }
+            AttributeInfo::StackMapTable(table) => {
+                assert!(matches!(table.entries[0], StackMapFrame::Same { offset_delta: 12 }));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn recomputes_max_stack_and_locals() {
+    use jvmti_bindings::classfile::ExceptionTableEntry;
+
+    let bytes = build_test_class();
+    let mut classfile = ClassFile::parse(&bytes).expect("parse class file");
+    let ClassFile { constant_pool, methods, .. } = &mut classfile;
+    let method = constant_pool.add_methodref("Test", "next", "(J)J").unwrap().to_be_bytes();
+    let code = methods[0].code_mut().unwrap();
+
+    // static void m(long, int): switch (i) { case 0: next(l); } with a catch-all handler.
+    code.code = vec![
+        0x1C, // 0: iload_2
+        0xAA, 0x00, 0x00, // 1: tableswitch, padded to offset 4
+        0x00, 0x00, 0x00, 0x18, // default -> 25
+        0x00, 0x00, 0x00, 0x00, // low
+        0x00, 0x00, 0x00, 0x00, // high
+        0x00, 0x00, 0x00, 0x13, // 0 -> 20
+        0x1E, // 20: lload_0
+        0xB8, method[0], method[1], // 21: invokestatic next(J)J
+        0x58, // 24: pop2
+        0xB1, // 25: return
+        0x3A, 0x05, // 26: astore 5
+        0xB1, // 28: return
+    ];
+    code.exception_table = vec![ExceptionTableEntry { start_pc: 20, end_pc: 25, handler_pc: 26, catch_type: 0 }];
+    code.max_stack = 0;
+    code.max_locals = 0;
+    code.recompute_maxes(constant_pool, true, "(JI)V").unwrap();
+    assert_eq!((code.max_stack, code.max_locals), (2, 6));
+
+    code.recompute_maxes(constant_pool, false, "(JID)V").unwrap();
+    assert_eq!((code.max_stack, code.max_locals), (2, 6));
+    code.recompute_maxes(constant_pool, false, "(JIDD)V").unwrap();
+    assert_eq!(code.max_locals, 8);
+
+    code.code = vec![0x57, 0xB1]; // pop; return
+    code.exception_table.clear();
+    assert!(matches!(code.recompute_maxes(constant_pool, true, "()V"), Err(ClassFileError::InvalidAttribute(_))));
+
+    code.code = vec![0x04]; // iconst_1, then falls off the end
+    assert!(matches!(code.recompute_maxes(constant_pool, true, "()V"), Err(ClassFileError::InvalidAttribute(_))));
+}
+
+#[test]
+fn resolves_line_numbers_and_local_variables() {
+    use jvmti_bindings::classfile::LocalVariable;
+
+    let bytes = build_test_class();
+    let classfile = ClassFile::parse(&bytes).expect("parse class file");
+    let code = classfile.methods[0].code().unwrap();
+
+    let lines = code.line_numbers();
+    assert_eq!(lines.len(), 1);
+    assert_eq!((lines[0].start_pc, lines[0].line_number), (0, 1));
+    assert_eq!(code.line_number_at(0), Some(1));
+
+    let locals = code.local_variables(&classfile.constant_pool).unwrap();
+    assert_eq!(
+        locals,
+        vec![LocalVariable {
+            start_pc: 0,
+            length: 1,
+            name: "arg0".to_string(),
+            descriptor: "I".to_string(),
+            signature: Some("Ljava/lang/Object;".to_string()),
+            index: 0,
+        }]
+    );
+    assert!(locals[0].is_live_at(0));
+    assert!(!locals[0].is_live_at(1));
+}
+
+#[test]
+fn resolves_runtime_visible_annotations() {
+    use jvmti_bindings::classfile::{Annotation, AnnotationValue, ElementValue, ElementValuePair};
+
+    let bytes = build_test_class();
+    let mut classfile = ClassFile::parse(&bytes).expect("parse class file");
+
+    let class_annotations = classfile.annotations().unwrap();
+    assert_eq!(class_annotations.len(), 1);
+    assert_eq!(class_annotations[0].type_name(), "MyAnno");
+    assert!(class_annotations[0].elements.is_empty());
+    assert!(classfile.has_annotation("LMyAnno;").unwrap());
+    assert!(!classfile.has_annotation("LOther;").unwrap());
+
+    let ClassFile { constant_pool: cp, methods, .. } = &mut classfile;
+    assert_eq!(methods[0].parameter_annotations(cp).unwrap(), vec![Vec::new()]);
+    assert!(methods[0].annotations(cp).unwrap().is_empty());
+
+    let mut utf8 = |value: &str| cp.add_utf8(value).unwrap();
+    let names = [utf8("value"), utf8("limit"), utf8("unit"), utf8("flags")];
+    let (timed_type, db, time_unit, millis, string) =
+        (utf8("Lcom/example/Timed;"), utf8("db"), utf8("Ljava/util/concurrent/TimeUnit;"), utf8("MILLISECONDS"), utf8("Ljava/lang/String;"));
+    let (limit, one) = (cp.add(CpInfo::Long(5_000)).unwrap(), cp.add(CpInfo::Integer(1)).unwrap());
+    let values = [
+        ElementValue::Const { tag: b's', const_value_index: db },
+        ElementValue::Const { tag: b'J', const_value_index: limit },
+        ElementValue::EnumConst { type_name_index: time_unit, const_name_index: millis },
+        ElementValue::ArrayValue(vec![
+            ElementValue::Const { tag: b'Z', const_value_index: one },
+            ElementValue::ClassInfo { class_info_index: string },
+        ]),
+    ];
+    let timed = Annotation {
+        type_index: timed_type,
+        element_value_pairs: names
+            .into_iter()
+            .zip(values)
+            .map(|(element_name_index, value)| ElementValuePair { element_name_index, value })
+            .collect(),
+    };
+    methods[0].attributes.push(AttributeInfo::RuntimeVisibleAnnotations { annotations: vec![timed] });
+
+    let reparsed = ClassFile::parse(&classfile.to_bytes().expect("serialize")).expect("reparse");
+    let cp = &reparsed.constant_pool;
+    let method = &reparsed.methods[0];
+    assert!(method.has_annotation(cp, "Lcom/example/Timed;").unwrap());
+    let annotations = method.annotations(cp).unwrap();
+    assert_eq!(annotations[0].type_name(), "com.example.Timed");
+    assert_eq!(annotations[0].get("value"), Some(&AnnotationValue::String("db".to_string())));
+    assert_eq!(annotations[0].get("limit"), Some(&AnnotationValue::Long(5_000)));
+    assert_eq!(
+        annotations[0].get("unit"),
+        Some(&AnnotationValue::Enum {
+            type_descriptor: "Ljava/util/concurrent/TimeUnit;".to_string(),
+            const_name: "MILLISECONDS".to_string(),
+        })
+    );
+    assert_eq!(
+        annotations[0].get("flags"),
+        Some(&AnnotationValue::Array(vec![
+            AnnotationValue::Boolean(true),
+            AnnotationValue::Class("Ljava/lang/String;".to_string()),
+        ]))
+    );
+    assert_eq!(annotations[0].get("missing"), None);
+}
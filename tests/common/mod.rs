@@ -0,0 +1,49 @@
+//! Stub JVMTI environments shared by the test binaries.
+
+#![allow(dead_code)]
+
+use jvmti_bindings::env::Jvmti;
+use jvmti_bindings::sys::jvmti;
+
+/// A fake `jvmtiEnv` over a function table that starts out zeroed, so every
+/// slot a test does not set is `None`.
+pub struct StubJvmti {
+    functions: Box<jvmti::jvmtiInterface_1_>,
+    env: *mut jvmti::jvmtiEnv,
+}
+
+/// Builds a stub environment, letting `configure` fill in the slots the test calls.
+pub fn stub_jvmti(configure: impl FnOnce(&mut jvmti::jvmtiInterface_1_)) -> StubJvmti {
+    let mut functions: Box<jvmti::jvmtiInterface_1_> = Box::new(unsafe { std::mem::zeroed() });
+    configure(&mut functions);
+    let env = Box::into_raw(Box::new(jvmti::jvmtiEnv { functions: &*functions }));
+    StubJvmti { functions, env }
+}
+
+impl StubJvmti {
+    /// Changes slots of the table; wrappers taken before keep the old view,
+    /// so take a new one with [`jvmti`](Self::jvmti).
+    pub fn configure(&mut self, configure: impl FnOnce(&mut jvmti::jvmtiInterface_1_)) {
+        configure(&mut self.functions);
+        // SAFETY: `env` came from `Box::into_raw` and is only freed on drop.
+        unsafe { (*self.env).functions = &*self.functions };
+    }
+
+    /// A wrapper over the stub; it must not outlive `self`.
+    pub fn jvmti(&self) -> Jvmti {
+        // SAFETY: `env` points to a live `jvmtiEnv` whose table outlives it.
+        unsafe { Jvmti::from_raw(self.env) }
+    }
+
+    /// The raw `jvmtiEnv*`, for calling trampolines directly.
+    pub fn raw(&self) -> *mut jvmti::jvmtiEnv {
+        self.env
+    }
+}
+
+impl Drop for StubJvmti {
+    fn drop(&mut self) {
+        // SAFETY: `env` came from `Box::into_raw` in `stub_jvmti`.
+        drop(unsafe { Box::from_raw(self.env) });
+    }
+}
@@ -0,0 +1,119 @@
+//! Extension event dispatch through a stubbed `jvmtiEnv` and the global agent.
+//!
+//! Kept in its own test binary because it installs the process-wide agent.
+
+mod common;
+
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use jvmti_bindings::env::ExtensionArg;
+use jvmti_bindings::sys::{jni, jvmti};
+use jvmti_bindings::Agent;
+
+const SAMPLE_EVENT: jni::jint = 49;
+
+static CALLBACK: AtomicUsize = AtomicUsize::new(usize::MAX);
+static DELIVERED: Mutex<Vec<(jni::jint, jni::jint, bool, usize)>> = Mutex::new(Vec::new());
+
+struct Recorder;
+
+impl Agent for Recorder {
+    fn on_load(&self, _vm: *mut jni::JavaVM, _options: &str) -> jni::jint {
+        jni::JNI_OK
+    }
+
+    fn extension_event(&self, index: jni::jint, params: &[ExtensionArg]) {
+        if let [ExtensionArg::Ptr(jni_env), ExtensionArg::Int(count), ExtensionArg::Boolean(flag)] = params {
+            DELIVERED.lock().unwrap().push((index, *count, *flag, *jni_env as usize));
+        }
+    }
+}
+
+unsafe extern "system" fn get_extension_events(
+    _env: *mut jvmti::jvmtiEnv,
+    count_ptr: *mut jni::jint,
+    extensions_ptr: *mut *mut jvmti::jvmtiExtensionEventInfo,
+) -> jvmti::jvmtiError {
+    let param = |kind, base_type| jvmti::jvmtiExtensionParamInfo { name: ptr::null_mut(), kind, base_type, null_ok: 0 };
+    let params = Box::leak(Box::new([
+        param(jvmti::JVMTI_KIND_IN_PTR, jvmti::JVMTI_TYPE_JNIENV),
+        param(jvmti::JVMTI_KIND_IN, jvmti::JVMTI_TYPE_JINT),
+        param(jvmti::JVMTI_KIND_IN, jvmti::JVMTI_TYPE_JBOOLEAN),
+    ]));
+    let events = Box::leak(Box::new([
+        jvmti::jvmtiExtensionEventInfo {
+            extension_event_index: SAMPLE_EVENT,
+            id: b"com.example.events.Sample\0".as_ptr() as *mut c_char,
+            short_description: ptr::null_mut(),
+            param_count: params.len() as jni::jint,
+            params: params.as_mut_ptr(),
+        },
+        jvmti::jvmtiExtensionEventInfo {
+            extension_event_index: SAMPLE_EVENT + 1,
+            id: b"com.example.events.Double\0".as_ptr() as *mut c_char,
+            short_description: ptr::null_mut(),
+            param_count: 1,
+            params: Box::leak(Box::new(param(jvmti::JVMTI_KIND_IN, jvmti::JVMTI_TYPE_JDOUBLE))),
+        },
+        jvmti::jvmtiExtensionEventInfo {
+            extension_event_index: SAMPLE_EVENT + 2,
+            id: b"com.example.events.Float\0".as_ptr() as *mut c_char,
+            short_description: ptr::null_mut(),
+            param_count: 2,
+            params: Box::leak(Box::new([
+                param(jvmti::JVMTI_KIND_IN_PTR, jvmti::JVMTI_TYPE_JNIENV),
+                param(jvmti::JVMTI_KIND_IN, jvmti::JVMTI_TYPE_JFLOAT),
+            ]))
+            .as_mut_ptr(),
+        },
+    ]));
+    *count_ptr = events.len() as jni::jint;
+    *extensions_ptr = events.as_mut_ptr();
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn deallocate(_env: *mut jvmti::jvmtiEnv, _mem: *mut u8) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn set_extension_event_callback(
+    _env: *mut jvmti::jvmtiEnv,
+    _index: jni::jint,
+    callback: jvmti::jvmtiExtensionEventCallback,
+) -> jvmti::jvmtiError {
+    // `disable_extension_event` passes NULL through the same slot.
+    CALLBACK.store(callback as usize, Ordering::SeqCst);
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+#[cfg(any(target_arch = "x86_64", all(target_arch = "aarch64", not(target_vendor = "apple"))))]
+fn extension_events_reach_the_agent() {
+    jvmti_bindings::set_global_agent(Box::new(Recorder)).unwrap();
+
+    let stub = common::stub_jvmti(|functions| {
+        functions.GetExtensionEvents = Some(get_extension_events);
+        functions.Deallocate = Some(deallocate);
+        functions.SetExtensionEventCallback = Some(set_extension_event_callback);
+    });
+    let jvmti_env = stub.jvmti();
+
+    assert_eq!(jvmti_env.enable_extension_event(7), Err(jvmti::jvmtiError::ILLEGAL_ARGUMENT));
+    // Floating-point varargs do not arrive in integer words.
+    assert_eq!(jvmti_env.enable_extension_event(SAMPLE_EVENT + 1), Err(jvmti::jvmtiError::NOT_AVAILABLE));
+    assert_eq!(jvmti_env.enable_extension_event(SAMPLE_EVENT + 2), Err(jvmti::jvmtiError::NOT_AVAILABLE));
+    assert_eq!(CALLBACK.load(Ordering::SeqCst), usize::MAX);
+    assert_eq!(jvmti_env.enable_extension_event(SAMPLE_EVENT), Ok(()));
+
+    // Deliver the event the way a fixed-arity receiver sees it.
+    type Receiver = unsafe extern "C" fn(*mut jvmti::jvmtiEnv, usize, usize, usize, usize, usize, usize, usize, usize);
+    let callback: Receiver = unsafe { std::mem::transmute(CALLBACK.load(Ordering::SeqCst)) };
+    unsafe { callback(stub.raw(), 0x1000, (-5i32) as u32 as usize, 1, 0, 0, 0, 0, 0) };
+    assert_eq!(*DELIVERED.lock().unwrap(), vec![(SAMPLE_EVENT, -5, true, 0x1000)]);
+
+    assert_eq!(jvmti_env.disable_extension_event(SAMPLE_EVENT), Ok(()));
+    assert_eq!(CALLBACK.load(Ordering::SeqCst), 0);
+}
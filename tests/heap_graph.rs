@@ -0,0 +1,308 @@
+#![cfg(feature = "heap-graph")]
+
+use jvmti_bindings::advanced::heap_graph::{HeapGraph, HeapNode};
+
+fn node(class_tag: i64, size: u64) -> HeapNode {
+    HeapNode { class_tag, size, length: -1 }
+}
+
+// root -> 10 -> {11, 12} -> 13 -> 14, plus an unreachable 99.
+fn diamond() -> HeapGraph {
+    let mut graph = HeapGraph::default();
+    graph.class_names.insert(1, "LA;".to_string());
+    graph.class_names.insert(2, "LB;".to_string());
+    graph.class_names.insert(3, "LC;".to_string());
+    graph.nodes.insert(10, node(1, 8));
+    graph.nodes.insert(11, node(2, 16));
+    graph.nodes.insert(12, node(2, 16));
+    graph.nodes.insert(13, node(3, 32));
+    graph.nodes.insert(14, node(3, 64));
+    graph.nodes.insert(99, node(1, 1000));
+    graph.roots = vec![10];
+    graph.edges = vec![(10, 11), (10, 12), (11, 13), (12, 13), (13, 14), (99, 10)];
+    graph
+}
+
+#[test]
+fn dominators_follow_the_diamond() {
+    let dominators = diamond().dominators();
+    assert_eq!(dominators.immediate_dominator(10), None);
+    assert_eq!(dominators.immediate_dominator(11), Some(10));
+    assert_eq!(dominators.immediate_dominator(12), Some(10));
+    assert_eq!(dominators.immediate_dominator(13), Some(10));
+    assert_eq!(dominators.immediate_dominator(14), Some(13));
+    assert_eq!(dominators.immediate_dominator(99), None);
+}
+
+#[test]
+fn retained_sizes_accumulate_dominated_objects() {
+    let dominators = diamond().dominators();
+    assert_eq!(dominators.retained_size(14), Some(64));
+    assert_eq!(dominators.retained_size(13), Some(96));
+    assert_eq!(dominators.retained_size(11), Some(16));
+    assert_eq!(dominators.retained_size(10), Some(136));
+    assert_eq!(dominators.retained_size(99), None);
+    assert_eq!(dominators.total_size(), 136);
+}
+
+#[test]
+fn retained_size_by_class_does_not_double_count() {
+    let by_class = diamond().retained_size_by_class();
+    assert_eq!(
+        by_class,
+        vec![("LA;".to_string(), 136), ("LC;".to_string(), 96), ("LB;".to_string(), 32)]
+    );
+}
+
+#[test]
+fn cycles_between_roots_are_handled() {
+    let mut graph = HeapGraph::default();
+    graph.nodes.insert(1, node(0, 10));
+    graph.nodes.insert(2, node(0, 20));
+    graph.roots = vec![1, 2];
+    graph.edges = vec![(1, 2), (2, 1)];
+    let dominators = graph.dominators();
+    assert_eq!(dominators.immediate_dominator(1), None);
+    assert_eq!(dominators.immediate_dominator(2), None);
+    assert_eq!(dominators.retained_size(1), Some(10));
+    assert_eq!(graph.retained_size_by_class(), vec![("<unknown>".to_string(), 30)]);
+}
+
+fn leaky() -> HeapGraph {
+    use jvmti_bindings::advanced::heap_graph::ReferenceInfo;
+    use jvmti_bindings::sys::jvmti;
+
+    let mut graph = HeapGraph::default();
+    graph.class_names.insert(1, "LHolder;".to_string());
+    graph.class_names.insert(2, "[Ljava/lang/Object;".to_string());
+    graph.field_names.insert(1, vec!["id".to_string(), "cache".to_string()]);
+    graph.nodes.insert(10, node(1, 16));
+    graph.nodes.insert(11, node(2, 32));
+    graph.nodes.insert(12, node(2, 32));
+    graph.nodes.insert(13, node(1, 16));
+    graph.roots = vec![10, 11];
+    // Roots 10 and 11; 10 -> 13 directly, 11 -> 12 -> 13, and a 13 <-> 12 cycle.
+    graph.edges = vec![(10, 13), (11, 12), (12, 13), (13, 12)];
+    let info = |kind, index| ReferenceInfo { kind, index };
+    graph.reference_info.insert((0, 10), info(jvmti::JVMTI_HEAP_REFERENCE_STACK_LOCAL, None));
+    graph.reference_info.insert((0, 11), info(jvmti::JVMTI_HEAP_REFERENCE_JNI_GLOBAL, None));
+    graph.reference_info.insert((10, 13), info(jvmti::JVMTI_HEAP_REFERENCE_FIELD, Some(1)));
+    graph.reference_info.insert((11, 12), info(jvmti::JVMTI_HEAP_REFERENCE_ARRAY_ELEMENT, Some(0)));
+    graph.reference_info.insert((12, 13), info(jvmti::JVMTI_HEAP_REFERENCE_ARRAY_ELEMENT, Some(3)));
+    graph.reference_info.insert((13, 12), info(jvmti::JVMTI_HEAP_REFERENCE_FIELD, Some(0)));
+    graph
+}
+
+#[test]
+fn paths_to_roots_returns_shortest_chain_per_root() {
+    use jvmti_bindings::sys::jvmti;
+
+    let paths = leaky().paths_to_roots(13, 8);
+    assert_eq!(paths.len(), 2);
+
+    let direct = &paths[0];
+    assert_eq!(direct.len(), 2);
+    assert_eq!(direct[0].referrer, None);
+    assert_eq!(direct[0].referenced, 10);
+    assert_eq!(direct[0].kind, jvmti::JVMTI_HEAP_REFERENCE_STACK_LOCAL);
+    assert_eq!(direct[1].referrer, Some(10));
+    assert_eq!(direct[1].kind, jvmti::JVMTI_HEAP_REFERENCE_FIELD);
+    assert_eq!(direct[1].referrer_class.as_deref(), Some("LHolder;"));
+    assert_eq!(direct[1].field_name.as_deref(), Some("cache"));
+
+    let via_array = &paths[1];
+    let hops: Vec<_> = via_array.iter().map(|edge| (edge.referrer, edge.referenced)).collect();
+    assert_eq!(hops, vec![(None, 11), (Some(11), 12), (Some(12), 13)]);
+    assert_eq!(via_array[2].index, Some(3));
+    assert_eq!(via_array[2].field_name, None);
+}
+
+#[test]
+fn paths_to_roots_respects_max_paths() {
+    let graph = leaky();
+    assert_eq!(graph.paths_to_roots(13, 1).len(), 1);
+    assert!(graph.paths_to_roots(13, 0).is_empty());
+    assert!(graph.paths_to_roots(999, 4).is_empty());
+    assert_eq!(graph.paths_to_roots(10, 4), vec![vec![graph.paths_to_roots(13, 1)[0][0].clone()]]);
+}
+
+struct HprofReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl HprofReader<'_> {
+    fn take(&mut self, n: usize) -> &[u8] {
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        head
+    }
+
+    fn u1(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+
+    fn u2(&mut self) -> u16 {
+        u16::from_be_bytes(self.take(2).try_into().unwrap())
+    }
+
+    fn u4(&mut self) -> u32 {
+        u32::from_be_bytes(self.take(4).try_into().unwrap())
+    }
+
+    fn id(&mut self) -> u64 {
+        u64::from_be_bytes(self.take(8).try_into().unwrap())
+    }
+}
+
+#[test]
+fn write_hprof_emits_a_well_formed_dump() {
+    use jvmti_bindings::advanced::heap_graph::ReferenceInfo;
+    use jvmti_bindings::sys::jvmti;
+    use std::collections::HashMap;
+
+    let mut graph = HeapGraph::default();
+    graph.class_names.insert(1, "Ljava/lang/Class;".to_string());
+    graph.class_names.insert(2, "LHolder;".to_string());
+    graph.class_names.insert(3, "[Ljava/lang/Object;".to_string());
+    graph.class_names.insert(4, "[I".to_string());
+    graph.field_names.insert(2, vec!["id".to_string(), "items".to_string()]);
+    graph.nodes.insert(2, node(1, 64));
+    graph.nodes.insert(10, node(2, 24));
+    graph.nodes.insert(11, HeapNode { class_tag: 3, size: 40, length: 3 });
+    graph.nodes.insert(12, HeapNode { class_tag: 4, size: 24, length: 2 });
+    graph.roots = vec![2];
+    graph.edges = vec![(2, 10), (10, 11), (11, 12)];
+    let info = |kind, index| ReferenceInfo { kind, index };
+    graph.reference_info.insert((0, 2), info(jvmti::JVMTI_HEAP_REFERENCE_SYSTEM_CLASS, None));
+    graph.reference_info.insert((2, 10), info(jvmti::JVMTI_HEAP_REFERENCE_STATIC_FIELD, Some(0)));
+    graph.reference_info.insert((10, 11), info(jvmti::JVMTI_HEAP_REFERENCE_FIELD, Some(1)));
+    graph.reference_info.insert((11, 12), info(jvmti::JVMTI_HEAP_REFERENCE_ARRAY_ELEMENT, Some(2)));
+
+    let mut bytes = Vec::new();
+    graph.write_hprof(&mut bytes).unwrap();
+
+    let mut reader = HprofReader { bytes: &bytes };
+    assert_eq!(reader.take(19), b"JAVA PROFILE 1.0.2\0");
+    assert_eq!(reader.u4(), 8);
+    reader.take(8);
+
+    let mut strings = HashMap::new();
+    let mut loaded = Vec::new();
+    let mut sub_records = Vec::new();
+    let mut instance_field_names: HashMap<u64, Vec<String>> = HashMap::new();
+    let mut ended = false;
+    while !reader.bytes.is_empty() {
+        assert!(!ended, "records after HEAP DUMP END");
+        let tag = reader.u1();
+        reader.u4();
+        let length = reader.u4() as usize;
+        let mut body = HprofReader { bytes: reader.take(length) };
+        match tag {
+            0x01 => {
+                let id = body.id();
+                strings.insert(id, String::from_utf8(body.bytes.to_vec()).unwrap());
+            }
+            0x02 => {
+                body.u4();
+                let class_id = body.id();
+                body.u4();
+                loaded.push((class_id, strings[&body.id()].clone()));
+            }
+            0x05 => assert_eq!(body.bytes.len(), 12),
+            0x1C => {
+                while !body.bytes.is_empty() {
+                    let sub = body.u1();
+                    match sub {
+                        0xFF | 0x05 | 0x07 => {
+                            body.id();
+                        }
+                        0x01 => {
+                            body.id();
+                            body.id();
+                        }
+                        0x20 => {
+                            let class_id = body.id();
+                            body.u4();
+                            body.take(6 * 8);
+                            let instance_size = body.u4();
+                            assert_eq!(body.u2(), 0);
+                            for _ in 0..body.u2() {
+                                body.id();
+                                assert_eq!(body.u1(), 2);
+                                body.id();
+                            }
+                            let count = body.u2();
+                            assert_eq!(instance_size, u32::from(count) * 8);
+                            let names = (0..count)
+                                .map(|_| {
+                                    let name = strings[&body.id()].clone();
+                                    assert_eq!(body.u1(), 2);
+                                    name
+                                })
+                                .collect();
+                            instance_field_names.insert(class_id, names);
+                        }
+                        0x21 => {
+                            let id = body.id();
+                            body.u4();
+                            let class_id = body.id();
+                            let length = body.u4() as usize;
+                            let values = body.take(length).to_vec();
+                            assert_eq!(id, 10);
+                            assert_eq!(class_id, 2);
+                            assert_eq!(values, 11u64.to_be_bytes());
+                        }
+                        0x22 => {
+                            let id = body.id();
+                            body.u4();
+                            let count = body.u4();
+                            assert_eq!(body.id(), 3);
+                            let elements: Vec<u64> = (0..count).map(|_| body.id()).collect();
+                            assert_eq!((id, elements), (11, vec![0, 0, 12]));
+                        }
+                        0x23 => {
+                            let id = body.id();
+                            body.u4();
+                            let count = body.u4() as usize;
+                            assert_eq!((id, body.u1()), (12, 10));
+                            body.take(count * 4);
+                        }
+                        other => panic!("unexpected sub-record {other:#x}"),
+                    }
+                    sub_records.push(sub);
+                }
+            }
+            0x2C => ended = true,
+            other => panic!("unexpected record {other:#x}"),
+        }
+    }
+
+    assert!(ended);
+    assert_eq!(loaded.len(), 4);
+    assert!(loaded.contains(&(2, "Holder".to_string())));
+    assert!(loaded.contains(&(4, "[I".to_string())));
+    assert_eq!(instance_field_names[&2], vec!["items".to_string()]);
+    assert_eq!(sub_records.iter().filter(|&&sub| sub == 0x20).count(), 4);
+    assert!(sub_records.contains(&0x05));
+    for sub in [0x21, 0x22, 0x23] {
+        assert_eq!(sub_records.iter().filter(|&&s| s == sub).count(), 1);
+    }
+}
+
+#[test]
+fn write_hprof_rejects_counts_that_overflow_their_fields() {
+    use jvmti_bindings::advanced::heap_graph::ReferenceInfo;
+    use jvmti_bindings::sys::jvmti;
+
+    let mut graph = HeapGraph::default();
+    graph.class_names.insert(1, "LHolder;".to_string());
+    graph.nodes.insert(1, node(1, 64));
+    for index in 0..=u16::MAX as i32 {
+        let referenced = 100 + index as i64;
+        let info = ReferenceInfo { kind: jvmti::JVMTI_HEAP_REFERENCE_STATIC_FIELD, index: Some(index) };
+        graph.reference_info.insert((1, referenced), info);
+    }
+
+    let err = graph.write_hprof(Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,42 @@
+//! `export_jni_onload!` entry point. Kept in its own test binary because it
+//! installs the process-wide agent.
+
+use std::ptr;
+
+use jvmti_bindings::prelude::*;
+
+#[derive(Default)]
+struct Hybrid;
+
+impl Agent for Hybrid {
+    fn on_load(&self, _vm: *mut jni::JavaVM, _options: &str) -> jni::jint {
+        jni::JNI_OK
+    }
+
+    fn jni_on_load(&self, vm: *mut jni::JavaVM) -> jni::jint {
+        if vm.is_null() { jni::JNI_VERSION_1_8 } else { jni::JNI_VERSION_21 }
+    }
+}
+
+export_jni_onload!(Hybrid);
+
+struct Defaults;
+
+impl Agent for Defaults {
+    fn on_load(&self, _vm: *mut jni::JavaVM, _options: &str) -> jni::jint {
+        jni::JNI_OK
+    }
+}
+
+#[test]
+fn jni_onload_returns_the_agent_version() {
+    assert_eq!(Defaults.jni_on_load(ptr::null_mut()), jni::JNI_VERSION_1_8);
+
+    let version = unsafe { JNI_OnLoad(ptr::null_mut(), ptr::null_mut()) };
+    assert_eq!(version, jni::JNI_VERSION_1_8);
+    assert!(jvmti_bindings::GLOBAL_AGENT.get().is_some());
+
+    let mut vm_storage = 0usize;
+    let vm = &mut vm_storage as *mut usize as *mut jni::JavaVM;
+    assert_eq!(unsafe { JNI_OnLoad(vm, ptr::null_mut()) }, jni::JNI_VERSION_21);
+}
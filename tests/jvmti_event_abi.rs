@@ -1,7 +1,7 @@
 use std::mem::{align_of, size_of, MaybeUninit};
 use std::ptr::addr_of;
 
-use jvmti_bindings::sys::jvmti;
+use jvmti_bindings::sys::{jni, jvmti};
 
 macro_rules! offset_of {
     ($ty:ty, $field:ident) => {{
@@ -112,3 +112,76 @@ fn default_callback_table_wires_new_events_and_keeps_reserved_slots_null() {
     assert!(callbacks.reserved79.is_none());
     assert!(callbacks.reserved85.is_none());
 }
+
+#[test]
+fn heap_callback_table_matches_jvmti_abi() {
+    let slot = size_of::<usize>();
+    assert_eq!(size_of::<jvmti::jvmtiHeapCallbacks>(), 16 * slot);
+    assert_eq!(align_of::<jvmti::jvmtiHeapCallbacks>(), align_of::<usize>());
+    assert_eq!(offset_of!(jvmti::jvmtiHeapCallbacks, heap_iteration_callback), 0);
+    assert_eq!(offset_of!(jvmti::jvmtiHeapCallbacks, heap_reference_callback), slot);
+    assert_eq!(offset_of!(jvmti::jvmtiHeapCallbacks, primitive_field_callback), 2 * slot);
+    assert_eq!(offset_of!(jvmti::jvmtiHeapCallbacks, array_primitive_value_callback), 3 * slot);
+    assert_eq!(offset_of!(jvmti::jvmtiHeapCallbacks, string_primitive_value_callback), 4 * slot);
+    assert_eq!(offset_of!(jvmti::jvmtiHeapCallbacks, reserved15), 15 * slot);
+
+    // The largest union member is the eight-`jlong` reserved block.
+    assert_eq!(size_of::<jvmti::jvmtiHeapReferenceInfo>(), 64);
+}
+
+#[test]
+fn heap_callback_builder_sets_only_requested_slots() {
+    unsafe extern "system" fn count_object(
+        _class_tag: jni::jlong,
+        _size: jni::jlong,
+        _tag_ptr: *mut jni::jlong,
+        _length: jni::jint,
+        _user_data: *mut std::ffi::c_void,
+    ) -> jni::jint {
+        0
+    }
+    unsafe extern "system" fn read_string(
+        _class_tag: jni::jlong,
+        _size: jni::jlong,
+        _tag_ptr: *mut jni::jlong,
+        _value: *const jni::jchar,
+        _value_length: jni::jint,
+        _user_data: *mut std::ffi::c_void,
+    ) -> jni::jint {
+        0
+    }
+
+    let empty = jvmti::jvmtiHeapCallbacks::default();
+    assert!(empty.heap_iteration_callback.is_none());
+    assert!(empty.heap_reference_callback.is_none());
+
+    let callbacks = jvmti::jvmtiHeapCallbacks::default().heap_iteration(count_object).string_primitive_value(read_string);
+    assert!(callbacks.heap_iteration_callback.is_some());
+    assert!(callbacks.string_primitive_value_callback.is_some());
+    assert!(callbacks.heap_reference_callback.is_none());
+    assert!(callbacks.primitive_field_callback.is_none());
+    assert!(callbacks.array_primitive_value_callback.is_none());
+    assert!(callbacks.reserved5.is_none());
+    assert!(callbacks.reserved15.is_none());
+}
+
+#[cfg(target_pointer_width = "64")]
+#[test]
+fn addr_location_map_matches_jvmti_abi() {
+    assert_eq!(offset_of!(jvmti::jvmtiAddrLocationMap, start_address), 0);
+    assert_eq!(offset_of!(jvmti::jvmtiAddrLocationMap, location), 8);
+    assert_eq!(size_of::<jvmti::jvmtiAddrLocationMap>(), 16);
+}
+
+#[test]
+fn start_function_receives_the_jni_env() {
+    unsafe extern "system" fn start(
+        _jvmti_env: *mut jvmti::jvmtiEnv,
+        _jni_env: *mut jvmti_bindings::jni::JNIEnv,
+        _arg: *mut std::os::raw::c_void,
+    ) {
+    }
+    let _: jvmti::jvmtiStartFunction = start;
+    assert_eq!(jvmti::JVMTI_THREAD_NORM_PRIORITY, 5);
+    assert_eq!(jvmti::JVMTI_THREAD_MAX_PRIORITY, 10);
+}
@@ -0,0 +1,103 @@
+//! The opt-in diagnostics hook.
+//!
+//! Kept in its own test binary because the log callback is process-wide and
+//! no agent may be registered.
+
+mod common;
+
+use std::sync::Mutex;
+
+use jvmti_bindings::env::Event;
+use jvmti_bindings::sys::{jni, jvmti};
+use jvmti_bindings::{clear_log_callback, get_default_callbacks, set_log_callback, LogLevel};
+
+static MESSAGES: Mutex<Vec<(LogLevel, String)>> = Mutex::new(Vec::new());
+
+/// Serializes the tests, which install and clear the same process-wide hook.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn record(level: LogLevel, message: &str) {
+    MESSAGES.lock().unwrap().push((level, message.to_string()));
+}
+
+fn take_messages() -> Vec<(LogLevel, String)> {
+    std::mem::take(&mut *MESSAGES.lock().unwrap())
+}
+
+unsafe extern "system" fn accept_mode(
+    _env: *mut jvmti::jvmtiEnv,
+    _mode: jni::jint,
+    _event_type: u32,
+    _thread: jni::jthread,
+) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn reject_deallocate(_env: *mut jvmti::jvmtiEnv, _mem: *mut u8) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::INVALID_ENVIRONMENT
+}
+
+unsafe extern "system" fn accept_enter(_env: *mut jvmti::jvmtiEnv, _monitor: jvmti::jrawMonitorID) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn reject_exit(_env: *mut jvmti::jvmtiEnv, _monitor: jvmti::jrawMonitorID) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::NOT_MONITOR_OWNER
+}
+
+#[test]
+fn log_callback_reports_internal_events_only_while_installed() {
+    let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let stub = common::stub_jvmti(|functions| {
+        functions.SetEventNotificationMode = Some(accept_mode);
+        functions.Deallocate = Some(reject_deallocate);
+    });
+    let jvmti_env = stub.jvmti();
+    let mut block = 0u8;
+
+    jvmti_env.enable(Event::MethodEntry, std::ptr::null_mut()).unwrap();
+    assert!(take_messages().is_empty());
+
+    set_log_callback(record);
+    jvmti_env.enable(Event::MethodEntry, std::ptr::null_mut()).unwrap();
+    assert!(jvmti_env.deallocate(&mut block).is_err());
+    let gc_start = get_default_callbacks().GarbageCollectionStart.unwrap();
+    unsafe { gc_start(stub.raw()) };
+
+    let messages = take_messages();
+    assert_eq!(messages.len(), 3, "{messages:?}");
+    assert_eq!(messages[0].0, LogLevel::Debug);
+    assert!(messages[0].1.starts_with("enabled MethodEntry"), "{}", messages[0].1);
+    assert_eq!(messages[1].0, LogLevel::Warn);
+    assert!(messages[1].1.starts_with("Deallocate("), "{}", messages[1].1);
+    assert_eq!(messages[2].0, LogLevel::Warn);
+    assert!(messages[2].1.contains("before an agent was registered"));
+
+    clear_log_callback();
+    assert!(jvmti_env.deallocate(&mut block).is_err());
+    assert!(take_messages().is_empty());
+    assert_eq!(LogLevel::Warn.to_string(), "WARN");
+}
+
+#[test]
+fn guard_release_failures_reach_the_log_callback() {
+    let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let stub = common::stub_jvmti(|functions| {
+        functions.RawMonitorEnter = Some(accept_enter);
+        functions.RawMonitorExit = Some(reject_exit);
+    });
+    let jvmti_env = stub.jvmti();
+    let monitor = std::ptr::NonNull::dangling().as_ptr();
+
+    drop(jvmti_env.enter_raw_monitor(monitor).unwrap());
+    assert!(take_messages().is_empty());
+
+    set_log_callback(record);
+    drop(jvmti_env.enter_raw_monitor(monitor).unwrap());
+    clear_log_callback();
+
+    let messages = take_messages();
+    assert_eq!(messages.len(), 1, "{messages:?}");
+    assert_eq!(messages[0].0, LogLevel::Error);
+    assert!(messages[0].1.starts_with("RawMonitorExit failed"), "{}", messages[0].1);
+}
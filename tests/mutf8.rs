@@ -0,0 +1,40 @@
+use jvmti_bindings::mutf8;
+
+#[test]
+fn ascii_and_bmp_text_is_plain_utf8() {
+    for s in ["", "java/lang/String", "caf\u{e9}", "\u{4e2d}\u{6587}"] {
+        assert_eq!(mutf8::encode(s), s.as_bytes());
+        assert_eq!(mutf8::decode(s.as_bytes()), s);
+    }
+}
+
+#[test]
+fn embedded_nul_is_two_bytes() {
+    let encoded = mutf8::encode("a\0b");
+    assert_eq!(encoded, [b'a', 0xC0, 0x80, b'b']);
+    assert_eq!(mutf8::decode(&encoded), "a\0b");
+    assert_eq!(mutf8::to_cstring("a\0b").as_bytes(), encoded);
+}
+
+#[test]
+fn supplementary_characters_are_surrogate_pairs() {
+    // U+1F600 is the surrogate pair D83D DE00.
+    let encoded = mutf8::encode("hi \u{1F600}!");
+    assert_eq!(encoded, [b'h', b'i', b' ', 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80, b'!']);
+    assert_eq!(mutf8::decode(&encoded), "hi \u{1F600}!");
+    // Standard four-byte UTF-8 is accepted too.
+    assert_eq!(mutf8::decode("\u{1F600}".as_bytes()), "\u{1F600}");
+}
+
+#[test]
+fn round_trips_through_c_strings() {
+    let s = "log \u{1F680} line\0with nul";
+    let c = mutf8::to_cstring(s);
+    assert_eq!(unsafe { mutf8::from_cstr(c.as_ptr()) }, s);
+}
+
+#[test]
+fn malformed_input_decodes_lossily() {
+    assert_eq!(mutf8::decode(&[b'a', 0xED, 0xA0, 0xBD]), "a\u{FFFD}");
+    assert_eq!(mutf8::decode(&[b'a', 0xFF]), "a\u{FFFD}");
+}
@@ -0,0 +1,86 @@
+#![cfg(feature = "profiling")]
+
+use jvmti_bindings::env::{JniEnv, Jvmti, SymbolicFrame};
+use jvmti_bindings::profiling::{frame_label, AllocSampler, ClassAllocations, CpuSampler, FoldedStacks, ThreadCpuDelta};
+use jvmti_bindings::sys::jvmti;
+
+fn frame(class_signature: &str, method_name: &str) -> SymbolicFrame {
+    SymbolicFrame {
+        method: std::ptr::null_mut(),
+        location: 0,
+        class_signature: class_signature.to_string(),
+        method_name: method_name.to_string(),
+        method_signature: "()V".to_string(),
+        line_number: None,
+    }
+}
+
+#[test]
+fn frame_label_uses_dotted_class_names() {
+    assert_eq!(frame_label(&frame("Ljava/lang/String;", "indexOf")), "java.lang.String.indexOf");
+    assert_eq!(frame_label(&frame("[I", "clone")), "[I.clone");
+}
+
+#[test]
+fn folded_stacks_are_root_first_and_counted() {
+    let mut stacks = FoldedStacks::new();
+    let leaf_first = [frame("LWorker;", "compute"), frame("LWorker;", "run"), frame("Ljava/lang/Thread;", "run")];
+    stacks.record(&leaf_first);
+    stacks.record(&leaf_first);
+    stacks.record_weighted(&leaf_first[1..], 5);
+    stacks.record(&[]);
+
+    assert_eq!(stacks.len(), 2);
+    assert_eq!(stacks.total(), 7);
+    assert_eq!(stacks.count("java.lang.Thread.run;Worker.run;Worker.compute"), 2);
+
+    let mut out = Vec::new();
+    stacks.write(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "java.lang.Thread.run;Worker.run 5\njava.lang.Thread.run;Worker.run;Worker.compute 2\n"
+    );
+
+    stacks.clear();
+    assert!(stacks.is_empty());
+}
+
+#[test]
+fn alloc_sampler_counts_bytes_per_class_and_stack() {
+    let stack = [frame("LCache;", "put"), frame("LMain;", "main")];
+    let sampler = AllocSampler::with_stacks(16);
+    sampler.record_sample("[B", &stack, 1024);
+    sampler.record_sample("[B", &stack, 512);
+    sampler.record_sample("Ljava/lang/String;", &[], 24);
+
+    assert_eq!(
+        sampler.classes(),
+        [
+            ("byte[]".to_string(), ClassAllocations { samples: 2, bytes: 1536 }),
+            ("java.lang.String".to_string(), ClassAllocations { samples: 1, bytes: 24 }),
+        ]
+    );
+    assert_eq!(sampler.total_bytes(), 1560);
+    let mut out = Vec::new();
+    sampler.write_folded(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "Main.main;Cache.put;byte[] 1536\njava.lang.String 24\n");
+
+    let by_class = AllocSampler::new();
+    by_class.record_sample("[B", &stack, 1024);
+    assert!(by_class.stacks().is_empty());
+    let mut out = Vec::new();
+    by_class.write_folded(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "byte[] 1024\n");
+
+    sampler.clear();
+    assert_eq!(sampler.total_bytes(), 0);
+    assert!(sampler.stacks().is_empty());
+}
+
+#[test]
+fn cpu_sampler_is_public_api() {
+    let _ = CpuSampler::sample as fn(&mut CpuSampler, &Jvmti, &JniEnv) -> Result<Vec<ThreadCpuDelta>, jvmti::jvmtiError>;
+    let _ = CpuSampler::clear as fn(&mut CpuSampler);
+    fn assert_send<T: Send>() {}
+    assert_send::<CpuSampler>();
+}
@@ -0,0 +1,121 @@
+//! `Jvmti::retransform_with` alongside the agent's own `ClassFileLoadHook`.
+//!
+//! Kept in its own test binary because it installs the process-wide agent.
+
+mod common;
+
+use std::ptr;
+use std::sync::Mutex;
+
+use jvmti_bindings::env::Event;
+use jvmti_bindings::sys::{jni, jvmti};
+use jvmti_bindings::{get_default_callbacks, Agent};
+
+static MODES: Mutex<Vec<(jni::jint, usize)>> = Mutex::new(Vec::new());
+static RETRANSFORMED: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+static DEALLOCATED: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+struct Instrumenter;
+
+impl Agent for Instrumenter {
+    fn on_load(&self, _vm: *mut jni::JavaVM, _options: &str) -> jni::jint {
+        jni::JNI_OK
+    }
+
+    fn transform_class(&self, _name: Option<&str>, class_data: &[u8]) -> Option<Vec<u8>> {
+        Some([class_data, &[0xA]].concat())
+    }
+}
+
+unsafe extern "system" fn live_phase(_env: *mut jvmti::jvmtiEnv, phase_ptr: *mut jni::jint) -> jvmti::jvmtiError {
+    *phase_ptr = jvmti::JVMTI_PHASE_LIVE;
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn retransform_capable(_env: *mut jvmti::jvmtiEnv, caps: *mut jvmti::jvmtiCapabilities) -> jvmti::jvmtiError {
+    (*caps).set_can_retransform_classes(true);
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn current_thread(_env: *mut jvmti::jvmtiEnv, thread_ptr: *mut jni::jthread) -> jvmti::jvmtiError {
+    *thread_ptr = 0x7 as jni::jthread;
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn accept_callbacks(
+    _env: *mut jvmti::jvmtiEnv,
+    _callbacks: *const jvmti::jvmtiEventCallbacks,
+    _size: jni::jint,
+) -> jvmti::jvmtiError {
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn record_mode(_env: *mut jvmti::jvmtiEnv, mode: jni::jint, _event_type: u32, thread: jni::jthread) -> jvmti::jvmtiError {
+    MODES.lock().unwrap().push((mode, thread as usize));
+    jvmti::jvmtiError::NONE
+}
+
+unsafe extern "system" fn leak_allocate(_env: *mut jvmti::jvmtiEnv, size: jni::jlong, mem_ptr: *mut *mut u8) -> jvmti::jvmtiError {
+    *mem_ptr = Box::leak(vec![0u8; size as usize].into_boxed_slice()).as_mut_ptr();
+    jvmti::jvmtiError::NONE
+}
+
+// Records what was freed; every allocation here is three or four bytes.
+unsafe extern "system" fn record_deallocate(_env: *mut jvmti::jvmtiEnv, mem: *mut u8) -> jvmti::jvmtiError {
+    DEALLOCATED.lock().unwrap().push(std::slice::from_raw_parts(mem, 3).to_vec());
+    jvmti::jvmtiError::NONE
+}
+
+// Runs the crate's ClassFileLoadHook for each class, as HotSpot does.
+unsafe extern "system" fn retransform_through_hook(
+    env: *mut jvmti::jvmtiEnv,
+    class_count: jni::jint,
+    classes: *const jni::jclass,
+) -> jvmti::jvmtiError {
+    let hook = get_default_callbacks().ClassFileLoadHook.unwrap();
+    for &klass in std::slice::from_raw_parts(classes, class_count as usize) {
+        let original = [0xCA, 0xFE];
+        let mut new_len: jni::jint = 0;
+        let mut new_data: *mut u8 = ptr::null_mut();
+        let name = b"com/example/Foo\0".as_ptr() as *const std::os::raw::c_char;
+        hook(env, ptr::null_mut(), klass, ptr::null_mut(), name, ptr::null_mut(), 2, original.as_ptr(), &mut new_len, &mut new_data);
+        let bytes = if new_data.is_null() { Vec::new() } else { std::slice::from_raw_parts(new_data, new_len as usize).to_vec() };
+        RETRANSFORMED.lock().unwrap().push(bytes);
+    }
+    jvmti::jvmtiError::NONE
+}
+
+#[test]
+fn retransform_with_runs_after_the_agent_hook() {
+    jvmti_bindings::set_global_agent(Box::new(Instrumenter)).unwrap();
+    let stub = common::stub_jvmti(|functions| {
+        functions.GetPhase = Some(live_phase);
+        functions.GetCapabilities = Some(retransform_capable);
+        functions.GetCurrentThread = Some(current_thread);
+        functions.SetEventCallbacks = Some(accept_callbacks);
+        functions.SetEventNotificationMode = Some(record_mode);
+        functions.Allocate = Some(leak_allocate);
+        functions.Deallocate = Some(record_deallocate);
+        functions.RetransformClasses = Some(retransform_through_hook);
+    });
+    let jvmti_env = stub.jvmti();
+    jvmti_env.set_agent_callbacks_for(&[Event::ClassFileLoadHook]).unwrap();
+
+    // The agent's instrumentation survives; the closure sees and extends it.
+    let mut seen = Vec::new();
+    let result = jvmti_env.retransform_with(&[0x10 as jni::jclass, 0x20 as jni::jclass], |_, bytes| {
+        seen.push(bytes.to_vec());
+        (seen.len() == 2).then(|| [bytes, &[7]].concat())
+    });
+    assert_eq!(result, Ok(()));
+    assert_eq!(seen, [vec![0xCA, 0xFE, 0xA], vec![0xCA, 0xFE, 0xA]]);
+    assert_eq!(*RETRANSFORMED.lock().unwrap(), [vec![0xCA, 0xFE, 0xA], vec![0xCA, 0xFE, 0xA, 7]]);
+    assert_eq!(*DEALLOCATED.lock().unwrap(), [vec![0xCA, 0xFE, 0xA]]);
+
+    // A thread-level enable made beforehand cannot be observed, so it is
+    // cleared along with the one `retransform_with` made.
+    MODES.lock().unwrap().clear();
+    jvmti_env.set_event_notification_mode(true, jvmti::JVMTI_EVENT_CLASS_FILE_LOAD_HOOK, 0x7 as jni::jthread).unwrap();
+    assert_eq!(jvmti_env.retransform_with(&[], |_, _| None), Ok(()));
+    assert_eq!(*MODES.lock().unwrap(), [(1, 0x7), (1, 0x7), (0, 0x7)]);
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "serde")]
+
+use jvmti_bindings::env::{ExtensionFunctionInfo, MonitorUsage, StackInfo, SymbolicFrame, ThreadInfo, ThreadState};
+use jvmti_bindings::jni;
+use jvmti_bindings::sys::jvmti;
+
+fn object(address: usize) -> jni::jobject {
+    address as jni::jobject
+}
+
+#[test]
+fn references_serialize_as_addresses() {
+    let info = ThreadInfo {
+        name: Some("main".to_string()),
+        priority: 5,
+        is_daemon: false,
+        thread_group: object(0x1000),
+        context_class_loader: std::ptr::null_mut(),
+    };
+    let json = serde_json::to_value(&info).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "name": "main",
+            "priority": 5,
+            "is_daemon": false,
+            "thread_group": 0x1000,
+            "context_class_loader": 0,
+        })
+    );
+    // Handles are never rebuilt from addresses.
+    let back: ThreadInfo = serde_json::from_value(json).unwrap();
+    assert_eq!(back.name.as_deref(), Some("main"));
+    assert!(back.thread_group.is_null());
+    assert!(back.context_class_loader.is_null());
+}
+
+#[test]
+fn stacks_and_monitors_round_trip() {
+    let stack = StackInfo {
+        thread: object(0x20),
+        state: jvmti::JVMTI_JAVA_LANG_THREAD_STATE_RUNNABLE,
+        frames: vec![jvmti::jvmtiFrameInfo { method: 0x30 as jni::jmethodID, location: 7 }],
+    };
+    let json = serde_json::to_string(&stack).unwrap();
+    assert!(json.contains(r#""frames":[{"method":48,"location":7}]"#), "{json}");
+    let back: StackInfo = serde_json::from_str(&json).unwrap();
+    assert!(back.thread.is_null());
+    assert!(back.frames[0].method.is_null());
+    assert_eq!(back.frames[0].location, 7);
+
+    let usage = MonitorUsage { owner: object(1), entry_count: 2, waiters: vec![object(3)], notify_waiters: vec![] };
+    let json = serde_json::to_value(&usage).unwrap();
+    assert_eq!(json["waiters"], serde_json::json!([3]));
+
+    assert_eq!(serde_json::to_string(&ThreadState(5)).unwrap(), "5");
+}
+
+#[test]
+fn frames_and_extension_errors_serialize() {
+    let frame = SymbolicFrame {
+        method: std::ptr::null_mut(),
+        location: -1,
+        class_signature: "LMain;".to_string(),
+        method_name: "main".to_string(),
+        method_signature: "([Ljava/lang/String;)V".to_string(),
+        line_number: None,
+    };
+    let back: SymbolicFrame = serde_json::from_str(&serde_json::to_string(&frame).unwrap()).unwrap();
+    assert_eq!(back, frame);
+
+    let info = ExtensionFunctionInfo {
+        func: std::ptr::null_mut(),
+        id: Some("com.sun.hotspot.functions.Example".to_string()),
+        short_description: None,
+        params: vec![],
+        errors: vec![jvmti::jvmtiError::WRONG_PHASE],
+    };
+    let json = serde_json::to_value(&info).unwrap();
+    assert_eq!(json["errors"], serde_json::json!([112]));
+    assert!(serde_json::from_value::<ExtensionFunctionInfo>(serde_json::json!({
+        "func": 0, "id": null, "short_description": null, "params": [], "errors": [33]
+    }))
+    .is_err());
+}
@@ -0,0 +1,41 @@
+//! The `VMDeath`-driven shutdown hook.
+//!
+//! Kept in its own test binary because it installs the process-wide agent.
+
+mod common;
+
+use std::sync::Mutex;
+
+use jvmti_bindings::env::JniEnv;
+use jvmti_bindings::sys::jni;
+use jvmti_bindings::{get_default_callbacks, Agent};
+
+static CALLS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+struct Recorder;
+
+impl Agent for Recorder {
+    fn on_load(&self, _vm: *mut jni::JavaVM, _options: &str) -> jni::jint {
+        jni::JNI_OK
+    }
+
+    fn vm_death(&self, _jni: *mut jni::JNIEnv) {
+        CALLS.lock().unwrap().push("vm_death");
+    }
+
+    fn shutdown(&self, jni: &JniEnv) {
+        assert_eq!(jni.raw() as usize, 0x40);
+        CALLS.lock().unwrap().push("shutdown");
+    }
+}
+
+#[test]
+fn shutdown_runs_once_after_vm_death() {
+    jvmti_bindings::set_global_agent(Box::new(Recorder)).unwrap();
+    let stub = common::stub_jvmti(|_| {});
+    let vm_death = get_default_callbacks().VMDeath.unwrap();
+
+    unsafe { vm_death(stub.raw(), 0x40 as *mut jni::JNIEnv) };
+    unsafe { vm_death(stub.raw(), 0x40 as *mut jni::JNIEnv) };
+    assert_eq!(*CALLS.lock().unwrap(), ["vm_death", "shutdown", "vm_death"]);
+}